@@ -13,12 +13,11 @@
 //!
 //! For Gmail, you'll need to use an [App Password](https://support.google.com/accounts/answer/185833).
 
-use email_sync::matcher::OtpMatcher;
-use email_sync::{ImapConfig, ImapEmailClient};
+use email_sync::prelude::*;
 use std::env;
 
 #[tokio::main]
-async fn main() -> email_sync::Result<()> {
+async fn main() -> Result<()> {
     // Read credentials from environment
     let email = env::var("EMAIL_ADDRESS").expect("EMAIL_ADDRESS environment variable required");
     let password =
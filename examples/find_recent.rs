@@ -11,13 +11,12 @@
 //! cargo run --example find_recent
 //! ```
 
-use email_sync::matcher::{OtpMatcher, RegexMatcher, UrlMatcher};
-use email_sync::{ImapConfig, ImapEmailClient};
+use email_sync::prelude::*;
 use std::env;
 use std::time::Duration;
 
 #[tokio::main]
-async fn main() -> email_sync::Result<()> {
+async fn main() -> Result<()> {
     let email = env::var("EMAIL_ADDRESS").expect("EMAIL_ADDRESS environment variable required");
     let password =
         env::var("EMAIL_PASSWORD").expect("EMAIL_PASSWORD environment variable required");
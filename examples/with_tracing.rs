@@ -15,15 +15,14 @@
 //! cargo run --example with_tracing
 //! ```
 
-use email_sync::matcher::OtpMatcher;
-use email_sync::{ImapConfig, ImapEmailClient};
+use email_sync::prelude::*;
 use std::env;
 use std::time::Duration;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
-async fn main() -> email_sync::Result<()> {
+async fn main() -> Result<()> {
     // Initialize tracing subscriber with environment filter
     // Use RUST_LOG environment variable to control log levels
     // Example: RUST_LOG=email_sync=debug,info
@@ -0,0 +1,556 @@
+//! Transport-independent mailbox access.
+//!
+//! [`MailBackend`] is the small set of operations the wait/find logic needs from
+//! a mail store: (re)connect, select a mailbox, search for new or recent
+//! messages, fetch their raw content and wait between polls. It is implemented
+//! by [`ImapEmailClient`], [`MaildirBackend`](crate::maildir::MaildirBackend),
+//! [`MboxBackend`](crate::mbox::MboxBackend) and, with the `ews` feature,
+//! `EwsClient`; new transports (JMAP, Microsoft Graph, POP3) only need to
+//! implement it.
+//!
+//! [`MailClient`] runs the wait/find operations over any backend, including a
+//! `Box<dyn MailBackend>` chosen at runtime. Features tied to IMAP (Gmail
+//! labels, threads, flags, quotas) stay on [`ImapEmailClient`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::backend::{MailBackend, MailClient};
+//! use email_sync::maildir::MaildirBackend;
+//! use email_sync::matcher::OtpMatcher;
+//! use email_sync::{ImapConfig, ImapEmailClient};
+//!
+//! # async fn example(offline: bool, config: ImapConfig) -> email_sync::Result<()> {
+//! let backend: Box<dyn MailBackend> = if offline {
+//!     Box::new(MaildirBackend::open("/home/ci/Maildir").await?)
+//! } else {
+//!     Box::new(ImapEmailClient::connect(config).await?)
+//! };
+//!
+//! let mut client = MailClient::new(backend);
+//! let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+//! println!("Got code: {code}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ImapEmailClient`]: crate::ImapEmailClient
+
+use crate::config::PollingConfig;
+use crate::email::WaitReport;
+use crate::error::{Error, Result};
+use crate::matcher::Matcher;
+use crate::parser::{ExtractResult, Extractor};
+use crate::runtime;
+use futures::future::BoxFuture;
+use secrecy::SecretString;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, instrument, warn};
+
+/// A fetched message: its key and raw RFC 5322 content.
+pub type RawMessage = (String, Vec<u8>);
+
+/// Number of messages fetched at once while scanning.
+const FETCH_BATCH: usize = 10;
+
+/// Operations a mail store provides to the wait/find logic.
+///
+/// Messages are identified by opaque string keys that are only meaningful to the
+/// backend that returned them (an IMAP UID, a Maildir unique name, an EWS item
+/// ID). Methods return boxed futures so the trait can be used as
+/// `dyn MailBackend`.
+///
+/// See the [module documentation](self).
+pub trait MailBackend: Send {
+    /// Re-establishes the connection after a transient error.
+    ///
+    /// The baseline of new messages is kept. Backends without a connection do
+    /// nothing.
+    fn connect(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Selects `mailbox` for the following operations.
+    ///
+    /// Backends with a single mailbox only accept `INBOX`.
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Returns the keys of the messages that arrived since the baseline and were
+    /// not returned by an earlier call, oldest first.
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    /// Returns the keys of the messages received within `max_age`, newest
+    /// first.
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    /// Fetches the raw RFC 5322 content of the messages `keys`, in order.
+    ///
+    /// Messages that no longer exist are left out.
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>>;
+
+    /// Waits up to `timeout` before the next check for new messages.
+    ///
+    /// Backends that are notified of new messages (IMAP IDLE) may return early;
+    /// the default sleeps for `timeout`.
+    fn idle(&mut self, timeout: Duration) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            runtime::sleep(timeout).await;
+            Ok(())
+        })
+    }
+
+    /// Closes the connection. Backends without a connection do nothing.
+    fn logout(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl<B: MailBackend + ?Sized> MailBackend for Box<B> {
+    fn connect(&mut self) -> BoxFuture<'_, Result<()>> {
+        (**self).connect()
+    }
+
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+        (**self).select(mailbox)
+    }
+
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+        (**self).search_new()
+    }
+
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+        (**self).search_recent(max_age)
+    }
+
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+        (**self).fetch(keys)
+    }
+
+    fn idle(&mut self, timeout: Duration) -> BoxFuture<'_, Result<()>> {
+        (**self).idle(timeout)
+    }
+
+    fn logout(&mut self) -> BoxFuture<'_, Result<()>> {
+        (**self).logout()
+    }
+}
+
+/// Wait/find operations over any [`MailBackend`].
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct MailClient<B = Box<dyn MailBackend>> {
+    backend: B,
+    polling: PollingConfig,
+    normalize_text: bool,
+}
+
+impl<B: MailBackend> MailClient<B> {
+    /// Creates a client over `backend` with the default polling settings.
+    #[must_use]
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            polling: PollingConfig::default(),
+            normalize_text: false,
+        }
+    }
+
+    /// Sets the polling interval and maximum wait of the wait methods.
+    #[must_use]
+    pub fn polling(mut self, polling: PollingConfig) -> Self {
+        self.polling = polling;
+        self
+    }
+
+    /// Applies NFKC normalization and strips invisible characters before
+    /// matching, like [`ImapConfigBuilder::normalize_text`](crate::ImapConfigBuilder::normalize_text).
+    #[must_use]
+    pub fn normalize_text(mut self, enabled: bool) -> Self {
+        self.normalize_text = enabled;
+        self
+    }
+
+    /// Waits for a new message matching the provided pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - The backend fails with a permanent error
+    #[instrument(
+        name = "MailClient::wait_for_match",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
+        self.wait_for_any(&[matcher]).await.map(|(_, value)| value)
+    }
+
+    /// Waits for a new message matching the provided pattern, returning the
+    /// value as a [`SecretString`] that is redacted when debug-formatted.
+    ///
+    /// See [`ImapEmailClient::wait_for_secret_match`](crate::ImapEmailClient::wait_for_secret_match).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`wait_for_match`](Self::wait_for_match).
+    pub async fn wait_for_secret_match(&mut self, matcher: &dyn Matcher) -> Result<SecretString> {
+        self.wait_for_match(matcher).await.map(SecretString::from)
+    }
+
+    /// Waits for a new message matching any of the provided patterns.
+    ///
+    /// Returns the index of the matcher that fired (into `matchers`) together
+    /// with the extracted value. Transient errors reconnect the backend and are
+    /// retried until the wait times out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - The backend fails with a permanent error
+    #[instrument(
+        name = "MailClient::wait_for_any",
+        skip(self, matchers),
+        fields(matchers = matchers.len())
+    )]
+    pub async fn wait_for_any(&mut self, matchers: &[&dyn Matcher]) -> Result<(usize, String)> {
+        let extract = Extractor::local(self.normalize_text);
+        wait_for_any(&mut self.backend, matchers, &self.polling, extract).await
+    }
+
+    /// Finds a matching message among those received within `max_age`, newest
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching message is found, or the
+    /// backend error if searching or fetching fails.
+    #[instrument(
+        name = "MailClient::find_recent_match",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<String> {
+        let extract = Extractor::local(self.normalize_text);
+        find_recent_match(&mut self.backend, matcher, max_age, extract).await
+    }
+
+    /// Returns the backend.
+    #[must_use]
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Returns the backend mutably, e.g. to [`select`](MailBackend::select)
+    /// another mailbox.
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Returns the backend, consuming the client.
+    #[must_use]
+    pub fn into_backend(self) -> B {
+        self.backend
+    }
+}
+
+/// Polls `backend` until a new message matches one of `matchers` or
+/// `polling.max_wait` elapses.
+///
+/// Transient errors reconnect the backend; messages whose fetch failed are
+/// checked on the next poll.
+pub(crate) async fn wait_for_any<B: MailBackend + ?Sized>(
+    backend: &mut B,
+    matchers: &[&dyn Matcher],
+    polling: &PollingConfig,
+    mut extract: Extractor,
+) -> Result<(usize, String)> {
+    if matchers.is_empty() {
+        return Err(Error::InvalidConfig {
+            message: "at least one matcher is required".into(),
+        });
+    }
+
+    let started = Instant::now();
+    let max_wait = polling.max_wait;
+    let mut report = WaitReport::default();
+    let mut pending = Vec::new();
+
+    loop {
+        report.polls += 1;
+        match poll(backend, matchers, &mut extract, &mut pending, &mut report).await {
+            Ok(Some((index, value))) => {
+                info!(
+                    matcher = %matchers[index].description(),
+                    polls = report.polls,
+                    "Found matching email"
+                );
+                return Ok((index, value));
+            }
+            Ok(None) => {}
+            Err(e) if e.is_retryable() => {
+                warn!(error = %e, "Poll failed, reconnecting");
+                report.reconnects += 1;
+                match backend.connect().await {
+                    Ok(()) => {}
+                    Err(e) if e.is_retryable() => warn!(error = %e, "Reconnection failed"),
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        let Some(remaining) = max_wait.checked_sub(started.elapsed()) else {
+            return Err(Error::WaitTimeout {
+                timeout: max_wait,
+                report: report.finish(started),
+            });
+        };
+        backend.idle(polling.interval.min(remaining)).await?;
+    }
+}
+
+/// Checks the messages in `pending` and those that arrived since the last poll,
+/// oldest first, and returns the first match.
+///
+/// Messages that could not be fetched are left in `pending`.
+async fn poll<B: MailBackend + ?Sized>(
+    backend: &mut B,
+    matchers: &[&dyn Matcher],
+    extract: &mut Extractor,
+    pending: &mut Vec<String>,
+    report: &mut WaitReport,
+) -> Result<Option<(usize, String)>> {
+    pending.extend(backend.search_new().await?);
+    if pending.is_empty() {
+        return Ok(None);
+    }
+    debug!(count = pending.len(), "Found new emails");
+
+    let keys = std::mem::take(pending);
+    for (at, batch) in keys.chunks(FETCH_BATCH).enumerate() {
+        let messages = match backend.fetch(batch).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                pending.extend_from_slice(&keys[at * FETCH_BATCH..]);
+                return Err(e);
+            }
+        };
+
+        for (_, raw) in messages {
+            report.messages_scanned += 1;
+            if let ExtractResult::Match { index, details } =
+                extract.extract_raw_match(&raw, matchers)
+            {
+                return Ok(Some((index, details.value.into_owned())));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Checks the messages received within `max_age`, newest first, and returns
+/// the value of the first match.
+pub(crate) async fn find_recent_match<B: MailBackend + ?Sized>(
+    backend: &mut B,
+    matcher: &dyn Matcher,
+    max_age: Duration,
+    mut extract: Extractor,
+) -> Result<String> {
+    let keys = backend.search_recent(max_age).await?;
+
+    for batch in keys.chunks(FETCH_BATCH) {
+        for (_, raw) in backend.fetch(batch).await? {
+            if let ExtractResult::Match { details, .. } =
+                extract.extract_raw_match(&raw, &[matcher])
+            {
+                return Ok(details.value.into_owned());
+            }
+        }
+    }
+
+    Err(Error::NoMatch)
+}
+
+/// Accepts `INBOX` (case-insensitive), the only mailbox of `backend`.
+pub(crate) fn select_inbox(backend: &str, mailbox: &str) -> Result<()> {
+    if mailbox.eq_ignore_ascii_case("INBOX") {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig {
+            message: format!("{backend} only has an INBOX, cannot select '{mailbox}'"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::OtpMatcher;
+    use secrecy::ExposeSecret;
+    use std::collections::VecDeque;
+
+    /// In-memory backend delivering one batch of messages per search.
+    #[derive(Default)]
+    struct FakeBackend {
+        deliveries: VecDeque<Vec<&'static str>>,
+        messages: Vec<&'static str>,
+        /// Number of fetches that fail with a transient error.
+        failing_fetches: u32,
+        connects: u32,
+    }
+
+    impl MailBackend for FakeBackend {
+        fn connect(&mut self) -> BoxFuture<'_, Result<()>> {
+            self.connects += 1;
+            Box::pin(async { Ok(()) })
+        }
+
+        fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move { select_inbox("fake backend", mailbox) })
+        }
+
+        fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+            let delivered = self.deliveries.pop_front().unwrap_or_default();
+            let first = self.messages.len();
+            self.messages.extend(delivered);
+            let keys = (first..self.messages.len())
+                .map(|key| key.to_string())
+                .collect();
+            Box::pin(async move { Ok(keys) })
+        }
+
+        fn search_recent(&mut self, _max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+            let keys = (0..self.messages.len())
+                .rev()
+                .map(|key| key.to_string())
+                .collect();
+            Box::pin(async move { Ok(keys) })
+        }
+
+        fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+            Box::pin(async move {
+                if self.failing_fetches > 0 {
+                    self.failing_fetches -= 1;
+                    return Err(Error::HttpTimeout {
+                        url: "https://fake.example/".into(),
+                        timeout: Duration::from_secs(1),
+                    });
+                }
+                Ok(keys
+                    .iter()
+                    .map(|key| {
+                        let body = self.messages[key.parse::<usize>().unwrap()];
+                        (
+                            key.clone(),
+                            format!("Subject: Test\r\n\r\n{body}\r\n").into_bytes(),
+                        )
+                    })
+                    .collect())
+            })
+        }
+    }
+
+    fn fast_polling() -> PollingConfig {
+        PollingConfig {
+            interval: Duration::from_millis(1),
+            max_wait: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_retries_failed_fetch() {
+        let backend = FakeBackend {
+            deliveries: VecDeque::from([vec!["No code"], vec![], vec!["Your code is 123456"]]),
+            failing_fetches: 1,
+            ..FakeBackend::default()
+        };
+        let mut client =
+            MailClient::new(Box::new(backend) as Box<dyn MailBackend>).polling(fast_polling());
+
+        let code = client
+            .wait_for_match(&OtpMatcher::six_digit())
+            .await
+            .unwrap();
+        assert_eq!(code, "123456");
+    }
+
+    #[tokio::test]
+    async fn test_wait_reconnects_after_transient_error() {
+        let backend = FakeBackend {
+            deliveries: VecDeque::from([vec!["Your code is 123456"]]),
+            failing_fetches: 2,
+            ..FakeBackend::default()
+        };
+        let mut client = MailClient::new(backend).polling(fast_polling());
+
+        let code = client
+            .wait_for_match(&OtpMatcher::six_digit())
+            .await
+            .unwrap();
+        assert_eq!(code, "123456");
+        assert_eq!(client.backend().connects, 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_secret_match() {
+        let backend = FakeBackend {
+            deliveries: VecDeque::from([vec!["Your code is 654321"]]),
+            ..FakeBackend::default()
+        };
+        let mut client = MailClient::new(backend).polling(fast_polling());
+
+        let code = client
+            .wait_for_secret_match(&OtpMatcher::six_digit())
+            .await
+            .unwrap();
+        assert_eq!(code.expose_secret(), "654321");
+        assert!(!format!("{code:?}").contains("654321"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_timeout_and_find_recent() {
+        let backend = FakeBackend {
+            deliveries: VecDeque::from([vec!["Code 111111", "Code 222222"]]),
+            ..FakeBackend::default()
+        };
+        let mut client = MailClient::new(backend).polling(PollingConfig {
+            interval: Duration::from_millis(1),
+            max_wait: Duration::from_millis(20),
+        });
+
+        let result = client.wait_for_match(&OtpMatcher::n_digit(8)).await;
+        assert!(matches!(
+            result,
+            Err(Error::WaitTimeout { report, .. }) if report.messages_scanned == 2
+        ));
+
+        // Newest first
+        let code = client
+            .find_recent_match(&OtpMatcher::six_digit(), Duration::from_hours(1))
+            .await
+            .unwrap();
+        assert_eq!(code, "222222");
+    }
+
+    #[tokio::test]
+    async fn test_select_inbox_only() {
+        let mut backend = FakeBackend::default();
+        assert!(backend.select("inbox").await.is_ok());
+        assert!(matches!(
+            backend.select("Archive").await,
+            Err(Error::InvalidConfig { .. })
+        ));
+    }
+}
@@ -0,0 +1,491 @@
+//! `email-sync` command-line interface.
+//!
+//! Thin wrapper around the library for shell-based pipelines.
+//!
+//! # Configuration
+//!
+//! Settings are read from an optional TOML file (`--config` or `EMAIL_SYNC_CONFIG`)
+//! and then overridden by environment variables:
+//!
+//! | Variable                   | File key          |
+//! |----------------------------|-------------------|
+//! | `EMAIL_SYNC_EMAIL`         | `email`           |
+//! | `EMAIL_SYNC_PASSWORD`      | `password`        |
+//! | `EMAIL_SYNC_IMAP_HOST`     | `imap_host`       |
+//! | `EMAIL_SYNC_IMAP_PORT`     | `imap_port`       |
+//! | `EMAIL_SYNC_RECIPIENT`     | `recipient_alias` |
+//! | `EMAIL_SYNC_PROXY_HOST`    | `proxy.host`      |
+//! | `EMAIL_SYNC_PROXY_PORT`    | `proxy.port`      |
+//! | `EMAIL_SYNC_PROXY_USER`    | `proxy.username`  |
+//! | `EMAIL_SYNC_PROXY_PASS`    | `proxy.password`  |
+//!
+//! # Exit codes
+//!
+//! - `0` - success
+//! - `1` - error (configuration, network, protocol, ...)
+//! - `2` - no matching email found (or wait timed out)
+
+use clap::{Args, Parser, Subcommand};
+use email_sync::known_servers::ServerRegistry;
+use email_sync::matcher::{
+    Matcher, MatcherRegistry, MatcherSpec, OtpMatcher, RegexMatcher, UrlMatcher,
+};
+use email_sync::{
+    Error, ErrorCategory, ImapConfig, ImapConfigBuilder, ImapEmailClient, Socks5Proxy,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "email-sync",
+    version,
+    about = "Wait for and extract content from IMAP mailboxes"
+)]
+struct Cli {
+    /// Path to a TOML configuration file.
+    #[arg(long, short, env = "EMAIL_SYNC_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    /// Enable debug logging to stderr.
+    #[arg(long, short, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Wait for a new email containing an N-digit OTP code and print it.
+    WaitOtp {
+        /// Number of digits in the code.
+        #[arg(long, default_value_t = 6, value_parser = positive_usize())]
+        digits: usize,
+
+        /// Maximum time to wait, in seconds.
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+
+        /// Polling interval, in seconds.
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+
+    /// Search recent emails and print the first match.
+    FindRecent {
+        #[command(flatten)]
+        matcher: MatcherArgs,
+
+        /// Only consider emails newer than this many seconds.
+        #[arg(long, default_value_t = 3600)]
+        max_age: u64,
+    },
+
+    /// List all mailboxes of the account.
+    ListMailboxes,
+
+    /// Show the IMAP host discovered for a domain and check that it is reachable.
+    Probe {
+        /// Email domain (e.g. `gmail.com`) or full email address.
+        domain: String,
+
+        /// IMAP port to probe.
+        #[arg(long, default_value_t = 993)]
+        port: u16,
+
+        /// Connection timeout, in seconds.
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+    },
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+struct MatcherArgs {
+    /// Regex whose first capture group is printed.
+    #[arg(long)]
+    regex: Option<String>,
+
+    /// Print the first URL pointing at this domain.
+    #[arg(long)]
+    url: Option<String>,
+
+    /// Print the first N-digit OTP code.
+    #[arg(long, value_parser = positive_usize())]
+    otp: Option<usize>,
+
+    /// Use a named matcher (`otp4`, `otp6`, `otp8`, `github-link`, or one
+    /// defined under `[matchers]` in the configuration file).
+    #[arg(long)]
+    matcher: Option<String>,
+}
+
+impl MatcherArgs {
+    fn build(&self, registry: &MatcherRegistry) -> Result<Box<dyn Matcher>, String> {
+        if let Some(pattern) = &self.regex {
+            return RegexMatcher::new(pattern)
+                .map(|m| Box::new(m) as Box<dyn Matcher>)
+                .map_err(|e| format!("invalid regex: {e}"));
+        }
+        if let Some(domain) = &self.url {
+            return Ok(Box::new(UrlMatcher::new(domain)));
+        }
+        if let Some(name) = &self.matcher {
+            return registry.create(name).ok_or_else(|| {
+                format!(
+                    "unknown matcher '{name}' (available: {})",
+                    registry.names().join(", ")
+                )
+            });
+        }
+        match self.otp {
+            Some(digits) => Ok(Box::new(OtpMatcher::n_digit(digits))),
+            None => Err("one of --regex, --url, --otp or --matcher is required".into()),
+        }
+    }
+}
+
+/// Parser for counts that must be at least 1, such as OTP digits.
+fn positive_usize() -> clap::builder::RangedU64ValueParser<usize> {
+    clap::builder::RangedU64ValueParser::new().range(1..)
+}
+
+/// Contents of the optional TOML configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    email: Option<String>,
+    password: Option<String>,
+    imap_host: Option<String>,
+    imap_port: Option<u16>,
+    recipient_alias: Option<String>,
+    proxy: Option<FileProxy>,
+    /// Named matchers, e.g. `[matchers.order-id]` with `type = "regex"`.
+    #[serde(default)]
+    matchers: HashMap<String, MatcherSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProxy {
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl FileConfig {
+    fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        toml::from_str(&contents).map_err(|e| format!("failed to parse {}: {e}", path.display()))
+    }
+
+    /// Applies `EMAIL_SYNC_*` environment variable overrides.
+    fn merge_env(mut self) -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok();
+
+        if let Some(email) = var("EMAIL_SYNC_EMAIL") {
+            self.email = Some(email);
+        }
+        if let Some(password) = var("EMAIL_SYNC_PASSWORD") {
+            self.password = Some(password);
+        }
+        if let Some(host) = var("EMAIL_SYNC_IMAP_HOST") {
+            self.imap_host = Some(host);
+        }
+        if let Some(port) = var("EMAIL_SYNC_IMAP_PORT") {
+            self.imap_port = Some(parse_port("EMAIL_SYNC_IMAP_PORT", &port)?);
+        }
+        if let Some(alias) = var("EMAIL_SYNC_RECIPIENT") {
+            self.recipient_alias = Some(alias);
+        }
+        if let Some(host) = var("EMAIL_SYNC_PROXY_HOST") {
+            let port = var("EMAIL_SYNC_PROXY_PORT")
+                .ok_or("EMAIL_SYNC_PROXY_PORT is required when EMAIL_SYNC_PROXY_HOST is set")?;
+            self.proxy = Some(FileProxy {
+                host,
+                port: parse_port("EMAIL_SYNC_PROXY_PORT", &port)?,
+                username: var("EMAIL_SYNC_PROXY_USER"),
+                password: var("EMAIL_SYNC_PROXY_PASS"),
+            });
+        }
+
+        Ok(self)
+    }
+
+    fn into_builder(self) -> ImapConfigBuilder {
+        let mut builder = ImapConfig::builder();
+
+        if let Some(email) = self.email {
+            builder = builder.email(email);
+        }
+        if let Some(password) = self.password {
+            builder = builder.password(password);
+        }
+        if let Some(host) = self.imap_host {
+            builder = builder.imap_host(host);
+        }
+        if let Some(port) = self.imap_port {
+            builder = builder.imap_port(port);
+        }
+        if let Some(alias) = self.recipient_alias {
+            builder = builder.recipient_alias(alias);
+        }
+        if let Some(proxy) = self.proxy {
+            builder = builder.proxy(match (proxy.username, proxy.password) {
+                (Some(user), Some(pass)) => {
+                    Socks5Proxy::with_auth(proxy.host, proxy.port, user, pass)
+                }
+                _ => Socks5Proxy::new(proxy.host, proxy.port),
+            });
+        }
+
+        builder
+    }
+}
+
+fn parse_port(name: &str, value: &str) -> Result<u16, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{name} must be a valid port number, got '{value}'"))
+}
+
+fn load_config(
+    path: Option<&Path>,
+    command: &Command,
+) -> Result<(ImapConfig, MatcherRegistry), String> {
+    let mut file = match path {
+        Some(path) => FileConfig::load(path)?,
+        None => FileConfig::default(),
+    };
+
+    let mut registry = MatcherRegistry::with_defaults();
+    for (name, spec) in std::mem::take(&mut file.matchers) {
+        registry
+            .register_spec(&name, &spec)
+            .map_err(|e| format!("matcher '{name}': {e}"))?;
+    }
+
+    Ok((imap_config(file.merge_env()?, command)?, registry))
+}
+
+/// Builds the client configuration, with the polling settings of `command`.
+fn imap_config(file: FileConfig, command: &Command) -> Result<ImapConfig, String> {
+    let mut builder = file.into_builder();
+    if let Command::WaitOtp {
+        timeout, interval, ..
+    } = command
+    {
+        builder = builder
+            .max_wait(Duration::from_secs(*timeout))
+            .poll_interval(Duration::from_secs(*interval));
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// What a subcommand does once connected, with its arguments validated.
+enum Action {
+    WaitFor(Box<dyn Matcher>),
+    FindRecent(Box<dyn Matcher>, Duration),
+    ListMailboxes,
+}
+
+impl Action {
+    fn new(command: Command, matchers: &MatcherRegistry) -> Result<Self, String> {
+        match command {
+            Command::WaitOtp { digits, .. } => {
+                Ok(Self::WaitFor(Box::new(OtpMatcher::n_digit(digits))))
+            }
+            Command::FindRecent { matcher, max_age } => Ok(Self::FindRecent(
+                matcher.build(matchers)?,
+                Duration::from_secs(max_age),
+            )),
+            Command::ListMailboxes => Ok(Self::ListMailboxes),
+            Command::Probe { .. } => unreachable!("probe does not need a client"),
+        }
+    }
+}
+
+/// Outcome of a subcommand, mapped to the process exit code.
+enum Outcome {
+    Done,
+    NotFound(String),
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    if cli.verbose {
+        tracing_subscriber::fmt()
+            .with_env_filter("email_sync=debug")
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
+    match run(cli).await {
+        Ok(Outcome::Done) => ExitCode::SUCCESS,
+        Ok(Outcome::NotFound(message)) => {
+            eprintln!("{message}");
+            ExitCode::from(2)
+        }
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<Outcome, String> {
+    match cli.command {
+        Command::Probe {
+            domain,
+            port,
+            timeout,
+        } => probe(&domain, port, Duration::from_secs(timeout)).await,
+        command => {
+            // Everything is validated before connecting
+            let (config, matchers) = load_config(cli.config.as_deref(), &command)?;
+            let action = Action::new(command, &matchers)?;
+            with_client(config, action).await
+        }
+    }
+}
+
+async fn with_client(config: ImapConfig, action: Action) -> Result<Outcome, String> {
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .map_err(|e| describe(&e))?;
+
+    let result = match action {
+        Action::WaitFor(matcher) => client
+            .wait_for_match(matcher.as_ref())
+            .await
+            .map(|code| println!("{code}")),
+        Action::FindRecent(matcher, max_age) => client
+            .find_recent_match(matcher.as_ref(), max_age)
+            .await
+            .map(|value| println!("{value}")),
+        Action::ListMailboxes => client
+            .list_mailboxes()
+            .await
+            .map(|mailboxes| mailboxes.iter().for_each(|m| println!("{m}"))),
+    };
+
+    if let Err(e) = client.logout().await {
+        tracing::warn!(error = %e, "Logout failed");
+    }
+
+    match result {
+        Ok(()) => Ok(Outcome::Done),
+        Err(e)
+            if e.category() == ErrorCategory::NotFound
+                || matches!(e, Error::WaitTimeout { .. }) =>
+        {
+            Ok(Outcome::NotFound(e.to_string()))
+        }
+        Err(e) => Err(describe(&e)),
+    }
+}
+
+async fn probe(domain: &str, port: u16, timeout: Duration) -> Result<Outcome, String> {
+    let registry = ServerRegistry::with_defaults();
+    let domain = domain.rsplit('@').next().unwrap_or(domain);
+    let host = registry.discover(domain);
+
+    println!("domain:    {domain}");
+    println!("imap_host: {host}");
+    println!("known:     {}", registry.is_known(domain));
+
+    let address = format!("{host}:{port}");
+    let started = std::time::Instant::now();
+
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(&address)).await {
+        Ok(Ok(_)) => {
+            println!("reachable: yes ({} ms)", started.elapsed().as_millis());
+            Ok(Outcome::Done)
+        }
+        Ok(Err(e)) => Err(format!("{address} is not reachable: {e}")),
+        Err(_) => Err(format!("{address} did not respond within {timeout:?}")),
+    }
+}
+
+/// Formats an error together with its source chain.
+fn describe(error: &Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("email-sync").chain(args.iter().copied()))
+    }
+
+    fn file_config() -> FileConfig {
+        FileConfig {
+            email: Some("user@example.com".into()),
+            password: Some("app-password".into()),
+            ..FileConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_wait_otp_args() {
+        let cli = parse(&["wait-otp", "--digits", "8", "--interval", "5"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::WaitOtp {
+                digits: 8,
+                timeout: 300,
+                interval: 5
+            }
+        ));
+
+        assert!(parse(&["wait-otp", "--digits", "0"]).is_err());
+        assert!(parse(&["wait-otp", "--interval", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_find_recent_args() {
+        assert!(parse(&["find-recent", "--otp", "6"]).is_ok());
+        assert!(parse(&["find-recent", "--otp", "0"]).is_err());
+        assert!(parse(&["find-recent"]).is_err());
+        assert!(parse(&["find-recent", "--otp", "6", "--url", "example.com"]).is_err());
+
+        let cli = parse(&["find-recent", "--matcher", "no-such-matcher"]).unwrap();
+        let result = Action::new(cli.command, &MatcherRegistry::with_defaults());
+        assert!(result.is_err_and(|e| e.contains("unknown matcher")));
+    }
+
+    #[test]
+    fn test_polling_settings_are_validated() {
+        let cli = parse(&["wait-otp", "--timeout", "60", "--interval", "5"]).unwrap();
+        let config = imap_config(file_config(), &cli.command).unwrap();
+        assert_eq!(config.polling.max_wait, Duration::from_secs(60));
+        assert_eq!(config.polling.interval, Duration::from_secs(5));
+
+        // The interval must not exceed the timeout
+        let cli = parse(&["wait-otp", "--timeout", "5", "--interval", "10"]).unwrap();
+        assert!(imap_config(file_config(), &cli.command).is_err());
+    }
+}
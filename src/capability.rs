@@ -0,0 +1,193 @@
+//! Server capability introspection.
+//!
+//! See [`ImapEmailClient::capabilities`](crate::ImapEmailClient::capabilities).
+
+use std::collections::HashSet;
+
+/// Extensions turned on with `ENABLE` (RFC 5161) after login when the server
+/// advertises them.
+pub(crate) const NEGOTIATED_EXTENSIONS: [&str; 3] = ["CONDSTORE", "QRESYNC", "UTF8=ACCEPT"];
+
+/// Largest non-synchronizing literal allowed by `LITERAL-` (RFC 7888).
+const MAX_LITERAL_MINUS: usize = 4096;
+
+/// Capabilities advertised by an IMAP server in response to `CAPABILITY`.
+///
+/// Names are compared case-insensitively; authentication mechanisms appear as
+/// `AUTH=<MECHANISM>` (e.g. `AUTH=PLAIN`).
+///
+/// Right after login the client enables the useful extensions among
+/// `CONDSTORE`, `QRESYNC` and `UTF8=ACCEPT`; [`is_enabled`](Self::is_enabled)
+/// tells which ones the server accepted.
+///
+/// # Example
+///
+/// ```no_run
+/// use email_sync::{ImapConfig, ImapEmailClient};
+///
+/// # async fn example() -> email_sync::Result<()> {
+/// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+/// let mut client = ImapEmailClient::connect(config).await?;
+///
+/// let capabilities = client.capabilities().await?;
+/// if capabilities.supports_idle() {
+///     println!("server can push new mail");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    names: HashSet<String>,
+    enabled: HashSet<String>,
+}
+
+impl Capabilities {
+    /// Creates a capability set from capability names.
+    pub(crate) fn new(names: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            names: names
+                .into_iter()
+                .map(|name| name.to_ascii_uppercase())
+                .collect(),
+            enabled: HashSet::new(),
+        }
+    }
+
+    /// Returns the extensions of [`NEGOTIATED_EXTENSIONS`] worth enabling on this
+    /// server: those it advertises, provided it supports `ENABLE`.
+    pub(crate) fn negotiable(&self) -> Vec<&'static str> {
+        if !self.has("ENABLE") {
+            return Vec::new();
+        }
+        NEGOTIATED_EXTENSIONS
+            .into_iter()
+            .filter(|name| self.has(name))
+            .collect()
+    }
+
+    /// Records the extensions the server reported as enabled.
+    pub(crate) fn set_enabled(&mut self, names: impl IntoIterator<Item = String>) {
+        self.enabled = names
+            .into_iter()
+            .map(|name| name.to_ascii_uppercase())
+            .collect();
+    }
+
+    /// Returns `true` if the server advertises `name` (case-insensitive).
+    #[must_use]
+    pub fn has(&self, name: &str) -> bool {
+        self.names.contains(&name.to_ascii_uppercase())
+    }
+
+    /// Returns `true` if the server supports `IDLE` push notifications (RFC 2177).
+    #[must_use]
+    pub fn supports_idle(&self) -> bool {
+        self.has("IDLE")
+    }
+
+    /// Returns `true` if the server supports the `MOVE` command (RFC 6851).
+    #[must_use]
+    pub fn supports_move(&self) -> bool {
+        self.has("MOVE")
+    }
+
+    /// Returns `true` if the server supports `CONDSTORE` (RFC 7162).
+    #[must_use]
+    pub fn supports_condstore(&self) -> bool {
+        self.has("CONDSTORE")
+    }
+
+    /// Returns `true` if the server accepts non-synchronizing literals of any size
+    /// (`LITERAL+`, RFC 7888).
+    #[must_use]
+    pub fn supports_literal_plus(&self) -> bool {
+        self.has("LITERAL+")
+    }
+
+    /// Returns `true` if a literal of `len` bytes may be sent without waiting for
+    /// a continuation request: always with `LITERAL+`, up to 4096 bytes with
+    /// `LITERAL-`.
+    pub(crate) fn accepts_non_sync_literal(&self, len: usize) -> bool {
+        self.supports_literal_plus() || (self.has("LITERAL-") && len <= MAX_LITERAL_MINUS)
+    }
+
+    /// Returns `true` if the extension `name` was enabled on this session with
+    /// `ENABLE` (case-insensitive).
+    #[must_use]
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.contains(&name.to_ascii_uppercase())
+    }
+
+    /// Returns `true` if UTF-8 strings may be sent in commands (`UTF8=ACCEPT`,
+    /// RFC 6855, enabled on this session).
+    #[must_use]
+    pub fn utf8_enabled(&self) -> bool {
+        self.is_enabled("UTF8=ACCEPT")
+    }
+
+    /// Returns `true` if the server supports the SASL mechanism `mechanism`.
+    #[must_use]
+    pub fn supports_auth(&self, mechanism: &str) -> bool {
+        self.has(&format!("AUTH={mechanism}"))
+    }
+
+    /// Iterates over the advertised capability names (uppercased, in no particular order).
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// Returns the number of advertised capabilities.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if no capabilities were advertised.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_lookup() {
+        let capabilities = Capabilities::new(
+            ["IMAP4rev1", "idle", "Move", "AUTH=PLAIN"]
+                .into_iter()
+                .map(String::from),
+        );
+
+        assert!(capabilities.has("imap4rev1"));
+        assert!(capabilities.supports_idle());
+        assert!(capabilities.supports_move());
+        assert!(!capabilities.supports_condstore());
+        assert!(capabilities.supports_auth("plain"));
+        assert_eq!(capabilities.len(), 4);
+    }
+
+    #[test]
+    fn test_negotiable_extensions() {
+        let mut capabilities = Capabilities::new(
+            ["IMAP4rev1", "ENABLE", "CONDSTORE", "utf8=accept"]
+                .into_iter()
+                .map(String::from),
+        );
+        assert_eq!(capabilities.negotiable(), ["CONDSTORE", "UTF8=ACCEPT"]);
+        assert!(!capabilities.utf8_enabled());
+
+        capabilities.set_enabled(["utf8=accept".to_string()]);
+        assert!(capabilities.utf8_enabled());
+        assert!(!capabilities.is_enabled("CONDSTORE"));
+
+        assert!(!capabilities.accepts_non_sync_literal(1));
+
+        // Nothing can be enabled without ENABLE
+        let capabilities = Capabilities::new(["CONDSTORE".to_string()]);
+        assert!(capabilities.negotiable().is_empty());
+    }
+}
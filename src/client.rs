@@ -32,16 +32,268 @@
 //! # }
 //! ```
 
-use crate::config::ImapConfig;
-use crate::connection;
+use crate::backend::{MailBackend, RawMessage};
+use crate::capability::Capabilities;
+use crate::config::{
+    ExpungeMode, ImapConfig, Order, RetryConfig, SearchOptions, TimeoutConfig, WaitOptions,
+};
+use crate::connection::{self, ConnectStats, TlsStream};
+use crate::email::{EmailMessage, MatchedEmail, MessageSummary, WaitReport};
 use crate::error::{Error, Result};
-use crate::matcher::Matcher;
-use crate::parser::{self, ExtractResult};
+use crate::events::{self, Event, Operation, WaitProgress};
+use crate::filter::Filter;
+use crate::handle::ImapEmailClientHandle;
+use crate::mailbox::{MailboxStatus, Namespace, Quota};
+use crate::matcher::{MatchDetails, Matcher};
+use crate::parser::{self, ExtractResult, Extractor};
+use crate::proxy::ProxyChoice;
+use crate::runtime;
 use crate::session::{self, AuthConfig, ImapSession};
-use chrono::{NaiveDate, Utc};
+use crate::shutdown::TaskGuard;
+use async_imap::types::Fetch;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use futures::future::BoxFuture;
 use futures::StreamExt;
+use secrecy::SecretString;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::ops::ControlFlow;
 use std::time::{Duration, Instant};
-use tracing::{debug, instrument, warn};
+use tracing::{debug, info, info_span, instrument, warn, Instrument, Span};
+
+/// Returns `true` if `flag` is a single IMAP flag atom, optionally prefixed with `\`.
+fn is_valid_flag(flag: &str) -> bool {
+    let atom = flag.strip_prefix('\\').unwrap_or(flag);
+    !atom.is_empty()
+        && atom
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !b"(){%*\"\\]".contains(&b))
+}
+
+/// Runs an IMAP command future, failing with [`Error::CommandTimeout`] after `timeout`.
+async fn with_command_timeout<T>(
+    timeout: Duration,
+    command: &str,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    runtime::timeout(timeout, future)
+        .await
+        .map_err(|_| Error::CommandTimeout {
+            command: command.into(),
+            timeout,
+        })?
+}
+
+/// Evaluates to a future that awaits `$attempt` (re-evaluated on each retry)
+/// until it succeeds, fails with an error that is not retryable or the
+/// [`RetryConfig`] allows no further retry.
+///
+/// Callers bound the whole future with the command timeout. A macro rather than
+/// a function taking an async closure, whose futures the compiler cannot prove
+/// `Send` for the client handle's task.
+macro_rules! with_retries {
+    ($retry:expr, $command:literal, $attempt:expr) => {
+        async {
+            let retry: &RetryConfig = $retry;
+            let mut retries = 0;
+            loop {
+                let result: Result<_> = $attempt.await;
+                match result {
+                    Err(e) if e.is_retryable() => {
+                        retries += 1;
+                        let Some(delay) = retry.delay(retries) else {
+                            break Err(e);
+                        };
+                        warn!(
+                            error = %e,
+                            command = $command,
+                            retry = retries,
+                            ?delay,
+                            "Command failed, retrying"
+                        );
+                        runtime::sleep(delay).await;
+                    }
+                    result => break result,
+                }
+            }
+        }
+    };
+}
+
+/// Creates the spans of the polls of a wait.
+///
+/// With the `observability` feature, each span is linked to the span of the
+/// previous poll, so trace viewers show a long wait as a sequence of polls.
+#[derive(Default)]
+struct PollSpans {
+    #[cfg(feature = "observability")]
+    previous: Option<opentelemetry::trace::SpanContext>,
+}
+
+impl PollSpans {
+    /// Returns the span of poll `poll` of a wait ending at `deadline`.
+    #[cfg_attr(not(feature = "observability"), allow(clippy::unused_self))]
+    fn next(&mut self, poll: u32, deadline: Instant) -> Span {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let remaining_ms = u64::try_from(remaining.as_millis()).unwrap_or(u64::MAX);
+        let span = info_span!("ImapEmailClient::poll", poll, remaining_ms);
+
+        #[cfg(feature = "observability")]
+        {
+            use opentelemetry::trace::TraceContextExt;
+            use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+            if let Some(previous) = self.previous.take() {
+                span.add_link(previous);
+            }
+            self.previous = Some(span.context().span().span_context().clone());
+        }
+
+        span
+    }
+}
+
+/// Completes the report of a wait that started at `started` and logs a summary.
+fn finish_wait(report: WaitReport, started: Instant, outcome: &str) -> WaitReport {
+    let report = report.finish(started);
+    info!(
+        outcome,
+        polls = report.polls,
+        messages_scanned = report.messages_scanned,
+        reconnects = report.reconnects,
+        elapsed_ms = u64::try_from(report.elapsed.as_millis()).unwrap_or(u64::MAX),
+        "Wait finished"
+    );
+    report
+}
+
+/// Returns how long a wait ending at `deadline` sleeps before its next poll:
+/// `interval`, cut short at the deadline so the last poll happens on time rather
+/// than up to an interval late. `None` once the deadline has passed.
+fn next_poll_delay(interval: Duration, deadline: Instant) -> Option<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .map(|remaining| interval.min(remaining))
+}
+
+/// Returns the arrival time emails must not predate to be within `max_age` of
+/// `now`, or `None` if `max_age` reaches back beyond any representable date.
+fn age_cutoff(now: DateTime<Utc>, max_age: Duration) -> Option<DateTime<Utc>> {
+    chrono::Duration::from_std(max_age)
+        .ok()
+        .and_then(|max_age| now.checked_sub_signed(max_age))
+}
+
+/// Sorts matches by INTERNALDATE (by UID for equal or missing dates) in `order`.
+///
+/// UIDs of moved or copied emails are not chronological, so they only break ties.
+fn sort_by_arrival<T>(found: &mut [(MatchOrigin, T)], order: Order) {
+    found.sort_by_key(|(origin, _)| (origin.internal_date, origin.uid));
+    if order == Order::NewestFirst {
+        found.reverse();
+    }
+}
+
+/// Capability advertised by Gmail for its `X-GM-*` IMAP extensions.
+const GMAIL_CAPABILITY: &str = "X-GM-EXT-1";
+
+/// Time the NOOP of [`ImapEmailClient::health_check`] may take before the
+/// connection is considered lost.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// NOOP round trip above which [`ImapEmailClient::health_check`] reports
+/// [`HealthStatus::Degraded`].
+const DEGRADED_LATENCY: Duration = Duration::from_secs(1);
+
+/// Result of [`ImapEmailClient::health_check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HealthStatus {
+    /// The server answered promptly.
+    Healthy {
+        /// NOOP round trip time.
+        latency: Duration,
+    },
+    /// The server answered, but slowly (over one second).
+    Degraded {
+        /// NOOP round trip time.
+        latency: Duration,
+    },
+    /// The server did not answer in time or the NOOP failed; the client should be
+    /// reconnected.
+    Disconnected,
+}
+
+impl HealthStatus {
+    /// Classifies a successful NOOP round trip.
+    fn from_latency(latency: Duration) -> Self {
+        if latency > DEGRADED_LATENCY {
+            Self::Degraded { latency }
+        } else {
+            Self::Healthy { latency }
+        }
+    }
+
+    /// Returns `true` unless the connection is lost.
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        !matches!(self, Self::Disconnected)
+    }
+}
+
+/// A session opened by [`ImapEmailClient::initialize_session`].
+struct NewSession {
+    session: ImapSession,
+    /// Timings of each connection phase.
+    stats: ConnectStats,
+    /// Capabilities and enabled extensions, unless they could not be queried.
+    capabilities: Option<Capabilities>,
+    /// UIDVALIDITY of the selected mailbox.
+    uid_validity: Option<u32>,
+}
+
+/// What [`ImapEmailClient::fetch_content`] downloads of each email.
+#[derive(Debug, Clone, Copy)]
+enum FetchContent {
+    /// The header section.
+    Headers,
+    /// The content matched by waits and searches.
+    Scan,
+    /// The whole email.
+    Whole,
+}
+
+/// Baseline and consumed emails of a mailbox that is not selected.
+#[derive(Debug)]
+struct FolderState {
+    uid_validity: Option<u32>,
+    start_uid: u32,
+    consumed: HashSet<u32>,
+}
+
+/// The email a match was found in.
+#[derive(Debug)]
+struct MatchOrigin {
+    uid: Option<u32>,
+    message_id: Option<String>,
+    /// Server arrival time, reported with the match event.
+    internal_date: Option<DateTime<FixedOffset>>,
+    /// `RFC822.SIZE` of the email.
+    size: Option<u32>,
+}
+
+impl MatchOrigin {
+    fn of(message: &Fetch) -> Self {
+        Self {
+            uid: message.uid,
+            message_id: parser::message_id(message),
+            internal_date: message.internal_date(),
+            size: message.size,
+        }
+    }
+}
 
 /// Async IMAP client for email monitoring and pattern matching.
 ///
@@ -75,17 +327,40 @@ pub struct ImapEmailClient {
     session: Box<ImapSession>,
     config: ImapConfig,
     start_uid: u32,
+    /// UIDs of emails already returned as a match, never returned again.
+    consumed: HashSet<u32>,
+    /// UIDVALIDITY of the selected mailbox; the baseline is only valid while it
+    /// stays the same.
+    uid_validity: Option<u32>,
+    /// Baseline and consumed emails of the other mailboxes selected before.
+    folders: HashMap<String, FolderState>,
+    /// Message-IDs of emails already returned as a match, in any mailbox, so
+    /// duplicate deliveries of the same email are not returned again.
+    consumed_ids: HashSet<String>,
+    /// Capabilities advertised by the server and extensions enabled after login
+    /// (queried again on use if that failed).
+    capabilities: Option<Capabilities>,
+    /// Personal namespace (discovered on first use).
+    namespace: Option<Namespace>,
+    /// Name of the selected mailbox.
+    mailbox: String,
+    /// Phase timings of the current connection.
+    connect_stats: ConnectStats,
+    /// When the server was last talked to, for the keepalive.
+    last_activity: Instant,
 }
 
 impl ImapEmailClient {
     /// Connects to the IMAP server and prepares for email monitoring.
     ///
     /// This establishes a TLS connection, authenticates, and selects the INBOX.
+    /// Transient network errors while connecting are retried with backoff, see
+    /// [`TimeoutConfig::connect_attempts`](crate::TimeoutConfig::connect_attempts).
     ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - Connection cannot be established
+    /// - Connection cannot be established within the configured attempts
     /// - Authentication fails
     /// - Mailbox selection fails
     ///
@@ -110,11 +385,16 @@ impl ImapEmailClient {
         fields(
             email = %config.email(),
             imap_host = %config.effective_imap_host(),
-            proxy_enabled = config.proxy.is_some()
+            proxy_enabled = config.proxy.is_some() || config.proxy_pool.is_some()
         )
     )]
     pub async fn connect(config: ImapConfig) -> Result<Self> {
-        let mut session = Self::initialize_session(&config).await?;
+        let NewSession {
+            mut session,
+            stats: connect_stats,
+            capabilities,
+            uid_validity,
+        } = Self::initialize_session(&config, "INBOX").await?;
         let start_uid = Self::get_initial_uid(&mut session, &config).await?;
 
         debug!(start_uid, "Client connected and ready");
@@ -123,13 +403,99 @@ impl ImapEmailClient {
             session: Box::new(session),
             config,
             start_uid,
+            consumed: HashSet::new(),
+            uid_validity,
+            folders: HashMap::new(),
+            consumed_ids: HashSet::new(),
+            capabilities,
+            namespace: None,
+            mailbox: "INBOX".to_string(),
+            connect_stats,
+            last_activity: Instant::now(),
         })
     }
 
+    /// Connects, waits for an email matching `matcher` and logs out, all within
+    /// `budget`.
+    ///
+    /// Connecting, authenticating and waiting share the one budget: the wait gets
+    /// whatever time the connection left, instead of the connect timeouts and
+    /// [`PollingConfig::max_wait`](crate::PollingConfig::max_wait) adding up. A
+    /// poll still running at the deadline is cut short. Only emails arriving
+    /// after the connection is established are considered, as with
+    /// [`connect`](Self::connect). The logout runs in the background, as when
+    /// dropping an [`ImapEmailClientGuard`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The budget runs out while connecting ([`Error::ConnectTimeout`]) or
+    ///   waiting ([`Error::WaitTimeout`]; the report lacks the counts of a poll
+    ///   cut short)
+    /// - The connection or IMAP operations fail
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// // The code must arrive within 90 seconds, however slow the proxy is
+    /// let code = ImapEmailClient::connect_and_wait(
+    ///     config,
+    ///     &OtpMatcher::six_digit(),
+    ///     Duration::from_secs(90),
+    /// )
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::connect_and_wait",
+        skip_all,
+        fields(
+            email = %config.email(),
+            matcher = %matcher.description(),
+            budget_secs = budget.as_secs()
+        )
+    )]
+    pub async fn connect_and_wait(
+        config: ImapConfig,
+        matcher: &dyn Matcher,
+        budget: Duration,
+    ) -> Result<String> {
+        let started = Instant::now();
+        let target = config.server_address();
+
+        let client = runtime::timeout(budget, Box::pin(Self::connect(config)))
+            .await
+            .map_err(|_| Error::ConnectTimeout {
+                target,
+                timeout: budget,
+            })??;
+        // Logs out on drop, without holding up the result
+        let mut client = client.into_guard();
+
+        let wait_started = Instant::now();
+        let remaining = budget.saturating_sub(started.elapsed());
+        let options = WaitOptions::new().timeout(remaining);
+        let wait = Box::pin(client.wait_for_match_with(matcher, &options));
+        runtime::timeout(remaining, wait)
+            .await
+            .map_err(|_| Error::WaitTimeout {
+                timeout: budget,
+                report: WaitReport::default().finish(wait_started),
+            })?
+    }
+
     /// Waits for an email matching the provided pattern.
     ///
     /// Polls the mailbox at the configured interval until a match is found
-    /// or the timeout is reached.
+    /// or the timeout is reached. A poll failing with a transient error (see
+    /// [`Error::is_retryable`]) reconnects and the wait carries on.
     ///
     /// # Errors
     ///
@@ -157,139 +523,2262 @@ impl ImapEmailClient {
         fields(matcher = %matcher.description())
     )]
     pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
-        let timeout = self.config.polling.max_wait;
-        let poll_interval = self.config.polling.interval;
-        let deadline = Instant::now() + timeout;
-
-        loop {
-            if Instant::now() > deadline {
-                return Err(Error::WaitTimeout { timeout });
-            }
-
-            if let Some(result) = self.check_new_emails(matcher).await? {
-                return Ok(result);
-            }
-
-            tokio::time::sleep(poll_interval).await;
-        }
+        self.refresh_if_idle().await?;
+        self.poll_for_any(&[matcher], &WaitOptions::default())
+            .await
+            .map(|email| email.value)
     }
 
-    /// Finds a matching email among recent messages.
+    /// Waits for an email matching the provided pattern, returning the value as
+    /// a [`SecretString`].
     ///
-    /// Unlike [`wait_for_match`](Self::wait_for_match), this checks existing messages
-    /// immediately without polling for new emails.
+    /// Behaves like [`wait_for_match`](Self::wait_for_match). The secret prints
+    /// as `[REDACTED]` when debug-formatted and is zeroed on drop, so an OTP or
+    /// token cannot end up in logs by accident; read it with
+    /// [`ExposeSecret::expose_secret`](secrecy::ExposeSecret::expose_secret).
     ///
-    /// # Arguments
+    /// # Errors
     ///
-    /// * `matcher` - The pattern to match
-    /// * `max_age` - Only consider emails newer than this duration
+    /// Same as [`wait_for_match`](Self::wait_for_match).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::matcher::OtpMatcher;
+    /// use email_sync::{ExposeSecret, ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    /// let code = client.wait_for_secret_match(&OtpMatcher::six_digit()).await?;
+    /// tracing::info!(?code, "Got code"); // logs `[REDACTED]`
+    /// submit_code(code.expose_secret());
+    /// # Ok(())
+    /// # }
+    /// # fn submit_code(_: &str) {}
+    /// ```
+    pub async fn wait_for_secret_match(&mut self, matcher: &dyn Matcher) -> Result<SecretString> {
+        self.refresh_if_idle().await?;
+        self.wait_for_match(matcher).await.map(SecretString::from)
+    }
+
+    /// Waits for an email matching the provided pattern, with per-call options.
+    ///
+    /// Use [`WaitOptions::skip`] when a service may resend a code and the stale
+    /// first email must be ignored: matching emails are counted in arrival (UID)
+    /// order, and the first `skip` of them are passed over.
     ///
     /// # Errors
     ///
-    /// Returns [`Error::NoMatch`] if no matching email is found.
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - IMAP operations fail
     ///
     /// # Example
     ///
     /// ```no_run
-    /// use email_sync::{ImapConfig, ImapEmailClient};
-    /// use email_sync::matcher::UrlMatcher;
-    /// use std::time::Duration;
+    /// use email_sync::{ImapConfig, ImapEmailClient, WaitOptions};
+    /// use email_sync::matcher::OtpMatcher;
     ///
     /// # async fn example() -> email_sync::Result<()> {
     /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
     /// let mut client = ImapEmailClient::connect(config).await?;
     ///
-    /// // Find activation link from the last 5 minutes
-    /// let matcher = UrlMatcher::new("example.com");
-    /// let url = client.find_recent_match(&matcher, Duration::from_secs(300)).await?;
+    /// // The service sends a code, then resends it: wait for the second one
+    /// let options = WaitOptions::new().skip(1);
+    /// let code = client
+    ///     .wait_for_match_with(&OtpMatcher::six_digit(), &options)
+    ///     .await?;
     /// # Ok(())
     /// # }
     /// ```
     #[instrument(
-        name = "ImapEmailClient::find_recent_match",
+        name = "ImapEmailClient::wait_for_match_with",
         skip(self, matcher),
-        fields(
-            matcher = %matcher.description(),
-            max_age_secs = max_age.as_secs()
-        )
+        fields(matcher = %matcher.description())
     )]
-    pub async fn find_recent_match(
+    pub async fn wait_for_match_with(
         &mut self,
         matcher: &dyn Matcher,
-        max_age: Duration,
+        options: &WaitOptions,
     ) -> Result<String> {
-        let since_date = Self::calculate_since_date(max_age);
+        self.refresh_if_idle().await?;
+        self.poll_for_any(&[matcher], options)
+            .await
+            .map(|email| email.value)
+    }
 
-        debug!(since_date = %since_date, "Searching for recent emails");
+    /// Waits for an email matching the provided pattern and returns details about it.
+    ///
+    /// Like [`wait_for_match_with`](Self::wait_for_match_with), but returns a
+    /// [`MatchedEmail`] with the UID, a [`WaitReport`] of the wait and, on Gmail,
+    /// the `X-GM-*` attributes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - IMAP operations fail
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient, WaitOptions};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let email = client
+    ///     .wait_for_email(&OtpMatcher::six_digit(), &WaitOptions::default())
+    ///     .await?;
+    /// if let Some(gmail) = &email.gmail {
+    ///     println!("code {} in Gmail thread {}", email.value, gmail.thread_id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::wait_for_email",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_email(
+        &mut self,
+        matcher: &dyn Matcher,
+        options: &WaitOptions,
+    ) -> Result<MatchedEmail> {
+        self.refresh_if_idle().await?;
+        self.poll_for_any(&[matcher], options).await
+    }
 
-        let uids = self.search_emails_since(since_date).await?;
+    /// Sends a probe email and waits for it to arrive, measuring delivery latency.
+    ///
+    /// The probe is sent with [`SendRequest::send`], then found by its Message-ID
+    /// among new emails; `matcher` must also match it (e.g. to check that the
+    /// body survived delivery, or to extract a value). Waits at most `timeout`
+    /// for the probe, instead of [`PollingConfig::max_wait`].
+    ///
+    /// Call this after connecting (and selecting the mailbox the probe lands in),
+    /// so that only emails arriving after the send are considered.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::smtp::{SendRequest, SmtpSender};
+    /// use email_sync::matcher::RegexMatcher;
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// let config = ImapConfig::builder()
+    ///     .email("monitor@example.com")
+    ///     .password("secret")
+    ///     .build()?;
+    ///
+    /// let mut client = ImapEmailClient::connect(config.clone()).await?;
+    /// let request = SendRequest::new(SmtpSender::new(config), "monitor@example.com")
+    ///     .subject("Deliverability probe");
+    /// let matcher = RegexMatcher::new("email delivery").expect("valid regex");
+    /// let trip = client
+    ///     .round_trip(&request, &matcher, Duration::from_mins(5))
+    ///     .await?;
+    /// println!("delivered in {:?}", trip.latency);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending fails (see [`SmtpSender::send`]), or
+    /// [`Error::WaitTimeout`] if the probe has not matched within `timeout`.
+    ///
+    /// [`SendRequest::send`]: crate::smtp::SendRequest::send
+    /// [`SmtpSender::send`]: crate::smtp::SmtpSender::send
+    /// [`PollingConfig::max_wait`]: crate::PollingConfig::max_wait
+    #[cfg(feature = "smtp")]
+    #[instrument(
+        name = "ImapEmailClient::round_trip",
+        skip(self, request, matcher),
+        fields(to = request.to(), matcher = %matcher.description())
+    )]
+    pub async fn round_trip(
+        &mut self,
+        request: &crate::smtp::SendRequest,
+        matcher: &dyn Matcher,
+        timeout: Duration,
+    ) -> Result<crate::smtp::RoundTrip> {
+        self.refresh_if_idle().await?;
+        let started = Instant::now();
+        let message_id = request.send().await?;
+        let accepted = Instant::now();
+        debug!(message_id = %message_id, "Probe sent");
 
-        if uids.is_empty() {
-            return Err(Error::NoMatch);
-        }
+        let options = WaitOptions::new()
+            .filter(Filter::new().header("Message-ID", message_id.clone()))
+            .timeout(timeout);
+        let email = self.wait_for_email(matcher, &options).await?;
+        let latency = accepted.elapsed();
+        info!(latency_ms = latency.as_millis(), "Probe delivered");
 
-        self.find_match_in_uids(&uids, matcher).await
+        Ok(crate::smtp::RoundTrip {
+            message_id,
+            send_time: accepted.duration_since(started),
+            latency,
+            email,
+        })
     }
 
-    /// Logs out from the IMAP server.
+    /// Waits for an email matching any of the provided patterns.
     ///
-    /// This should be called when you're done with the client.
-    /// If you don't call this, the connection will be dropped without
-    /// a clean logout (which is usually fine, but not ideal).
+    /// Polls like [`wait_for_match`](Self::wait_for_match), but each new email is
+    /// checked against every matcher. Returns the index of the matcher that fired
+    /// (into `matchers`) together with the extracted value. When several matchers
+    /// match the same email, the one listed first wins.
     ///
     /// # Errors
     ///
-    /// Returns an error if the logout command fails.
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - IMAP operations fail
     ///
     /// # Example
     ///
     /// ```no_run
     /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::{OtpMatcher, UrlMatcher};
     ///
     /// # async fn example() -> email_sync::Result<()> {
     /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
     /// let mut client = ImapEmailClient::connect(config).await?;
-    /// // ... use client ...
-    /// client.logout().await?;
+    ///
+    /// let otp = OtpMatcher::six_digit();
+    /// let link = UrlMatcher::new("example.com");
+    ///
+    /// match client.wait_for_any(&[&otp, &link]).await? {
+    ///     (0, code) => println!("Got code: {code}"),
+    ///     (_, url) => println!("Got verification link: {url}"),
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument(name = "ImapEmailClient::logout", skip(self))]
-    pub async fn logout(&mut self) -> Result<()> {
-        session::logout(&mut self.session).await
+    #[instrument(
+        name = "ImapEmailClient::wait_for_any",
+        skip(self, matchers),
+        fields(matchers = matchers.len())
+    )]
+    pub async fn wait_for_any(&mut self, matchers: &[&dyn Matcher]) -> Result<(usize, String)> {
+        self.refresh_if_idle().await?;
+        if matchers.is_empty() {
+            return Err(Error::InvalidConfig {
+                message: "at least one matcher is required".into(),
+            });
+        }
+
+        self.poll_for_any(matchers, &WaitOptions::default())
+            .await
+            .map(|email| (email.matcher_index, email.value))
     }
 
-    /// Converts this client into a guard that logs out on drop.
+    /// Waits until every provided pattern has been seen in new emails.
     ///
-    /// This is useful for ensuring cleanup in the face of early returns
-    /// or panics.
+    /// Each new email is checked against all matchers that have not fired yet, so
+    /// the values may come from different emails (e.g. a code and a link sent
+    /// separately). If a matcher fires more than once, the first value is kept.
+    ///
+    /// Returns the extracted values in the same order as `matchers`:
+    /// `values[i]` is what `matchers[i]` extracted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Not all patterns were seen within `timeout` ([`Error::WaitTimeout`])
+    /// - IMAP operations fail
     ///
     /// # Example
     ///
     /// ```no_run
     /// use email_sync::{ImapConfig, ImapEmailClient};
-    /// use email_sync::matcher::OtpMatcher;
+    /// use email_sync::matcher::{OtpMatcher, UrlMatcher};
+    /// use std::time::Duration;
     ///
     /// # async fn example() -> email_sync::Result<()> {
     /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
-    /// let client = ImapEmailClient::connect(config).await?;
-    /// let mut guard = client.into_guard();
+    /// let mut client = ImapEmailClient::connect(config).await?;
     ///
-    /// let code = guard.wait_for_match(&OtpMatcher::six_digit()).await?;
-    /// // Guard will logout when dropped
+    /// let otp = OtpMatcher::six_digit();
+    /// let link = UrlMatcher::new("example.com");
+    ///
+    /// let values = client
+    ///     .wait_for_all(&[&otp, &link], Duration::from_secs(120))
+    ///     .await?;
+    /// let (code, url) = (&values[0], &values[1]);
     /// # Ok(())
     /// # }
     /// ```
-    #[must_use]
-    pub fn into_guard(self) -> ImapEmailClientGuard {
-        ImapEmailClientGuard { inner: Some(self) }
-    }
+    #[instrument(
+        name = "ImapEmailClient::wait_for_all",
+        skip(self, matchers),
+        fields(matchers = matchers.len(), timeout_secs = timeout.as_secs())
+    )]
+    pub async fn wait_for_all(
+        &mut self,
+        matchers: &[&dyn Matcher],
+        timeout: Duration,
+    ) -> Result<Vec<String>> {
+        self.refresh_if_idle().await?;
+        if matchers.is_empty() {
+            return Err(Error::InvalidConfig {
+                message: "at least one matcher is required".into(),
+            });
+        }
 
-    /// Returns the email address used for this connection.
-    #[must_use]
-    pub fn email(&self) -> &str {
-        self.config.email()
-    }
+        let poll_interval = self.config.polling.interval;
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let mut values: Vec<Option<String>> = vec![None; matchers.len()];
+        let mut matched = Vec::new();
+        let mut extract = Extractor::new(&self.config);
+        let mut report = WaitReport::default();
+        let mut spans = PollSpans::default();
+
+        loop {
+            report.polls += 1;
+            let span = spans.next(report.polls, deadline);
+            let polled = self.check_new_emails(matchers, None, |message| {
+                report.messages_scanned += 1;
+                // Only check matchers that are still pending
+                let (indices, pending): (Vec<usize>, Vec<&dyn Matcher>) = matchers
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| values[*index].is_none())
+                    .map(|(index, matcher)| (index, *matcher))
+                    .unzip();
+
+                let hits = extract.extract_all_matches(message, &pending);
+                if !hits.is_empty() {
+                    matched.push(MatchOrigin::of(message));
+                }
+                for (pending_index, value) in hits {
+                    values[indices[pending_index]] = Some(value.into_owned());
+                }
+
+                if values.iter().all(Option::is_some) {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+            if let Err(e) = polled.instrument(span).await {
+                self.recover(e, &mut report).await?;
+            }
+            self.report_progress(started, &report);
+
+            for origin in matched.drain(..) {
+                self.mark_consumed(origin);
+            }
+
+            if values.iter().all(Option::is_some) {
+                debug!("All patterns matched");
+                finish_wait(report, started, "matched");
+                return Ok(values.into_iter().flatten().collect());
+            }
+
+            let Some(delay) = next_poll_delay(poll_interval, deadline) else {
+                let report = finish_wait(report, started, "timeout");
+                return Err(Error::WaitTimeout { timeout, report });
+            };
+            runtime::sleep(delay).await;
+        }
+    }
+
+    /// Waits for an email matching `matcher` in any of several mailboxes.
+    ///
+    /// The mailboxes are polled in turn over this connection, each from its own
+    /// baseline (see [`select_mailbox`](Self::select_mailbox)): emails already in a
+    /// mailbox when it is first visited are not considered. The returned
+    /// [`MatchedEmail::mailbox`] tells where the match was found. Afterwards the
+    /// previously selected mailbox is selected again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `mailboxes` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - A mailbox cannot be selected or IMAP operations fail
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// // The code may be filed as spam
+    /// let email = client
+    ///     .watch_folders(&["INBOX", "Junk"], &OtpMatcher::six_digit())
+    ///     .await?;
+    /// println!("code {} found in {}", email.value, email.mailbox);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::watch_folders",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn watch_folders(
+        &mut self,
+        mailboxes: &[&str],
+        matcher: &dyn Matcher,
+    ) -> Result<MatchedEmail> {
+        self.refresh_if_idle().await?;
+        if mailboxes.is_empty() {
+            return Err(Error::InvalidConfig {
+                message: "at least one mailbox is required".into(),
+            });
+        }
+
+        let home = self.mailbox.clone();
+        let result = self.poll_folders(mailboxes, matcher).await;
+
+        let restored = if self.mailbox == home {
+            Ok(())
+        } else {
+            self.enter_folder(&home).await
+        };
+        if let Err(e) = &restored {
+            warn!(mailbox = %home, error = %e, "Failed to reselect mailbox after watching folders");
+        }
+        result.and_then(|email| restored.map(|()| email))
+    }
+
+    /// Finds a matching email among recent messages.
+    ///
+    /// Unlike [`wait_for_match`](Self::wait_for_match), this checks existing messages
+    /// immediately without polling for new emails.
+    ///
+    /// # Arguments
+    ///
+    /// * `matcher` - The pattern to match
+    /// * `max_age` - Only consider emails newer than this duration
+    ///
+    /// Candidates are found with an IMAP `SINCE` search (day granularity, reaching
+    /// back an extra [`ImapConfig::clock_skew_margin`] for servers in other time
+    /// zones) and then narrowed down by their INTERNALDATE (server arrival time),
+    /// so `max_age` is honoured to the second. Candidates are checked newest
+    /// first: by `Date` header via `SORT (REVERSE DATE)` when the server supports
+    /// the SORT extension, by INTERNALDATE otherwise. Use
+    /// [`find_recent_match_with`](Self::find_recent_match_with) to check them
+    /// oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching email is found, or
+    /// [`Error::ScanLimitReached`] if none was found within
+    /// [`ImapConfig::scan_limits`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::UrlMatcher;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// // Find activation link from the last 5 minutes
+    /// let matcher = UrlMatcher::new("example.com");
+    /// let url = client.find_recent_match(&matcher, Duration::from_secs(300)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::find_recent_match",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<String> {
+        self.refresh_if_idle().await?;
+        self.find_recent_email(matcher, max_age)
+            .await
+            .map(|email| email.value)
+    }
+
+    /// Finds matching content in recent emails, with per-call options.
+    ///
+    /// Like [`find_recent_match`](Self::find_recent_match), but with
+    /// [`SearchOptions`], e.g. to return the first email that matched instead of
+    /// the latest.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching email is found, or
+    /// [`Error::ScanLimitReached`] if none was found within
+    /// [`ImapConfig::scan_limits`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient, Order, SearchOptions};
+    /// use email_sync::matcher::OtpMatcher;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// // The first code sent today, not the latest resend
+    /// let options = SearchOptions::new().order(Order::OldestFirst);
+    /// let code = client
+    ///     .find_recent_match_with(&OtpMatcher::six_digit(), Duration::from_hours(24), &options)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::find_recent_match_with",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match_with(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+        options: &SearchOptions,
+    ) -> Result<String> {
+        self.refresh_if_idle().await?;
+        self.find_recent_email_with(matcher, max_age, options)
+            .await
+            .map(|email| email.value)
+    }
+
+    /// Finds a matching email among recent messages and returns details about it.
+    ///
+    /// Like [`find_recent_match`](Self::find_recent_match), but returns a
+    /// [`MatchedEmail`] with the UID and, on Gmail, the `X-GM-*` attributes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching email is found, or
+    /// [`Error::ScanLimitReached`] if none was found within
+    /// [`ImapConfig::scan_limits`].
+    #[instrument(
+        name = "ImapEmailClient::find_recent_email",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_email(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<MatchedEmail> {
+        self.refresh_if_idle().await?;
+        self.find_recent_email_with(matcher, max_age, &SearchOptions::default())
+            .await
+    }
+
+    /// Finds a matching email among recent messages, with per-call options, and
+    /// returns details about it.
+    ///
+    /// Like [`find_recent_match_with`](Self::find_recent_match_with), but returns a
+    /// [`MatchedEmail`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching email is found, or
+    /// [`Error::ScanLimitReached`] if none was found within
+    /// [`ImapConfig::scan_limits`].
+    #[instrument(
+        name = "ImapEmailClient::find_recent_email_with",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_email_with(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+        options: &SearchOptions,
+    ) -> Result<MatchedEmail> {
+        self.refresh_if_idle().await?;
+        let uids = self.search_recent_uids(max_age).await?;
+
+        if uids.is_empty() {
+            return Err(Error::NoMatch);
+        }
+
+        let mut uids = self.sort_newest_first(uids).await;
+        if options.order == Order::OldestFirst {
+            uids.reverse();
+        }
+
+        self.find_match_in_uids(&uids, &[matcher]).await
+    }
+
+    /// Finds every recent email matching a pattern.
+    ///
+    /// Checks the same emails as [`find_recent_match`](Self::find_recent_match),
+    /// but returns all matches instead of the first. They are sorted by
+    /// INTERNALDATE, exposed as [`MatchedEmail::internal_date`], in
+    /// [`SearchOptions::order`] (newest first by default); UID order is not always
+    /// chronological, e.g. for moved or copied emails. Every returned email is
+    /// marked as consumed.
+    ///
+    /// [`ImapConfig::scan_limits`] does not apply: every email within `max_age`
+    /// is fetched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox cannot be searched or the emails cannot be
+    /// fetched. Finding no match is not an error: the list is empty.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient, SearchOptions};
+    /// use email_sync::matcher::OtpMatcher;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// // Every code resent within the last hour, latest first
+    /// let emails = client
+    ///     .find_all_recent_matches(
+    ///         &OtpMatcher::six_digit(),
+    ///         Duration::from_hours(1),
+    ///         &SearchOptions::new(),
+    ///     )
+    ///     .await?;
+    /// if let Some(latest) = emails.first() {
+    ///     println!("latest code {} arrived {:?}", latest.value, latest.internal_date);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::find_all_recent_matches",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_all_recent_matches(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+        options: &SearchOptions,
+    ) -> Result<Vec<MatchedEmail>> {
+        self.refresh_if_idle().await?;
+        let uids = self.search_recent_uids(max_age).await?;
+
+        let mut extract = Extractor::new(&self.config);
+        let mut found = Vec::new();
+        for batch in uids.chunks(self.config.scan_concurrency.max(1)) {
+            let messages = self.fetch_messages(&session::uid_set(batch), false).await?;
+            for message in messages {
+                if !self.is_fresh(&message, None) {
+                    continue;
+                }
+                if let ExtractResult::Match { index, details } =
+                    extract.extract_match(&message, &[matcher])
+                {
+                    found.push((MatchOrigin::of(&message), (index, details.into_owned())));
+                }
+            }
+        }
+        sort_by_arrival(&mut found, options.order);
+
+        debug!(
+            checked = uids.len(),
+            matched = found.len(),
+            "Found matching emails"
+        );
+
+        let mut emails = Vec::with_capacity(found.len());
+        for (origin, (index, details)) in found {
+            emails.push(self.complete_match(origin, (index, matcher), details).await);
+        }
+        Ok(emails)
+    }
+
+    /// Counts the recent emails matching a pattern.
+    ///
+    /// Checks every email that arrived within `max_age`, like
+    /// [`find_recent_match`](Self::find_recent_match), but counts the matches
+    /// instead of returning the first value. Consumed emails are counted too, so
+    /// an email sent twice by the product under test shows up as 2.
+    ///
+    /// [`ImapConfig::scan_limits`] does not apply: every email within `max_age`
+    /// is fetched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox cannot be searched or the emails cannot be
+    /// fetched.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::UrlMatcher;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let matcher = UrlMatcher::new("example.com");
+    /// let sent = client.count_matching(&matcher, Duration::from_mins(5)).await?;
+    /// assert_eq!(sent, 1, "confirmation email sent {sent} times");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::count_matching",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn count_matching(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<usize> {
+        self.refresh_if_idle().await?;
+        let uids = self.search_recent_uids(max_age).await?;
+
+        let mut extract = Extractor::new(&self.config);
+        let mut count = 0;
+        for batch in uids.chunks(self.config.scan_concurrency.max(1)) {
+            let messages = self.fetch_messages(&session::uid_set(batch), false).await?;
+            count += messages
+                .iter()
+                .filter(|message| Self::is_for_recipient(&self.config, message))
+                .filter(|message| {
+                    matches!(
+                        extract.extract_match(message, &[matcher]),
+                        ExtractResult::Match { .. }
+                    )
+                })
+                .count();
+        }
+
+        debug!(checked = uids.len(), count, "Counted matching emails");
+        Ok(count)
+    }
+
+    /// Finds a matching email carrying a Gmail label, newest first.
+    ///
+    /// Uses the Gmail `X-GM-LABELS` search key; system labels are written with a
+    /// backslash (e.g. `\Important`), user labels by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MissingCapability`] if the server is not Gmail,
+    /// [`Error::InvalidConfig`] if `label` contains CR, LF or NUL,
+    /// [`Error::NoMatch`] if no labelled email matches, or
+    /// [`Error::ScanLimitReached`] if none was found within
+    /// [`ImapConfig::scan_limits`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@gmail.com").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let email = client
+    ///     .find_by_gmail_label("Verification", &OtpMatcher::six_digit())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::find_by_gmail_label",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn find_by_gmail_label(
+        &mut self,
+        label: &str,
+        matcher: &dyn Matcher,
+    ) -> Result<MatchedEmail> {
+        self.refresh_if_idle().await?;
+        self.require_capability(GMAIL_CAPABILITY).await?;
+
+        let timeout = self.config.timeouts.uid_fetch;
+        let retry = self.config.retry;
+        let (session, capabilities) = self.negotiated_session().await;
+        let mut uids = runtime::timeout(
+            timeout,
+            with_retries!(
+                &retry,
+                "SEARCH",
+                session::search_gmail_label(session, &capabilities, label)
+            ),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout })??;
+
+        if uids.is_empty() {
+            return Err(Error::NoMatch);
+        }
+
+        // Without SORT, the highest UIDs are usually the newest
+        if let Some(sorted) = self.server_sort_newest_first(&uids).await {
+            uids = sorted;
+        } else {
+            uids.sort_unstable_by(|a, b| b.cmp(a));
+        }
+
+        self.find_match_in_uids(&uids, &[matcher]).await
+    }
+
+    /// Finds a matching reply within the thread of a known email.
+    ///
+    /// Searches the mailbox for emails whose `In-Reply-To` or `References` header
+    /// contains `message_id` and checks them newest first. Angle brackets around
+    /// the Message-ID are optional.
+    ///
+    /// To wait for a reply that has not arrived yet, use
+    /// [`wait_for_reply`](Self::wait_for_reply).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no reply in the thread matches,
+    /// [`Error::ScanLimitReached`] if none was found within
+    /// [`ImapConfig::scan_limits`], or [`Error::InvalidConfig`] if `message_id`
+    /// contains CR, LF or NUL.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::UrlMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let matcher = UrlMatcher::new("support.example.com");
+    /// let url = client
+    ///     .find_in_thread("<ticket-1234@example.com>", &matcher)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::find_in_thread",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn find_in_thread(
+        &mut self,
+        message_id: &str,
+        matcher: &dyn Matcher,
+    ) -> Result<String> {
+        self.refresh_if_idle().await?;
+        let message_id = parser::bare_message_id(message_id);
+        let timeout = self.config.timeouts.uid_fetch;
+
+        let retry = self.config.retry;
+        let (session, capabilities) = self.negotiated_session().await;
+        let mut uids = runtime::timeout(
+            timeout,
+            with_retries!(
+                &retry,
+                "SEARCH",
+                session::search_thread(session, &capabilities, message_id)
+            ),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout })??;
+
+        if uids.is_empty() {
+            return Err(Error::NoMatch);
+        }
+
+        // Without SORT, the highest UIDs are usually the newest
+        if let Some(sorted) = self.server_sort_newest_first(&uids).await {
+            uids = sorted;
+        } else {
+            uids.sort_unstable_by(|a, b| b.cmp(a));
+        }
+
+        self.find_match_in_uids(&uids, &[matcher])
+            .await
+            .map(|email| email.value)
+    }
+
+    /// Waits for a new reply to a known email that matches `matcher`.
+    ///
+    /// Polls like [`wait_for_match`](Self::wait_for_match), but only considers
+    /// emails whose `In-Reply-To` or `References` header contains `message_id`,
+    /// so round trips (send an email, await the automated reply) can be expressed
+    /// directly. Angle brackets around the Message-ID are optional. Shorthand for
+    /// [`wait_for_match_with`](Self::wait_for_match_with) with
+    /// [`WaitOptions::in_thread`].
+    ///
+    /// Only emails arriving after the baseline count; call
+    /// [`reset_baseline`](Self::reset_baseline) before sending the original email.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without a matching reply ([`Error::WaitTimeout`])
+    /// - IMAP operations fail
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// client.reset_baseline().await?;
+    /// // ... send an email with Message-ID <request-42@example.com> ...
+    /// let code = client
+    ///     .wait_for_reply("<request-42@example.com>", &OtpMatcher::six_digit())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::wait_for_reply",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_reply(
+        &mut self,
+        message_id: &str,
+        matcher: &dyn Matcher,
+    ) -> Result<String> {
+        self.refresh_if_idle().await?;
+        let options = WaitOptions::new().in_thread(message_id);
+        self.poll_for_any(&[matcher], &options)
+            .await
+            .map(|email| email.value)
+    }
+
+    /// Fetches and parses the email with UID `uid` in the selected mailbox.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no email has the UID,
+    /// [`Error::InvalidConfig`] with [`ImapConfigBuilder::envelope_only`], or an
+    /// error if the email cannot be fetched or parsed.
+    ///
+    /// [`ImapConfigBuilder::envelope_only`]: crate::ImapConfigBuilder::envelope_only
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient, WaitOptions};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let email = client
+    ///     .wait_for_email(&OtpMatcher::six_digit(), &WaitOptions::default())
+    ///     .await?;
+    /// let message = client.fetch_message(email.uid).await?;
+    /// for attachment in &message.attachments {
+    ///     println!("{:?} ({} bytes)", attachment.filename, attachment.size);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::fetch_message", skip(self))]
+    pub async fn fetch_message(&mut self, uid: u32) -> Result<EmailMessage> {
+        self.refresh_if_idle().await?;
+        self.require_content("fetch_message")?;
+        let messages = self.fetch_whole_messages(&uid.to_string()).await?;
+        let message = messages
+            .iter()
+            .find(|message| message.uid == Some(uid))
+            .ok_or(Error::NoMatch)?;
+        parser::parse_message(message)
+    }
+
+    /// Fetches and parses the email with the given Message-ID.
+    ///
+    /// Searches the selected mailbox with `HEADER Message-ID`; angle brackets
+    /// around `message_id` are optional. When several emails carry the Message-ID
+    /// (e.g. duplicates delivered twice), the newest is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no email has the Message-ID,
+    /// [`Error::InvalidConfig`] with [`ImapConfigBuilder::envelope_only`] or if
+    /// `message_id` contains CR, LF or NUL, or an error if the email cannot be
+    /// fetched or parsed.
+    ///
+    /// [`ImapConfigBuilder::envelope_only`]: crate::ImapConfigBuilder::envelope_only
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let message = client.find_by_message_id("<abc@host>").await?;
+    /// println!("{:?} from {:?}", message.subject, message.from);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::find_by_message_id", skip(self))]
+    pub async fn find_by_message_id(&mut self, message_id: &str) -> Result<EmailMessage> {
+        self.refresh_if_idle().await?;
+        self.require_content("find_by_message_id")?;
+        let message_id = parser::bare_message_id(message_id);
+        let timeout = self.config.timeouts.uid_fetch;
+
+        let retry = self.config.retry;
+        let (session, capabilities) = self.negotiated_session().await;
+        let uids = runtime::timeout(
+            timeout,
+            with_retries!(
+                &retry,
+                "SEARCH",
+                session::search_message_id(session, &capabilities, message_id)
+            ),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout })??;
+
+        if uids.is_empty() {
+            return Err(Error::NoMatch);
+        }
+
+        // HEADER searches match substrings; keep only exact Message-IDs
+        let mut messages = self.fetch_whole_messages(&session::uid_set(&uids)).await?;
+        messages.sort_by_key(|message| std::cmp::Reverse(message.uid));
+        for message in &messages {
+            let parsed = parser::parse_message(message)?;
+            if parsed.message_id.as_deref() == Some(message_id) {
+                return Ok(parsed);
+            }
+        }
+
+        Err(Error::NoMatch)
+    }
+
+    /// Lists the newest `n` emails in the selected mailbox, newest first.
+    ///
+    /// Only the header section of each email is fetched. Meant for debugging a
+    /// matcher that never fires: it shows what the mailbox actually contains.
+    /// Consumed emails and the baseline are not affected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox cannot be searched or the headers cannot be
+    /// fetched.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// for summary in client.peek_recent(10).await? {
+    ///     println!("{} {:?} {:?} {:?}", summary.uid, summary.date, summary.from, summary.subject);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::peek_recent", skip(self))]
+    pub async fn peek_recent(&mut self, n: usize) -> Result<Vec<MessageSummary>> {
+        self.refresh_if_idle().await?;
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let timeout = self.config.timeouts.uid_fetch;
+
+        let started = Instant::now();
+        let session = &mut self.session;
+        let uids = runtime::timeout(
+            timeout,
+            with_retries!(
+                &self.config.retry,
+                "SEARCH",
+                session::search_all_uids(session)
+            ),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout });
+        events::record(
+            self.config.event_hook.as_deref(),
+            Operation::Search,
+            started,
+        );
+        let uids = uids??;
+
+        let newest = &uids[uids.len().saturating_sub(n)..];
+        if newest.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut summaries: Vec<MessageSummary> = self
+            .fetch_messages(&session::uid_set(newest), true)
+            .await?
+            .iter()
+            .map(parser::summarize)
+            .collect();
+        summaries.sort_unstable_by_key(|summary| std::cmp::Reverse(summary.uid));
+        Ok(summaries)
+    }
+
+    /// Moves the baseline to the newest email currently in the mailbox.
+    ///
+    /// Emails that arrived since [`connect`](Self::connect) (or the last wait) but were
+    /// not yet checked are ignored; only emails arriving after this call count as new.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the latest UID cannot be fetched.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// // ... trigger the action that sends the email ...
+    /// client.reset_baseline().await?;
+    /// let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::reset_baseline", skip(self))]
+    pub async fn reset_baseline(&mut self) -> Result<()> {
+        self.refresh_if_idle().await?;
+        self.start_uid = Self::get_initial_uid(&mut self.session, &self.config).await?;
+        debug!(start_uid = self.start_uid, "Baseline reset");
+        Ok(())
+    }
+
+    /// Treats every email with a UID of `uid` or higher as new.
+    ///
+    /// Useful to resume from a UID recorded earlier, e.g. before a reconnect.
+    /// `start_from_uid(1)` makes every email in the mailbox count as new.
+    pub fn start_from_uid(&mut self, uid: u32) {
+        self.start_uid = uid.saturating_sub(1);
+        debug!(start_uid = self.start_uid, "Baseline set");
+    }
+
+    /// Returns `true` if emails arrived in the selected mailbox after the baseline.
+    ///
+    /// Compares the mailbox's `UIDNEXT` (from `STATUS`) with the
+    /// [baseline](Self::baseline_uid) without fetching or parsing anything, so it
+    /// can gate a heavier [`wait_for_match`](Self::wait_for_match) or
+    /// [`find_recent_match`](Self::find_recent_match) pass. Servers that omit
+    /// `UIDNEXT` are asked for the UIDs above the baseline instead. Emails deleted
+    /// since they arrived, and emails already consumed, still count as new; a
+    /// changed UIDVALIDITY is reported as new mail as well.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// if client.has_new_mail().await? {
+    ///     let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::has_new_mail",
+        skip(self),
+        fields(mailbox = %self.mailbox, start_uid = self.start_uid)
+    )]
+    pub async fn has_new_mail(&mut self) -> Result<bool> {
+        self.refresh_if_idle().await?;
+        let timeout = self.config.timeouts.command;
+        let status = runtime::timeout(
+            timeout,
+            session::mailbox_status(&mut self.session, &self.mailbox),
+        )
+        .await
+        .map_err(|_| Error::CommandTimeout {
+            command: "STATUS".into(),
+            timeout,
+        })??;
+
+        if status.uid_validity.is_some() && status.uid_validity != self.uid_validity {
+            debug!(
+                old = ?self.uid_validity,
+                new = ?status.uid_validity,
+                "UIDVALIDITY changed"
+            );
+            return Ok(true);
+        }
+        if let Some(uid_next) = status.uid_next {
+            return Ok(uid_next > self.start_uid.saturating_add(1));
+        }
+
+        let timeout = self.config.timeouts.uid_fetch;
+        let uids = runtime::timeout(
+            timeout,
+            session::search_uids_after(&mut self.session, self.start_uid),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout })??;
+        Ok(!uids.is_empty())
+    }
+
+    /// Returns the UID of the newest email already considered seen.
+    ///
+    /// Only emails with a higher UID count as new for
+    /// [`wait_for_match`](Self::wait_for_match).
+    #[must_use]
+    pub fn baseline_uid(&self) -> u32 {
+        self.start_uid
+    }
+
+    /// Forgets which emails have already been returned as a match.
+    ///
+    /// Each email satisfies at most one wait or search per client, so overlapping
+    /// [`wait_for_match`](Self::wait_for_match) and
+    /// [`find_recent_match`](Self::find_recent_match) calls never return the same
+    /// email twice. Duplicates of a consumed email (same Message-ID, e.g. delivered
+    /// twice or copied to another Gmail label) are skipped as well. After clearing,
+    /// previously consumed emails can match again.
+    pub fn clear_consumed(&mut self) {
+        debug!(count = self.consumed.len(), "Clearing consumed emails");
+        self.consumed.clear();
+        self.consumed_ids.clear();
+    }
+
+    /// Checks the connection with a NOOP.
+    ///
+    /// The NOOP gets a short timeout (five seconds), independent of the configured
+    /// timeouts, so pools and supervisors can probe clients cheaply. Never fails:
+    /// errors are reported as [`HealthStatus::Disconnected`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{HealthStatus, ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// if let HealthStatus::Degraded { latency } = client.health_check().await {
+    ///     println!("Slow server: {latency:?}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::health_check", skip(self))]
+    pub async fn health_check(&mut self) -> HealthStatus {
+        let started = Instant::now();
+        self.last_activity = started;
+        let status = match runtime::timeout(HEALTH_CHECK_TIMEOUT, self.session.noop()).await {
+            Ok(Ok(())) => HealthStatus::from_latency(started.elapsed()),
+            Ok(Err(e)) => {
+                warn!(error = %e, "Health check NOOP failed");
+                HealthStatus::Disconnected
+            }
+            Err(_) => {
+                warn!(
+                    timeout_secs = HEALTH_CHECK_TIMEOUT.as_secs(),
+                    "Health check NOOP timed out"
+                );
+                HealthStatus::Disconnected
+            }
+        };
+
+        debug!(?status, "Health check finished");
+        status
+    }
+
+    /// Sends a NOOP so the server does not drop an idle connection.
+    pub(crate) async fn keepalive(&mut self) -> Result<()> {
+        self.last_activity = Instant::now();
+        let session = &mut self.session;
+        with_command_timeout(
+            self.config.timeouts.command,
+            "NOOP",
+            with_retries!(&self.config.retry, "NOOP", async {
+                session
+                    .noop()
+                    .await
+                    .map_err(|source| Error::ImapNoop { source })
+            }),
+        )
+        .await
+    }
+
+    /// Sends a keepalive NOOP before the next command once the connection has been
+    /// idle for [`ImapConfig::keepalive`], reconnecting if the server dropped it in
+    /// the meantime.
+    async fn refresh_if_idle(&mut self) -> Result<()> {
+        let Some(interval) = self.config.keepalive else {
+            return Ok(());
+        };
+        let idle = self.last_activity.elapsed();
+        if idle < interval {
+            self.last_activity = Instant::now();
+            return Ok(());
+        }
+
+        match self.keepalive().await {
+            Ok(()) => debug!(idle_secs = idle.as_secs(), "Sent keepalive NOOP"),
+            Err(e) => {
+                warn!(error = %e, "Keepalive NOOP failed, reconnecting");
+                Box::pin(self.reconnect()).await?;
+            }
+        }
+        self.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// Returns how long each phase of establishing the current connection took.
+    ///
+    /// Updated on [`reconnect`](Self::reconnect).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn example(client: email_sync::ImapEmailClient) {
+    /// let stats = client.connect_stats();
+    /// println!(
+    ///     "dns={}ms tcp={}ms tls={}ms auth={}ms select={}ms via {:?}",
+    ///     stats.dns_ms, stats.tcp_ms, stats.tls_ms, stats.auth_ms, stats.select_ms, stats.endpoint
+    /// );
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn connect_stats(&self) -> &ConnectStats {
+        &self.connect_stats
+    }
+
+    /// Returns the name of the mailbox being watched.
+    #[must_use]
+    pub fn selected_mailbox(&self) -> &str {
+        &self.mailbox
+    }
+
+    /// Switches the watched mailbox to `mailbox`.
+    ///
+    /// The current mailbox is left with `UNSELECT` when the server supports it, so
+    /// emails flagged `\Deleted` there are not expunged.
+    ///
+    /// The baseline and consumed emails are tracked per mailbox, since UIDs are
+    /// only unique within one: switching back to a mailbox continues where it left
+    /// off. A mailbox selected for the first time, or whose UIDVALIDITY changed in
+    /// the meantime, starts from its newest email.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox cannot be selected or a command times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// // Some providers file verification emails as spam
+    /// client.select_mailbox("Junk").await?;
+    /// let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::select_mailbox", skip(self))]
+    pub async fn select_mailbox(&mut self, mailbox: &str) -> Result<()> {
+        self.refresh_if_idle().await?;
+        let supported = self.has_capability("UNSELECT").await;
+        with_command_timeout(
+            self.config.timeouts.command,
+            "UNSELECT",
+            session::unselect(&mut self.session, supported),
+        )
+        .await?;
+
+        self.enter_folder(mailbox).await?;
+
+        debug!(start_uid = self.start_uid, "Switched mailbox");
+        Ok(())
+    }
+
+    /// Lists the names of all mailboxes visible to this account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the LIST command fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    /// for mailbox in client.list_mailboxes().await? {
+    ///     println!("{mailbox}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::list_mailboxes", skip(self))]
+    pub async fn list_mailboxes(&mut self) -> Result<Vec<String>> {
+        self.refresh_if_idle().await?;
+        let timeout = self.config.timeouts.command;
+
+        runtime::timeout(timeout, session::list_mailboxes(&mut self.session))
+            .await
+            .map_err(|_| Error::CommandTimeout {
+                command: "LIST".into(),
+                timeout,
+            })?
+    }
+
+    /// Returns the capabilities advertised by the server.
+    ///
+    /// Queried with `CAPABILITY` right after login, when the useful extensions are
+    /// also enabled, and cached for the lifetime of the connection, so callers can
+    /// branch on IDLE/MOVE/CONDSTORE support cheaply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the CAPABILITY command fails or times out.
+    #[instrument(name = "ImapEmailClient::capabilities", skip(self))]
+    pub async fn capabilities(&mut self) -> Result<&Capabilities> {
+        self.refresh_if_idle().await?;
+        let capabilities = if let Some(capabilities) = self.capabilities.take() {
+            capabilities
+        } else {
+            let timeout = self.config.timeouts.command;
+            runtime::timeout(timeout, session::capabilities(&mut self.session))
+                .await
+                .map_err(|_| Error::CommandTimeout {
+                    command: "CAPABILITY".into(),
+                    timeout,
+                })??
+        };
+
+        Ok(self.capabilities.insert(capabilities))
+    }
+
+    /// Sends a raw IMAP command and returns its untagged responses.
+    ///
+    /// An escape hatch for provider-specific extensions the crate does not model.
+    /// Pass the command without a tag (the client adds one); each untagged response
+    /// is returned in its parsed, debug-formatted form.
+    ///
+    /// **Unstable:** available with the `unstable` feature; the signature and the
+    /// response format may change in any release. Only responses the underlying IMAP
+    /// parser understands can be read; a command producing other responses (e.g.
+    /// `NAMESPACE`) leaves the connection unusable. Commands that change the
+    /// session state (`SELECT`, `LOGOUT`, ...) confuse the client.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImapCommand`] if the server answers `NO` or `BAD`, or an
+    /// error if the command times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// for response in client.run_command("UID SEARCH X-GM-RAW \"has:attachment\"").await? {
+    ///     println!("{response}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "unstable")]
+    #[instrument(name = "ImapEmailClient::run_command", skip(self))]
+    pub async fn run_command(&mut self, command: &str) -> Result<Vec<String>> {
+        self.refresh_if_idle().await?;
+        let name = command
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        let timeout = self.config.timeouts.command;
+        let mut responses = Vec::new();
+
+        runtime::timeout(
+            timeout,
+            session::run_tagged_command(&mut self.session, &name, command, |response| {
+                responses.push(format!("{response:?}"));
+            }),
+        )
+        .await
+        .map_err(|_| Error::CommandTimeout {
+            command: name.clone(),
+            timeout,
+        })??;
+
+        debug!(response_count = responses.len(), "Raw command completed");
+
+        Ok(responses)
+    }
+
+    /// Returns the personal namespace of the account.
+    ///
+    /// The namespace tells whether folders live at the top level (`"Archive"`) or
+    /// below INBOX (`"INBOX.Archive"`). Methods taking a mailbox name resolve it
+    /// with [`Namespace::resolve`], so callers can always pass the short name.
+    ///
+    /// Servers advertising the `NAMESPACE` capability (RFC 2342) are asked directly.
+    /// Otherwise, or when that fails or reports no personal namespace, it is inferred
+    /// from `LIST`: the hierarchy delimiter, and whether every folder sits below
+    /// INBOX. The result is cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the LIST commands of the fallback fail or time out.
+    #[instrument(name = "ImapEmailClient::namespace", skip(self))]
+    pub async fn namespace(&mut self) -> Result<Namespace> {
+        self.refresh_if_idle().await?;
+        if let Some(namespace) = &self.namespace {
+            return Ok(namespace.clone());
+        }
+
+        let timeout = self.config.timeouts.command;
+        if self.has_capability("NAMESPACE").await {
+            match runtime::timeout(timeout, session::namespace(&mut self.session)).await {
+                Ok(Ok(Some(namespace))) => {
+                    debug!(prefix = %namespace.prefix, delimiter = ?namespace.delimiter, "Queried namespace");
+                    self.namespace = Some(namespace.clone());
+                    return Ok(namespace);
+                }
+                Ok(Ok(None)) => debug!("No personal namespace reported, inferring from LIST"),
+                Ok(Err(e)) => warn!(error = %e, "NAMESPACE failed, inferring from LIST"),
+                Err(_) => warn!(?timeout, "NAMESPACE timed out, inferring from LIST"),
+            }
+        }
+
+        let map_timeout = |_| Error::CommandTimeout {
+            command: "LIST".into(),
+            timeout,
+        };
+
+        let delimiter = runtime::timeout(timeout, session::hierarchy_delimiter(&mut self.session))
+            .await
+            .map_err(map_timeout)??;
+        let mailboxes = runtime::timeout(timeout, session::list_mailboxes(&mut self.session))
+            .await
+            .map_err(map_timeout)??;
+
+        let namespace = Namespace::infer(delimiter, &mailboxes);
+        debug!(prefix = %namespace.prefix, delimiter = ?namespace.delimiter, "Discovered namespace");
+
+        self.namespace = Some(namespace.clone());
+        Ok(namespace)
+    }
+
+    /// Returns the message counters of a mailbox without selecting it.
+    ///
+    /// Uses the IMAP `STATUS` command, so it is cheap enough for health checks and
+    /// does not disturb the mailbox being monitored. `mailbox` is resolved against
+    /// the account's [`namespace`](Self::namespace). Meant for mailboxes other than
+    /// the selected one, for which servers may report stale counters; use
+    /// [`unread_count`](Self::unread_count) there.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox does not exist or the command fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let status = client.mailbox_status("INBOX").await?;
+    /// println!("{} messages, {:?} unseen", status.messages, status.unseen);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::mailbox_status", skip(self))]
+    pub async fn mailbox_status(&mut self, mailbox: &str) -> Result<MailboxStatus> {
+        self.refresh_if_idle().await?;
+        let mailbox = self.namespace().await?.resolve(mailbox);
+        let timeout = self.config.timeouts.command;
+
+        runtime::timeout(
+            timeout,
+            session::mailbox_status(&mut self.session, &mailbox),
+        )
+        .await
+        .map_err(|_| Error::CommandTimeout {
+            command: "STATUS".into(),
+            timeout,
+        })?
+    }
+
+    /// Returns the number of unread emails in the selected mailbox.
+    ///
+    /// Counts the results of `SEARCH UNSEEN`: `STATUS` must not be used on the
+    /// selected mailbox (RFC 3501, section 6.3.10), and servers may answer it with
+    /// stale counters. Nothing is fetched, so it is cheap enough to poll from a
+    /// dashboard.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// if client.unread_count().await? > 100 {
+    ///     eprintln!("mailbox backlog is growing");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::unread_count",
+        skip(self),
+        fields(mailbox = %self.mailbox)
+    )]
+    pub async fn unread_count(&mut self) -> Result<u32> {
+        self.refresh_if_idle().await?;
+        let timeout = self.config.timeouts.command;
+        runtime::timeout(timeout, session::count_unseen(&mut self.session))
+            .await
+            .map_err(|_| Error::CommandTimeout {
+                command: "SEARCH".into(),
+                timeout,
+            })?
+    }
+
+    /// Returns the storage quotas that apply to the INBOX.
+    ///
+    /// Uses `GETQUOTAROOT`, so automation can alert before a mailbox fills up and
+    /// starts bouncing emails. An empty list means the server reports no quota.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MissingCapability`] if the server lacks the QUOTA extension,
+    /// or an error if the command fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// for quota in client.quota().await? {
+    ///     if quota.max_usage_percent() > 90.0 {
+    ///         eprintln!("quota root '{}' is almost full", quota.root);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::quota", skip(self))]
+    pub async fn quota(&mut self) -> Result<Vec<Quota>> {
+        self.refresh_if_idle().await?;
+        self.require_capability("QUOTA").await?;
+
+        let timeout = self.config.timeouts.command;
+
+        runtime::timeout(timeout, session::quota_root(&mut self.session, "INBOX"))
+            .await
+            .map_err(|_| Error::CommandTimeout {
+                command: "GETQUOTAROOT".into(),
+                timeout,
+            })?
+    }
+
+    /// Deletes emails from the selected mailbox.
+    ///
+    /// The emails are flagged `\Deleted` and then expunged according to the
+    /// configured [`ExpungeMode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if flagging or expunging fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient, WaitOptions};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let email = client
+    ///     .wait_for_email(&OtpMatcher::six_digit(), &WaitOptions::default())
+    ///     .await?;
+    /// client.delete_emails(&[email.uid]).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::delete_emails", skip(self, uids), fields(uid_count = uids.len()))]
+    pub async fn delete_emails(&mut self, uids: &[u32]) -> Result<()> {
+        self.refresh_if_idle().await?;
+        if uids.is_empty() {
+            return Ok(());
+        }
+
+        let timeout = self.config.timeouts.command;
+        with_command_timeout(
+            timeout,
+            "STORE",
+            session::add_flags(&mut self.session, uids, "\\Deleted"),
+        )
+        .await?;
+
+        let scope = match self.config.expunge {
+            ExpungeMode::Deferred => None,
+            ExpungeMode::Immediate => Some(None),
+            ExpungeMode::Uid => {
+                if self.has_capability("UIDPLUS").await {
+                    Some(Some(uids))
+                } else {
+                    debug!("Server lacks UIDPLUS, leaving emails flagged as deleted");
+                    None
+                }
+            }
+        };
+
+        if let Some(uids) = scope {
+            let (session, capabilities) = self.negotiated_session().await;
+            with_command_timeout(
+                timeout,
+                "EXPUNGE",
+                session::expunge(session, &capabilities, uids),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates a mailbox.
+    ///
+    /// `mailbox` is resolved against the account's [`namespace`](Self::namespace).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox already exists or the command fails or times out.
+    #[instrument(name = "ImapEmailClient::create_mailbox", skip(self))]
+    pub async fn create_mailbox(&mut self, mailbox: &str) -> Result<()> {
+        self.refresh_if_idle().await?;
+        let mailbox = self.namespace().await?.resolve(mailbox);
+        let timeout = self.config.timeouts.command;
+
+        with_command_timeout(
+            timeout,
+            "CREATE",
+            session::create_mailbox(&mut self.session, &mailbox),
+        )
+        .await
+    }
+
+    /// Creates a mailbox unless it already exists.
+    ///
+    /// Returns `true` if the mailbox was created. Handy in setup code that needs,
+    /// for example, a "Processed" folder to move handled emails into.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if listing or creating the mailbox fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    /// client.ensure_mailbox("Processed").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::ensure_mailbox", skip(self))]
+    pub async fn ensure_mailbox(&mut self, mailbox: &str) -> Result<bool> {
+        self.refresh_if_idle().await?;
+        let resolved = self.namespace().await?.resolve(mailbox);
+
+        if self.list_mailboxes().await?.contains(&resolved) {
+            debug!(mailbox = %resolved, "Mailbox already exists");
+            return Ok(false);
+        }
+
+        self.create_mailbox(&resolved).await?;
+        Ok(true)
+    }
+
+    /// Deletes a mailbox and every message in it.
+    ///
+    /// `mailbox` is resolved against the account's [`namespace`](Self::namespace).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mailbox does not exist or the command fails or times out.
+    #[instrument(name = "ImapEmailClient::delete_mailbox", skip(self))]
+    pub async fn delete_mailbox(&mut self, mailbox: &str) -> Result<()> {
+        self.refresh_if_idle().await?;
+        let mailbox = self.namespace().await?.resolve(mailbox);
+        let timeout = self.config.timeouts.command;
+
+        with_command_timeout(
+            timeout,
+            "DELETE",
+            session::delete_mailbox(&mut self.session, &mailbox),
+        )
+        .await
+    }
+
+    /// Renames a mailbox.
+    ///
+    /// Both names are resolved against the account's [`namespace`](Self::namespace).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `from` does not exist, `to` already exists, or the command
+    /// fails or times out.
+    #[instrument(name = "ImapEmailClient::rename_mailbox", skip(self))]
+    pub async fn rename_mailbox(&mut self, from: &str, to: &str) -> Result<()> {
+        self.refresh_if_idle().await?;
+        let namespace = self.namespace().await?;
+        let (from, to) = (namespace.resolve(from), namespace.resolve(to));
+        let timeout = self.config.timeouts.command;
+
+        with_command_timeout(
+            timeout,
+            "RENAME",
+            session::rename_mailbox(&mut self.session, &from, &to),
+        )
+        .await
+    }
+
+    /// Subscribes to a mailbox, so mail clients show it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command fails or times out.
+    #[instrument(name = "ImapEmailClient::subscribe_mailbox", skip(self))]
+    pub async fn subscribe_mailbox(&mut self, mailbox: &str) -> Result<()> {
+        self.refresh_if_idle().await?;
+        self.set_subscribed(mailbox, true).await
+    }
+
+    /// Unsubscribes from a mailbox.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command fails or times out.
+    #[instrument(name = "ImapEmailClient::unsubscribe_mailbox", skip(self))]
+    pub async fn unsubscribe_mailbox(&mut self, mailbox: &str) -> Result<()> {
+        self.refresh_if_idle().await?;
+        self.set_subscribed(mailbox, false).await
+    }
+
+    /// Appends a raw RFC 822 message to a mailbox.
+    ///
+    /// Useful in tests to plant a message and then assert that a matcher finds it.
+    /// `flags` are system or keyword flags such as `\Seen`; `date` sets the
+    /// message's INTERNALDATE (the server uses the current time when `None`).
+    /// `mailbox` is resolved against the account's [`namespace`](Self::namespace).
+    ///
+    /// Returns the UID of the new message when the server supports UIDPLUS
+    /// (`APPENDUID`), `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MailboxNotFound`] if the mailbox does not exist,
+    /// [`Error::InvalidConfig`] for a malformed flag, or an error if the command
+    /// fails or times out.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let message = b"From: test@example.com\r\nSubject: Code\r\n\r\nYour code is 123456.";
+    /// client.append("INBOX", message, &[], None).await?;
+    ///
+    /// let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+    /// assert_eq!(code, "123456");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::append",
+        skip(self, message),
+        fields(size = message.len())
+    )]
+    pub async fn append(
+        &mut self,
+        mailbox: &str,
+        message: &[u8],
+        flags: &[&str],
+        date: Option<DateTime<Utc>>,
+    ) -> Result<Option<u32>> {
+        self.refresh_if_idle().await?;
+        if let Some(flag) = flags.iter().find(|flag| !is_valid_flag(flag)) {
+            return Err(Error::InvalidConfig {
+                message: format!("invalid IMAP flag '{flag}'"),
+            });
+        }
+
+        let mailbox = self.namespace().await?.resolve(mailbox);
+        let flags = (!flags.is_empty()).then(|| format!("({})", flags.join(" ")));
+        // IMAP date-time: "dd-Mon-yyyy hh:mm:ss +zzzz" with a space-padded day
+        let date = date.map(|date| date.format("\"%e-%b-%Y %H:%M:%S %z\"").to_string());
+        let timeout = self.config.timeouts.command;
+        let (session, capabilities) = self.negotiated_session().await;
+
+        runtime::timeout(
+            timeout,
+            session::append(
+                session,
+                &capabilities,
+                &mailbox,
+                flags.as_deref(),
+                date.as_deref(),
+                message,
+            ),
+        )
+        .await
+        .map_err(|_| Error::CommandTimeout {
+            command: "APPEND".into(),
+            timeout,
+        })?
+    }
+
+    /// Logs out from the IMAP server.
+    ///
+    /// This should be called when you're done with the client.
+    /// If you don't call this, the connection will be dropped without
+    /// a clean logout (which is usually fine, but not ideal).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the logout command fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    /// // ... use client ...
+    /// client.logout().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(name = "ImapEmailClient::logout", skip(self))]
+    pub async fn logout(&mut self) -> Result<()> {
+        session::logout(&mut self.session).await
+    }
+
+    /// Replaces the connection with a new one.
+    ///
+    /// Connects, authenticates and selects the watched mailbox again, keeping the
+    /// baseline and the consumed emails, so waits continue where they left off. Use
+    /// it to recover from errors where [`Error::is_retryable`] returns `true`
+    /// without reconstructing the client. The old connection is logged out on a
+    /// best-effort basis.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new connection cannot be established; the client then
+    /// keeps the old connection and `reconnect` can be retried.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    /// let matcher = OtpMatcher::six_digit();
+    ///
+    /// let code = match client.wait_for_match(&matcher).await {
+    ///     Err(e) if e.is_retryable() => {
+    ///         client.reconnect().await?;
+    ///         client.wait_for_match(&matcher).await?
+    ///     }
+    ///     result => result?,
+    /// };
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(
+        name = "ImapEmailClient::reconnect",
+        skip(self),
+        fields(mailbox = %self.mailbox, start_uid = self.start_uid)
+    )]
+    pub async fn reconnect(&mut self) -> Result<()> {
+        let NewSession {
+            session,
+            stats: connect_stats,
+            capabilities,
+            uid_validity,
+        } = Self::initialize_session(&self.config, &self.mailbox).await?;
+        let mut old = std::mem::replace(&mut self.session, Box::new(session));
+        self.capabilities = capabilities;
+        self.connect_stats = connect_stats;
+        self.last_activity = Instant::now();
+
+        // UIDs from before the reconnect mean nothing if the mailbox was recreated
+        if uid_validity != self.uid_validity {
+            warn!(
+                mailbox = %self.mailbox,
+                old = ?self.uid_validity,
+                new = ?uid_validity,
+                "UIDVALIDITY changed, resetting baseline"
+            );
+            self.uid_validity = uid_validity;
+            self.consumed.clear();
+            self.reset_baseline().await?;
+        }
+
+        let logout_timeout = self.config.timeouts.logout;
+        match runtime::timeout(logout_timeout, session::logout(&mut old)).await {
+            Ok(Ok(())) => debug!("Logged out of previous connection"),
+            Ok(Err(e)) => debug!(error = %e, "Previous connection logout failed"),
+            Err(_) => debug!("Previous connection logout timed out"),
+        }
+
+        debug!("Reconnected");
+        Ok(())
+    }
+
+    /// Logs out synchronously, blocking the current thread until the server confirms
+    /// or the logout timeout elapses.
+    ///
+    /// Intended for synchronous teardown code (CLI exit paths, test fixtures, `Drop`
+    /// impls) where `.logout().await` is not available. Outside any async runtime a
+    /// temporary one drives the logout; inside a multi-threaded Tokio runtime the
+    /// worker thread is handed off while blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LogoutTimeout`] or [`Error::ImapLogout`] if the logout does not
+    /// complete cleanly, and [`Error::BlockingInAsyncContext`] when called from a
+    /// single-threaded runtime (the connection is then dropped without logout).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    ///
+    /// # fn example(runtime: tokio::runtime::Runtime) -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let client = runtime.block_on(ImapEmailClient::connect(config))?;
+    /// // ... later, in synchronous code ...
+    /// client.shutdown_blocking()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "blocking")]
+    pub fn shutdown_blocking(mut self) -> Result<()> {
+        let timeout = self.config.timeouts.logout;
+
+        runtime::block_on(async move {
+            runtime::timeout(timeout, self.logout())
+                .await
+                .map_err(|_| Error::LogoutTimeout { timeout })?
+        })
+        .ok_or(Error::BlockingInAsyncContext)?
+    }
+
+    /// Converts this client into a guard that logs out on drop.
+    ///
+    /// This is useful for ensuring cleanup in the face of early returns
+    /// or panics.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let client = ImapEmailClient::connect(config).await?;
+    /// let mut guard = client.into_guard();
+    ///
+    /// let code = guard.wait_for_match(&OtpMatcher::six_digit()).await?;
+    /// // Guard will logout when dropped
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn into_guard(self) -> ImapEmailClientGuard {
+        ImapEmailClientGuard { inner: Some(self) }
+    }
+
+    /// Moves this client into a background task and returns a cloneable handle to it.
+    ///
+    /// Use this when several tasks need to issue requests against one connection.
+    /// See [`ImapEmailClientHandle`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient};
+    /// use email_sync::matcher::OtpMatcher;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let handle = ImapEmailClient::connect(config).await?.into_handle();
+    /// let other = handle.clone();
+    ///
+    /// let code = other.wait_for_match(OtpMatcher::six_digit()).await?;
+    /// handle.logout().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn into_handle(self) -> ImapEmailClientHandle {
+        ImapEmailClientHandle::spawn(self)
+    }
+
+    /// Returns the email address used for this connection.
+    #[must_use]
+    pub fn email(&self) -> &str {
+        self.config.email()
+    }
+
+    /// Returns the configuration this client was created with.
+    #[must_use]
+    pub fn config(&self) -> &ImapConfig {
+        &self.config
+    }
+
+    /// Overrides the timeouts of this client until the returned guard is dropped.
+    ///
+    /// The guard dereferences to the client, so any operation can run with the
+    /// scoped timeouts, e.g. a generous fetch timeout for one search over a slow
+    /// proxy while keeping quick NOOPs otherwise. Reconnections within the scope
+    /// use the scoped timeouts too.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::{ImapConfig, ImapEmailClient, TimeoutConfig};
+    /// use email_sync::matcher::OtpMatcher;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> email_sync::Result<()> {
+    /// # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+    /// let mut client = ImapEmailClient::connect(config).await?;
+    ///
+    /// let timeouts = TimeoutConfig {
+    ///     message_fetch: Duration::from_mins(2),
+    ///     ..client.config().timeouts.clone()
+    /// };
+    /// let code = client
+    ///     .with_timeouts(timeouts)
+    ///     .find_recent_match(&OtpMatcher::six_digit(), Duration::from_mins(10))
+    ///     .await?;
+    /// // The configured timeouts apply again
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_timeouts(&mut self, timeouts: TimeoutConfig) -> ScopedTimeouts<'_> {
+        let saved = std::mem::replace(&mut self.config.timeouts, timeouts);
+        ScopedTimeouts {
+            client: self,
+            saved: Some(saved),
+        }
+    }
 
     /// Returns the IMAP host used for this connection.
     #[must_use]
@@ -301,129 +2790,803 @@ impl ImapEmailClient {
     // Private methods
     // ─────────────────────────────────────────────────────────────────────────
 
-    /// Initializes IMAP session with connection, authentication, and mailbox selection.
-    async fn initialize_session(config: &ImapConfig) -> Result<ImapSession> {
+    /// Opens the TLS connection to the server, through the configured proxy or
+    /// the next one of the proxy pool.
+    async fn open_tls_stream(config: &ImapConfig, stats: &mut ConnectStats) -> Result<TlsStream> {
         let imap_host = config.effective_imap_host();
         let target_addr = config.server_address();
         let timeouts = &config.timeouts;
 
+        let proxy = ProxyChoice::pick(config.proxy.as_ref(), config.proxy_pool.as_ref());
+
         // Establish TLS connection
-        let tls_stream = tokio::time::timeout(
+        let connected = runtime::timeout(
             timeouts.connect,
-            connection::establish_tls_connection(&imap_host, &target_addr, config.proxy.as_ref()),
+            connection::establish_tls_connection(
+                &imap_host,
+                &config.tls,
+                &target_addr,
+                proxy.proxy(),
+                stats,
+            ),
         )
         .await
         .map_err(|_| Error::ConnectTimeout {
             target: target_addr.clone(),
             timeout: timeouts.connect,
-        })??;
+        })
+        .and_then(|result| result);
+
+        proxy.report(connected.is_ok());
+        connected
+    }
+
+    /// Opens the TLS connection, retrying transient failures as configured by
+    /// [`TimeoutConfig::connect_attempts`](crate::TimeoutConfig::connect_attempts).
+    async fn open_tls_stream_with_retries(
+        config: &ImapConfig,
+        stats: &mut ConnectStats,
+    ) -> Result<TlsStream> {
+        let mut attempt = 1;
+        loop {
+            match Self::open_tls_stream(config, stats).await {
+                Err(e) if e.is_retryable() => {
+                    let Some(delay) = config.timeouts.connect_retry_delay(attempt) else {
+                        return Err(e);
+                    };
+                    warn!(error = %e, attempt, ?delay, "Connection failed, retrying");
+                    runtime::sleep(delay).await;
+                    *stats = ConnectStats::default();
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Initializes IMAP session with connection, authentication, feature
+    /// negotiation and mailbox selection, reporting the outcome to the event hook.
+    async fn initialize_session(config: &ImapConfig, mailbox: &str) -> Result<NewSession> {
+        let result = Self::open_session(config, mailbox).await;
+        if let Some(hook) = &config.event_hook {
+            match &result {
+                Ok(new) => hook.on_event(&Event::Connected {
+                    stats: new.stats.clone(),
+                }),
+                Err(e) => hook.on_event(&Event::failed(e)),
+            }
+        }
+        result
+    }
 
+    /// Opens a session for [`initialize_session`](Self::initialize_session).
+    async fn open_session(config: &ImapConfig, mailbox: &str) -> Result<NewSession> {
+        let timeouts = &config.timeouts;
+        let mut stats = ConnectStats::default();
+
+        let tls_stream = Self::open_tls_stream_with_retries(config, &mut stats).await?;
         debug!("TLS connection established");
 
         // Authenticate
+        #[cfg(feature = "gssapi")]
+        let imap_host = config.effective_imap_host();
         let auth_config = AuthConfig {
             email: config.email(),
             password: config.password(),
+            method: config.auth,
+            #[cfg(feature = "ntlm")]
+            ntlm_domain: config.ntlm_domain.as_deref(),
+            #[cfg(feature = "gssapi")]
+            gssapi: config
+                .gssapi_provider
+                .as_deref()
+                .map(|provider| (provider, imap_host.as_str())),
         };
 
-        let mut session = tokio::time::timeout(
+        let started = Instant::now();
+        let mut session = runtime::timeout(
             timeouts.auth,
             session::authenticate(tls_stream, &auth_config),
         )
         .await
-        .map_err(|_| Error::AuthTimeout {
-            email: config.email().to_string(),
-            timeout: timeouts.auth,
-        })??;
+        .map_err(|_| Error::AuthTimeout {
+            email: config.email().to_string(),
+            timeout: timeouts.auth,
+        })??;
+
+        debug!("Authenticated");
+        stats.auth_ms = connection::elapsed_ms(started);
+
+        // Enable extensions before SELECT, which reports CONDSTORE state once enabled
+        let capabilities = match with_command_timeout(
+            timeouts.command,
+            "CAPABILITY",
+            session::negotiate(&mut session),
+        )
+        .await
+        {
+            Ok(capabilities) => Some(capabilities),
+            Err(e) => {
+                warn!(error = %e, "Failed to negotiate capabilities");
+                None
+            }
+        };
+
+        // Identify the client; some providers refuse further commands otherwise.
+        // Only sent when advertised, and a rejection is not fatal.
+        if let Some(client_id) = &config.client_id {
+            if capabilities.as_ref().is_some_and(|caps| caps.has("ID")) {
+                let started = Instant::now();
+                match with_command_timeout(
+                    timeouts.command,
+                    "ID",
+                    session::identify(&mut session, client_id),
+                )
+                .await
+                {
+                    Ok(server_id) => debug!(?server_id, "Sent client identification"),
+                    Err(e) => warn!(error = %e, "Server did not accept client identification"),
+                }
+                stats.auth_ms += connection::elapsed_ms(started);
+            } else {
+                debug!("Server does not advertise ID, skipping client identification");
+            }
+        }
+
+        let started = Instant::now();
+        let uid_validity = runtime::timeout(
+            timeouts.select,
+            session::select_mailbox(&mut session, mailbox),
+        )
+        .await
+        .map_err(|_| Error::SelectTimeout {
+            mailbox: mailbox.to_string(),
+            timeout: timeouts.select,
+        })??;
+
+        stats.select_ms = connection::elapsed_ms(started);
+
+        debug!(mailbox, ?stats, "Selected mailbox");
+
+        Ok(NewSession {
+            session,
+            stats,
+            capabilities,
+            uid_validity,
+        })
+    }
+
+    /// Gets the initial UID to start monitoring from.
+    async fn get_initial_uid(session: &mut ImapSession, config: &ImapConfig) -> Result<u32> {
+        runtime::timeout(config.timeouts.uid_fetch, session::get_latest_uid(session))
+            .await
+            .map_err(|_| Error::UidFetchTimeout {
+                timeout: config.timeouts.uid_fetch,
+            })?
+    }
+
+    /// Polls for new emails until one of `matchers` fires or the wait times out.
+    ///
+    /// The first `options.skip` matching emails (in arrival order) are passed over, and
+    /// emails outside `options.in_thread` are ignored.
+    async fn poll_for_any(
+        &mut self,
+        matchers: &[&dyn Matcher],
+        options: &WaitOptions,
+    ) -> Result<MatchedEmail> {
+        let timeout = options.timeout.unwrap_or(self.config.polling.max_wait);
+        let poll_interval = self.config.polling.interval;
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let mut to_skip = options.skip;
+        // Message-IDs of skipped emails, so their duplicates are not counted again
+        let mut skipped = HashSet::new();
+        let mut extract = Extractor::new(&self.config);
+        let mut report = WaitReport::default();
+        let mut spans = PollSpans::default();
+
+        loop {
+            report.polls += 1;
+            let span = spans.next(report.polls, deadline);
+            let polled = self
+                .check_new_emails(matchers, options.filter.as_ref(), |message| {
+                    report.messages_scanned += 1;
+                    if let Some(message_id) = &options.in_thread {
+                        if !parser::is_reply_to(message, message_id) {
+                            return ControlFlow::Continue(());
+                        }
+                    }
+
+                    match extract.extract_match(message, matchers) {
+                        ExtractResult::Match { index, details } => {
+                            let origin = MatchOrigin::of(message);
+                            if origin
+                                .message_id
+                                .as_ref()
+                                .is_some_and(|id| skipped.contains(id))
+                            {
+                                debug!(uid = message.uid, "Skipping duplicate of skipped email");
+                                return ControlFlow::Continue(());
+                            }
+                            if to_skip == 0 {
+                                return ControlFlow::Break((origin, index, details.into_owned()));
+                            }
+                            debug!(uid = message.uid, to_skip, "Skipping matching email");
+                            to_skip -= 1;
+                            skipped.extend(origin.message_id);
+                            ControlFlow::Continue(())
+                        }
+                        // Continue to next message (parse errors are logged in parser)
+                        ExtractResult::NoMatch | ExtractResult::ParseError => {
+                            ControlFlow::Continue(())
+                        }
+                    }
+                })
+                .instrument(span)
+                .await;
+
+            match polled {
+                Ok(Some((origin, index, details))) => {
+                    let mut email = self
+                        .complete_match(origin, (index, matchers[index]), details)
+                        .await;
+                    email.report = Some(finish_wait(report, started, "matched"));
+                    return Ok(email);
+                }
+                Ok(None) => {}
+                Err(e) => self.recover(e, &mut report).await?,
+            }
+            self.report_progress(started, &report);
+
+            let Some(delay) = next_poll_delay(poll_interval, deadline) else {
+                let report = finish_wait(report, started, "timeout");
+                return Err(Error::WaitTimeout { timeout, report });
+            };
+            runtime::sleep(delay).await;
+        }
+    }
+
+    /// Checks for new emails once and returns every one matching `matcher`, in
+    /// INTERNALDATE order (by UID for equal or missing dates).
+    pub(crate) async fn poll_matches(
+        &mut self,
+        matcher: &dyn Matcher,
+    ) -> Result<Vec<MatchedEmail>> {
+        let mut extract = Extractor::new(&self.config);
+        let mut found = Vec::new();
+        self.check_new_emails(&[matcher], None, |message| {
+            if let ExtractResult::Match { index, details } =
+                extract.extract_match(message, &[matcher])
+            {
+                found.push((MatchOrigin::of(message), (index, details.into_owned())));
+            }
+            ControlFlow::<()>::Continue(())
+        })
+        .await?;
+        sort_by_arrival(&mut found, Order::OldestFirst);
+
+        let mut emails = Vec::with_capacity(found.len());
+        for (origin, (index, details)) in found {
+            emails.push(self.complete_match(origin, (index, matcher), details).await);
+        }
+        Ok(emails)
+    }
+
+    /// Polls `mailboxes` in turn until one has a new email matching `matcher`.
+    async fn poll_folders(
+        &mut self,
+        mailboxes: &[&str],
+        matcher: &dyn Matcher,
+    ) -> Result<MatchedEmail> {
+        let timeout = self.config.polling.max_wait;
+        let poll_interval = self.config.polling.interval;
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let mut extract = Extractor::new(&self.config);
+        let mut report = WaitReport::default();
+        let mut spans = PollSpans::default();
+
+        loop {
+            for mailbox in mailboxes {
+                if self.mailbox != *mailbox {
+                    self.enter_folder(mailbox).await?;
+                }
+
+                report.polls += 1;
+                let span = spans.next(report.polls, deadline);
+                let polled = self
+                    .check_new_emails(&[matcher], None, |message| {
+                        report.messages_scanned += 1;
+                        match extract.extract_match(message, &[matcher]) {
+                            ExtractResult::Match { index, details } => ControlFlow::Break((
+                                MatchOrigin::of(message),
+                                index,
+                                details.into_owned(),
+                            )),
+                            ExtractResult::NoMatch | ExtractResult::ParseError => {
+                                ControlFlow::Continue(())
+                            }
+                        }
+                    })
+                    .instrument(span)
+                    .await;
+
+                match polled {
+                    Ok(Some((origin, index, details))) => {
+                        debug!(mailbox, "Found match while watching folders");
+                        let mut email =
+                            self.complete_match(origin, (index, matcher), details).await;
+                        email.report = Some(finish_wait(report, started, "matched"));
+                        return Ok(email);
+                    }
+                    Ok(None) => self.report_progress(started, &report),
+                    Err(e) => {
+                        self.recover(e, &mut report).await?;
+                        break;
+                    }
+                }
+            }
+
+            let Some(delay) = next_poll_delay(poll_interval, deadline) else {
+                let report = finish_wait(report, started, "timeout");
+                return Err(Error::WaitTimeout { timeout, report });
+            };
+            runtime::sleep(delay).await;
+        }
+    }
+
+    /// Selects `mailbox`, stashing the baseline and consumed emails of the current
+    /// mailbox and restoring those of `mailbox`.
+    ///
+    /// A mailbox entered for the first time, or whose UIDVALIDITY changed, gets its
+    /// baseline set to its newest email. `SELECT` closes the current mailbox
+    /// without expunging it.
+    async fn enter_folder(&mut self, mailbox: &str) -> Result<()> {
+        let timeout = self.config.timeouts.select;
+        let uid_validity =
+            runtime::timeout(timeout, session::select_mailbox(&mut self.session, mailbox))
+                .await
+                .map_err(|_| Error::SelectTimeout {
+                    mailbox: mailbox.to_string(),
+                    timeout,
+                })??;
+
+        let previous = std::mem::replace(&mut self.mailbox, mailbox.to_string());
+        self.folders.insert(
+            previous,
+            FolderState {
+                uid_validity: self.uid_validity,
+                start_uid: self.start_uid,
+                consumed: std::mem::take(&mut self.consumed),
+            },
+        );
+        self.uid_validity = uid_validity;
+
+        match self.folders.remove(mailbox) {
+            Some(state) if state.uid_validity == uid_validity => {
+                self.start_uid = state.start_uid;
+                self.consumed = state.consumed;
+            }
+            stale => {
+                if stale.is_some() {
+                    warn!(mailbox, "UIDVALIDITY changed, resetting baseline");
+                }
+                self.start_uid = Self::get_initial_uid(&mut self.session, &self.config).await?;
+            }
+        }
+
+        debug!(start_uid = self.start_uid, "Entered mailbox");
+        Ok(())
+    }
+
+    /// Calculates the IMAP SINCE date from a `max_age` duration, or `None` if
+    /// the lookback reaches beyond any representable date (search everything).
+    ///
+    /// `skew_margin` is subtracted as well: servers compare SINCE with dates in
+    /// their own time zone, which may still be on the previous day. The exact
+    /// window is applied afterwards by INTERNALDATE.
+    fn calculate_since_date(
+        now: DateTime<Utc>,
+        max_age: Duration,
+        skew_margin: Duration,
+    ) -> Option<NaiveDate> {
+        let lookback = max_age.saturating_add(skew_margin);
+        age_cutoff(now, lookback).map(|since| since.date_naive())
+    }
+
+    /// Returns the UIDs of the emails that arrived within `max_age`, newest first
+    /// by INTERNALDATE.
+    async fn search_recent_uids(&mut self, max_age: Duration) -> Result<Vec<u32>> {
+        let since_date =
+            Self::calculate_since_date(Utc::now(), max_age, self.config.clock_skew_margin);
+
+        debug!(since_date = ?since_date, "Searching for recent emails");
+
+        let uids = self.search_emails_since(since_date).await?;
+
+        if uids.is_empty() {
+            return Ok(uids);
+        }
+
+        // SINCE only has day granularity; narrow down to the exact window
+        self.filter_uids_by_age(uids, max_age).await
+    }
+
+    /// Searches for email UIDs since a given date.
+    async fn search_emails_since(&mut self, since_date: Option<NaiveDate>) -> Result<Vec<u32>> {
+        let timeout = self.config.timeouts.uid_fetch;
+
+        let started = Instant::now();
+        let session = &mut self.session;
+        let uids = runtime::timeout(
+            timeout,
+            with_retries!(
+                &self.config.retry,
+                "SEARCH",
+                session::search_emails_since(session, since_date)
+            ),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout });
+        events::record(
+            self.config.event_hook.as_deref(),
+            Operation::Search,
+            started,
+        );
+        uids?
+    }
 
-        debug!("Authenticated");
+    /// Keeps only the UIDs whose INTERNALDATE lies within `max_age` of now.
+    ///
+    /// The result is ordered newest first by INTERNALDATE (ties and messages without
+    /// an INTERNALDATE by UID). Messages without an INTERNALDATE are kept.
+    async fn filter_uids_by_age(&mut self, uids: Vec<u32>, max_age: Duration) -> Result<Vec<u32>> {
+        let timeout = self.config.timeouts.uid_fetch;
+        let cutoff = age_cutoff(Utc::now(), max_age);
 
-        // Select INBOX
-        tokio::time::timeout(
-            timeouts.select,
-            session::select_mailbox(&mut session, "INBOX"),
+        let dates: HashMap<u32, _> = runtime::timeout(
+            timeout,
+            session::fetch_internal_dates(&mut self.session, &uids),
         )
         .await
-        .map_err(|_| Error::SelectTimeout {
-            mailbox: "INBOX".to_string(),
-            timeout: timeouts.select,
-        })??;
+        .map_err(|_| Error::UidFetchTimeout { timeout })??
+        .into_iter()
+        .collect();
+
+        let total = uids.len();
+        let mut recent: Vec<u32> = uids
+            .into_iter()
+            .filter(|uid| {
+                dates
+                    .get(uid)
+                    .zip(cutoff)
+                    .is_none_or(|(date, cutoff)| *date >= cutoff)
+            })
+            .collect();
+        recent.sort_unstable_by_key(|uid| std::cmp::Reverse((dates.get(uid).copied(), *uid)));
 
-        debug!("Selected INBOX");
+        debug!(
+            total,
+            recent = recent.len(),
+            "Filtered emails by internal date"
+        );
 
-        Ok(session)
+        Ok(recent)
     }
 
-    /// Gets the initial UID to start monitoring from.
-    async fn get_initial_uid(session: &mut ImapSession, config: &ImapConfig) -> Result<u32> {
-        tokio::time::timeout(config.timeouts.uid_fetch, session::get_latest_uid(session))
-            .await
-            .map_err(|_| Error::UidFetchTimeout {
-                timeout: config.timeouts.uid_fetch,
-            })?
+    /// Orders `uids` newest first with server-side SORT when available.
+    ///
+    /// Falls back to the given order when the server lacks the SORT extension or
+    /// the command fails.
+    async fn sort_newest_first(&mut self, uids: Vec<u32>) -> Vec<u32> {
+        self.server_sort_newest_first(&uids).await.unwrap_or(uids)
+    }
+
+    /// Orders `uids` newest first with server-side SORT, or returns `None` when
+    /// the server lacks the SORT extension or the command fails.
+    async fn server_sort_newest_first(&mut self, uids: &[u32]) -> Option<Vec<u32>> {
+        if uids.len() < 2 {
+            return Some(uids.to_vec());
+        }
+        if !self.has_capability("SORT").await {
+            return None;
+        }
+
+        let timeout = self.config.timeouts.command;
+        match runtime::timeout(timeout, session::sort_newest_first(&mut self.session, uids)).await {
+            Ok(Ok(sorted)) if sorted.len() == uids.len() => Some(sorted),
+            Ok(Ok(sorted)) => {
+                warn!(
+                    expected = uids.len(),
+                    returned = sorted.len(),
+                    "SORT returned a different set of emails, using the search order"
+                );
+                None
+            }
+            Ok(Err(e)) => {
+                warn!(error = %e, "SORT failed, using the search order");
+                None
+            }
+            Err(_) => {
+                warn!(
+                    timeout_secs = timeout.as_secs(),
+                    "SORT timed out, using the search order"
+                );
+                None
+            }
+        }
     }
 
-    /// Calculates the IMAP SINCE date from a `max_age` duration.
-    fn calculate_since_date(max_age: Duration) -> NaiveDate {
-        let now = Utc::now();
-        let since_datetime =
-            now - chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::zero());
-        since_datetime.date_naive()
+    /// Finds matching content in a list of UIDs, checking them in the given order.
+    ///
+    /// Emails are fetched [`ImapConfig::scan_concurrency`] at a time, until
+    /// [`ImapConfig::scan_limits`] is reached.
+    async fn find_match_in_uids(
+        &mut self,
+        uids: &[u32],
+        matchers: &[&dyn Matcher],
+    ) -> Result<MatchedEmail> {
+        let mut extract = Extractor::new(&self.config);
+        let limits = self.config.scan_limits;
+        let mut candidates: Vec<u32> = uids
+            .iter()
+            .copied()
+            .filter(|uid| {
+                let consumed = self.consumed.contains(uid);
+                if consumed {
+                    debug!(uid, "Skipping already consumed email");
+                }
+                !consumed
+            })
+            .collect();
+
+        let mut truncated = false;
+        if let Some(max) = limits.max_messages {
+            truncated = candidates.len() > max;
+            candidates.truncate(max);
+        }
+
+        let (mut scanned, mut bytes) = (0, 0);
+        'scan: for batch in candidates.chunks(self.config.scan_concurrency.max(1)) {
+            let mut messages = self.fetch_messages(&session::uid_set(batch), false).await?;
+            // FETCH responses come in any order; check them in the order of `uids`
+            messages.sort_by_key(|message| {
+                batch
+                    .iter()
+                    .position(|uid| message.uid == Some(*uid))
+                    .unwrap_or(usize::MAX)
+            });
+
+            for message in messages {
+                scanned += 1;
+                bytes += message.size.map_or_else(
+                    || message.body().map_or(0, |body| body.len() as u64),
+                    u64::from,
+                );
+
+                if self.is_fresh(&message, None) {
+                    // Parse errors are logged in parser
+                    if let ExtractResult::Match { index, details } =
+                        extract.extract_match(&message, matchers)
+                    {
+                        let email = self
+                            .complete_match(
+                                MatchOrigin::of(&message),
+                                (index, matchers[index]),
+                                details,
+                            )
+                            .await;
+                        return Ok(email);
+                    }
+                }
+
+                if limits.max_bytes.is_some_and(|max| bytes >= max) && scanned < candidates.len() {
+                    truncated = true;
+                    break 'scan;
+                }
+            }
+        }
+
+        if truncated {
+            warn!(
+                messages = scanned,
+                bytes, "Scan limit reached before a matching email was found"
+            );
+            return Err(Error::ScanLimitReached {
+                messages: scanned,
+                bytes,
+            });
+        }
+        Err(Error::NoMatch)
     }
 
-    /// Searches for email UIDs since a given date.
-    async fn search_emails_since(&mut self, since_date: NaiveDate) -> Result<Vec<u32>> {
-        let timeout = self.config.timeouts.uid_fetch;
+    /// Subscribes to or unsubscribes from a mailbox.
+    async fn set_subscribed(&mut self, mailbox: &str, subscribed: bool) -> Result<()> {
+        let mailbox = self.namespace().await?.resolve(mailbox);
+        let timeout = self.config.timeouts.command;
+        let command = if subscribed {
+            "SUBSCRIBE"
+        } else {
+            "UNSUBSCRIBE"
+        };
 
-        tokio::time::timeout(
+        with_command_timeout(
             timeout,
-            session::search_emails_since(&mut self.session, since_date),
+            command,
+            session::set_subscribed(&mut self.session, &mailbox, subscribed),
         )
         .await
-        .map_err(|_| Error::UidFetchTimeout { timeout })?
     }
 
-    /// Finds matching content in a list of UIDs.
-    async fn find_match_in_uids(&mut self, uids: &[u32], matcher: &dyn Matcher) -> Result<String> {
-        let fetch_timeout = self.config.timeouts.message_fetch;
+    /// Returns `true` if `message` passes the configured recipient alias filter.
+    fn is_for_recipient(config: &ImapConfig, message: &Fetch) -> bool {
+        config
+            .recipient_alias()
+            .is_none_or(|alias| parser::is_addressed_to(message, alias))
+    }
+
+    /// Records the email of `origin` as consumed so neither it nor a duplicate with
+    /// the same Message-ID is returned as a match again.
+    fn mark_consumed(&mut self, origin: MatchOrigin) {
+        if let Some(uid) = origin.uid {
+            self.consumed.insert(uid);
+        }
+        if let Some(message_id) = origin.message_id {
+            self.consumed_ids.insert(message_id);
+        }
+    }
+
+    /// Calls the [`ImapConfig::on_progress`] hook, and the event hook with
+    /// [`Event::Polled`], after a poll of a wait that started at `started`.
+    fn report_progress(&self, started: Instant, report: &WaitReport) {
+        let progress = WaitProgress {
+            elapsed: started.elapsed(),
+            polls: report.polls,
+            last_uid: self.start_uid,
+        };
+        if let Some(hook) = &self.config.on_progress {
+            hook.on_progress(&progress);
+        }
+        if let Some(hook) = &self.config.event_hook {
+            hook.on_event(&Event::Polled { progress });
+        }
+    }
+
+    /// Reconnects after a poll of a wait failed with a transient error, counting
+    /// the reconnect in `report`; other errors are returned.
+    async fn recover(&mut self, error: Error, report: &mut WaitReport) -> Result<()> {
+        if let Some(hook) = &self.config.event_hook {
+            hook.on_event(&Event::failed(&error));
+        }
+        if !error.is_retryable() {
+            return Err(error);
+        }
+        warn!(error = %error, "Poll failed, reconnecting");
+        self.reconnect().await?;
+        report.reconnects += 1;
+        Ok(())
+    }
+
+    /// Marks a matched email as consumed and gathers the details returned with it.
+    ///
+    /// `matcher` is the index and matcher that fired.
+    async fn complete_match(
+        &mut self,
+        origin: MatchOrigin,
+        (index, matcher): (usize, &dyn Matcher),
+        details: MatchDetails<'_>,
+    ) -> MatchedEmail {
+        let uid = origin.uid.unwrap_or_default();
+        let (internal_date, size) = (origin.internal_date, origin.size);
+        self.mark_consumed(origin);
 
-        // Search in reverse order (newest first)
-        for uid in uids.iter().rev() {
-            let uid_str = uid.to_string();
+        if let Some(hook) = &self.config.event_hook {
+            hook.on_event(&Event::Matched {
+                uid,
+                matcher_index: index,
+                value: self.config.redact_matches.redact(&details.value),
+                since_arrival: internal_date
+                    .and_then(|date| (Utc::now() - date.with_timezone(&Utc)).to_std().ok()),
+            });
+        }
+        let mut email =
+            MatchedEmail::new((&self.mailbox, uid), index, matcher.description(), details);
+        email.internal_date = internal_date;
+        email.size = size;
 
-            let mut fetch_result = tokio::time::timeout(
-                fetch_timeout,
-                session::fetch_messages_by_uid_range(&mut self.session, &uid_str),
+        if self.has_capability(GMAIL_CAPABILITY).await {
+            let timeout = self.config.timeouts.command;
+            match runtime::timeout(
+                timeout,
+                session::fetch_gmail_attributes(&mut self.session, uid),
             )
             .await
-            .map_err(|_| Error::FetchTimeout {
-                uid_range: uid_str.clone(),
-                timeout: fetch_timeout,
-            })??;
+            {
+                Ok(Ok(gmail)) => email.gmail = gmail,
+                Ok(Err(e)) => warn!(uid, error = %e, "Failed to fetch Gmail attributes"),
+                Err(_) => warn!(uid, "Fetching Gmail attributes timed out"),
+            }
+        }
 
-            while let Some(message_result) = fetch_result.next().await {
-                let message = message_result.map_err(|source| Error::FetchMessage { source })?;
+        email
+    }
 
-                match parser::extract_match_from_message(&message, matcher) {
-                    ExtractResult::Match(result) => return Ok(result.into_owned()),
-                    ExtractResult::NoMatch | ExtractResult::ParseError => {
-                        // Continue to next message (parse errors are logged in parser)
-                    }
-                }
+    /// Returns `true` if the server advertises `capability`.
+    ///
+    /// A failed capability query counts as unsupported.
+    async fn has_capability(&mut self, capability: &str) -> bool {
+        match self.capabilities().await {
+            Ok(capabilities) => capabilities.has(capability),
+            Err(e) => {
+                warn!(error = %e, "Failed to query capabilities");
+                false
             }
         }
+    }
 
-        Err(Error::NoMatch)
+    /// Returns the session along with the negotiated capabilities, for session
+    /// functions that adapt to them.
+    ///
+    /// A failed capability query counts as nothing supported.
+    async fn negotiated_session(&mut self) -> (&mut ImapSession, Cow<'_, Capabilities>) {
+        if let Err(e) = self.capabilities().await {
+            warn!(error = %e, "Failed to query capabilities");
+        }
+        let capabilities = self
+            .capabilities
+            .as_ref()
+            .map_or_else(|| Cow::Owned(Capabilities::default()), Cow::Borrowed);
+        (&mut self.session, capabilities)
     }
 
-    /// Checks for new emails and searches for matching content.
-    #[instrument(name = "ImapEmailClient::check_new_emails", skip(self, matcher))]
-    async fn check_new_emails(&mut self, matcher: &dyn Matcher) -> Result<Option<String>> {
+    /// Fails with [`Error::MissingCapability`] unless the server advertises `capability`.
+    async fn require_capability(&mut self, capability: &str) -> Result<()> {
+        if self.has_capability(capability).await {
+            Ok(())
+        } else {
+            Err(Error::MissingCapability {
+                capability: capability.to_string(),
+            })
+        }
+    }
+
+    /// Checks for new emails and feeds them to `visit` in UID order.
+    ///
+    /// Emails that were already consumed are not visited. Stops early when `visit`
+    /// returns [`ControlFlow::Break`], returning its value. The baseline advances
+    /// past every new message either way.
+    ///
+    /// Emails rejected by `filter` are not visited either. With
+    /// [`ImapConfig::subject_first`], emails whose subject matches one of
+    /// `matchers` are visited with their header section only.
+    #[instrument(name = "ImapEmailClient::check_new_emails", skip_all)]
+    async fn check_new_emails<B>(
+        &mut self,
+        matchers: &[&dyn Matcher],
+        filter: Option<&Filter>,
+        visit: impl FnMut(&Fetch) -> ControlFlow<B>,
+    ) -> Result<Option<B>> {
         let timeout = self.config.timeouts.uid_fetch;
 
-        let latest_uid = tokio::time::timeout(timeout, session::get_latest_uid(&mut self.session))
-            .await
-            .map_err(|_| Error::UidFetchTimeout { timeout })??;
+        let started = Instant::now();
+        self.last_activity = started;
+        let session = &mut self.session;
+        let latest_uid = runtime::timeout(
+            timeout,
+            with_retries!(
+                &self.config.retry,
+                "SEARCH",
+                session::get_latest_uid(session)
+            ),
+        )
+        .await
+        .map_err(|_| Error::UidFetchTimeout { timeout });
+        events::record(
+            self.config.event_hook.as_deref(),
+            Operation::Search,
+            started,
+        );
+        let latest_uid = latest_uid??;
 
         debug!(
             latest_uid,
@@ -435,47 +3598,268 @@ impl ImapEmailClient {
             return Ok(None);
         }
 
-        let result = self.search_new_emails(matcher, latest_uid).await?;
+        let result = self
+            .search_new_emails(latest_uid, matchers, filter, visit)
+            .await?;
         self.start_uid = latest_uid;
         Ok(result)
     }
 
-    /// Searches through new emails for matching pattern.
+    /// Fetches emails between the baseline and `latest_uid` and visits each one.
     #[instrument(
         name = "ImapEmailClient::search_new_emails",
-        skip(self, matcher),
+        skip(self, matchers, filter, visit),
         fields(latest_uid)
     )]
-    async fn search_new_emails(
+    async fn search_new_emails<B>(
         &mut self,
-        matcher: &dyn Matcher,
         latest_uid: u32,
-    ) -> Result<Option<String>> {
-        let fetch_timeout = self.config.timeouts.message_fetch;
+        matchers: &[&dyn Matcher],
+        filter: Option<&Filter>,
+        mut visit: impl FnMut(&Fetch) -> ControlFlow<B>,
+    ) -> Result<Option<B>> {
         let uid_range = format!("{}:{}", self.start_uid + 1, latest_uid);
 
-        let mut fetch_result = tokio::time::timeout(
-            fetch_timeout,
-            session::fetch_messages_by_uid_range(&mut self.session, &uid_range),
-        )
-        .await
-        .map_err(|_| Error::FetchTimeout {
-            uid_range: uid_range.clone(),
-            timeout: fetch_timeout,
-        })??;
+        let mut messages = if self.config.subject_first && !self.config.envelope_only {
+            self.fetch_subject_first(&uid_range, matchers, filter)
+                .await?
+        } else {
+            self.fetch_messages(&uid_range, false).await?
+        };
+        // Servers are not required to return FETCH responses sorted by UID; visit
+        // messages in UID (arrival) order
+        messages.sort_by_key(|message| message.uid);
+
+        // Duplicates within the batch (same Message-ID) are visited once
+        let mut seen_ids = HashSet::new();
+        let fresh = messages.iter().filter(|message| {
+            self.is_fresh(message, filter)
+                && parser::message_id(message).is_none_or(|id| seen_ids.insert(id))
+        });
+        for message in fresh {
+            if let ControlFlow::Break(result) = visit(message) {
+                return Ok(Some(result));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fetches the headers of the emails in `uid_range`, then the full messages of
+    /// those whose subject does not match any of `matchers`.
+    ///
+    /// Emails whose subject matches are returned with their header section only.
+    /// Bodies are not fetched for emails that are consumed or rejected by `filter`.
+    async fn fetch_subject_first(
+        &mut self,
+        uid_range: &str,
+        matchers: &[&dyn Matcher],
+        filter: Option<&Filter>,
+    ) -> Result<Vec<Fetch>> {
+        let mut extract = Extractor::new(&self.config);
+        let headers = self.fetch_messages(uid_range, true).await?;
+
+        let (mut messages, unmatched): (Vec<Fetch>, Vec<Fetch>) = headers
+            .into_iter()
+            .partition(|message| extract.subject_matches(message, matchers));
+        let body_uids: Vec<u32> = unmatched
+            .iter()
+            .filter(|message| self.is_fresh(message, filter))
+            .filter_map(|message| message.uid)
+            .collect();
+
+        debug!(
+            subject_matches = messages.len(),
+            body_fetches = body_uids.len(),
+            "Checked subjects of new emails"
+        );
+
+        if !body_uids.is_empty() {
+            let uid_set = session::uid_set(&body_uids);
+            messages.extend(self.fetch_messages(&uid_set, false).await?);
+        }
+        Ok(messages)
+    }
+
+    /// Fetches the emails in `uid_range` for matching, either with the
+    /// configured [`fetch_items`](ImapConfig::fetch_items) (in full by default)
+    /// or only their header section.
+    ///
+    /// With [`ImapConfig::envelope_only`], only the ENVELOPE is fetched either way.
+    async fn fetch_messages(&mut self, uid_range: &str, headers_only: bool) -> Result<Vec<Fetch>> {
+        let content = if headers_only {
+            FetchContent::Headers
+        } else {
+            FetchContent::Scan
+        };
+        self.fetch_content(uid_range, content).await
+    }
+
+    /// Fetches the emails in `uid_range` in full, whatever the configured
+    /// [`fetch_items`](ImapConfig::fetch_items).
+    async fn fetch_whole_messages(&mut self, uid_range: &str) -> Result<Vec<Fetch>> {
+        self.fetch_content(uid_range, FetchContent::Whole).await
+    }
 
-        while let Some(message_result) = fetch_result.next().await {
-            let message = message_result.map_err(|source| Error::FetchMessage { source })?;
+    async fn fetch_content(
+        &mut self,
+        uid_range: &str,
+        content: FetchContent,
+    ) -> Result<Vec<Fetch>> {
+        let timeout = self.config.timeouts.message_fetch;
+        let envelope_only = self.config.envelope_only;
+        let fetch_items = self.config.fetch_items.as_ref();
 
-            match parser::extract_match_from_message(&message, matcher) {
-                ExtractResult::Match(result) => return Ok(Some(result.into_owned())),
-                ExtractResult::NoMatch | ExtractResult::ParseError => {
-                    // Continue to next message (parse errors are logged in parser)
+        let started = Instant::now();
+        let session = &mut self.session;
+        let fetch = with_retries!(&self.config.retry, "FETCH", async {
+            let mut fetch_result = match (content, fetch_items) {
+                _ if envelope_only => {
+                    session::fetch_envelopes_by_uid_range(session, uid_range).await?
                 }
+                (FetchContent::Headers, _) => {
+                    session::fetch_headers_by_uid_range(session, uid_range).await?
+                }
+                (FetchContent::Scan, Some(items)) => {
+                    session::fetch_items_by_uid_range(session, uid_range, items).await?
+                }
+                _ => session::fetch_messages_by_uid_range(session, uid_range).await?,
+            };
+
+            // Drain the whole response before returning, so the connection is left
+            // in a clean state
+            let mut messages = Vec::new();
+            while let Some(message_result) = fetch_result.next().await {
+                messages.push(message_result.map_err(|source| Error::FetchMessage { source })?);
             }
+            Ok(messages)
+        });
+        let messages =
+            runtime::timeout(timeout, fetch)
+                .await
+                .map_err(|_| Error::FetchTimeout {
+                    uid_range: uid_range.to_string(),
+                    timeout,
+                })??;
+        events::record(self.config.event_hook.as_deref(), Operation::Fetch, started);
+
+        Ok(messages)
+    }
+
+    /// Fails if the configuration forbids downloading message content, which
+    /// `operation` needs.
+    fn require_content(&self, operation: &str) -> Result<()> {
+        if self.config.envelope_only {
+            return Err(Error::InvalidConfig {
+                message: format!(
+                    "{operation} downloads message content, which envelope_only forbids"
+                ),
+            });
         }
+        Ok(())
+    }
 
-        Ok(None)
+    /// Returns `true` if `message` was not consumed yet (nor a duplicate of a
+    /// consumed email) and passes the recipient alias filter and `filter`.
+    fn is_fresh(&self, message: &Fetch, filter: Option<&Filter>) -> bool {
+        message.uid.is_none_or(|uid| !self.consumed.contains(&uid))
+            && !self.is_consumed_duplicate(message)
+            && Self::is_for_recipient(&self.config, message)
+            && filter.is_none_or(|filter| filter.accepts_message(message))
+    }
+
+    /// Returns `true` if an email with the Message-ID of `message` was consumed.
+    fn is_consumed_duplicate(&self, message: &Fetch) -> bool {
+        if self.consumed_ids.is_empty() {
+            return false;
+        }
+        let duplicate =
+            parser::message_id(message).is_some_and(|id| self.consumed_ids.contains(&id));
+        if duplicate {
+            debug!(uid = message.uid, "Skipping duplicate of consumed email");
+        }
+        duplicate
+    }
+}
+
+impl MailBackend for ImapEmailClient {
+    fn connect(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(self.reconnect())
+    }
+
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.select_mailbox(mailbox))
+    }
+
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let timeout = self.config.timeouts.uid_fetch;
+
+            let started = Instant::now();
+            let session = &mut self.session;
+            let uids = runtime::timeout(
+                timeout,
+                with_retries!(
+                    &self.config.retry,
+                    "SEARCH",
+                    session::search_uids_after(session, self.start_uid)
+                ),
+            )
+            .await
+            .map_err(|_| Error::UidFetchTimeout { timeout });
+            events::record(
+                self.config.event_hook.as_deref(),
+                Operation::Search,
+                started,
+            );
+            let uids = uids??;
+
+            if let Some(&latest) = uids.last() {
+                self.start_uid = latest;
+            }
+            Ok(uids
+                .into_iter()
+                .filter(|uid| !self.consumed.contains(uid))
+                .map(|uid| uid.to_string())
+                .collect())
+        })
+    }
+
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let uids = self.search_recent_uids(max_age).await?;
+            Ok(uids.into_iter().map(|uid| uid.to_string()).collect())
+        })
+    }
+
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+        Box::pin(async move {
+            let uids = keys
+                .iter()
+                .map(|key| {
+                    key.parse().map_err(|_| Error::InvalidConfig {
+                        message: format!("invalid UID '{key}'"),
+                    })
+                })
+                .collect::<Result<Vec<u32>>>()?;
+            if uids.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let messages = self.fetch_whole_messages(&session::uid_set(&uids)).await?;
+            let mut bodies: HashMap<u32, Vec<u8>> = messages
+                .iter()
+                .filter_map(|message| Some((message.uid?, message.body()?.to_vec())))
+                .collect();
+            Ok(uids
+                .into_iter()
+                .filter_map(|uid| Some((uid.to_string(), bodies.remove(&uid)?)))
+                .collect())
+        })
+    }
+
+    fn logout(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(ImapEmailClient::logout(self))
     }
 }
 
@@ -485,56 +3869,66 @@ impl std::fmt::Debug for ImapEmailClient {
             .field("email", &self.config.email())
             .field("imap_host", &self.config.effective_imap_host())
             .field("start_uid", &self.start_uid)
+            .field("consumed", &self.consumed.len())
             .finish_non_exhaustive()
     }
 }
 
 /// RAII guard for [`ImapEmailClient`] that logs out on drop.
 ///
-/// Created by [`ImapEmailClient::into_guard`].
+/// Created by [`ImapEmailClient::into_guard`]. The logout is spawned on the
+/// current Tokio runtime; a guard dropped outside of one closes the connection
+/// without logging out (use `shutdown_blocking` from synchronous code, with the
+/// `blocking` feature).
 pub struct ImapEmailClientGuard {
     inner: Option<ImapEmailClient>,
 }
 
 impl ImapEmailClientGuard {
-    /// Waits for an email matching the provided pattern.
-    ///
-    /// See [`ImapEmailClient::wait_for_match`].
+    /// Returns a reference to the wrapped client.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if the guard has already been consumed (e.g., after calling [`logout`](Self::logout)).
+    /// Returns [`Error::GuardConsumed`] if the client has already been taken out of the guard.
+    pub fn try_client(&self) -> Result<&ImapEmailClient> {
+        self.inner.as_ref().ok_or(Error::GuardConsumed)
+    }
+
+    /// Returns a mutable reference to the wrapped client.
     ///
     /// # Errors
     ///
-    /// Returns an error if timeout is reached or IMAP operations fail.
-    pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
-        self.inner
-            .as_mut()
-            .expect("guard already consumed")
-            .wait_for_match(matcher)
-            .await
+    /// Returns [`Error::GuardConsumed`] if the client has already been taken out of the guard.
+    pub fn try_client_mut(&mut self) -> Result<&mut ImapEmailClient> {
+        self.inner.as_mut().ok_or(Error::GuardConsumed)
     }
 
-    /// Finds a matching email among recent messages.
+    /// Waits for an email matching the provided pattern, without panicking.
     ///
-    /// See [`ImapEmailClient::find_recent_match`].
+    /// See [`ImapEmailClient::wait_for_match`].
     ///
-    /// # Panics
+    /// # Errors
+    ///
+    /// Returns [`Error::GuardConsumed`] if the guard no longer holds a client, or any
+    /// error returned by [`ImapEmailClient::wait_for_match`].
+    pub async fn try_wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
+        self.try_client_mut()?.wait_for_match(matcher).await
+    }
+
+    /// Finds a matching email among recent messages, without panicking.
     ///
-    /// Panics if the guard has already been consumed (e.g., after calling [`logout`](Self::logout)).
+    /// See [`ImapEmailClient::find_recent_match`].
     ///
     /// # Errors
     ///
-    /// Returns [`Error::NoMatch`] if no matching email is found.
-    pub async fn find_recent_match(
+    /// Returns [`Error::GuardConsumed`] if the guard no longer holds a client, or any
+    /// error returned by [`ImapEmailClient::find_recent_match`].
+    pub async fn try_find_recent_match(
         &mut self,
         matcher: &dyn Matcher,
         max_age: Duration,
     ) -> Result<String> {
-        self.inner
-            .as_mut()
-            .expect("guard already consumed")
+        self.try_client_mut()?
             .find_recent_match(matcher, max_age)
             .await
     }
@@ -554,14 +3948,42 @@ impl ImapEmailClientGuard {
         }
     }
 
-    /// Returns the email address used for this connection.
+    /// Logs out synchronously and consumes the guard.
     ///
-    /// # Panics
+    /// See [`ImapEmailClient::shutdown_blocking`].
     ///
-    /// Panics if the guard has already been consumed (e.g., after calling [`logout`](Self::logout)).
-    #[must_use]
-    pub fn email(&self) -> &str {
-        self.inner.as_ref().expect("guard already consumed").email()
+    /// # Errors
+    ///
+    /// Returns an error if the logout fails or times out, or
+    /// [`Error::BlockingInAsyncContext`] when called from a single-threaded runtime.
+    #[cfg(feature = "blocking")]
+    pub fn shutdown_blocking(mut self) -> Result<()> {
+        match self.inner.take() {
+            Some(client) => client.shutdown_blocking(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Gives access to every [`ImapEmailClient`] method through the guard.
+///
+/// # Panics
+///
+/// Dereferencing panics if the guard no longer holds a client. This cannot happen through
+/// the public API: the consuming methods, [`logout`](ImapEmailClientGuard::logout) and
+/// `shutdown_blocking` (with the `blocking` feature), take the guard by value. Use
+/// [`try_client`](ImapEmailClientGuard::try_client) if you prefer an explicit error.
+impl std::ops::Deref for ImapEmailClientGuard {
+    type Target = ImapEmailClient;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref().expect("guard already consumed")
+    }
+}
+
+impl std::ops::DerefMut for ImapEmailClientGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut().expect("guard already consumed")
     }
 }
 
@@ -569,33 +3991,28 @@ impl Drop for ImapEmailClientGuard {
     fn drop(&mut self) {
         if let Some(mut client) = self.inner.take() {
             let logout_timeout = client.config.timeouts.logout;
+            // Counts as a background task until the logout is done
+            let task = TaskGuard::new(client.config.shutdown.as_ref());
 
-            // Try to get the current tokio runtime handle
-            match tokio::runtime::Handle::try_current() {
-                Ok(handle) => {
-                    // We're in an async context, spawn the logout task
-                    handle.spawn(async move {
-                        match tokio::time::timeout(logout_timeout, client.logout()).await {
-                            Ok(Ok(())) => debug!("Client logged out successfully"),
-                            Ok(Err(e)) => warn!(error = %e, "Client logout failed"),
-                            Err(_) => warn!(
-                                timeout_secs = logout_timeout.as_secs(),
-                                "Client logout timed out"
-                            ),
-                        }
-                    });
-                }
-                Err(_) => {
-                    // No tokio runtime available - we're in a sync context
-                    // Log a warning since we can't perform async logout
-                    warn!(
-                        "ImapEmailClientGuard dropped outside of tokio runtime context. \
-                         Connection will be closed without proper IMAP logout. \
-                         Consider calling .logout().await explicitly before dropping."
-                    );
-                    // The underlying connection will be dropped and closed,
-                    // which is not ideal but acceptable as a fallback
+            let logout = async move {
+                let _task = task;
+                match runtime::timeout(logout_timeout, client.logout()).await {
+                    Ok(Ok(())) => debug!("Client logged out successfully"),
+                    Ok(Err(e)) => warn!(error = %e, "Client logout failed"),
+                    Err(_) => warn!(
+                        timeout_secs = logout_timeout.as_secs(),
+                        "Client logout timed out"
+                    ),
                 }
+            };
+
+            // Spawn the logout on the current runtime, if there is one. Without
+            // one, the connection's I/O belongs to a runtime this thread cannot
+            // drive, so the connection is closed without a logout.
+            if runtime::try_spawn(logout).is_err() {
+                warn!(
+                    "Guard dropped outside of an async runtime, connection closed without IMAP logout"
+                );
             }
         }
     }
@@ -608,3 +4025,164 @@ impl std::fmt::Debug for ImapEmailClientGuard {
             .finish()
     }
 }
+
+/// Guard restoring the timeouts of an [`ImapEmailClient`] on drop.
+///
+/// Created by [`ImapEmailClient::with_timeouts`]; dereferences to the client.
+#[must_use = "the timeouts are restored as soon as the guard is dropped"]
+pub struct ScopedTimeouts<'a> {
+    client: &'a mut ImapEmailClient,
+    /// Timeouts to restore; taken on drop.
+    saved: Option<TimeoutConfig>,
+}
+
+impl std::ops::Deref for ScopedTimeouts<'_> {
+    type Target = ImapEmailClient;
+
+    fn deref(&self) -> &Self::Target {
+        self.client
+    }
+}
+
+impl std::ops::DerefMut for ScopedTimeouts<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client
+    }
+}
+
+impl Drop for ScopedTimeouts<'_> {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            self.client.config.timeouts = saved;
+        }
+    }
+}
+
+impl std::fmt::Debug for ScopedTimeouts<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScopedTimeouts")
+            .field("timeouts", &self.client.config.timeouts)
+            .field("saved", &self.saved)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_CLOCK_SKEW_MARGIN;
+
+    #[test]
+    fn test_calculate_since_date() {
+        let now = DateTime::parse_from_rfc3339("2025-07-02T00:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let date = |max_age, margin| {
+            ImapEmailClient::calculate_since_date(now, max_age, margin)
+                .map(|date| date.to_string())
+                .unwrap_or_default()
+        };
+
+        assert_eq!(date(Duration::from_mins(10), Duration::ZERO), "2025-07-02");
+        // A server on UTC-5 still files emails of the last 10 minutes under July 1
+        assert_eq!(
+            date(Duration::from_mins(10), DEFAULT_CLOCK_SKEW_MARGIN),
+            "2025-07-01"
+        );
+        assert_eq!(
+            date(Duration::from_hours(48), DEFAULT_CLOCK_SKEW_MARGIN),
+            "2025-06-29"
+        );
+        // Lookbacks beyond the representable dates search everything
+        assert_eq!(date(Duration::MAX, DEFAULT_CLOCK_SKEW_MARGIN), "");
+        assert_eq!(
+            date(Duration::from_hours(24 * 365 * 400_000), Duration::ZERO),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_age_cutoff() {
+        let now = DateTime::parse_from_rfc3339("2025-07-02T00:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            age_cutoff(now, Duration::from_mins(30)).map(|cutoff| cutoff.to_rfc3339()),
+            Some("2025-07-02T00:00:00+00:00".into())
+        );
+        assert_eq!(age_cutoff(now, Duration::MAX), None);
+        assert_eq!(
+            age_cutoff(now, Duration::from_hours(24 * 365 * 400_000)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sort_by_arrival() {
+        let origin = |uid, date: Option<&str>| MatchOrigin {
+            uid: Some(uid),
+            message_id: None,
+            internal_date: date.map(|date| DateTime::parse_from_rfc3339(date).unwrap()),
+            size: None,
+        };
+        let uids = |found: &[(MatchOrigin, ())]| -> Vec<_> {
+            found
+                .iter()
+                .map(|(origin, ())| origin.uid.unwrap())
+                .collect()
+        };
+        // UID 3 was moved into the mailbox after UID 7 arrived
+        let mut found = vec![
+            (origin(3, Some("2025-07-02T10:05:00+00:00")), ()),
+            (origin(7, Some("2025-07-02T10:00:00+00:00")), ()),
+            (origin(5, Some("2025-07-02T12:00:00+02:00")), ()),
+        ];
+
+        sort_by_arrival(&mut found, Order::OldestFirst);
+        assert_eq!(uids(&found), [5, 7, 3]);
+
+        sort_by_arrival(&mut found, Order::NewestFirst);
+        assert_eq!(uids(&found), [3, 7, 5]);
+    }
+
+    #[test]
+    fn test_next_poll_delay() {
+        let interval = Duration::from_secs(2);
+        let now = Instant::now();
+
+        let delay = next_poll_delay(interval, now + Duration::from_mins(1));
+        assert_eq!(delay, Some(interval));
+        // A 5s wait polling every 2s sleeps 1s before its last poll, not 2s
+        let delay = next_poll_delay(interval, now + Duration::from_secs(1)).unwrap();
+        assert!(delay <= Duration::from_secs(1));
+        assert_eq!(next_poll_delay(interval, now), None);
+    }
+
+    #[test]
+    fn test_is_valid_flag() {
+        assert!(is_valid_flag("\\Seen"));
+        assert!(is_valid_flag("$Label1"));
+        assert!(!is_valid_flag(""));
+        assert!(!is_valid_flag("\\"));
+        assert!(!is_valid_flag("two words"));
+        assert!(!is_valid_flag("\\Seen)"));
+    }
+
+    #[test]
+    fn test_health_status_from_latency() {
+        let fast = Duration::from_millis(40);
+        assert_eq!(
+            HealthStatus::from_latency(fast),
+            HealthStatus::Healthy { latency: fast }
+        );
+
+        let slow = Duration::from_secs(3);
+        assert_eq!(
+            HealthStatus::from_latency(slow),
+            HealthStatus::Degraded { latency: slow }
+        );
+        assert!(HealthStatus::from_latency(slow).is_connected());
+        assert!(!HealthStatus::Disconnected.is_connected());
+    }
+}
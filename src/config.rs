@@ -13,12 +13,28 @@
 //! ```
 
 use crate::error::{Error, Result};
+use crate::events::{EventHook, ProgressHook};
+use crate::filter::Filter;
+#[cfg(feature = "gssapi")]
+use crate::gssapi::GssapiProvider;
 use crate::known_servers::ServerRegistry;
-use crate::proxy::Socks5Proxy;
+use crate::proxy::{ProxyPool, Socks5Proxy};
+use crate::shutdown::Shutdown;
 use email_address::EmailAddress;
 use secrecy::{ExposeSecret, SecretString};
+use sha2::Digest;
+use std::fmt::Write;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// Default for [`ImapConfig::max_parse_size`]: 25 MiB, the message size limit of
+/// most providers.
+pub const DEFAULT_MAX_PARSE_SIZE: usize = 25 * 1024 * 1024;
+
+/// Default for [`ImapConfig::clock_skew_margin`]: one day, covering servers in
+/// any time zone.
+pub const DEFAULT_CLOCK_SKEW_MARGIN: Duration = Duration::from_hours(24);
+
 /// Configuration for connecting to an IMAP server.
 ///
 /// Create using [`ImapConfig::builder()`].
@@ -39,23 +55,101 @@ pub struct ImapConfig {
     pub imap_port: u16,
     /// Optional SOCKS5 proxy for connection.
     pub proxy: Option<Socks5Proxy>,
+    /// TLS protocol settings.
+    pub tls: TlsOptions,
+    /// Proxies rotated across connections when no single `proxy` is set.
+    pub proxy_pool: Option<ProxyPool>,
     /// Timeout configuration.
     pub timeouts: TimeoutConfig,
+    /// Retries of failed IMAP commands.
+    pub retry: RetryConfig,
     /// Polling configuration for waiting operations.
     pub polling: PollingConfig,
+    /// How deleted emails are expunged.
+    pub expunge: ExpungeMode,
+    /// How the client authenticates.
+    pub auth: AuthMethod,
+    /// Windows domain for [`AuthMethod::Ntlm`].
+    #[cfg(feature = "ntlm")]
+    pub ntlm_domain: Option<String>,
+    /// Security context provider for [`AuthMethod::Gssapi`].
+    #[cfg(feature = "gssapi")]
+    pub gssapi_provider: Option<Arc<dyn GssapiProvider>>,
+    /// Only consider emails sent to this address (e.g. a plus-addressed alias).
+    recipient_alias: Option<EmailAddress>,
+    /// Identification sent with the `ID` command after login, when the server
+    /// advertises `ID` (`None` to skip it).
+    pub client_id: Option<ClientId>,
+    /// Idle time after which the connection gets a keepalive NOOP (`None` to disable).
+    pub keepalive: Option<Duration>,
+    /// Stops the background tasks of clients with this configuration.
+    pub shutdown: Option<Shutdown>,
+    /// Receives operation latencies and match events.
+    pub event_hook: Option<Arc<dyn EventHook>>,
+    /// Receives the progress of waits after each poll.
+    pub on_progress: Option<Arc<dyn ProgressHook>>,
+    /// Apply NFKC normalization and strip invisible characters before matching.
+    pub normalize_text: bool,
+    /// How matched values appear in logs and events.
+    pub redact_matches: Redaction,
+    /// Match new emails on their subject before downloading their bodies.
+    pub subject_first: bool,
+    /// Fetch and match only the ENVELOPE of emails, never their content.
+    pub envelope_only: bool,
+    /// FETCH items downloaded by waits and searches instead of the whole email
+    /// (`None` for `BODY[]`).
+    pub fetch_items: Option<FetchItems>,
+    /// Largest number of bytes of an email parsed for matching; the rest is cut
+    /// off (`None` for no limit).
+    pub max_parse_size: Option<usize>,
+    /// Number of emails fetched per round trip when searching existing emails.
+    pub scan_concurrency: usize,
+    /// Budget of searches of existing emails.
+    pub scan_limits: ScanLimits,
+    /// Extra lookback of the day-granular `SINCE` search behind searches of
+    /// recent emails, for servers whose clock or time zone differs.
+    pub clock_skew_margin: Duration,
 }
 
 impl std::fmt::Debug for ImapConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ImapConfig")
+        let mut debug = f.debug_struct("ImapConfig");
+        debug
             .field("email", &self.email.as_str())
             .field("password", &"[REDACTED]")
             .field("imap_host", &self.imap_host)
             .field("imap_port", &self.imap_port)
             .field("proxy", &self.proxy)
+            .field("tls", &self.tls)
+            .field("proxy_pool", &self.proxy_pool)
             .field("timeouts", &self.timeouts)
+            .field("retry", &self.retry)
             .field("polling", &self.polling)
-            .finish()
+            .field("expunge", &self.expunge)
+            .field("auth", &self.auth)
+            .field(
+                "recipient_alias",
+                &self.recipient_alias.as_ref().map(EmailAddress::as_str),
+            )
+            .field("client_id", &self.client_id)
+            .field("keepalive", &self.keepalive)
+            .field("shutdown", &self.shutdown)
+            .field("event_hook", &self.event_hook)
+            .field("on_progress", &self.on_progress)
+            .field("normalize_text", &self.normalize_text)
+            .field("redact_matches", &self.redact_matches)
+            .field("subject_first", &self.subject_first)
+            .field("envelope_only", &self.envelope_only)
+            .field("fetch_items", &self.fetch_items)
+            .field("max_parse_size", &self.max_parse_size)
+            .field("scan_concurrency", &self.scan_concurrency)
+            .field("scan_limits", &self.scan_limits)
+            .field("clock_skew_margin", &self.clock_skew_margin);
+        #[cfg(feature = "ntlm")]
+        debug.field("ntlm_domain", &self.ntlm_domain);
+        #[cfg(feature = "gssapi")]
+        debug.field("gssapi_provider", &self.gssapi_provider);
+        debug.finish()
     }
 }
 
@@ -80,13 +174,111 @@ impl ImapConfig {
     pub fn password(&self) -> &str {
         self.password.expose_secret()
     }
+
+    /// Returns the recipient alias emails are filtered by, if any.
+    ///
+    /// See [`ImapConfigBuilder::recipient_alias`].
+    #[must_use]
+    pub fn recipient_alias(&self) -> Option<&str> {
+        self.recipient_alias.as_ref().map(EmailAddress::as_str)
+    }
+}
+
+/// A SHA-256 digest, as used for public key pins.
+pub type Sha256 = [u8; 32];
+
+/// TLS protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[non_exhaustive]
+pub enum TlsVersion {
+    /// TLS 1.2 (RFC 5246).
+    #[default]
+    Tls12,
+    /// TLS 1.3 (RFC 8446).
+    Tls13,
+}
+
+/// TLS settings for the connection to the IMAP server.
+///
+/// The defaults accept TLS 1.2 and 1.3, offer no ALPN protocol and pin no key.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::{ImapConfig, TlsOptions, TlsVersion};
+///
+/// // Security baseline: TLS 1.3 only
+/// let config = ImapConfig::builder()
+///     .email("user@example.com")
+///     .password("app-password")
+///     .tls(TlsOptions::new().min_version(TlsVersion::Tls13).alpn("imap"))
+///     .build()
+///     .expect("valid config");
+///
+/// assert_eq!(config.tls.min_version, TlsVersion::Tls13);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TlsOptions {
+    /// Oldest TLS version accepted.
+    pub min_version: TlsVersion,
+    /// ALPN protocols offered in the handshake, in order of preference.
+    pub alpn_protocols: Vec<String>,
+    /// SHA-256 digests of the accepted server public keys (empty to accept any).
+    pub pinned_spki: Vec<Sha256>,
+}
+
+impl TlsOptions {
+    /// Creates options with default values.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the oldest TLS version accepted.
+    #[must_use]
+    pub fn min_version(mut self, version: TlsVersion) -> Self {
+        self.min_version = version;
+        self
+    }
+
+    /// Offers `protocol` (e.g. `imap`, RFC 9051) with ALPN, after those added before.
+    #[must_use]
+    pub fn alpn(mut self, protocol: impl Into<String>) -> Self {
+        self.alpn_protocols.push(protocol.into());
+        self
+    }
+
+    /// Only trusts servers whose public key has one of the given SHA-256 digests.
+    ///
+    /// A pin is the digest of the DER-encoded `SubjectPublicKeyInfo`, as printed by
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | sha256sum`.
+    /// Only the key of the server's own certificate is pinned, and the certificate
+    /// chain is still verified. Keep a backup pin so a key rotation does not lock
+    /// the client out.
+    ///
+    /// Connections to servers with other keys fail with
+    /// [`Error::CertificatePinMismatch`](crate::Error::CertificatePinMismatch).
+    #[must_use]
+    pub fn pinned_spki(mut self, pins: Vec<Sha256>) -> Self {
+        self.pinned_spki = pins;
+        self
+    }
 }
 
 /// Timeout configuration for various operations.
+///
+/// The [`default`](Self::default) suits direct connections over the internet;
+/// [`fast`](Self::fast) and [`proxied`](Self::proxied) are presets for local
+/// and SOCKS5-proxied servers.
 #[derive(Debug, Clone)]
 pub struct TimeoutConfig {
     /// Timeout for establishing TCP/TLS connection.
     pub connect: Duration,
+    /// Number of attempts to establish the TCP/TLS connection when it fails with
+    /// a transient error (at least 1).
+    pub connect_attempts: u32,
+    /// Delay before the second connection attempt; doubled for each further one.
+    pub connect_backoff: Duration,
     /// Timeout for IMAP authentication.
     pub auth: Duration,
     /// Timeout for selecting a mailbox.
@@ -95,6 +287,8 @@ pub struct TimeoutConfig {
     pub uid_fetch: Duration,
     /// Timeout for fetching message content.
     pub message_fetch: Duration,
+    /// Timeout for miscellaneous IMAP commands (LIST, STATUS, ...).
+    pub command: Duration,
     /// Timeout for logout operation.
     pub logout: Duration,
 }
@@ -103,16 +297,154 @@ impl Default for TimeoutConfig {
     fn default() -> Self {
         Self {
             connect: Duration::from_secs(30),
+            connect_attempts: 3,
+            connect_backoff: Duration::from_millis(500),
             auth: Duration::from_secs(30),
             select: Duration::from_secs(10),
             uid_fetch: Duration::from_secs(10),
             message_fetch: Duration::from_secs(30),
+            command: Duration::from_secs(10),
             logout: Duration::from_secs(5),
         }
     }
 }
 
+/// Longest delay between two attempts of a connection or command.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Returns `base` doubled for each retry after the first (counted from 1),
+/// capped at [`MAX_BACKOFF`].
+fn backoff_delay(base: Duration, retry: u32) -> Duration {
+    let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::MAX);
+    base.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+impl TimeoutConfig {
+    /// Short timeouts for servers on the local network or in the same data
+    /// center, e.g. a test mail server in CI, so a dead server fails fast.
+    ///
+    /// [`default`](Self::default) suits direct connections over the internet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::{ImapConfig, PollingConfig, TimeoutConfig};
+    ///
+    /// let config = ImapConfig::builder()
+    ///     .email("test@mail.local")
+    ///     .password("secret")
+    ///     .timeouts(TimeoutConfig::fast())
+    ///     .polling(PollingConfig::fast())
+    ///     .build()?;
+    /// # Ok::<(), email_sync::Error>(())
+    /// ```
+    #[must_use]
+    pub fn fast() -> Self {
+        Self {
+            connect: Duration::from_secs(5),
+            connect_attempts: 3,
+            connect_backoff: Duration::from_millis(200),
+            auth: Duration::from_secs(5),
+            select: Duration::from_secs(3),
+            uid_fetch: Duration::from_secs(3),
+            message_fetch: Duration::from_secs(10),
+            command: Duration::from_secs(3),
+            logout: Duration::from_secs(2),
+        }
+    }
+
+    /// Generous timeouts for connections through a SOCKS5 proxy, which adds a
+    /// round trip to every command and often limits bandwidth.
+    ///
+    /// Fetches get the most slack, as message content is the bulk of the
+    /// traffic.
+    #[must_use]
+    pub fn proxied() -> Self {
+        Self {
+            connect: Duration::from_mins(1),
+            connect_attempts: 4,
+            connect_backoff: Duration::from_secs(1),
+            auth: Duration::from_mins(1),
+            select: Duration::from_secs(30),
+            uid_fetch: Duration::from_secs(30),
+            message_fetch: Duration::from_mins(2),
+            command: Duration::from_secs(30),
+            logout: Duration::from_secs(10),
+        }
+    }
+
+    /// Returns the delay before connection attempt `attempt` (counted from 1),
+    /// or `None` if no attempt is left.
+    pub(crate) fn connect_retry_delay(&self, attempt: u32) -> Option<Duration> {
+        (attempt < self.connect_attempts).then(|| backoff_delay(self.connect_backoff, attempt))
+    }
+}
+
+/// Retries of IMAP commands (NOOP, SEARCH, FETCH) that fail with a retryable
+/// error, such as a server answering `NO [UNAVAILABLE]` under load.
+///
+/// Retries happen within the timeout of the command: once it expires, the
+/// command fails with its timeout error whatever retries are left. The default
+/// does not retry.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::{ImapConfig, RetryConfig};
+/// use std::time::Duration;
+///
+/// let config = ImapConfig::builder()
+///     .email("user@example.com")
+///     .password("secret")
+///     .retry(RetryConfig::new(3).backoff(Duration::from_millis(100)))
+///     .build()?;
+/// # Ok::<(), email_sync::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Number of retries after the first attempt (0 disables retries).
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further one.
+    pub backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::from_millis(200),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Retries failed commands up to `max_retries` times.
+    #[must_use]
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the delay before the first retry.
+    #[must_use]
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns the delay before retry `retry` (counted from 1), or `None` if no
+    /// retry is left.
+    pub(crate) fn delay(&self, retry: u32) -> Option<Duration> {
+        (retry <= self.max_retries).then(|| backoff_delay(self.backoff, retry))
+    }
+}
+
 /// Polling configuration for wait operations.
+///
+/// Like [`TimeoutConfig`], comes with [`fast`](Self::fast) and
+/// [`proxied`](Self::proxied) presets besides the [`default`](Self::default).
 #[derive(Debug, Clone)]
 pub struct PollingConfig {
     /// Interval between polling attempts when waiting for email.
@@ -130,6 +462,477 @@ impl Default for PollingConfig {
     }
 }
 
+impl PollingConfig {
+    /// Frequent polls and a short wait, for a local test mail server that
+    /// delivers within a second; pair with [`TimeoutConfig::fast`].
+    #[must_use]
+    pub fn fast() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            max_wait: Duration::from_mins(1),
+        }
+    }
+
+    /// Sparser polls and a longer wait for proxied connections, where each
+    /// poll is slow; pair with [`TimeoutConfig::proxied`].
+    #[must_use]
+    pub fn proxied() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            max_wait: Duration::from_mins(10),
+        }
+    }
+}
+
+/// Budget of a search of existing emails for the first match.
+///
+/// The search stops once either limit is reached and, if nothing matched by
+/// then, fails with [`Error::ScanLimitReached`] instead of
+/// [`Error::NoMatch`]. Both limits are off by default.
+///
+/// The limits apply to the searches that stop at the first match:
+/// [`find_recent_match`](crate::ImapEmailClient::find_recent_match),
+/// [`find_recent_email`](crate::ImapEmailClient::find_recent_email), their
+/// `_with` variants,
+/// [`find_by_gmail_label`](crate::ImapEmailClient::find_by_gmail_label) and
+/// [`find_in_thread`](crate::ImapEmailClient::find_in_thread).
+/// [`find_all_recent_matches`](crate::ImapEmailClient::find_all_recent_matches)
+/// and [`count_matching`](crate::ImapEmailClient::count_matching) check every
+/// email within their `max_age`, since a partial scan would give an incomplete
+/// answer; bound them with a shorter `max_age`.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::{ImapConfig, ScanLimits};
+///
+/// let config = ImapConfig::builder()
+///     .email("user@example.com")
+///     .password("secret")
+///     .scan_limits(ScanLimits::new().max_messages(200).max_bytes(20 << 20))
+///     .build()?;
+/// # Ok::<(), email_sync::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScanLimits {
+    /// Largest number of emails checked (`None` for no limit).
+    pub max_messages: Option<usize>,
+    /// Largest number of bytes downloaded, by `RFC822.SIZE` (`None` for no
+    /// limit). Checked after each email, so the last batch of
+    /// [`ImapConfigBuilder::scan_concurrency`] emails may go past it.
+    pub max_bytes: Option<u64>,
+}
+
+impl ScanLimits {
+    /// Creates limits with nothing limited.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops after checking `messages` emails.
+    #[must_use]
+    pub fn max_messages(mut self, messages: usize) -> Self {
+        self.max_messages = Some(messages);
+        self
+    }
+
+    /// Stops after downloading `bytes` bytes of emails.
+    #[must_use]
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+}
+
+/// How emails deleted by the client are removed from the mailbox.
+///
+/// Providers differ here: some hide `\Deleted` emails immediately, others keep
+/// showing them until an expunge. Used by
+/// [`ImapEmailClient::delete_emails`](crate::ImapEmailClient::delete_emails).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ExpungeMode {
+    /// Expunge only the deleted emails with `UID EXPUNGE` when the server supports
+    /// UIDPLUS; otherwise leave them flagged `\Deleted` like [`Deferred`](Self::Deferred).
+    #[default]
+    Uid,
+    /// Run `EXPUNGE` right away. This also removes emails other clients flagged
+    /// `\Deleted` in the mailbox.
+    Immediate,
+    /// Only flag emails `\Deleted` and leave expunging to the server or another client.
+    Deferred,
+}
+
+/// How matched values appear in telemetry, set with
+/// [`ImapConfigBuilder::redact_matches`].
+///
+/// # Example
+///
+/// ```
+/// use email_sync::Redaction;
+///
+/// assert_eq!(Redaction::Mask.redact("123456"), "******");
+/// assert!(Redaction::Hash.redact("123456").starts_with("sha256:"));
+/// assert_eq!(Redaction::Off.redact("123456"), "123456");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Redaction {
+    /// Replace every character with `*`, keeping only the length.
+    #[default]
+    Mask,
+    /// Replace the value with the start of its SHA-256 digest, so the same
+    /// value can be recognized across log lines. Short values such as 6-digit
+    /// codes can be recovered from the digest by brute force.
+    Hash,
+    /// Show values as they are.
+    Off,
+}
+
+impl Redaction {
+    /// Returns `value` as it should appear in telemetry.
+    #[must_use]
+    pub fn redact(self, value: &str) -> String {
+        match self {
+            Self::Mask => "*".repeat(value.chars().count()),
+            Self::Hash => {
+                let digest = sha2::Sha256::digest(value.as_bytes());
+                let mut hashed = String::from("sha256:");
+                for b in &digest[..6] {
+                    let _ = write!(hashed, "{b:02x}");
+                }
+                hashed
+            }
+            Self::Off => value.to_string(),
+        }
+    }
+}
+
+/// How the client authenticates with its email and password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AuthMethod {
+    /// Pick a method from the capabilities the server advertises before login:
+    /// `CRAM-MD5` when advertised, otherwise `LOGIN`, or SASL `PLAIN` when the
+    /// server disables `LOGIN` (`LOGINDISABLED`).
+    ///
+    /// [`SmtpSender`](crate::smtp::SmtpSender) follows the same order over the
+    /// `AUTH` mechanisms of `EHLO`, with `AUTH LOGIN` in place of the `LOGIN`
+    /// command and `PLAIN` used when `LOGIN` is not offered.
+    #[default]
+    Auto,
+    /// The `LOGIN` command.
+    Login,
+    /// SASL `PLAIN` (RFC 4616) through `AUTHENTICATE`.
+    Plain,
+    /// SASL `CRAM-MD5` (RFC 2195) challenge-response, for legacy servers.
+    CramMd5,
+    /// `NTLMv2` through `AUTHENTICATE NTLM`, for on-premises Exchange.
+    ///
+    /// Authenticates as `DOMAIN\user` when
+    /// [`ImapConfigBuilder::ntlm_domain`] is set, otherwise with the email address
+    /// as user principal name.
+    #[cfg(feature = "ntlm")]
+    Ntlm,
+    /// Kerberos single sign-on through `AUTHENTICATE GSSAPI` (RFC 4752).
+    ///
+    /// Needs a [`GssapiProvider`](crate::gssapi::GssapiProvider) set with
+    /// [`ImapConfigBuilder::gssapi_provider`]; no password is used.
+    #[cfg(feature = "gssapi")]
+    Gssapi,
+}
+
+impl AuthMethod {
+    /// Resolves [`Auto`](Self::Auto): `CRAM-MD5` when `offers` it, otherwise
+    /// `LOGIN` if `login_allowed`, falling back to `PLAIN` when offered.
+    pub(crate) fn choose(offers: impl Fn(&str) -> bool, login_allowed: bool) -> Self {
+        if offers("CRAM-MD5") {
+            Self::CramMd5
+        } else if !login_allowed && offers("PLAIN") {
+            Self::Plain
+        } else {
+            Self::Login
+        }
+    }
+}
+
+/// Client identification sent with the IMAP `ID` command (RFC 2971).
+///
+/// Some providers (163.com, qq.com) reject clients that do not identify themselves
+/// after login. It is only sent to servers advertising the `ID` capability. The
+/// default sends `name` and `version` of this crate.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::{ClientId, ImapConfig};
+///
+/// let config = ImapConfig::builder()
+///     .email("user@163.com")
+///     .password("app-password")
+///     .client_id(ClientId::new().field("name", "my-tests").field("version", "1.0"))
+///     .build()
+///     .expect("valid config");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientId {
+    fields: Vec<(String, String)>,
+}
+
+impl ClientId {
+    /// Creates an identification without any fields.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { fields: Vec::new() }
+    }
+
+    /// Adds a field such as `name`, `version` or `vendor`.
+    ///
+    /// Setting a field again replaces its value.
+    #[must_use]
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let value = value.into();
+        match self
+            .fields
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&key))
+        {
+            Some(field) => field.1 = value,
+            None => self.fields.push((key, value)),
+        }
+        self
+    }
+
+    /// Returns the fields in the order they were added.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl Default for ClientId {
+    fn default() -> Self {
+        Self::new()
+            .field("name", env!("CARGO_PKG_NAME"))
+            .field("version", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// Per-call options for wait operations.
+///
+/// Used with [`ImapEmailClient::wait_for_match_with`](crate::ImapEmailClient::wait_for_match_with).
+///
+/// # Example
+///
+/// ```
+/// use email_sync::WaitOptions;
+///
+/// // Ignore the first matching email and return the second
+/// let options = WaitOptions::new().skip(1);
+/// assert_eq!(options.skip, 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WaitOptions {
+    /// Number of matching emails to pass over before returning a match.
+    ///
+    /// Matching emails are counted in arrival (UID) order.
+    pub skip: usize,
+    /// Only consider replies within the thread of this Message-ID.
+    ///
+    /// An email belongs to the thread when its `In-Reply-To` or `References`
+    /// header contains the Message-ID.
+    pub in_thread: Option<String>,
+    /// Only consider emails passing this filter.
+    pub filter: Option<Filter>,
+    /// Maximum time to wait, instead of [`PollingConfig::max_wait`].
+    pub timeout: Option<Duration>,
+}
+
+impl WaitOptions {
+    /// Creates options with default values (return the first match).
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skips the first `n` matching emails and returns the next one.
+    #[must_use]
+    pub fn skip(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Only considers replies within the thread of `message_id`.
+    ///
+    /// Angle brackets around the Message-ID are optional.
+    #[must_use]
+    pub fn in_thread(mut self, message_id: impl Into<String>) -> Self {
+        self.in_thread = Some(message_id.into());
+        self
+    }
+
+    /// Only considers emails passing `filter`, checked before any matcher runs.
+    ///
+    /// With [`ImapConfigBuilder::subject_first`], bodies of filtered-out emails
+    /// are not downloaded.
+    #[must_use]
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Waits at most `timeout`, instead of [`PollingConfig::max_wait`].
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// Order in which a search checks existing emails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// Newest email first: returns the latest match (default).
+    #[default]
+    NewestFirst,
+    /// Oldest email first: returns the first email that matched, e.g. for audits.
+    OldestFirst,
+}
+
+/// Per-call options for searches of existing emails.
+///
+/// Used with [`ImapEmailClient::find_recent_match_with`](crate::ImapEmailClient::find_recent_match_with)
+/// and [`ImapEmailClient::find_all_recent_matches`](crate::ImapEmailClient::find_all_recent_matches).
+///
+/// # Example
+///
+/// ```
+/// use email_sync::{Order, SearchOptions};
+///
+/// // Return the first email that matched rather than the latest
+/// let options = SearchOptions::new().order(Order::OldestFirst);
+/// assert_eq!(options.order, Order::OldestFirst);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Order in which emails are checked.
+    pub order: Order,
+}
+
+impl SearchOptions {
+    /// Creates options with default values (newest first).
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks emails in `order`.
+    #[must_use]
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+/// A validated list of FETCH items, downloaded for each email by waits and
+/// searches instead of the whole email.
+///
+/// See [`ImapConfigBuilder::fetch_items`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchItems {
+    items: String,
+    /// Body parts fetched whole, e.g. `[1, 2]` for `BODY.PEEK[1.2]`.
+    parts: Vec<Vec<u32>>,
+}
+
+impl FetchItems {
+    /// Parses a FETCH item list, e.g. `BODY.PEEK[TEXT]` or
+    /// `(BODY.PEEK[HEADER] BODY.PEEK[1])`.
+    ///
+    /// Supported body sections are the whole email (`[]`), `HEADER`,
+    /// `HEADER.FIELDS`, `HEADER.FIELDS.NOT`, `TEXT`, and parts with an
+    /// optional `.MIME` suffix; `RFC822`, `RFC822.HEADER` and `RFC822.TEXT` are
+    /// accepted too.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`] if a section is malformed or
+    /// unsupported, or if no item downloads text to match on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::FetchItems;
+    ///
+    /// assert!(FetchItems::new("(BODY.PEEK[HEADER] BODY.PEEK[1])").is_ok());
+    /// // A header alone leaves nothing to match on
+    /// assert!(FetchItems::new("BODY.PEEK[HEADER]").is_err());
+    /// ```
+    pub fn new(items: impl Into<String>) -> Result<Self> {
+        let items = items.into();
+        let invalid = |message: String| Error::InvalidConfig { message };
+        let upper = items.to_ascii_uppercase();
+
+        let mut parts = Vec::new();
+        let mut has_text = upper
+            .split(|c: char| c.is_ascii_whitespace() || c == '(' || c == ')')
+            .any(|item| item == "RFC822" || item == "RFC822.TEXT");
+        for (start, _) in upper.match_indices("BODY") {
+            let rest = &upper[start + "BODY".len()..];
+            let rest = rest.strip_prefix(".PEEK").unwrap_or(rest);
+            // BODY and BODYSTRUCTURE carry no section
+            let Some(rest) = rest.strip_prefix('[') else {
+                continue;
+            };
+            let Some(end) = rest.find(']') else {
+                return Err(invalid(format!(
+                    "unclosed section in FETCH items '{items}'"
+                )));
+            };
+            let section = &rest[..end];
+
+            if section.is_empty() || section == "TEXT" {
+                has_text = true;
+            } else if section.starts_with("HEADER") {
+                // HEADER, HEADER.FIELDS (...) and HEADER.FIELDS.NOT (...)
+            } else {
+                let (path, mime) = match section.strip_suffix(".MIME") {
+                    Some(path) => (path, true),
+                    None => (section, false),
+                };
+                let path = path
+                    .split('.')
+                    .map(|part| part.parse::<u32>().ok().filter(|&part| part > 0))
+                    .collect::<Option<Vec<u32>>>()
+                    .ok_or_else(|| invalid(format!("unsupported FETCH section '[{section}]'")))?;
+                if !mime {
+                    parts.push(path);
+                    has_text = true;
+                }
+            }
+        }
+
+        if !has_text {
+            return Err(invalid(format!(
+                "FETCH items '{items}' download no text to match on"
+            )));
+        }
+        Ok(Self { items, parts })
+    }
+
+    /// Returns the item list as given.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.items
+    }
+
+    /// Returns the body parts fetched whole.
+    pub(crate) fn parts(&self) -> &[Vec<u32>] {
+        &self.parts
+    }
+}
+
 impl ImapConfig {
     /// Creates a new configuration builder.
     ///
@@ -169,7 +972,7 @@ impl ImapConfig {
 /// Validates an email address format.
 ///
 /// Returns the validated `EmailAddress` if valid, or an error if invalid.
-fn validate_email(email: &str) -> Result<EmailAddress> {
+pub(crate) fn validate_email(email: &str) -> Result<EmailAddress> {
     EmailAddress::parse_with_options(email, email_address::Options::default()).map_err(|_| {
         Error::InvalidEmailFormat {
             email: email.to_string(),
@@ -177,17 +980,56 @@ fn validate_email(email: &str) -> Result<EmailAddress> {
     })
 }
 
+/// Returns `false` for methods that authenticate without a password.
+fn requires_password(auth: Option<AuthMethod>) -> bool {
+    // Kerberos uses the ticket cache instead of a password
+    #[cfg(feature = "gssapi")]
+    if auth == Some(AuthMethod::Gssapi) {
+        return false;
+    }
+    let _ = auth;
+    true
+}
+
 /// Builder for [`ImapConfig`].
 #[derive(Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ImapConfigBuilder {
     email: Option<String>,
     password: Option<String>,
     imap_host: Option<String>,
     imap_port: Option<u16>,
     proxy: Option<Socks5Proxy>,
+    proxy_pool: Option<ProxyPool>,
+    tls: Option<TlsOptions>,
     timeouts: Option<TimeoutConfig>,
+    retry: Option<RetryConfig>,
     polling: Option<PollingConfig>,
+    expunge: Option<ExpungeMode>,
+    auth: Option<AuthMethod>,
+    #[cfg(feature = "ntlm")]
+    ntlm_domain: Option<String>,
+    #[cfg(feature = "gssapi")]
+    gssapi_provider: Option<Arc<dyn GssapiProvider>>,
     server_registry: Option<ServerRegistry>,
+    recipient_alias: Option<String>,
+    client_id: Option<ClientId>,
+    disable_client_id: bool,
+    keepalive: Option<Duration>,
+    shutdown: Option<Shutdown>,
+    proxy_from_env: bool,
+    event_hook: Option<Arc<dyn EventHook>>,
+    on_progress: Option<Arc<dyn ProgressHook>>,
+    normalize_text: bool,
+    redact_matches: Option<Redaction>,
+    subject_first: bool,
+    envelope_only: bool,
+    fetch_items: Option<String>,
+    max_parse_size: Option<usize>,
+    no_parse_size_limit: bool,
+    scan_concurrency: Option<usize>,
+    scan_limits: ScanLimits,
+    clock_skew_margin: Option<Duration>,
 }
 
 impl ImapConfigBuilder {
@@ -262,6 +1104,43 @@ impl ImapConfigBuilder {
         self
     }
 
+    /// Reads the proxy from the standard environment variables when none is set
+    /// explicitly with [`proxy`](Self::proxy).
+    ///
+    /// Checks `SOCKS_PROXY`, then `ALL_PROXY` (lowercase names first), and skips the
+    /// proxy if the IMAP host matches `NO_PROXY`. The value must be a `socks5://`
+    /// URL, see [`Socks5Proxy::from_url`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::ImapConfig;
+    ///
+    /// // ALL_PROXY=socks5://proxy.internal:1080
+    /// let config = ImapConfig::builder()
+    ///     .email("user@gmail.com")
+    ///     .password("app-password")
+    ///     .proxy_from_env()
+    ///     .build()?;
+    /// # Ok::<(), email_sync::Error>(())
+    /// ```
+    #[must_use]
+    pub fn proxy_from_env(mut self) -> Self {
+        self.proxy_from_env = true;
+        self
+    }
+
+    /// Rotates connections through a pool of SOCKS5 proxies.
+    ///
+    /// Each connection and reconnection takes the next proxy of the pool, and
+    /// reports connection failures back to it. A proxy set with
+    /// [`proxy`](Self::proxy) (or from the environment) takes precedence.
+    #[must_use]
+    pub fn proxy_pool(mut self, pool: ProxyPool) -> Self {
+        self.proxy_pool = Some(pool);
+        self
+    }
+
     /// Sets timeout configuration.
     #[must_use]
     pub fn timeouts(mut self, timeouts: TimeoutConfig) -> Self {
@@ -269,6 +1148,13 @@ impl ImapConfigBuilder {
         self
     }
 
+    /// Retries NOOP, SEARCH and FETCH commands that fail with a retryable error.
+    #[must_use]
+    pub fn retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
     /// Sets the connection timeout.
     #[must_use]
     pub fn connect_timeout(mut self, timeout: Duration) -> Self {
@@ -278,6 +1164,17 @@ impl ImapConfigBuilder {
         self
     }
 
+    /// Sets how many times the connection is attempted before
+    /// [`connect`](crate::ImapEmailClient::connect) gives up on a transient
+    /// network error (1 disables retries).
+    #[must_use]
+    pub fn connect_attempts(mut self, attempts: u32) -> Self {
+        self.timeouts
+            .get_or_insert_with(TimeoutConfig::default)
+            .connect_attempts = attempts.max(1);
+        self
+    }
+
     /// Sets the authentication timeout.
     #[must_use]
     pub fn auth_timeout(mut self, timeout: Duration) -> Self {
@@ -295,6 +1192,9 @@ impl ImapConfigBuilder {
     }
 
     /// Sets the polling interval for wait operations.
+    ///
+    /// [`build`](Self::build) fails if it exceeds the
+    /// [`max_wait`](Self::max_wait).
     #[must_use]
     pub fn poll_interval(mut self, interval: Duration) -> Self {
         self.polling
@@ -304,6 +1204,9 @@ impl ImapConfigBuilder {
     }
 
     /// Sets the maximum wait time for email operations.
+    ///
+    /// The last poll of a wait happens at this deadline, however it falls
+    /// between poll intervals.
     #[must_use]
     pub fn max_wait(mut self, max_wait: Duration) -> Self {
         self.polling
@@ -312,6 +1215,328 @@ impl ImapConfigBuilder {
         self
     }
 
+    /// Sets the TLS protocol settings.
+    ///
+    /// Default is [`TlsOptions::default`].
+    #[must_use]
+    pub fn tls(mut self, options: TlsOptions) -> Self {
+        self.tls = Some(options);
+        self
+    }
+
+    /// Sets how deleted emails are expunged.
+    ///
+    /// Default is [`ExpungeMode::Uid`].
+    #[must_use]
+    pub fn expunge_mode(mut self, mode: ExpungeMode) -> Self {
+        self.expunge = Some(mode);
+        self
+    }
+
+    /// Sets the authentication method.
+    ///
+    /// Default is [`AuthMethod::Auto`].
+    #[must_use]
+    pub fn auth_method(mut self, method: AuthMethod) -> Self {
+        self.auth = Some(method);
+        self
+    }
+
+    /// Sets the Windows domain for [`AuthMethod::Ntlm`].
+    ///
+    /// The user is then the local part of the email address, e.g. `CORP\jdoe` for
+    /// `jdoe@corp.example.com`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::{AuthMethod, ImapConfig};
+    ///
+    /// let config = ImapConfig::builder()
+    ///     .email("jdoe@corp.example.com")
+    ///     .password("domain-password")
+    ///     .imap_host("exchange.corp.example.com")
+    ///     .auth_method(AuthMethod::Ntlm)
+    ///     .ntlm_domain("CORP")
+    ///     .build()
+    ///     .expect("valid config");
+    /// ```
+    #[cfg(feature = "ntlm")]
+    #[must_use]
+    pub fn ntlm_domain(mut self, domain: impl Into<String>) -> Self {
+        self.ntlm_domain = Some(domain.into());
+        self
+    }
+
+    /// Sets the security context provider for [`AuthMethod::Gssapi`].
+    ///
+    /// See the [`gssapi`](crate::gssapi) module.
+    #[cfg(feature = "gssapi")]
+    #[must_use]
+    pub fn gssapi_provider(mut self, provider: Arc<dyn GssapiProvider>) -> Self {
+        self.gssapi_provider = Some(provider);
+        self
+    }
+
+    /// Sets a hook receiving operation latencies and match events.
+    ///
+    /// See the [`events`](crate::events) module.
+    #[must_use]
+    pub fn event_hook(mut self, hook: impl EventHook + 'static) -> Self {
+        self.event_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook called after each poll of a wait, with the time elapsed, the
+    /// number of polls and the newest UID checked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::events::WaitProgress;
+    /// use email_sync::ImapConfig;
+    ///
+    /// let config = ImapConfig::builder()
+    ///     .email("user@gmail.com")
+    ///     .password("app-password")
+    ///     .on_progress(|progress: &WaitProgress| {
+    ///         eprint!("\rWaiting for email... {}s", progress.elapsed.as_secs());
+    ///     })
+    ///     .build()?;
+    /// # Ok::<(), email_sync::Error>(())
+    /// ```
+    #[must_use]
+    pub fn on_progress(mut self, hook: impl ProgressHook + 'static) -> Self {
+        self.on_progress = Some(Arc::new(hook));
+        self
+    }
+
+    /// Normalizes email text before matching: NFKC normalization plus removal of
+    /// zero-width and other invisible characters.
+    ///
+    /// Off by default. Useful when senders break up codes with invisible
+    /// characters, see [`normalize_text`](crate::matcher::normalize_text).
+    #[must_use]
+    pub fn normalize_text(mut self) -> Self {
+        self.normalize_text = true;
+        self
+    }
+
+    /// Sets how matched values (codes, links) appear in the crate's logs and
+    /// [`Event`](crate::events::Event)s.
+    ///
+    /// Defaults to [`Redaction::Mask`]. Values returned to the caller are never
+    /// redacted.
+    #[must_use]
+    pub fn redact_matches(mut self, redaction: Redaction) -> Self {
+        self.redact_matches = Some(redaction);
+        self
+    }
+
+    /// Matches new emails on their subject line first, downloading the body only
+    /// for emails whose subject does not match.
+    ///
+    /// Off by default. Many verification emails carry the code in the subject
+    /// (`123456 is your code`), so this cuts latency and bandwidth while waiting.
+    /// An email whose subject matches is not checked against its body, and
+    /// searches of existing emails always download bodies.
+    #[must_use]
+    pub fn subject_first(mut self) -> Self {
+        self.subject_first = true;
+        self
+    }
+
+    /// Fetches only the ENVELOPE (subject, addresses, date, Message-ID) of
+    /// emails, never their headers or body, and matches on the subject and
+    /// senders.
+    ///
+    /// Off by default. For deployments that must not download message content:
+    /// waits, searches and [`Filter`]s work on the envelope, while
+    /// [`fetch_message`](crate::ImapEmailClient::fetch_message) and
+    /// [`find_by_message_id`](crate::ImapEmailClient::find_by_message_id) fail
+    /// with [`Error::InvalidConfig`]. Filters on other headers reject every
+    /// email. Supersedes [`subject_first`](Self::subject_first).
+    #[must_use]
+    pub fn envelope_only(mut self) -> Self {
+        self.envelope_only = true;
+        self
+    }
+
+    /// Sets the FETCH items downloaded for each email by waits and searches,
+    /// instead of the whole email (`BODY[]`).
+    ///
+    /// For providers that fail to return `BODY[]` for some emails, or to
+    /// download less: the message text (`BODY.PEEK[TEXT]`), specific body parts
+    /// (`BODY.PEEK[1]`, with `BODY.PEEK[1.MIME]` to decode them) and a subset of
+    /// the header (`BODY.PEEK[HEADER.FIELDS (TO MESSAGE-ID)]`). Include a header
+    /// section to keep the recipient alias, [`Filter`]s on headers and duplicate
+    /// detection working. `INTERNALDATE` and `RFC822.SIZE` are always fetched.
+    ///
+    /// [`fetch_message`](crate::ImapEmailClient::fetch_message) and
+    /// [`find_by_message_id`](crate::ImapEmailClient::find_by_message_id) still
+    /// download whole emails. [`build`](Self::build) fails if the list is
+    /// invalid (see [`FetchItems::new`]) or [`envelope_only`](Self::envelope_only)
+    /// is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::ImapConfig;
+    ///
+    /// let config = ImapConfig::builder()
+    ///     .email("user@example.com")
+    ///     .password("secret")
+    ///     .fetch_items("(BODY.PEEK[HEADER] BODY.PEEK[TEXT])")
+    ///     .build()?;
+    /// # Ok::<(), email_sync::Error>(())
+    /// ```
+    #[must_use]
+    pub fn fetch_items(mut self, items: impl Into<String>) -> Self {
+        self.fetch_items = Some(items.into());
+        self
+    }
+
+    /// Sets the largest number of bytes of an email parsed for matching (default
+    /// [`DEFAULT_MAX_PARSE_SIZE`]).
+    ///
+    /// Longer emails are cut off before parsing and a warning is logged, so a
+    /// pathological email cannot exhaust the memory of a long-running service.
+    /// Verification codes sit near the top of an email, well within the limit.
+    #[must_use]
+    pub fn max_parse_size(mut self, bytes: usize) -> Self {
+        self.max_parse_size = Some(bytes);
+        self.no_parse_size_limit = false;
+        self
+    }
+
+    /// Parses emails in full, however large they are.
+    #[must_use]
+    pub fn no_parse_size_limit(mut self) -> Self {
+        self.no_parse_size_limit = true;
+        self
+    }
+
+    /// Sets how many emails are in flight at once when searching existing emails
+    /// (default 1), e.g. in
+    /// [`find_recent_match`](crate::ImapEmailClient::find_recent_match).
+    ///
+    /// Candidates are fetched in batches of `emails` per `FETCH` command and
+    /// checked newest first. Larger batches save round trips on mailboxes with
+    /// hundreds of candidates, but download emails past the match and weigh more
+    /// on providers that throttle bandwidth or concurrent fetches.
+    #[must_use]
+    pub fn scan_concurrency(mut self, emails: usize) -> Self {
+        self.scan_concurrency = Some(emails);
+        self
+    }
+
+    /// Caps how many emails, or bytes of emails, a search of existing emails
+    /// downloads (default unlimited), e.g. in
+    /// [`find_recent_match`](crate::ImapEmailClient::find_recent_match) with a
+    /// wide `max_age` on a busy mailbox. See [`ScanLimits`].
+    #[must_use]
+    pub fn scan_limits(mut self, limits: ScanLimits) -> Self {
+        self.scan_limits = limits;
+        self
+    }
+
+    /// Sets how far before `max_age` the `SINCE` search of recent emails reaches
+    /// (default [`DEFAULT_CLOCK_SKEW_MARGIN`]), e.g. in
+    /// [`find_recent_match`](crate::ImapEmailClient::find_recent_match).
+    ///
+    /// `SINCE` only takes a date, which the server interprets in its own time
+    /// zone, so without a margin emails from "yesterday" server time are missed.
+    /// Candidates are then narrowed down to `max_age` by their INTERNALDATE, so
+    /// a larger margin costs a wider search, not wrong results.
+    #[must_use]
+    pub fn clock_skew_margin(mut self, margin: Duration) -> Self {
+        self.clock_skew_margin = Some(margin);
+        self
+    }
+
+    /// Only considers emails sent to `alias`.
+    ///
+    /// Useful when one inbox is shared through plus-addressing
+    /// (`user+run42@gmail.com`): each client only sees emails whose `To`, `Cc`,
+    /// `Delivered-To` or `X-Original-To` header contains the alias. Applies to
+    /// every wait and search of the client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::ImapConfig;
+    ///
+    /// let config = ImapConfig::builder()
+    ///     .email("user@gmail.com")
+    ///     .password("app-password")
+    ///     .recipient_alias("user+run42@gmail.com")
+    ///     .build()
+    ///     .expect("valid config");
+    ///
+    /// assert_eq!(config.recipient_alias(), Some("user+run42@gmail.com"));
+    /// ```
+    #[must_use]
+    pub fn recipient_alias(mut self, alias: impl Into<String>) -> Self {
+        self.recipient_alias = Some(alias.into());
+        self
+    }
+
+    /// Sets the identification sent with the `ID` command after login, when the
+    /// server advertises `ID`.
+    ///
+    /// Default is [`ClientId::default`] (crate name and version).
+    #[must_use]
+    pub fn client_id(mut self, id: ClientId) -> Self {
+        self.client_id = Some(id);
+        self.disable_client_id = false;
+        self
+    }
+
+    /// Skips the `ID` command after login.
+    #[must_use]
+    pub fn disable_client_id(mut self) -> Self {
+        self.disable_client_id = true;
+        self
+    }
+
+    /// Sends a NOOP whenever the connection has been idle for `interval`.
+    ///
+    /// Servers drop connections that stay silent for too long (often about 30
+    /// minutes). An [`ImapEmailClientHandle`](crate::ImapEmailClientHandle) sends the
+    /// NOOP from its background task. A bare [`ImapEmailClient`](crate::ImapEmailClient)
+    /// has no background task: it sends the NOOP before its next command, and
+    /// reconnects first if the server dropped the connection in the meantime.
+    /// Disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::ImapConfig;
+    /// use std::time::Duration;
+    ///
+    /// let config = ImapConfig::builder()
+    ///     .email("user@example.com")
+    ///     .password("app-password")
+    ///     .keepalive(Duration::from_mins(10))
+    ///     .build()
+    ///     .expect("valid config");
+    /// ```
+    #[must_use]
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    /// Registers the background tasks of clients with this configuration with
+    /// `shutdown`, which stops them and logs out their sessions.
+    ///
+    /// See the [`shutdown`](crate::shutdown) module.
+    #[must_use]
+    pub fn shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
     /// Builds the configuration.
     ///
     /// # Errors
@@ -325,9 +1550,61 @@ impl ImapConfigBuilder {
         // Validate email format using email_address crate
         let email = validate_email(&email_raw)?;
 
-        let password_raw = self.password.ok_or_else(|| Error::InvalidConfig {
-            message: "password is required".into(),
-        })?;
+        let password_raw = self
+            .password
+            .or_else(|| (!requires_password(self.auth)).then(String::new))
+            .ok_or_else(|| Error::InvalidConfig {
+                message: "password is required".into(),
+            })?;
+
+        #[cfg(feature = "gssapi")]
+        if self.auth == Some(AuthMethod::Gssapi) && self.gssapi_provider.is_none() {
+            return Err(Error::InvalidConfig {
+                message: "GSSAPI authentication requires a gssapi_provider".into(),
+            });
+        }
+
+        if self.max_parse_size == Some(0) {
+            return Err(Error::InvalidConfig {
+                message: "max_parse_size must be greater than zero".into(),
+            });
+        }
+
+        if self.scan_concurrency == Some(0) {
+            return Err(Error::InvalidConfig {
+                message: "scan_concurrency must be greater than zero".into(),
+            });
+        }
+
+        if let Some(polling) = &self.polling {
+            if polling.interval > polling.max_wait {
+                return Err(Error::InvalidConfig {
+                    message: format!(
+                        "poll interval ({:?}) must not exceed max_wait ({:?})",
+                        polling.interval, polling.max_wait
+                    ),
+                });
+            }
+        }
+
+        if self.keepalive.is_some_and(|interval| interval.is_zero()) {
+            return Err(Error::InvalidConfig {
+                message: "keepalive interval must be greater than zero".into(),
+            });
+        }
+
+        let fetch_items = self.fetch_items.map(FetchItems::new).transpose()?;
+        if fetch_items.is_some() && self.envelope_only {
+            return Err(Error::InvalidConfig {
+                message: "fetch_items cannot be combined with envelope_only".into(),
+            });
+        }
+
+        let recipient_alias = self
+            .recipient_alias
+            .as_deref()
+            .map(validate_email)
+            .transpose()?;
 
         // Resolve IMAP host: explicit > registry > default discovery
         let imap_host = self.imap_host.or_else(|| {
@@ -335,14 +1612,50 @@ impl ImapConfigBuilder {
                 .map(|registry| registry.discover(email.as_str()).into_owned())
         });
 
+        let proxy = match self.proxy {
+            Some(proxy) => Some(proxy),
+            None if self.proxy_from_env => {
+                let host = imap_host
+                    .clone()
+                    .unwrap_or_else(|| crate::known_servers::discover_imap_host(email.as_str()));
+                crate::proxy::from_env(&host)?
+            }
+            None => None,
+        };
+
         Ok(ImapConfig {
             email,
             password: SecretString::from(password_raw),
             imap_host,
             imap_port: self.imap_port.unwrap_or(993),
-            proxy: self.proxy,
+            proxy,
+            tls: self.tls.unwrap_or_default(),
+            proxy_pool: self.proxy_pool.filter(|pool| !pool.is_empty()),
             timeouts: self.timeouts.unwrap_or_default(),
+            retry: self.retry.unwrap_or_default(),
             polling: self.polling.unwrap_or_default(),
+            expunge: self.expunge.unwrap_or_default(),
+            auth: self.auth.unwrap_or_default(),
+            #[cfg(feature = "ntlm")]
+            ntlm_domain: self.ntlm_domain,
+            #[cfg(feature = "gssapi")]
+            gssapi_provider: self.gssapi_provider,
+            recipient_alias,
+            client_id: (!self.disable_client_id).then(|| self.client_id.unwrap_or_default()),
+            keepalive: self.keepalive,
+            shutdown: self.shutdown,
+            event_hook: self.event_hook,
+            on_progress: self.on_progress,
+            normalize_text: self.normalize_text,
+            redact_matches: self.redact_matches.unwrap_or_default(),
+            subject_first: self.subject_first,
+            envelope_only: self.envelope_only,
+            fetch_items,
+            max_parse_size: (!self.no_parse_size_limit)
+                .then(|| self.max_parse_size.unwrap_or(DEFAULT_MAX_PARSE_SIZE)),
+            scan_concurrency: self.scan_concurrency.unwrap_or(1),
+            scan_limits: self.scan_limits,
+            clock_skew_margin: self.clock_skew_margin.unwrap_or(DEFAULT_CLOCK_SKEW_MARGIN),
         })
     }
 }
@@ -363,6 +1676,9 @@ mod tests {
         assert_eq!(config.password(), "secret");
         assert_eq!(config.imap_port, 993);
         assert!(config.proxy.is_none());
+        assert_eq!(config.expunge, ExpungeMode::Uid);
+        assert_eq!(config.auth, AuthMethod::Auto);
+        assert_eq!(config.client_id, Some(ClientId::default()));
     }
 
     #[test]
@@ -385,6 +1701,103 @@ mod tests {
         assert_eq!(config.polling.interval, Duration::from_secs(5));
     }
 
+    #[test]
+    fn test_builder_rejects_interval_over_max_wait() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        assert!(builder()
+            .poll_interval(Duration::from_secs(10))
+            .max_wait(Duration::from_secs(5))
+            .build()
+            .is_err());
+        assert!(builder()
+            .poll_interval(Duration::from_secs(5))
+            .max_wait(Duration::from_secs(5))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_presets() {
+        let timeouts = |t: &TimeoutConfig| {
+            [
+                t.connect,
+                t.auth,
+                t.select,
+                t.uid_fetch,
+                t.message_fetch,
+                t.command,
+                t.logout,
+            ]
+        };
+        let (fast, default, proxied) = (
+            timeouts(&TimeoutConfig::fast()),
+            timeouts(&TimeoutConfig::default()),
+            timeouts(&TimeoutConfig::proxied()),
+        );
+        for ((fast, default), proxied) in fast.iter().zip(default).zip(proxied) {
+            assert!(*fast < default && default < proxied);
+        }
+
+        for polling in [
+            PollingConfig::fast(),
+            PollingConfig::default(),
+            PollingConfig::proxied(),
+        ] {
+            let config = ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+                .polling(polling)
+                .build();
+            assert!(config.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_connect_retry_delay() {
+        let timeouts = TimeoutConfig {
+            connect_attempts: 4,
+            connect_backoff: Duration::from_secs(1),
+            ..TimeoutConfig::default()
+        };
+        assert_eq!(
+            timeouts.connect_retry_delay(1),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            timeouts.connect_retry_delay(3),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(timeouts.connect_retry_delay(4), None);
+
+        let timeouts = TimeoutConfig {
+            connect_attempts: u32::MAX,
+            ..TimeoutConfig::default()
+        };
+        assert_eq!(timeouts.connect_retry_delay(40), Some(MAX_BACKOFF));
+
+        let config = ImapConfig::builder()
+            .email("user@example.com")
+            .password("secret")
+            .connect_attempts(0)
+            .build()
+            .unwrap();
+        assert_eq!(config.timeouts.connect_retry_delay(1), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = RetryConfig::new(2).backoff(Duration::from_millis(100));
+        assert_eq!(retry.delay(1), Some(Duration::from_millis(100)));
+        assert_eq!(retry.delay(2), Some(Duration::from_millis(200)));
+        assert_eq!(retry.delay(3), None);
+        assert_eq!(RetryConfig::default().delay(1), None);
+    }
+
     #[test]
     fn test_builder_missing_email() {
         let result = ImapConfig::builder().password("secret").build();
@@ -406,6 +1819,261 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_builder_invalid_recipient_alias() {
+        let result = ImapConfig::builder()
+            .email("user@example.com")
+            .password("secret")
+            .recipient_alias("not-an-email")
+            .build();
+
+        assert!(matches!(result, Err(Error::InvalidEmailFormat { .. })));
+    }
+
+    #[test]
+    fn test_client_id() {
+        let id = ClientId::default()
+            .field("Name", "my-tests")
+            .field("vendor", "acme");
+        let fields: Vec<_> = id.fields().collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("name", "my-tests"),
+                ("version", env!("CARGO_PKG_VERSION")),
+                ("vendor", "acme"),
+            ]
+        );
+
+        let config = ImapConfig::builder()
+            .email("user@example.com")
+            .password("secret")
+            .disable_client_id()
+            .build()
+            .unwrap();
+        assert!(config.client_id.is_none());
+    }
+
+    #[test]
+    fn test_builder_max_parse_size() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        let config = builder().build().unwrap();
+        assert_eq!(config.max_parse_size, Some(DEFAULT_MAX_PARSE_SIZE));
+
+        let config = builder().max_parse_size(1024).build().unwrap();
+        assert_eq!(config.max_parse_size, Some(1024));
+
+        let config = builder().no_parse_size_limit().build().unwrap();
+        assert_eq!(config.max_parse_size, None);
+
+        let result = builder().max_parse_size(0).build();
+        assert!(matches!(result, Err(Error::InvalidConfig { .. })));
+    }
+
+    #[test]
+    fn test_fetch_items() {
+        let items =
+            FetchItems::new("(BODY.PEEK[HEADER.FIELDS (TO)] BODY.PEEK[1.2] BODY.PEEK[1.2.MIME])")
+                .unwrap();
+        assert_eq!(items.parts(), [vec![1, 2]]);
+        assert!(FetchItems::new("body.peek[text]")
+            .unwrap()
+            .parts()
+            .is_empty());
+        assert!(FetchItems::new("RFC822").is_ok());
+
+        for invalid in [
+            "BODY.PEEK[HEADER]",
+            "(ENVELOPE BODYSTRUCTURE)",
+            "BODY.PEEK[1.MIME]",
+            "BODY.PEEK[TEXT",
+            "BODY.PEEK[0]",
+            "BODY.PEEK[1.TEXT]",
+        ] {
+            assert!(FetchItems::new(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_builder_fetch_items() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        assert!(builder().build().unwrap().fetch_items.is_none());
+        let config = builder().fetch_items("BODY.PEEK[TEXT]").build().unwrap();
+        assert_eq!(
+            config.fetch_items.as_ref().map(FetchItems::as_str),
+            Some("BODY.PEEK[TEXT]")
+        );
+        assert!(builder().fetch_items("UID").build().is_err());
+        assert!(builder()
+            .fetch_items("BODY.PEEK[TEXT]")
+            .envelope_only()
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder_envelope_only() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        assert!(!builder().build().unwrap().envelope_only);
+        assert!(builder().envelope_only().build().unwrap().envelope_only);
+    }
+
+    #[test]
+    fn test_builder_clock_skew_margin() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        let config = builder().build().unwrap();
+        assert_eq!(config.clock_skew_margin, DEFAULT_CLOCK_SKEW_MARGIN);
+
+        let config = builder().clock_skew_margin(Duration::ZERO).build().unwrap();
+        assert_eq!(config.clock_skew_margin, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_builder_scan_limits() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        let config = builder().build().unwrap();
+        assert_eq!(config.scan_limits, ScanLimits::default());
+        assert_eq!(config.scan_limits.max_messages, None);
+
+        let limits = ScanLimits::new().max_messages(50).max_bytes(1 << 20);
+        let config = builder().scan_limits(limits).build().unwrap();
+        assert_eq!(config.scan_limits.max_messages, Some(50));
+        assert_eq!(config.scan_limits.max_bytes, Some(1 << 20));
+    }
+
+    #[test]
+    fn test_builder_scan_concurrency() {
+        let builder = || {
+            ImapConfig::builder()
+                .email("user@example.com")
+                .password("secret")
+        };
+
+        assert_eq!(builder().build().unwrap().scan_concurrency, 1);
+        let config = builder().scan_concurrency(16).build().unwrap();
+        assert_eq!(config.scan_concurrency, 16);
+
+        let result = builder().scan_concurrency(0).build();
+        assert!(matches!(result, Err(Error::InvalidConfig { .. })));
+    }
+
+    #[test]
+    fn test_builder_keepalive() {
+        let config = ImapConfig::builder()
+            .email("user@example.com")
+            .password("secret")
+            .keepalive(Duration::from_mins(10))
+            .build()
+            .unwrap();
+        assert_eq!(config.keepalive, Some(Duration::from_mins(10)));
+
+        let result = ImapConfig::builder()
+            .email("user@example.com")
+            .password("secret")
+            .keepalive(Duration::ZERO)
+            .build();
+        assert!(matches!(result, Err(Error::InvalidConfig { .. })));
+    }
+
+    #[cfg(feature = "gssapi")]
+    #[test]
+    fn test_builder_gssapi_without_password() {
+        // No password needed, but a provider is
+        let result = ImapConfig::builder()
+            .email("jdoe@corp.example.com")
+            .auth_method(AuthMethod::Gssapi)
+            .build();
+        assert!(
+            matches!(result, Err(Error::InvalidConfig { message }) if message.contains("gssapi_provider"))
+        );
+    }
+
+    #[test]
+    fn test_redaction() {
+        assert_eq!(Redaction::default(), Redaction::Mask);
+        assert_eq!(Redaction::Mask.redact("ab-12é"), "******");
+        // First 6 bytes of SHA-256("123456")
+        assert_eq!(Redaction::Hash.redact("123456"), "sha256:8d969eef6eca");
+        assert_eq!(Redaction::Off.redact("123456"), "123456");
+
+        let config = ImapConfig::builder()
+            .email("user@example.com")
+            .password("secret")
+            .redact_matches(Redaction::Hash)
+            .build()
+            .unwrap();
+        assert_eq!(config.redact_matches, Redaction::Hash);
+    }
+
+    #[test]
+    fn test_auth_method_choose() {
+        let offering = |offered: &'static [&'static str]| move |m: &str| offered.contains(&m);
+
+        assert_eq!(
+            AuthMethod::choose(offering(&["PLAIN", "LOGIN", "CRAM-MD5"]), true),
+            AuthMethod::CramMd5
+        );
+        assert_eq!(
+            AuthMethod::choose(offering(&["PLAIN"]), true),
+            AuthMethod::Login
+        );
+        assert_eq!(
+            AuthMethod::choose(offering(&["PLAIN"]), false),
+            AuthMethod::Plain
+        );
+        assert_eq!(AuthMethod::choose(offering(&[]), false), AuthMethod::Login);
+    }
+
+    #[test]
+    fn test_wait_options() {
+        assert_eq!(WaitOptions::default().skip, 0);
+        assert_eq!(WaitOptions::new().skip(2).skip, 2);
+        assert!(WaitOptions::default().in_thread.is_none());
+        assert_eq!(
+            WaitOptions::new().in_thread("<a@example.com>").in_thread,
+            Some("<a@example.com>".into())
+        );
+        assert!(WaitOptions::default().timeout.is_none());
+        assert_eq!(
+            WaitOptions::new().timeout(Duration::from_secs(30)).timeout,
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_search_options() {
+        assert_eq!(SearchOptions::default().order, Order::NewestFirst);
+        assert_eq!(
+            SearchOptions::new().order(Order::OldestFirst).order,
+            Order::OldestFirst
+        );
+    }
+
     #[test]
     fn test_server_address() {
         let config = ImapConfig::builder()
@@ -1,23 +1,63 @@
 //! Internal module for establishing TLS connections to IMAP servers.
 //!
-//! Supports both direct connections and SOCKS5 proxy connections.
+//! Supports both direct connections and SOCKS5 proxy connections. TLS uses
+//! rustls, or the platform TLS stack (Schannel, Secure Transport, OpenSSL) with
+//! the `native-tls` feature.
 
+use crate::config::{Sha256, TlsOptions};
 use crate::error::{Error, Result};
 use crate::proxy::Socks5Proxy;
-use rustls::ClientConfig;
-use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio_rustls::TlsConnector;
+use crate::runtime::{self, TcpStream};
+use sha2::Digest;
+use std::net::SocketAddr;
+use std::time::Instant;
 use tokio_socks::tcp::Socks5Stream;
 use tracing::{debug, instrument};
-use webpki_roots::TLS_SERVER_ROOTS;
 
-/// A TLS stream over TCP, used for IMAP communication.
-pub(crate) type TlsStream = tokio_rustls::client::TlsStream<TcpStream>;
+pub(crate) use tls::TlsStream;
+
+/// Time spent in each phase of establishing a connection.
+///
+/// Returned by [`ImapEmailClient::connect_stats`](crate::ImapEmailClient::connect_stats)
+/// to tell a slow proxy from a slow TLS handshake or a slow provider.
+///
+/// When a proxy is used, `dns_ms` covers resolving the proxy host (the proxy
+/// resolves the IMAP host itself) and `tcp_ms` includes the SOCKS5 handshake.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectStats {
+    /// DNS resolution time in milliseconds.
+    pub dns_ms: u64,
+    /// TCP connection time in milliseconds.
+    pub tcp_ms: u64,
+    /// TLS handshake time in milliseconds.
+    pub tls_ms: u64,
+    /// Authentication time in milliseconds, including client identification.
+    pub auth_ms: u64,
+    /// Mailbox selection time in milliseconds.
+    pub select_ms: u64,
+    /// Address the TCP connection was made to (the proxy when one is used).
+    pub endpoint: Option<SocketAddr>,
+}
+
+impl ConnectStats {
+    /// Returns the total connection time in milliseconds.
+    #[must_use]
+    pub fn total_ms(&self) -> u64 {
+        self.dns_ms + self.tcp_ms + self.tls_ms + self.auth_ms + self.select_ms
+    }
+}
+
+/// Returns the milliseconds elapsed since `start`.
+pub(crate) fn elapsed_ms(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
 
 /// Establishes a TLS connection to an IMAP server.
 ///
-/// If a proxy is provided, the connection is routed through SOCKS5.
+/// If a proxy is provided, the connection is routed through SOCKS5. The TLS
+/// handshake with `imap_host` follows `tls`. The DNS, TCP and TLS timings are
+/// recorded in `stats`.
 #[instrument(
     name = "connection::establish_tls",
     skip_all,
@@ -29,41 +69,311 @@ pub(crate) type TlsStream = tokio_rustls::client::TlsStream<TcpStream>;
 )]
 pub(crate) async fn establish_tls_connection(
     imap_host: &str,
+    tls: &TlsOptions,
     target_addr: &str,
     proxy: Option<&Socks5Proxy>,
+    stats: &mut ConnectStats,
+) -> Result<TlsStream> {
+    let tcp_stream = connect_tcp(target_addr, proxy, stats).await?;
+    start_tls(imap_host, tls, target_addr, tcp_stream, stats).await
+}
+
+/// Performs the TLS handshake with `host` over an established TCP connection,
+/// e.g. after an SMTP `STARTTLS` command.
+pub(crate) async fn start_tls(
+    host: &str,
+    tls: &TlsOptions,
+    target_addr: &str,
+    tcp_stream: TcpStream,
+    stats: &mut ConnectStats,
 ) -> Result<TlsStream> {
-    let connector = create_tls_connector();
-    let server_name = parse_server_name(imap_host)?;
-    let tcp_stream = connect_tcp(target_addr, proxy).await?;
+    let server_name = parse_server_name(host)?;
+    stats.endpoint = tcp_stream.peer_addr().ok();
 
     debug!("Performing TLS handshake");
 
-    connector
-        .connect(server_name, tcp_stream)
+    let started = Instant::now();
+    let tls_stream = tls::handshake(server_name, tls, tcp_stream)
         .await
-        .map_err(|source| Error::TlsConnect {
-            target: target_addr.to_string(),
-            source,
-        })
+        .map_err(|source| {
+            if tls::is_pin_mismatch(&source) {
+                Error::CertificatePinMismatch {
+                    host: host.to_string(),
+                }
+            } else {
+                Error::TlsConnect {
+                    target: target_addr.to_string(),
+                    source,
+                }
+            }
+        })?;
+    stats.tls_ms = elapsed_ms(started);
+
+    Ok(tls_stream)
+}
+
+/// TLS backed by rustls.
+#[cfg(not(feature = "native-tls"))]
+mod tls {
+    use crate::config::{Sha256, TlsOptions, TlsVersion};
+    use crate::runtime::TcpStream;
+    use rustls::client::{Resumption, ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+    use rustls::{Certificate, CertificateError, ClientConfig, ServerName};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex, OnceLock};
+    use tokio_rustls::TlsConnector;
+    use webpki_roots::TLS_SERVER_ROOTS;
+
+    /// A TLS stream over TCP, used for IMAP communication.
+    pub(crate) type TlsStream = tokio_rustls::client::TlsStream<TcpStream>;
+
+    /// Performs the TLS handshake with `server_name` over `stream`.
+    pub(super) async fn handshake(
+        server_name: rustls::ServerName,
+        options: &TlsOptions,
+        stream: TcpStream,
+    ) -> std::io::Result<TlsStream> {
+        TlsConnector::from(tls_config(options)?)
+            .connect(server_name, stream)
+            .await
+    }
+
+    /// Returns `true` if a handshake failed because of [`PinnedVerifier`].
+    pub(super) fn is_pin_mismatch(error: &std::io::Error) -> bool {
+        error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+            .is_some_and(|inner| {
+                matches!(
+                    inner,
+                    rustls::Error::InvalidCertificate(
+                        CertificateError::ApplicationVerificationFailure
+                    )
+                )
+            })
+    }
+
+    /// Verifies certificates like rustls does, then requires the public key of
+    /// the server certificate to be pinned.
+    ///
+    /// Intermediates are not checked: they are public, so anyone holding a trusted
+    /// certificate for the host could attach a pinned one to the handshake.
+    pub(super) struct PinnedVerifier {
+        pub(super) inner: WebPkiVerifier,
+        pub(super) pins: Vec<Sha256>,
+    }
+
+    impl ServerCertVerifier for PinnedVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &Certificate,
+            intermediates: &[Certificate],
+            server_name: &ServerName,
+            scts: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            now: std::time::SystemTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            let verified = self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+
+            if super::is_pinned(&end_entity.0, &self.pins) {
+                Ok(verified)
+            } else {
+                Err(rustls::Error::InvalidCertificate(
+                    CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+        }
+    }
+
+    /// Number of servers whose TLS sessions are kept for resumption.
+    const TLS_SESSION_CACHE_SIZE: usize = 256;
+
+    /// Returns the TLS configuration shared by all connections of the process
+    /// with the same `options`.
+    ///
+    /// Sharing it shares its session cache: reconnects (pools, retries) resume the
+    /// previous session to the same host with a ticket instead of doing a full
+    /// handshake.
+    pub(super) fn tls_config(options: &TlsOptions) -> std::io::Result<Arc<ClientConfig>> {
+        static CONFIGS: OnceLock<Mutex<HashMap<TlsOptions, Arc<ClientConfig>>>> = OnceLock::new();
+
+        let mut configs = CONFIGS
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some(config) = configs.get(options) {
+            return Ok(config.clone());
+        }
+        let config = create_tls_config(options)?;
+        configs.insert(options.clone(), config.clone());
+        Ok(config)
+    }
+
+    /// Creates a TLS configuration with system root certificates.
+    fn create_tls_config(options: &TlsOptions) -> std::io::Result<Arc<ClientConfig>> {
+        let mut root_cert_store = rustls::RootCertStore::empty();
+        root_cert_store.add_trust_anchors(TLS_SERVER_ROOTS.iter().map(|ta| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+
+        let versions: &[&rustls::SupportedProtocolVersion] = match options.min_version {
+            TlsVersion::Tls12 => &[&rustls::version::TLS13, &rustls::version::TLS12],
+            TlsVersion::Tls13 => &[&rustls::version::TLS13],
+        };
+
+        let builder = ClientConfig::builder()
+            .with_safe_default_cipher_suites()
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(versions)
+            .map_err(std::io::Error::other)?;
+        let mut tls_config = if options.pinned_spki.is_empty() {
+            builder
+                .with_root_certificates(root_cert_store)
+                .with_no_client_auth()
+        } else {
+            builder
+                .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+                    inner: WebPkiVerifier::new(root_cert_store, None),
+                    pins: options.pinned_spki.clone(),
+                }))
+                .with_no_client_auth()
+        };
+        tls_config.resumption = Resumption::in_memory_sessions(TLS_SESSION_CACHE_SIZE);
+        tls_config.alpn_protocols = options
+            .alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+
+        Ok(Arc::new(tls_config))
+    }
+}
+
+/// TLS backed by the platform TLS stack.
+#[cfg(feature = "native-tls")]
+mod tls {
+    use crate::config::{TlsOptions, TlsVersion};
+    use crate::runtime::TcpStream;
+    use native_tls::Protocol;
+
+    /// The server certificate's public key is not pinned.
+    #[derive(Debug, thiserror::Error)]
+    #[error("server public key is not pinned")]
+    struct PinMismatch;
+
+    /// Returns `true` if a handshake failed because the key is not pinned.
+    pub(super) fn is_pin_mismatch(error: &std::io::Error) -> bool {
+        error
+            .get_ref()
+            .is_some_and(<dyn std::error::Error + Send + Sync>::is::<PinMismatch>)
+    }
+
+    /// A TLS stream over TCP, used for IMAP communication.
+    pub(crate) type TlsStream = tokio_native_tls::TlsStream<TcpStream>;
+
+    /// Performs the TLS handshake with `server_name` over `stream`.
+    ///
+    /// Certificates are verified against the system trust store, so corporate
+    /// TLS policies apply. Pins are checked against the server's own certificate
+    /// once the handshake completes; the platform APIs do not expose the chain.
+    pub(super) async fn handshake(
+        server_name: rustls::ServerName,
+        options: &TlsOptions,
+        stream: TcpStream,
+    ) -> std::io::Result<TlsStream> {
+        let host = match server_name {
+            rustls::ServerName::DnsName(name) => name.as_ref().to_string(),
+            rustls::ServerName::IpAddress(ip) => ip.to_string(),
+            _ => return Err(std::io::Error::other("unsupported server name")),
+        };
+        let alpn: Vec<&str> = options.alpn_protocols.iter().map(String::as_str).collect();
+        let connector = native_tls::TlsConnector::builder()
+            .min_protocol_version(Some(match options.min_version {
+                TlsVersion::Tls12 => Protocol::Tlsv12,
+                TlsVersion::Tls13 => Protocol::Tlsv13,
+            }))
+            .request_alpns(&alpn)
+            .build()
+            .map_err(std::io::Error::other)?;
+
+        let stream = tokio_native_tls::TlsConnector::from(connector)
+            .connect(&host, stream)
+            .await
+            .map_err(std::io::Error::other)?;
+
+        if !options.pinned_spki.is_empty() {
+            let certificate = stream
+                .get_ref()
+                .peer_certificate()
+                .map_err(std::io::Error::other)?
+                .and_then(|certificate| certificate.to_der().ok());
+            if !certificate.is_some_and(|der| super::is_pinned(&der, &options.pinned_spki)) {
+                return Err(std::io::Error::other(PinMismatch));
+            }
+        }
+
+        Ok(stream)
+    }
 }
 
-/// Creates a TLS connector with system root certificates.
-fn create_tls_connector() -> TlsConnector {
-    let mut root_cert_store = rustls::RootCertStore::empty();
-    root_cert_store.add_trust_anchors(TLS_SERVER_ROOTS.iter().map(|ta| {
-        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
-            ta.subject,
-            ta.spki,
-            ta.name_constraints,
-        )
-    }));
-
-    let tls_config = ClientConfig::builder()
-        .with_safe_defaults()
-        .with_root_certificates(root_cert_store)
-        .with_no_client_auth();
-
-    TlsConnector::from(Arc::new(tls_config))
+/// Returns `true` if the SHA-256 digest of the public key of `certificate` (DER)
+/// is one of `pins`.
+fn is_pinned(certificate: &[u8], pins: &[Sha256]) -> bool {
+    subject_public_key_info(certificate)
+        .is_some_and(|spki| pins.contains(&sha2::Sha256::digest(spki).into()))
+}
+
+/// Returns the DER-encoded `SubjectPublicKeyInfo` of a DER-encoded X.509
+/// certificate (RFC 5280, section 4.1).
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = der_element(certificate)?;
+    let (_, tbs_certificate, _) = der_element(certificate)?;
+
+    let mut rest = tbs_certificate;
+    // Skip the optional explicit version, then serialNumber, signature, issuer,
+    // validity and subject
+    if rest.first() == Some(&0xA0) {
+        rest = der_element(rest)?.2;
+    }
+    for _ in 0..5 {
+        rest = der_element(rest)?.2;
+    }
+    der_element(rest).map(|(spki, _, _)| spki)
+}
+
+/// Splits the first DER element off `data`, returning the whole element, its
+/// contents and the remaining bytes.
+fn der_element(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let length = *data.get(1)?;
+    let (header_len, content_len) = if length < 0x80 {
+        (2, usize::from(length))
+    } else {
+        // Long form: the low bits give the number of length bytes
+        let count = usize::from(length & 0x7F);
+        if count == 0 || count > std::mem::size_of::<usize>() {
+            return None;
+        }
+        let bytes = data.get(2..2 + count)?;
+        let len = bytes
+            .iter()
+            .fold(0, |len, &byte| (len << 8) | usize::from(byte));
+        (2 + count, len)
+    };
+
+    let end = header_len.checked_add(content_len)?;
+    let element = data.get(..end)?;
+    Some((element, &element[header_len..], &data[end..]))
 }
 
 /// Parses server name for TLS SNI.
@@ -83,24 +393,36 @@ fn parse_server_name(host: &str) -> Result<rustls::ServerName> {
         via_proxy = proxy.is_some()
     )
 )]
-async fn connect_tcp(target_addr: &str, proxy: Option<&Socks5Proxy>) -> Result<TcpStream> {
+pub(crate) async fn connect_tcp(
+    target_addr: &str,
+    proxy: Option<&Socks5Proxy>,
+    stats: &mut ConnectStats,
+) -> Result<TcpStream> {
     match proxy {
-        Some(proxy) => connect_via_socks5(target_addr, proxy).await,
-        None => connect_direct(target_addr).await,
+        Some(proxy) => connect_via_socks5(target_addr, proxy, stats).await,
+        None => connect_direct(target_addr, stats).await,
     }
 }
 
 /// Direct TCP connection.
 #[instrument(name = "connection::direct", skip_all)]
-async fn connect_direct(target_addr: &str) -> Result<TcpStream> {
+async fn connect_direct(target_addr: &str, stats: &mut ConnectStats) -> Result<TcpStream> {
     debug!(target = %target_addr, "Establishing direct TCP connection");
 
-    TcpStream::connect(target_addr)
-        .await
-        .map_err(|source| Error::TcpConnect {
-            target: target_addr.to_string(),
-            source,
-        })
+    let tcp_error = |source| Error::TcpConnect {
+        target: target_addr.to_string(),
+        source,
+    };
+
+    let started = Instant::now();
+    let addrs = runtime::lookup_host(target_addr).await.map_err(tcp_error)?;
+    stats.dns_ms = elapsed_ms(started);
+
+    let started = Instant::now();
+    let stream = runtime::connect_tcp(&addrs).await.map_err(tcp_error)?;
+    stats.tcp_ms = elapsed_ms(started);
+
+    Ok(stream)
 }
 
 /// TCP connection via SOCKS5 proxy.
@@ -112,14 +434,31 @@ async fn connect_direct(target_addr: &str) -> Result<TcpStream> {
         has_auth = proxy.requires_auth()
     )
 )]
-async fn connect_via_socks5(target_addr: &str, proxy: &Socks5Proxy) -> Result<TcpStream> {
+async fn connect_via_socks5(
+    target_addr: &str,
+    proxy: &Socks5Proxy,
+    stats: &mut ConnectStats,
+) -> Result<TcpStream> {
     debug!(
         proxy = %proxy,
         target = %target_addr,
         "Connecting via SOCKS5 proxy"
     );
 
-    let proxy_addr = (proxy.host.as_str(), proxy.port);
+    let socks_error = |source| Error::Socks5Connect {
+        proxy_host: proxy.host.clone(),
+        target: target_addr.to_string(),
+        source,
+    };
+
+    let started = Instant::now();
+    let proxy_addrs = runtime::lookup_host(&proxy.address())
+        .await
+        .map_err(|e| socks_error(tokio_socks::Error::Io(e)))?;
+    stats.dns_ms = elapsed_ms(started);
+
+    let started = Instant::now();
+    let proxy_addr = proxy_addrs.as_slice();
 
     let stream = match &proxy.auth {
         Some(auth) => {
@@ -134,13 +473,10 @@ async fn connect_via_socks5(target_addr: &str, proxy: &Socks5Proxy) -> Result<Tc
         None => Socks5Stream::connect(proxy_addr, target_addr).await,
     };
 
-    stream
-        .map(Socks5Stream::into_inner)
-        .map_err(|source| Error::Socks5Connect {
-            proxy_host: proxy.host.clone(),
-            target: target_addr.to_string(),
-            source,
-        })
+    let stream = stream.map(Socks5Stream::into_inner).map_err(socks_error)?;
+    stats.tcp_ms = elapsed_ms(started);
+
+    Ok(stream)
 }
 
 #[cfg(test)]
@@ -159,4 +495,107 @@ mod tests {
         let result = parse_server_name("");
         assert!(result.is_err());
     }
+
+    #[cfg(not(feature = "native-tls"))]
+    #[test]
+    fn test_tls_config_shared() {
+        // One config, so one session cache for all connections
+        let options = TlsOptions::default();
+        let config = tls::tls_config(&options).unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            &config,
+            &tls::tls_config(&options).unwrap()
+        ));
+
+        let tls13 = TlsOptions::new()
+            .min_version(crate::TlsVersion::Tls13)
+            .alpn("imap");
+        let config = tls::tls_config(&tls13).unwrap();
+        assert_eq!(config.alpn_protocols, [b"imap".to_vec()]);
+    }
+
+    /// Self-signed certificate for `imap.example.com` with a P-256 key.
+    const CERTIFICATE: &str = "MIIBjTCCATOgAwIBAgIUT+tur7nNH3DXwHgZT3OJ66yjorwwCgYIKoZIzj0EAwIwGzEZMBcGA1UEAwwQaW1hcC5leGFtcGxlLmNvbTAgFw0yNjEwMTYxMzM5MTRaGA8yMTI2MDkyMjEzMzkxNFowGzEZMBcGA1UEAwwQaW1hcC5leGFtcGxlLmNvbTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABGcFSY11BGGqJg+nbcyoZ98PhhdsBxqczG+HiAANmlnbkcCIraw8QzWr4/nvw+oXxDE4oLsph8zFVrbHiQVNRnSjUzBRMB0GA1UdDgQWBBRUAspPIdfUctM1BxKfdF/ToCxgfTAfBgNVHSMEGDAWgBRUAspPIdfUctM1BxKfdF/ToCxgfTAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIDKQ0XxICGs1nvjMrq8sZvkdXExpunqysoyefpq1rzhEAiEAnPunbvHeVA4N5BV/tYd5JZE85sF3MaWgTJJlus6ro8Q=";
+
+    /// SHA-256 of the certificate's `SubjectPublicKeyInfo`, from `openssl pkey`.
+    const PIN: &str = "21cfc47a4db03f0c76572c6b9796e64b29ad35cdb59cd0960f467f1e5d44f8ed";
+
+    fn der(certificate: &str) -> Vec<u8> {
+        use base64::Engine;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(certificate)
+            .unwrap()
+    }
+
+    fn pin(hex: &str) -> Sha256 {
+        std::array::from_fn(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+    }
+
+    #[test]
+    fn test_spki_pinning() {
+        let der = der(CERTIFICATE);
+        let pin = pin(PIN);
+
+        assert!(is_pinned(&der, &[[0; 32], pin]));
+        assert!(!is_pinned(&der, &[[0; 32]]));
+        assert!(!is_pinned(&der[..100], &[pin]));
+    }
+
+    #[cfg(not(feature = "native-tls"))]
+    #[test]
+    fn test_pinned_verifier_ignores_attached_intermediates() {
+        use rustls::client::{ServerCertVerifier, WebPkiVerifier};
+        use rustls::{Certificate, RootCertStore, ServerName};
+
+        /// Test chain, valid from 2026-10-16: `Test-Root` signs the `pinned-ca` and
+        /// `other-ca` intermediates, and `other-ca` signs `imap.example.com`.
+        const ROOT_CA: &str = "MIIBjzCCATWgAwIBAgIUTmjcKe2mFLt+eVPVJX4yELF96dQwCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJVGVzdC1Sb290MCAXDTI2MTAxNjE2NTM0N1oYDzIxMjYwOTIyMTY1MzQ3WjAUMRIwEAYDVQQDDAlUZXN0LVJvb3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASn+h9Ce+b1Y+Zj9x1NV/9Iht5O+78eX3ovF4GPjcAsc0DoPQ4G2ONapyQQECmnOTsPQ/F14ILGtdqb4HFmaod5o2MwYTAdBgNVHQ4EFgQU/1HN1AKNK8+2OrXl+Ya9NNmFdNIwHwYDVR0jBBgwFoAU/1HN1AKNK8+2OrXl+Ya9NNmFdNIwDwYDVR0TAQH/BAUwAwEB/zAOBgNVHQ8BAf8EBAMCAQYwCgYIKoZIzj0EAwIDSAAwRQIhAJXJfaNfBpiDaE638TG3J656Hey9ddCjL5JtlKdqRIHAAiBKB1a07KN7hczqL5R+NYq0YMB6Yy1BznEVZ469BsM0+w==";
+        const PINNED_CA: &str = "MIIBjjCCATWgAwIBAgIUCVOdQIc3ZC2A4XzbCLkyBudOZHwwCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJVGVzdC1Sb290MCAXDTI2MTAxNjE2NTM0N1oYDzIxMjYwOTIyMTY1MzQ3WjAUMRIwEAYDVQQDDAlwaW5uZWQtY2EwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATiRD1+MtXCuRU1V9UVm7kWIjgdU/bmGJXMkbPk5VTaor6zVJXm9nTBQdWrj6g/GBSL4un3X+2HPTZg+2fHdTo7o2MwYTAPBgNVHRMBAf8EBTADAQH/MA4GA1UdDwEB/wQEAwIBBjAdBgNVHQ4EFgQUr9UGamuaChb/vyMJGEGEiFoN+o8wHwYDVR0jBBgwFoAU/1HN1AKNK8+2OrXl+Ya9NNmFdNIwCgYIKoZIzj0EAwIDRwAwRAIgWi0Hb2PlqdIMLA7yqt4PHBw/a9xWRXEDRuHAC1o0l2sCIAPksEvuVf7m0vo+NW4e1cXI1Re3r8L+weCI7uFiGC8U";
+        const OTHER_CA: &str = "MIIBjTCCATSgAwIBAgIUCVOdQIc3ZC2A4XzbCLkyBudOZH0wCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJVGVzdC1Sb290MCAXDTI2MTAxNjE2NTM0N1oYDzIxMjYwOTIyMTY1MzQ3WjATMREwDwYDVQQDDAhvdGhlci1jYTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABO516yJ1KHTirEl2wQaZwzTGucI6LHUs5kkwhTXwR6MSQAgDTicXmJMf4G79vLKu88XvrGAcPzZb8J5JD714ifqjYzBhMA8GA1UdEwEB/wQFMAMBAf8wDgYDVR0PAQH/BAQDAgEGMB0GA1UdDgQWBBSJeOUv5/wbk4PNKgbK8EMo9yhVjjAfBgNVHSMEGDAWgBT/Uc3UAo0rz7Y6teX5hr002YV00jAKBggqhkjOPQQDAgNHADBEAiAU1FzUzVI6BH/ZTI6QxCw0QaN/VNEFDReApbHJR/2h7wIgfpsXzgoDzGVF8slWAnUadgmmokyj4GfmMEDWuKtXAVk=";
+        const LEAF: &str = "MIIBtjCCAVygAwIBAgIUEJMK28OxjDssIQMKPudzi2/aQLowCgYIKoZIzj0EAwIwEzERMA8GA1UEAwwIb3RoZXItY2EwIBcNMjYxMDE2MTY1MzQ3WhgPMjEyNjA5MjIxNjUzNDdaMBsxGTAXBgNVBAMMEGltYXAuZXhhbXBsZS5jb20wWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASUUYaXPlnX4sJz8rcIIuEkfsZCeDtRNYqpxI0AKyZ2FlT9K7Y02fzNfck7nMIRi3F49Ee8ouw1N7g//6cnudtno4GDMIGAMAwGA1UdEwEB/wQCMAAwGwYDVR0RBBQwEoIQaW1hcC5leGFtcGxlLmNvbTATBgNVHSUEDDAKBggrBgEFBQcDATAdBgNVHQ4EFgQUBnXG3sVpSLIt67rWXnoJT4daENQwHwYDVR0jBBgwFoAUiXjlL+f8G5ODzSoGyvBDKPcoVY4wCgYIKoZIzj0EAwIDSAAwRQIgBbCL2hXrr/kkuqanW78Nz3YFVJzFpU0tELM77AtiSI4CIQCNG8hQ0f8uvABEKG7zZNR0LFuMxebfrLLS4NdNHLKWFg==";
+
+        /// SHA-256 of the `SubjectPublicKeyInfo` of `PINNED_CA` and `LEAF`.
+        const PINNED_CA_PIN: &str =
+            "27a5143f43cb559f695d122768580aa6c807551123370a411fa79745c73c4ba1";
+        const LEAF_PIN: &str = "72e5bbf5d79151013e9ed8a56f7ddde27c89df2d353921ac4765167d8441458f";
+
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(der(ROOT_CA))).unwrap();
+        let server_name = ServerName::try_from("imap.example.com").unwrap();
+        let now = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_900_000_000);
+        // A trusted chain for the host, with the public pinned intermediate attached
+        let intermediates = [Certificate(der(PINNED_CA)), Certificate(der(OTHER_CA))];
+
+        let verify = |pins: Vec<Sha256>| {
+            let verifier = tls::PinnedVerifier {
+                inner: WebPkiVerifier::new(roots.clone(), None),
+                pins,
+            };
+            verifier.verify_server_cert(
+                &Certificate(der(LEAF)),
+                &intermediates,
+                &server_name,
+                &mut std::iter::empty(),
+                &[],
+                now,
+            )
+        };
+
+        assert!(verify(vec![pin(PINNED_CA_PIN)]).is_err());
+        assert!(verify(vec![pin(LEAF_PIN)]).is_ok());
+    }
+
+    #[test]
+    fn test_connect_stats_total() {
+        let stats = ConnectStats {
+            dns_ms: 5,
+            tcp_ms: 20,
+            tls_ms: 40,
+            auth_ms: 100,
+            select_ms: 10,
+            endpoint: None,
+        };
+        assert_eq!(stats.total_ms(), 175);
+    }
 }
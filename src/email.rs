@@ -0,0 +1,221 @@
+//! Types describing emails returned by the client.
+//!
+//! [`MatchedEmail`] is returned by [`ImapEmailClient::wait_for_email`] and
+//! [`ImapEmailClient::find_recent_email`] and carries the extracted value together
+//! with details about the email it came from. [`EmailMessage`] is a whole parsed
+//! email and [`MessageSummary`] a short description of one. [`WaitReport`] tells
+//! how much work a wait took.
+//!
+//! [`ImapEmailClient::wait_for_email`]: crate::ImapEmailClient::wait_for_email
+//! [`ImapEmailClient::find_recent_email`]: crate::ImapEmailClient::find_recent_email
+
+use crate::matcher::MatchDetails;
+use chrono::{DateTime, FixedOffset};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Statistics of a wait for a matching email, e.g. to tune poll intervals.
+///
+/// Carried by [`MatchedEmail::report`] and
+/// [`Error::WaitTimeout`](crate::Error::WaitTimeout).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct WaitReport {
+    /// Number of times the mailbox was checked for new emails.
+    pub polls: u32,
+    /// Number of new emails checked against the matchers.
+    pub messages_scanned: usize,
+    /// Number of reconnections after a transient error.
+    pub reconnects: u32,
+    /// Time from the start of the wait until it ended.
+    pub elapsed: Duration,
+}
+
+impl WaitReport {
+    /// Returns the report with `elapsed` measured from `started`.
+    pub(crate) fn finish(mut self, started: Instant) -> Self {
+        self.elapsed = started.elapsed();
+        self
+    }
+}
+
+/// An email that satisfied a matcher, together with the extracted value.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct MatchedEmail {
+    /// UID of the email in `mailbox`.
+    pub uid: u32,
+    /// Mailbox the email was found in.
+    pub mailbox: String,
+    /// Value extracted by the matcher.
+    pub value: String,
+    /// The value as written in the email, when the matcher normalized it (e.g.
+    /// an OTP code written with Arabic-Indic digits).
+    pub original_value: Option<String>,
+    /// Index of the matcher that fired, for calls taking several matchers.
+    pub matcher_index: usize,
+    /// Description of the matcher that fired (see [`Matcher::description`]).
+    ///
+    /// [`Matcher::description`]: crate::matcher::Matcher::description
+    pub matcher: String,
+    /// Byte offsets of the value in the decoded body text (after normalization, if
+    /// enabled), if the matcher reported them.
+    pub span: Option<Range<usize>>,
+    /// Confidence score between 0.0 and 1.0, if the matcher rates its matches.
+    pub confidence: Option<f32>,
+    /// Server arrival time (INTERNALDATE) of the email, if the server reported it.
+    ///
+    /// Unlike UIDs, this stays chronological for emails moved or copied into the
+    /// mailbox, so it tells which of several matches is the latest.
+    pub internal_date: Option<DateTime<FixedOffset>>,
+    /// Size of the email in bytes (`RFC822.SIZE`), if the server reported it.
+    pub size: Option<u32>,
+    /// Gmail-specific attributes, when the server is Gmail (`X-GM-EXT-1`).
+    pub gmail: Option<GmailAttributes>,
+    /// Statistics of the wait that found the email (`None` for searches of
+    /// existing emails).
+    pub report: Option<WaitReport>,
+}
+
+impl MatchedEmail {
+    pub(crate) fn new(
+        (mailbox, uid): (&str, u32),
+        matcher_index: usize,
+        matcher: &str,
+        details: MatchDetails<'_>,
+    ) -> Self {
+        Self {
+            uid,
+            mailbox: mailbox.to_string(),
+            value: details.value.into_owned(),
+            original_value: details.original.map(Cow::into_owned),
+            matcher_index,
+            matcher: matcher.to_string(),
+            span: details.span,
+            confidence: details.confidence,
+            internal_date: None,
+            size: None,
+            gmail: None,
+            report: None,
+        }
+    }
+}
+
+/// An email fetched in full and parsed.
+///
+/// Returned by [`ImapEmailClient::fetch_message`],
+/// [`ImapEmailClient::find_by_message_id`] and [`parse_email`](crate::parse_email).
+///
+/// [`ImapEmailClient::fetch_message`]: crate::ImapEmailClient::fetch_message
+/// [`ImapEmailClient::find_by_message_id`]: crate::ImapEmailClient::find_by_message_id
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EmailMessage {
+    /// UID of the email in the selected mailbox.
+    pub uid: u32,
+    /// Message-ID without angle brackets.
+    pub message_id: Option<String>,
+    /// Sender addresses from the `From` header.
+    pub from: Vec<String>,
+    /// Recipient addresses from the `To` header.
+    pub to: Vec<String>,
+    /// Recipient addresses from the `Cc` header.
+    pub cc: Vec<String>,
+    /// Decoded subject.
+    pub subject: Option<String>,
+    /// Date from the `Date` header.
+    pub date: Option<DateTime<FixedOffset>>,
+    /// Decoded body of the first `text/plain` part.
+    pub text_body: Option<String>,
+    /// Decoded body of the first `text/html` part.
+    pub html_body: Option<String>,
+    /// All headers as decoded `(name, value)` pairs, in order.
+    pub headers: Vec<(String, String)>,
+    /// Attachments (metadata only).
+    pub attachments: Vec<AttachmentInfo>,
+    /// Server arrival time (INTERNALDATE), for emails fetched from a server.
+    pub internal_date: Option<DateTime<FixedOffset>>,
+    /// Size of the email in bytes (`RFC822.SIZE`), for emails fetched from a
+    /// server.
+    pub size: Option<u32>,
+}
+
+impl EmailMessage {
+    /// Returns the value of the first header called `name` (case-insensitive).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A short description of an email, as listed by
+/// [`ImapEmailClient::peek_recent`](crate::ImapEmailClient::peek_recent).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MessageSummary {
+    /// UID of the email in the selected mailbox.
+    pub uid: u32,
+    /// Sender addresses from the `From` header.
+    pub from: Vec<String>,
+    /// Decoded subject.
+    pub subject: Option<String>,
+    /// Date from the `Date` header, or the server arrival time (INTERNALDATE) when
+    /// the header is missing or unparsable.
+    pub date: Option<DateTime<FixedOffset>>,
+}
+
+/// Metadata of an email attachment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AttachmentInfo {
+    /// File name from the `Content-Disposition` or `Content-Type` header.
+    pub filename: Option<String>,
+    /// Lowercase MIME type, e.g. `application/pdf`.
+    pub content_type: String,
+    /// Size of the decoded content in bytes.
+    pub size: usize,
+}
+
+/// Gmail IMAP extension attributes of an email.
+///
+/// See <https://developers.google.com/gmail/imap/imap-extensions>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GmailAttributes {
+    /// Stable message ID (`X-GM-MSGID`), the same across all labels.
+    pub message_id: u64,
+    /// Conversation ID (`X-GM-THRID`).
+    pub thread_id: u64,
+    /// Labels applied to the email (`X-GM-LABELS`), e.g. `\Important` or `Receipts`.
+    pub labels: Vec<String>,
+}
+
+impl GmailAttributes {
+    /// Returns `true` if the email carries `label` (case-insensitive).
+    #[must_use]
+    pub fn has_label(&self, label: &str) -> bool {
+        self.labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gmail_has_label() {
+        let gmail = GmailAttributes {
+            message_id: 1,
+            thread_id: 1,
+            labels: vec!["\\Important".into(), "Receipts".into()],
+        };
+
+        assert!(gmail.has_label("receipts"));
+        assert!(gmail.has_label("\\Important"));
+        assert!(!gmail.has_label("Spam"));
+    }
+}
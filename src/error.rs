@@ -3,6 +3,7 @@
 //! All errors implement [`std::error::Error`] and provide context about what went wrong.
 //! Errors are categorized by their retryability - see [`Error::is_retryable`].
 
+use crate::email::WaitReport;
 use std::time::Duration;
 use thiserror::Error;
 
@@ -30,6 +31,35 @@ pub enum Error {
         message: String,
     },
 
+    /// The client guard no longer holds a client.
+    #[error("client guard has already been consumed")]
+    GuardConsumed,
+
+    /// The background task behind a client handle has stopped.
+    #[error("client connection task has stopped")]
+    ClientClosed,
+
+    /// A blocking call was made from a single-threaded async runtime.
+    #[error("cannot block inside a single-threaded async runtime; use the async method instead")]
+    BlockingInAsyncContext,
+
+    /// The server does not support an IMAP (or SMTP) extension required by the
+    /// operation.
+    #[error("server does not support the {capability} capability")]
+    MissingCapability {
+        /// The missing capability (e.g. `X-GM-EXT-1`).
+        capability: String,
+    },
+
+    /// A proxy URL could not be parsed.
+    #[error("invalid proxy URL '{url}': {reason}")]
+    InvalidProxyUrl {
+        /// The URL, with any password masked.
+        url: String,
+        /// Why the URL was rejected.
+        reason: String,
+    },
+
     /// Invalid DNS name for TLS.
     #[error("invalid DNS name for host '{host}'")]
     InvalidDnsName {
@@ -63,6 +93,16 @@ pub enum Error {
         source: std::io::Error,
     },
 
+    /// The server's public key matches none of the pins of
+    /// [`TlsOptions::pinned_spki`](crate::TlsOptions::pinned_spki).
+    ///
+    /// Not retryable: the server (or someone in between) presents another key.
+    #[error("public key of {host} does not match any pinned key")]
+    CertificatePinMismatch {
+        /// The IMAP host.
+        host: String,
+    },
+
     /// Failed to connect via SOCKS5 proxy.
     #[error("failed to connect via SOCKS5 proxy {proxy_host} to {target}")]
     Socks5Connect {
@@ -75,6 +115,26 @@ pub enum Error {
         source: tokio_socks::Error,
     },
 
+    /// An HTTP request to a web service (e.g. EWS) failed in transit.
+    #[error("HTTP request to {url} failed")]
+    HttpRequest {
+        /// The request URL.
+        url: String,
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// An SMTP session failed in transit.
+    #[error("SMTP session with {host} failed")]
+    SmtpRequest {
+        /// The SMTP server.
+        host: String,
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+
     // ─────────────────────────────────────────────────────────────────────────
     // Timeout errors (mixed retryability)
     // ─────────────────────────────────────────────────────────────────────────
@@ -122,10 +182,34 @@ pub enum Error {
     },
 
     /// Timeout waiting for matching email.
-    #[error("timeout waiting for matching email after {timeout:?}")]
+    #[error(
+        "timeout waiting for matching email after {timeout:?} ({} polls, {} emails scanned)",
+        report.polls,
+        report.messages_scanned
+    )]
     WaitTimeout {
         /// The timeout duration that was exceeded.
         timeout: Duration,
+        /// Statistics of the wait.
+        report: WaitReport,
+    },
+
+    /// Timeout for a miscellaneous IMAP command (LIST, STATUS, ...).
+    #[error("IMAP {command} command timeout after {timeout:?}")]
+    CommandTimeout {
+        /// The IMAP command that timed out.
+        command: String,
+        /// The timeout duration that was exceeded.
+        timeout: Duration,
+    },
+
+    /// An HTTP request to a web service (e.g. EWS) timed out.
+    #[error("HTTP request to {url} timed out after {timeout:?}")]
+    HttpTimeout {
+        /// The request URL.
+        url: String,
+        /// The timeout duration that was exceeded.
+        timeout: Duration,
     },
 
     /// Logout timeout (not critical).
@@ -192,6 +276,24 @@ pub enum Error {
         source: async_imap::error::Error,
     },
 
+    /// A miscellaneous IMAP command (LIST, STATUS, ...) failed.
+    #[error("IMAP {command} command failed")]
+    ImapCommand {
+        /// The IMAP command that failed.
+        command: String,
+        /// The underlying IMAP error.
+        #[source]
+        source: async_imap::error::Error,
+    },
+
+    /// The server refused a command because its target mailbox does not exist
+    /// (`NO [TRYCREATE]`); creating the mailbox first would let it succeed.
+    #[error("mailbox '{mailbox}' does not exist")]
+    MailboxNotFound {
+        /// The mailbox name.
+        mailbox: String,
+    },
+
     /// IMAP logout failed.
     #[error("IMAP logout failed")]
     ImapLogout {
@@ -200,6 +302,53 @@ pub enum Error {
         source: async_imap::error::Error,
     },
 
+    /// A web service answered with an HTTP error status (e.g. `401` for rejected
+    /// credentials).
+    ///
+    /// Retryable for server errors (`5xx`), `408` and `429`.
+    #[error("HTTP request to {url} failed with status {status}")]
+    HttpStatus {
+        /// The request URL.
+        url: String,
+        /// The HTTP status code.
+        status: u16,
+    },
+
+    /// Exchange Web Services rejected an operation.
+    ///
+    /// Retryable when Exchange reports being busy or a transient failure.
+    #[error("EWS {operation} failed with {code}: {message}")]
+    EwsResponse {
+        /// The EWS operation (e.g. `FindItem`).
+        operation: String,
+        /// The EWS response code (e.g. `ErrorAccessDenied`).
+        code: String,
+        /// The message text returned by Exchange.
+        message: String,
+    },
+
+    /// A web service answered with a body that could not be understood.
+    #[error("unexpected response from {url}: {message}")]
+    MalformedResponse {
+        /// The request URL.
+        url: String,
+        /// What was wrong with the response.
+        message: String,
+    },
+
+    /// An SMTP server rejected a command (e.g. `535` for rejected credentials).
+    ///
+    /// Retryable for transient (`4xx`) replies.
+    #[error("SMTP {command} failed with {code}: {message}")]
+    SmtpReply {
+        /// The SMTP command (e.g. `RCPT TO`).
+        command: String,
+        /// The reply code.
+        code: u16,
+        /// The reply text.
+        message: String,
+    },
+
     // ─────────────────────────────────────────────────────────────────────────
     // Email parsing errors (NOT retryable - malformed content won't change)
     // ─────────────────────────────────────────────────────────────────────────
@@ -219,12 +368,37 @@ pub enum Error {
         source: mailparse::MailParseError,
     },
 
+    // ─────────────────────────────────────────────────────────────────────────
+    // Local mailbox errors (NOT retryable)
+    // ─────────────────────────────────────────────────────────────────────────
+    /// A local mailbox (e.g. a Maildir) could not be read.
+    #[error("failed to read local mailbox {path}")]
+    LocalMailbox {
+        /// Path of the mailbox.
+        path: String,
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+
     // ─────────────────────────────────────────────────────────────────────────
     // Search result errors (NOT retryable)
     // ─────────────────────────────────────────────────────────────────────────
     /// No matching email found.
     #[error("no matching email found")]
     NoMatch,
+
+    /// No matching email found before the search hit its
+    /// [`ScanLimits`](crate::ScanLimits); older emails were not checked.
+    #[error(
+        "no matching email in the {messages} emails ({bytes} bytes) scanned before the scan limit"
+    )]
+    ScanLimitReached {
+        /// Number of emails checked.
+        messages: usize,
+        /// Number of bytes downloaded.
+        bytes: u64,
+    },
 }
 
 impl Error {
@@ -251,23 +425,48 @@ impl Error {
             | Error::SelectTimeout { .. }
             | Error::UidFetchTimeout { .. }
             | Error::FetchTimeout { .. }
+            | Error::CommandTimeout { .. }
             | Error::ImapLogin { .. }
             | Error::SelectMailbox { .. }
             | Error::ImapNoop { .. }
             | Error::ImapSearch { .. }
             | Error::ImapFetch { .. }
-            | Error::FetchMessage { .. } => true,
+            | Error::FetchMessage { .. }
+            | Error::ImapCommand { .. }
+            | Error::HttpRequest { .. }
+            | Error::HttpTimeout { .. }
+            | Error::SmtpRequest { .. } => true,
+
+            Error::HttpStatus { status, .. } => *status >= 500 || matches!(status, 408 | 429),
+            Error::EwsResponse { code, .. } => matches!(
+                code.as_str(),
+                "ErrorServerBusy"
+                    | "ErrorTimeoutExpired"
+                    | "ErrorInternalServerTransientError"
+                    | "ErrorMailboxStoreUnavailable"
+            ),
+            Error::SmtpReply { code, .. } => (400..500).contains(code),
 
             // NOT retryable: config errors, wait/logout timeouts, parsing, no match
             Error::InvalidEmailFormat { .. }
             | Error::InvalidConfig { .. }
+            | Error::GuardConsumed
+            | Error::ClientClosed
+            | Error::BlockingInAsyncContext
+            | Error::MissingCapability { .. }
+            | Error::InvalidProxyUrl { .. }
             | Error::InvalidDnsName { .. }
+            | Error::CertificatePinMismatch { .. }
             | Error::WaitTimeout { .. }
             | Error::LogoutTimeout { .. }
             | Error::ImapLogout { .. }
+            | Error::MailboxNotFound { .. }
             | Error::ParseEmail { .. }
             | Error::ExtractBody { .. }
-            | Error::NoMatch => false,
+            | Error::LocalMailbox { .. }
+            | Error::MalformedResponse { .. }
+            | Error::NoMatch
+            | Error::ScanLimitReached { .. } => false,
         }
     }
 
@@ -277,18 +476,30 @@ impl Error {
         match self {
             Error::InvalidEmailFormat { .. }
             | Error::InvalidConfig { .. }
-            | Error::InvalidDnsName { .. } => ErrorCategory::Configuration,
+            | Error::GuardConsumed
+            | Error::ClientClosed
+            | Error::BlockingInAsyncContext
+            | Error::MissingCapability { .. }
+            | Error::InvalidProxyUrl { .. }
+            | Error::InvalidDnsName { .. }
+            | Error::LocalMailbox { .. }
+            | Error::MailboxNotFound { .. } => ErrorCategory::Configuration,
 
-            Error::TcpConnect { .. } | Error::TlsConnect { .. } | Error::Socks5Connect { .. } => {
-                ErrorCategory::Network
-            }
+            Error::TcpConnect { .. }
+            | Error::TlsConnect { .. }
+            | Error::CertificatePinMismatch { .. }
+            | Error::Socks5Connect { .. }
+            | Error::HttpRequest { .. }
+            | Error::SmtpRequest { .. } => ErrorCategory::Network,
 
             Error::ConnectTimeout { .. }
             | Error::AuthTimeout { .. }
             | Error::SelectTimeout { .. }
             | Error::UidFetchTimeout { .. }
             | Error::FetchTimeout { .. }
+            | Error::CommandTimeout { .. }
             | Error::WaitTimeout { .. }
+            | Error::HttpTimeout { .. }
             | Error::LogoutTimeout { .. } => ErrorCategory::Timeout,
 
             Error::ImapLogin { .. }
@@ -297,11 +508,16 @@ impl Error {
             | Error::ImapSearch { .. }
             | Error::ImapFetch { .. }
             | Error::FetchMessage { .. }
-            | Error::ImapLogout { .. } => ErrorCategory::Protocol,
+            | Error::ImapCommand { .. }
+            | Error::ImapLogout { .. }
+            | Error::HttpStatus { .. }
+            | Error::EwsResponse { .. }
+            | Error::MalformedResponse { .. }
+            | Error::SmtpReply { .. } => ErrorCategory::Protocol,
 
             Error::ParseEmail { .. } | Error::ExtractBody { .. } => ErrorCategory::Parse,
 
-            Error::NoMatch => ErrorCategory::NotFound,
+            Error::NoMatch | Error::ScanLimitReached { .. } => ErrorCategory::NotFound,
         }
     }
 }
@@ -358,12 +574,33 @@ mod tests {
         // Wait timeout is not retryable (we already waited)
         let err = Error::WaitTimeout {
             timeout: Duration::from_secs(30),
+            report: WaitReport {
+                polls: 15,
+                messages_scanned: 3,
+                ..WaitReport::default()
+            },
         };
         assert!(!err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "timeout waiting for matching email after 30s (15 polls, 3 emails scanned)"
+        );
+
+        // A pinned key mismatch will not go away by retrying
+        let err = Error::CertificatePinMismatch {
+            host: "imap.example.com".into(),
+        };
+        assert!(!err.is_retryable());
+        assert_eq!(err.category(), ErrorCategory::Network);
 
         // NoMatch is not retryable
         let err = Error::NoMatch;
         assert!(!err.is_retryable());
+
+        // Using a consumed guard is a programming error, never retryable
+        let err = Error::GuardConsumed;
+        assert!(!err.is_retryable());
+        assert_eq!(err.category(), ErrorCategory::Configuration);
     }
 
     #[test]
@@ -381,5 +618,40 @@ mod tests {
 
         let err = Error::NoMatch;
         assert_eq!(err.category(), ErrorCategory::NotFound);
+
+        let err = Error::ScanLimitReached {
+            messages: 200,
+            bytes: 4096,
+        };
+        assert!(!err.is_retryable());
+        assert_eq!(err.category(), ErrorCategory::NotFound);
+    }
+
+    #[test]
+    fn test_http_status_retryable() {
+        let status = |status| Error::HttpStatus {
+            url: "https://mail.corp.example/EWS/Exchange.asmx".into(),
+            status,
+        };
+        assert!(status(503).is_retryable());
+        assert!(status(429).is_retryable());
+        assert!(!status(401).is_retryable());
+        assert_eq!(status(401).category(), ErrorCategory::Protocol);
+
+        let ews = |code: &str| Error::EwsResponse {
+            operation: "FindItem".into(),
+            code: code.into(),
+            message: String::new(),
+        };
+        assert!(ews("ErrorServerBusy").is_retryable());
+        assert!(!ews("ErrorAccessDenied").is_retryable());
+
+        let smtp = |code| Error::SmtpReply {
+            command: "RCPT TO".into(),
+            code,
+            message: String::new(),
+        };
+        assert!(smtp(451).is_retryable());
+        assert!(!smtp(550).is_retryable());
     }
 }
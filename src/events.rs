@@ -0,0 +1,500 @@
+//! Event hooks for observing the client.
+//!
+//! Register an [`EventHook`] with
+//! [`ImapConfigBuilder::event_hook`](crate::ImapConfigBuilder::event_hook) to
+//! receive an [`Event`] for every search, fetch, parse and match step, and for
+//! every match found. This is the place to feed latency histograms, e.g. for an
+//! SLO on the time from email arrival to match.
+//!
+//! A lighter [`ProgressHook`], registered with
+//! [`ImapConfigBuilder::on_progress`](crate::ImapConfigBuilder::on_progress), is
+//! called once per poll of a wait with a [`WaitProgress`], e.g. to drive a
+//! heartbeat indicator in interactive tools.
+//!
+//! [`JsonEventSink`] is a ready-made hook writing every event as one JSON line
+//! (NDJSON), e.g. to feed a SIEM or log shipper.
+//!
+//! Hooks run inline on the polling task, so they should return quickly.
+//!
+//! # Example
+//!
+//! ```
+//! use email_sync::events::Event;
+//! use email_sync::ImapConfig;
+//!
+//! let config = ImapConfig::builder()
+//!     .email("user@gmail.com")
+//!     .password("app-password")
+//!     .event_hook(|event: &Event| match event {
+//!         Event::Operation { operation, latency } => {
+//!             println!("{operation} took {latency:?}");
+//!         }
+//!         Event::Matched { since_arrival, .. } => {
+//!             println!("matched {since_arrival:?} after arrival");
+//!         }
+//!         _ => {}
+//!     })
+//!     .build()?;
+//! # Ok::<(), email_sync::Error>(())
+//! ```
+
+use crate::connection::ConnectStats;
+use crate::error::{Error, ErrorCategory};
+use chrono::{SecondsFormat, Utc};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// A step of checking for new emails, timed by [`Event::Operation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Searching the mailbox for new UIDs.
+    Search,
+    /// Fetching message contents.
+    Fetch,
+    /// Parsing a message into body text.
+    Parse,
+    /// Running the matchers over a message body.
+    Match,
+}
+
+impl Operation {
+    /// Returns the lowercase name of the operation, suitable as a metric label.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Search => "search",
+            Self::Fetch => "fetch",
+            Self::Parse => "parse",
+            Self::Match => "match",
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Something that happened in the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// An operation completed (successfully or not) after `latency`.
+    Operation {
+        /// The operation performed.
+        operation: Operation,
+        /// How long the operation took.
+        latency: Duration,
+    },
+    /// A matcher fired on an email.
+    Matched {
+        /// UID of the matched email.
+        uid: u32,
+        /// Index of the matcher that fired.
+        matcher_index: usize,
+        /// The extracted value, redacted as set with
+        /// [`ImapConfigBuilder::redact_matches`](crate::ImapConfigBuilder::redact_matches)
+        /// (masked by default).
+        value: String,
+        /// Time from the server receiving the email (its INTERNALDATE) to the
+        /// match, if the server reported the date. Subject to clock skew
+        /// between the server and this host.
+        since_arrival: Option<Duration>,
+    },
+    /// A connection (or reconnection) to the server was established, the
+    /// mailbox selected.
+    Connected {
+        /// Timings of the connection phases.
+        stats: ConnectStats,
+    },
+    /// A wait polled the mailbox (also reported to the
+    /// [`ProgressHook`]).
+    Polled {
+        /// Progress of the wait.
+        progress: WaitProgress,
+    },
+    /// Connecting or a poll of a wait failed. Retryable failures of a wait are
+    /// followed by a reconnect.
+    Failed {
+        /// Category of the error.
+        category: ErrorCategory,
+        /// Whether the error is transient.
+        retryable: bool,
+        /// The error message.
+        message: String,
+    },
+}
+
+impl Event {
+    /// Creates an [`Event::Failed`] describing `error`.
+    pub(crate) fn failed(error: &Error) -> Self {
+        Self::Failed {
+            category: error.category(),
+            retryable: error.is_retryable(),
+            message: error.to_string(),
+        }
+    }
+
+    /// Returns the event as a single-line JSON object, without timestamp.
+    fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        match self {
+            Self::Operation { operation, latency } => {
+                let _ = write!(
+                    json,
+                    r#""event":"operation","operation":"{operation}","latency_ms":{}"#,
+                    latency.as_millis()
+                );
+            }
+            Self::Matched {
+                uid,
+                matcher_index,
+                value,
+                since_arrival,
+            } => {
+                let _ = write!(
+                    json,
+                    r#""event":"matched","uid":{uid},"matcher_index":{matcher_index},"value":"#
+                );
+                push_json_string(&mut json, value);
+                if let Some(since_arrival) = since_arrival {
+                    let _ = write!(json, r#","since_arrival_ms":{}"#, since_arrival.as_millis());
+                }
+            }
+            Self::Connected { stats } => {
+                let _ = write!(
+                    json,
+                    r#""event":"connected","total_ms":{},"dns_ms":{},"tcp_ms":{},"tls_ms":{},"auth_ms":{},"select_ms":{}"#,
+                    stats.total_ms(),
+                    stats.dns_ms,
+                    stats.tcp_ms,
+                    stats.tls_ms,
+                    stats.auth_ms,
+                    stats.select_ms
+                );
+                if let Some(endpoint) = stats.endpoint {
+                    let _ = write!(json, r#","endpoint":"{endpoint}""#);
+                }
+            }
+            Self::Polled { progress } => {
+                let _ = write!(
+                    json,
+                    r#""event":"polled","polls":{},"elapsed_ms":{},"last_uid":{}"#,
+                    progress.polls,
+                    progress.elapsed.as_millis(),
+                    progress.last_uid
+                );
+            }
+            Self::Failed {
+                category,
+                retryable,
+                message,
+            } => {
+                let _ = write!(
+                    json,
+                    r#""event":"failed","category":"{category}","retryable":{retryable},"message":"#
+                );
+                push_json_string(&mut json, message);
+            }
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// Appends `value` to `json` as a quoted, escaped JSON string.
+fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Receives client [`Event`]s.
+///
+/// Implemented for closures taking `&Event`.
+pub trait EventHook: Send + Sync {
+    /// Called for every event.
+    fn on_event(&self, event: &Event);
+}
+
+impl<F> EventHook for F
+where
+    F: Fn(&Event) + Send + Sync,
+{
+    fn on_event(&self, event: &Event) {
+        self(event);
+    }
+}
+
+impl std::fmt::Debug for dyn EventHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventHook")
+    }
+}
+
+/// An [`EventHook`] writing each event as one line of JSON (NDJSON).
+///
+/// Every line holds a UTC timestamp (`ts`), the event name (`event`: one of
+/// `operation`, `matched`, `connected`, `polled` and `failed`), an optional
+/// `source` label, and the fields of the event; durations are in milliseconds
+/// (`latency_ms`, `since_arrival_ms`, ...). Write errors are ignored, so a full
+/// disk never fails a wait.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::events::JsonEventSink;
+/// use email_sync::ImapConfig;
+///
+/// let config = ImapConfig::builder()
+///     .email("user@gmail.com")
+///     .password("app-password")
+///     .event_hook(JsonEventSink::stdout().source("signup-tests"))
+///     .build()?;
+/// # Ok::<(), email_sync::Error>(())
+/// ```
+pub struct JsonEventSink<W> {
+    writer: Mutex<W>,
+    source: Option<String>,
+}
+
+impl JsonEventSink<io::Stdout> {
+    /// Writes events to standard output.
+    #[must_use]
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl JsonEventSink<std::fs::File> {
+    /// Appends events to the file at `path`, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub fn append(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Self::new)
+    }
+}
+
+impl<W: Write> JsonEventSink<W> {
+    /// Writes events to `writer`, one line each, flushed after every event.
+    #[must_use]
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            source: None,
+        }
+    }
+
+    /// Adds `"source": source` to every line, e.g. the account or job name.
+    #[must_use]
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the line written for `event`, without line break.
+    fn line(&self, event: &Event) -> String {
+        let mut line = format!(
+            r#"{{"ts":"{}""#,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+        );
+        if let Some(source) = &self.source {
+            line.push_str(r#","source":"#);
+            push_json_string(&mut line, source);
+        }
+        line.push(',');
+        line.push_str(&event.to_json()[1..]);
+        line
+    }
+}
+
+impl<W: Write + Send> EventHook for JsonEventSink<W> {
+    fn on_event(&self, event: &Event) {
+        let mut line = self.line(event);
+        line.push('\n');
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.flush());
+    }
+}
+
+impl<W> std::fmt::Debug for JsonEventSink<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonEventSink")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Progress of a wait for a matching email, reported after each poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WaitProgress {
+    /// Time since the wait started.
+    pub elapsed: Duration,
+    /// Number of polls so far, this one included.
+    pub polls: u32,
+    /// Newest UID checked in the polled mailbox.
+    pub last_uid: u32,
+}
+
+/// Receives a [`WaitProgress`] after each poll of a wait.
+///
+/// Implemented for closures taking `&WaitProgress`.
+pub trait ProgressHook: Send + Sync {
+    /// Called after each poll.
+    fn on_progress(&self, progress: &WaitProgress);
+}
+
+impl<F> ProgressHook for F
+where
+    F: Fn(&WaitProgress) + Send + Sync,
+{
+    fn on_progress(&self, progress: &WaitProgress) {
+        self(progress);
+    }
+}
+
+impl std::fmt::Debug for dyn ProgressHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// Emits an [`Event::Operation`] for an operation started at `started`.
+pub(crate) fn record(hook: Option<&dyn EventHook>, operation: Operation, started: Instant) {
+    if let Some(hook) = hook {
+        hook.on_event(&Event::Operation {
+            operation,
+            latency: started.elapsed(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_closure_hook_receives_events() {
+        let seen = Mutex::new(Vec::new());
+        let hook = |event: &Event| seen.lock().unwrap().push(event.clone());
+
+        record(Some(&hook), Operation::Fetch, Instant::now());
+        record(None, Operation::Parse, Instant::now());
+
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(matches!(
+            seen[0],
+            Event::Operation {
+                operation: Operation::Fetch,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_closure_progress_hook() {
+        let polls = Mutex::new(Vec::new());
+        let hook: &dyn ProgressHook =
+            &|progress: &WaitProgress| polls.lock().unwrap().push(progress.polls);
+
+        for polls in 1..=2 {
+            hook.on_progress(&WaitProgress {
+                elapsed: Duration::ZERO,
+                polls,
+                last_uid: 42,
+            });
+        }
+        assert_eq!(polls.into_inner().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn test_json_event_sink() {
+        let sink = JsonEventSink::new(Vec::new()).source("ci \"1\"");
+        sink.on_event(&Event::Operation {
+            operation: Operation::Search,
+            latency: Duration::from_millis(12),
+        });
+        sink.on_event(&Event::Failed {
+            category: ErrorCategory::Network,
+            retryable: true,
+            message: "line\nbreak \u{1b}".into(),
+        });
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"{"ts":""#));
+        assert!(lines[0].ends_with(
+            r#"Z","source":"ci \"1\"","event":"operation","operation":"search","latency_ms":12}"#
+        ));
+        assert!(lines[1].ends_with(
+            r#""event":"failed","category":"network","retryable":true,"message":"line\nbreak \u001b"}"#
+        ));
+    }
+
+    #[test]
+    fn test_event_json() {
+        let matched = Event::Matched {
+            uid: 7,
+            matcher_index: 1,
+            value: "******".into(),
+            since_arrival: None,
+        };
+        assert_eq!(
+            matched.to_json(),
+            r#"{"event":"matched","uid":7,"matcher_index":1,"value":"******"}"#
+        );
+
+        let connected = Event::Connected {
+            stats: ConnectStats {
+                tcp_ms: 5,
+                auth_ms: 10,
+                ..ConnectStats::default()
+            },
+        };
+        assert_eq!(
+            connected.to_json(),
+            r#"{"event":"connected","total_ms":15,"dns_ms":0,"tcp_ms":5,"tls_ms":0,"auth_ms":10,"select_ms":0}"#
+        );
+    }
+
+    #[test]
+    fn test_operation_names() {
+        assert_eq!(Operation::Search.to_string(), "search");
+        assert_eq!(Operation::Match.as_str(), "match");
+    }
+}
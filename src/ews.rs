@@ -0,0 +1,766 @@
+//! Exchange Web Services (EWS) backend.
+//!
+//! On-premises Exchange 2016/2019 deployments often have IMAP disabled and
+//! only EWS enabled. [`EwsClient`] offers the core wait/find operations of
+//! [`ImapEmailClient`](crate::ImapEmailClient) over EWS SOAP calls on the Inbox:
+//! new emails are listed with `FindItem`, their MIME content is downloaded with
+//! `GetItem` and matched exactly like emails fetched over IMAP. As a
+//! [`MailBackend`], items are keyed by their EWS item ID.
+//!
+//! The client takes an [`ImapConfig`] for the account and the shared settings
+//! (TLS, proxy, timeouts, polling, text normalization); the IMAP host and port
+//! are not used. With [`AuthMethod::Ntlm`] requests are authenticated with
+//! NTLM, as `DOMAIN\user` when [`ImapConfigBuilder::ntlm_domain`] is set;
+//! otherwise HTTP Basic authentication with the email address is used.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::ews::EwsClient;
+//! use email_sync::matcher::OtpMatcher;
+//! use email_sync::{AuthMethod, ImapConfig};
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let config = ImapConfig::builder()
+//!     .email("jdoe@corp.example")
+//!     .password("secret")
+//!     .auth_method(AuthMethod::Ntlm)
+//!     .ntlm_domain("CORP")
+//!     .build()?;
+//!
+//! let mut client = EwsClient::connect(config, "https://mail.corp.example/EWS/Exchange.asmx").await?;
+//! let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+//! println!("Got code: {code}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ImapConfigBuilder::ntlm_domain`]: crate::ImapConfigBuilder::ntlm_domain
+
+use crate::backend::{self, MailBackend, RawMessage};
+use crate::config::{AuthMethod, ImapConfig};
+use crate::error::{Error, Result};
+use crate::http::{HttpConnection, Response, Url};
+use crate::matcher::Matcher;
+use crate::ntlm::{self, NtlmAuthenticator};
+use crate::parser::{self, Extractor};
+use crate::runtime;
+use async_imap::Authenticator;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::time::Duration;
+use tracing::{debug, info, instrument, warn};
+
+/// Largest number of items listed by one `FindItem` call.
+const PAGE_SIZE: usize = 100;
+
+/// Start of every request: SOAP envelope and the requested schema version.
+const SOAP_HEADER: &str = concat!(
+    r#"<?xml version="1.0" encoding="utf-8"?>"#,
+    r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/""#,
+    r#" xmlns:t="http://schemas.microsoft.com/exchange/services/2006/types""#,
+    r#" xmlns:m="http://schemas.microsoft.com/exchange/services/2006/messages">"#,
+    r#"<soap:Header><t:RequestServerVersion Version="Exchange2013"/></soap:Header>"#,
+    "<soap:Body>",
+);
+
+/// End of every request.
+const SOAP_FOOTER: &str = "</soap:Body></soap:Envelope>";
+
+/// Client waiting for and searching emails over Exchange Web Services.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct EwsClient {
+    config: ImapConfig,
+    url: Url,
+    connection: Option<HttpConnection>,
+    /// NTLM authenticates the connection rather than each request.
+    authenticated: bool,
+    /// Server time of the last response (`Date` header).
+    server_time: Option<DateTime<Utc>>,
+    /// Emails received before this time are not considered new.
+    baseline: DateTime<Utc>,
+    /// Items already checked by a wait.
+    seen: HashSet<String>,
+}
+
+impl EwsClient {
+    /// Connects to the EWS `endpoint` (e.g.
+    /// `https://mail.example.com/EWS/Exchange.asmx`) and checks the credentials.
+    ///
+    /// Only emails received after connecting are considered by the wait methods.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `endpoint` is not an `https` URL ([`Error::InvalidConfig`])
+    /// - The connection fails
+    /// - The credentials are rejected ([`Error::HttpStatus`] with status `401`)
+    #[instrument(
+        name = "EwsClient::connect",
+        skip_all,
+        fields(email = %config.email(), endpoint = %endpoint)
+    )]
+    pub async fn connect(config: ImapConfig, endpoint: &str) -> Result<Self> {
+        let mut client = Self {
+            url: Url::parse(endpoint)?,
+            config,
+            connection: None,
+            authenticated: false,
+            server_time: None,
+            baseline: Utc::now(),
+            seen: HashSet::new(),
+        };
+        client.reset_baseline().await?;
+
+        info!("Connected to EWS");
+        Ok(client)
+    }
+
+    /// Waits for a new email matching the provided pattern.
+    ///
+    /// Polls the Inbox at the configured interval until an email received after
+    /// connecting matches, or [`PollingConfig::max_wait`](crate::PollingConfig::max_wait)
+    /// elapses. Transient errors are retried until then.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - EWS requests fail with a permanent error
+    #[instrument(
+        name = "EwsClient::wait_for_match",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
+        self.wait_for_any(&[matcher]).await.map(|(_, value)| value)
+    }
+
+    /// Waits for a new email matching any of the provided patterns.
+    ///
+    /// Returns the index of the matcher that fired (into `matchers`) together
+    /// with the extracted value. When several matchers match the same email, the
+    /// one listed first wins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - EWS requests fail with a permanent error
+    #[instrument(
+        name = "EwsClient::wait_for_any",
+        skip(self, matchers),
+        fields(matchers = matchers.len())
+    )]
+    pub async fn wait_for_any(&mut self, matchers: &[&dyn Matcher]) -> Result<(usize, String)> {
+        let polling = self.config.polling.clone();
+        let extract = Extractor::new(&self.config);
+        backend::wait_for_any(self, matchers, &polling, extract).await
+    }
+
+    /// Finds a matching email among those received within `max_age`, newest
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching email is found.
+    #[instrument(
+        name = "EwsClient::find_recent_match",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<String> {
+        let extract = Extractor::new(&self.config);
+        backend::find_recent_match(self, matcher, max_age, extract).await
+    }
+
+    /// Treats every email received so far as old, so waits only match emails
+    /// arriving from now on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request for the server time fails.
+    pub async fn reset_baseline(&mut self) -> Result<()> {
+        self.find_items(None, 1).await?;
+        self.baseline = self.now();
+        self.seen.clear();
+        debug!(baseline = %self.baseline, "EWS baseline set");
+        Ok(())
+    }
+
+    /// Returns the EWS endpoint URL.
+    #[must_use]
+    pub fn endpoint(&self) -> String {
+        self.url.to_string()
+    }
+
+    /// Returns the client configuration.
+    #[must_use]
+    pub fn config(&self) -> &ImapConfig {
+        &self.config
+    }
+
+    /// Returns the IDs of Inbox items received since `since`, newest first.
+    async fn find_items(
+        &mut self,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let timeout = self.config.timeouts.uid_fetch;
+        let xml = self
+            .call("FindItem", &find_item_request(since, limit), timeout)
+            .await?;
+
+        let content = response_messages(&xml, "FindItem")
+            .next()
+            .unwrap_or_else(|| Err(malformed("FindItem")))?;
+        Ok(elements(content, "ItemId")
+            .filter_map(|item| attribute(item.attributes, "Id"))
+            .map(Cow::into_owned)
+            .collect())
+    }
+
+    /// Sends the SOAP request `body` of `operation` and returns the response.
+    ///
+    /// A connection found closed by the server is reopened once.
+    async fn call(&mut self, operation: &str, body: &str, timeout: Duration) -> Result<String> {
+        let envelope = format!("{SOAP_HEADER}{body}{SOAP_FOOTER}");
+
+        let response = loop {
+            let reused = self.connection.is_some();
+            if !reused {
+                self.open().await?;
+            }
+
+            match runtime::timeout(timeout, self.exchange(envelope.as_bytes())).await {
+                Ok(Ok(response)) => break response,
+                // Servers close idle keep-alive connections
+                Ok(Err(e)) if reused => {
+                    debug!(error = %e, "EWS connection closed, reconnecting");
+                    self.connection = None;
+                }
+                Ok(Err(source)) => {
+                    self.connection = None;
+                    return Err(Error::HttpRequest {
+                        url: self.url.to_string(),
+                        source,
+                    });
+                }
+                Err(runtime::Elapsed) => {
+                    self.connection = None;
+                    return Err(Error::HttpTimeout {
+                        url: self.url.to_string(),
+                        timeout,
+                    });
+                }
+            }
+        };
+
+        if !response.keep_alive() {
+            self.connection = None;
+        }
+        if let Some(date) = response.header("Date") {
+            self.server_time = DateTime::parse_from_rfc2822(date)
+                .ok()
+                .map(|date| date.with_timezone(&Utc));
+        }
+
+        let xml = String::from_utf8_lossy(&response.body).into_owned();
+        match response.status {
+            200 => Ok(xml),
+            // SOAP faults come with a server error status
+            500 => Err(
+                soap_fault(operation, &xml).unwrap_or_else(|| Error::HttpStatus {
+                    url: self.url.to_string(),
+                    status: 500,
+                }),
+            ),
+            status => Err(Error::HttpStatus {
+                url: self.url.to_string(),
+                status,
+            }),
+        }
+    }
+
+    /// Opens a new connection to the endpoint.
+    async fn open(&mut self) -> Result<()> {
+        let timeout = self.config.timeouts.connect;
+        let connecting =
+            HttpConnection::open(&self.url, &self.config.tls, self.config.proxy.as_ref());
+        let connection =
+            runtime::timeout(timeout, connecting)
+                .await
+                .map_err(|_| Error::ConnectTimeout {
+                    target: format!("{}:{}", self.url.host, self.url.port),
+                    timeout,
+                })??;
+
+        debug!("EWS connection established");
+        self.connection = Some(connection);
+        self.authenticated = false;
+        Ok(())
+    }
+
+    /// Posts `envelope` on the open connection, authenticating as configured.
+    async fn exchange(&mut self, envelope: &[u8]) -> std::io::Result<Response> {
+        const CONTENT_TYPE: (&str, &str) = ("Content-Type", "text/xml; charset=utf-8");
+
+        let Some(connection) = self.connection.as_mut() else {
+            return Err(std::io::ErrorKind::NotConnected.into());
+        };
+        let (email, password) = (self.config.email(), self.config.password());
+
+        if self.config.auth != AuthMethod::Ntlm {
+            let credentials = STANDARD.encode(format!("{email}:{password}"));
+            let authorization = format!("Basic {credentials}");
+            let headers = [CONTENT_TYPE, ("Authorization", &authorization)];
+            return connection
+                .request("POST", &self.url, &headers, envelope)
+                .await;
+        }
+        if self.authenticated {
+            return connection
+                .request("POST", &self.url, &[CONTENT_TYPE], envelope)
+                .await;
+        }
+
+        // NTLM authenticates the connection in two round trips
+        let (user, domain) = ntlm::identity(email, self.config.ntlm_domain.as_deref());
+        let mut authenticator = NtlmAuthenticator::new(user, domain, password);
+        let negotiate = format!("NTLM {}", STANDARD.encode(authenticator.process(&[])));
+        let headers = [CONTENT_TYPE, ("Authorization", &negotiate)];
+        let response = connection
+            .request("POST", &self.url, &headers, envelope)
+            .await?;
+
+        let Some(challenge) = ntlm_challenge(&response) else {
+            return Ok(response);
+        };
+        let answer = format!(
+            "NTLM {}",
+            STANDARD.encode(authenticator.process(&challenge))
+        );
+        let headers = [CONTENT_TYPE, ("Authorization", &answer)];
+        let response = connection
+            .request("POST", &self.url, &headers, envelope)
+            .await?;
+
+        self.authenticated = response.status != 401;
+        Ok(response)
+    }
+
+    /// Returns the current server time, or the local time before any response.
+    fn now(&self) -> DateTime<Utc> {
+        self.server_time.unwrap_or_else(Utc::now)
+    }
+}
+
+impl MailBackend for EwsClient {
+    /// Drops the connection; the next request opens a new one.
+    fn connect(&mut self) -> BoxFuture<'_, Result<()>> {
+        self.connection = None;
+        Box::pin(async { Ok(()) })
+    }
+
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { backend::select_inbox("the EWS backend", mailbox) })
+    }
+
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut ids = self.find_items(Some(self.baseline), PAGE_SIZE).await?;
+            ids.retain(|id| self.seen.insert(id.clone()));
+            ids.reverse();
+            Ok(ids)
+        })
+    }
+
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let since = chrono::Duration::from_std(max_age)
+                .ok()
+                .and_then(|max_age| self.now().checked_sub_signed(max_age));
+            self.find_items(since, PAGE_SIZE).await
+        })
+    }
+
+    /// Downloads the MIME content of the items `keys`,
+    /// [`ImapConfig::scan_concurrency`] per `GetItem` request.
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+        Box::pin(async move {
+            let timeout = self.config.timeouts.message_fetch;
+            let mut messages = Vec::with_capacity(keys.len());
+            for batch in keys.chunks(self.config.scan_concurrency.max(1)) {
+                let xml = self
+                    .call("GetItem", &get_item_request(batch), timeout)
+                    .await?;
+                messages.extend(mime_contents(&xml));
+            }
+            Ok(messages)
+        })
+    }
+}
+
+/// Returns the NTLM CHALLENGE message of a `401` response, if any.
+fn ntlm_challenge(response: &Response) -> Option<Vec<u8>> {
+    if response.status != 401 {
+        return None;
+    }
+    response
+        .headers("WWW-Authenticate")
+        .find_map(|value| value.strip_prefix("NTLM "))
+        .and_then(|challenge| STANDARD.decode(challenge.trim()).ok())
+}
+
+/// Builds a `FindItem` request listing Inbox items received since `since`,
+/// newest first.
+fn find_item_request(since: Option<DateTime<Utc>>, limit: usize) -> String {
+    let restriction = since.map_or(String::new(), |since| {
+        format!(
+            concat!(
+                "<m:Restriction><t:IsGreaterThanOrEqualTo>",
+                r#"<t:FieldURI FieldURI="item:DateTimeReceived"/>"#,
+                r#"<t:FieldURIOrConstant><t:Constant Value="{}"/></t:FieldURIOrConstant>"#,
+                "</t:IsGreaterThanOrEqualTo></m:Restriction>",
+            ),
+            since.format("%Y-%m-%dT%H:%M:%SZ")
+        )
+    });
+
+    format!(
+        concat!(
+            r#"<m:FindItem Traversal="Shallow">"#,
+            "<m:ItemShape><t:BaseShape>IdOnly</t:BaseShape></m:ItemShape>",
+            r#"<m:IndexedPageItemView MaxEntriesReturned="{limit}" Offset="0" BasePoint="Beginning"/>"#,
+            "{restriction}",
+            r#"<m:SortOrder><t:FieldOrder Order="Descending">"#,
+            r#"<t:FieldURI FieldURI="item:DateTimeReceived"/></t:FieldOrder></m:SortOrder>"#,
+            r#"<m:ParentFolderIds><t:DistinguishedFolderId Id="inbox"/></m:ParentFolderIds>"#,
+            "</m:FindItem>",
+        ),
+        limit = limit,
+        restriction = restriction
+    )
+}
+
+/// Builds a `GetItem` request for the MIME content of `ids`.
+fn get_item_request(ids: &[String]) -> String {
+    let ids = ids.iter().fold(String::new(), |mut ids, id| {
+        let _ = write!(ids, r#"<t:ItemId Id="{}"/>"#, escape(id));
+        ids
+    });
+    format!(
+        concat!(
+            "<m:GetItem><m:ItemShape><t:BaseShape>IdOnly</t:BaseShape>",
+            "<t:IncludeMimeContent>true</t:IncludeMimeContent></m:ItemShape>",
+            "<m:ItemIds>{}</m:ItemIds></m:GetItem>",
+        ),
+        ids
+    )
+}
+
+/// Returns the decoded MIME content of each item in a `GetItem` response, with
+/// its ID. Items that could not be retrieved (e.g. deleted meanwhile) are
+/// logged and skipped.
+fn mime_contents(xml: &str) -> Vec<(String, Vec<u8>)> {
+    response_messages(xml, "GetItem")
+        .filter_map(|message| {
+            let content = message
+                .inspect_err(|e| warn!(error = %e, "Failed to get EWS item, skipping"))
+                .ok()?;
+            let id =
+                elements(content, "ItemId").find_map(|item| attribute(item.attributes, "Id"))?;
+            let mime = elements(content, "MimeContent").next()?.content;
+            let mime: String = mime.split_whitespace().collect();
+            match STANDARD.decode(mime) {
+                Ok(mime) => Some((id.into_owned(), mime)),
+                Err(e) => {
+                    warn!(error = %e, "Malformed EWS MIME content, skipping");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Returns the content of each `<operation>ResponseMessage` of a response, or
+/// the error it reports.
+fn response_messages<'a>(
+    xml: &'a str,
+    operation: &'a str,
+) -> impl Iterator<Item = Result<&'a str>> + 'a {
+    let name = format!("{operation}ResponseMessage");
+    elements(xml, &name).map(move |message| {
+        if attribute(message.attributes, "ResponseClass").as_deref() != Some("Error") {
+            return Ok(message.content);
+        }
+        Err(Error::EwsResponse {
+            operation: operation.to_string(),
+            code: text(message.content, "ResponseCode").unwrap_or_default(),
+            message: text(message.content, "MessageText").unwrap_or_default(),
+        })
+    })
+}
+
+/// Returns the error described by a SOAP fault, if `xml` is one.
+fn soap_fault(operation: &str, xml: &str) -> Option<Error> {
+    let fault = elements(xml, "Fault").next()?.content;
+    Some(Error::EwsResponse {
+        operation: operation.to_string(),
+        code: text(fault, "ResponseCode")
+            .or_else(|| text(fault, "faultcode"))
+            .unwrap_or_default(),
+        message: text(fault, "faultstring").unwrap_or_default(),
+    })
+}
+
+/// Error for a response that is not what the operation returns.
+fn malformed(operation: &str) -> Error {
+    Error::EwsResponse {
+        operation: operation.to_string(),
+        code: "MalformedResponse".into(),
+        message: "response has no response message".into(),
+    }
+}
+
+/// An XML element found by [`elements`].
+#[derive(Debug, Clone, Copy)]
+struct Element<'a> {
+    /// Raw attributes of the start tag.
+    attributes: &'a str,
+    /// Raw content between the start and end tags.
+    content: &'a str,
+}
+
+/// Finds the elements with local name `name` (any namespace prefix) in `xml`,
+/// not nested in each other.
+///
+/// EWS responses are machine-generated; this scanner does not handle comments,
+/// CDATA sections or `>` inside attribute values.
+fn elements<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = Element<'a>> {
+    let name = name.to_string();
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        rest = &rest[rest.find('<')? + 1..];
+        let tag_len = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?;
+        let tag = &rest[..tag_len];
+        if tag.rsplit(':').next() != Some(name.as_str()) {
+            continue;
+        }
+
+        let end = rest.find('>')?;
+        let head = &rest[tag_len..end];
+        let body = &rest[end + 1..];
+        if let Some(attributes) = head.strip_suffix('/') {
+            rest = body;
+            return Some(Element {
+                attributes,
+                content: "",
+            });
+        }
+
+        let close = format!("</{tag}>");
+        let Some(len) = body.find(&close) else {
+            rest = body;
+            continue;
+        };
+        rest = &body[len + close.len()..];
+        return Some(Element {
+            attributes: head,
+            content: &body[..len],
+        });
+    })
+}
+
+/// Returns the unescaped value of attribute `name` in `attributes`.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<Cow<'a, str>> {
+    let pattern = format!("{name}=\"");
+    attributes.match_indices(&pattern).find_map(|(at, _)| {
+        if !attributes[..at].ends_with(char::is_whitespace) {
+            return None;
+        }
+        let value = &attributes[at + pattern.len()..];
+        let value = &value[..value.find('"')?];
+        Some(parser::decode_html_entities(value))
+    })
+}
+
+/// Returns the unescaped, trimmed text of the first element `name` in `xml`.
+fn text(xml: &str, name: &str) -> Option<String> {
+    let element = elements(xml, name).next()?;
+    Some(parser::decode_html_entities(element.content.trim()).into_owned())
+}
+
+/// Escapes `value` for use in an attribute.
+fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::OtpMatcher;
+    use crate::parser::ExtractResult;
+
+    const FIND_ITEM_RESPONSE: &str = concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#,
+        r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body>"#,
+        r#"<m:FindItemResponse><m:ResponseMessages>"#,
+        r#"<m:FindItemResponseMessage ResponseClass="Success">"#,
+        "<m:ResponseCode>NoError</m:ResponseCode>",
+        r#"<m:RootFolder TotalItemsInView="2" IncludesLastItemInRange="true"><t:Items>"#,
+        r#"<t:Message><t:ItemId Id="AAMk2=" ChangeKey="CQAA"/></t:Message>"#,
+        r#"<t:Message><t:ItemId Id="AAMk1=" ChangeKey="CQAB"/></t:Message>"#,
+        "</t:Items></m:RootFolder></m:FindItemResponseMessage>",
+        "</m:ResponseMessages></m:FindItemResponse></s:Body></s:Envelope>",
+    );
+
+    #[test]
+    fn test_find_item_response() {
+        let content = response_messages(FIND_ITEM_RESPONSE, "FindItem")
+            .next()
+            .unwrap()
+            .unwrap();
+        let ids: Vec<_> = elements(content, "ItemId")
+            .filter_map(|item| attribute(item.attributes, "Id"))
+            .collect();
+        assert_eq!(ids, ["AAMk2=", "AAMk1="]);
+    }
+
+    #[test]
+    fn test_get_item_response() {
+        let mime = STANDARD.encode("Subject: Code\r\n\r\nYour code is 123456\r\n");
+        let xml = format!(
+            concat!(
+                "<m:ResponseMessages>",
+                r#"<m:GetItemResponseMessage ResponseClass="Success"><m:ResponseCode>NoError</m:ResponseCode>"#,
+                r#"<m:Items><t:Message><t:MimeContent CharacterSet="UTF-8">{}</t:MimeContent>"#,
+                r#"<t:ItemId Id="AAMk&amp;1" ChangeKey="CQAA"/></t:Message></m:Items>"#,
+                "</m:GetItemResponseMessage>",
+                r#"<m:GetItemResponseMessage ResponseClass="Error">"#,
+                "<m:MessageText>The specified object was not found in the store.</m:MessageText>",
+                "<m:ResponseCode>ErrorItemNotFound</m:ResponseCode><m:Items/>",
+                "</m:GetItemResponseMessage></m:ResponseMessages>",
+            ),
+            mime
+        );
+
+        let items = mime_contents(&xml);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].0, "AAMk&1");
+
+        let mut extract = Extractor::default();
+        let matcher = OtpMatcher::six_digit();
+        match extract.extract_raw_match(&items[0].1, &[&matcher]) {
+            ExtractResult::Match { details, .. } => assert_eq!(details.value, "123456"),
+            other => panic!("expected a match, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_error_response() {
+        let xml = concat!(
+            r#"<m:FindItemResponseMessage ResponseClass="Error">"#,
+            "<m:MessageText>Access is denied. Check credentials &amp; try again.</m:MessageText>",
+            "<m:ResponseCode>ErrorAccessDenied</m:ResponseCode>",
+            "</m:FindItemResponseMessage>",
+        );
+        let error = response_messages(xml, "FindItem")
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "EWS FindItem failed with ErrorAccessDenied: Access is denied. Check credentials & try again."
+        );
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_soap_fault() {
+        let xml = concat!(
+            "<s:Envelope><s:Body><s:Fault>",
+            "<faultcode>a:ErrorServerBusy</faultcode>",
+            "<faultstring>The server cannot service this request right now.</faultstring>",
+            "<detail><e:ResponseCode>ErrorServerBusy</e:ResponseCode></detail>",
+            "</s:Fault></s:Body></s:Envelope>",
+        );
+        let error = soap_fault("GetItem", xml).unwrap();
+        assert!(error.is_retryable());
+        assert!(soap_fault("GetItem", FIND_ITEM_RESPONSE).is_none());
+    }
+
+    #[test]
+    fn test_requests() {
+        let since = DateTime::parse_from_rfc3339("2024-05-01T12:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let request = find_item_request(Some(since), 100);
+        assert!(request.contains(r#"MaxEntriesReturned="100""#));
+        assert!(request.contains(r#"<t:Constant Value="2024-05-01T12:30:00Z"/>"#));
+        assert!(!find_item_request(None, 1).contains("Restriction"));
+
+        let request = get_item_request(&["AAMk\"1".to_string(), "AAMk2".to_string()]);
+        assert!(request.contains(r#"<t:ItemId Id="AAMk&quot;1"/><t:ItemId Id="AAMk2"/>"#));
+    }
+
+    #[test]
+    fn test_elements() {
+        let xml =
+            r#"<a:Item Id="1"/><Item Id="2">two</Item><a:ItemId Id="x"/><b:Item>three</b:Item>"#;
+        let items: Vec<_> = elements(xml, "Item")
+            .map(|item| (attribute(item.attributes, "Id"), item.content))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (Some("1".into()), ""),
+                (Some("2".into()), "two"),
+                (None, "three")
+            ]
+        );
+
+        // Attribute names must match whole
+        assert_eq!(
+            attribute(r#" ParentId="p" Id="i""#, "Id").as_deref(),
+            Some("i")
+        );
+    }
+
+    #[test]
+    fn test_ntlm_challenge() {
+        let response = |status, authenticate: &str| Response {
+            status,
+            headers: vec![("WWW-Authenticate".into(), authenticate.into())],
+            body: Vec::new(),
+        };
+        let challenge = response(401, "NTLM TlRMTVNTUAAC");
+        assert_eq!(ntlm_challenge(&challenge).unwrap(), b"NTLMSSP\0\x02");
+        assert!(ntlm_challenge(&response(401, "Negotiate")).is_none());
+        assert!(ntlm_challenge(&response(200, "NTLM TlRMTVNTUAAC")).is_none());
+    }
+}
@@ -0,0 +1,358 @@
+//! Filters applied to emails before matching.
+//!
+//! A [`Filter`] is a list of predicates on the headers, arrival date and size of
+//! an email; an email passes when all of them hold. Filters are set per call with
+//! [`WaitOptions::filter`](crate::WaitOptions::filter), so matchers only see
+//! emails from the expected sender and can stay simple.
+//!
+//! # Example
+//!
+//! ```
+//! use email_sync::filter::Filter;
+//!
+//! // Codes from GitHub or GitLab, with "verification" in the subject
+//! let filter = Filter::new()
+//!     .from("github.com")
+//!     .or(Filter::new().from("gitlab.com"))
+//!     .subject_contains("verification")
+//!     .max_size(512 * 1024);
+//! ```
+
+use chrono::{DateTime, FixedOffset, Utc};
+use mailparse::{parse_headers, MailHeader, MailHeaderMap};
+use std::sync::Arc;
+
+/// The parts of an email that filters inspect.
+///
+/// Passed to predicates registered with [`Filter::custom`].
+pub struct EmailInfo<'a> {
+    headers: Vec<MailHeader<'a>>,
+    internal_date: Option<DateTime<FixedOffset>>,
+    size: Option<u32>,
+}
+
+impl<'a> EmailInfo<'a> {
+    /// Parses the headers at the start of `raw` (a full message or its header section).
+    ///
+    /// Returns `None` when the headers cannot be parsed.
+    pub(crate) fn parse(
+        raw: &'a [u8],
+        internal_date: Option<DateTime<FixedOffset>>,
+        size: Option<u32>,
+    ) -> Option<Self> {
+        let (headers, _) = parse_headers(raw).ok()?;
+        Some(Self {
+            headers,
+            internal_date,
+            size,
+        })
+    }
+
+    /// Returns the decoded value of the first header called `name` (case-insensitive).
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.headers.get_first_value(name)
+    }
+
+    /// Returns the decoded subject.
+    #[must_use]
+    pub fn subject(&self) -> Option<String> {
+        self.header("Subject")
+    }
+
+    /// Returns the sender addresses from the `From` header.
+    #[must_use]
+    pub fn from(&self) -> Vec<String> {
+        self.addresses(&["From"])
+    }
+
+    /// Returns the recipient addresses from the `To`, `Cc`, `Delivered-To` and
+    /// `X-Original-To` headers.
+    #[must_use]
+    pub fn recipients(&self) -> Vec<String> {
+        self.addresses(&crate::parser::RECIPIENT_HEADERS)
+    }
+
+    /// Returns when the email arrived: its INTERNALDATE, or the `Date` header when
+    /// the server did not report one.
+    #[must_use]
+    pub fn date(&self) -> Option<DateTime<Utc>> {
+        self.internal_date
+            .map(|date| date.with_timezone(&Utc))
+            .or_else(|| {
+                let timestamp = mailparse::dateparse(&self.header("Date")?).ok()?;
+                DateTime::from_timestamp(timestamp, 0)
+            })
+    }
+
+    /// Returns the size of the whole email in bytes (`RFC822.SIZE`), if known.
+    #[must_use]
+    pub fn size(&self) -> Option<u32> {
+        self.size
+    }
+
+    fn addresses(&self, names: &[&str]) -> Vec<String> {
+        crate::parser::header_addresses(&self.headers, names)
+    }
+}
+
+impl std::fmt::Debug for EmailInfo<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailInfo")
+            .field("headers", &self.headers.len())
+            .field("internal_date", &self.internal_date)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+type Predicate = Arc<dyn Fn(&EmailInfo<'_>) -> bool + Send + Sync>;
+
+/// A single condition of a [`Filter`].
+#[derive(Clone)]
+enum Rule {
+    From(String),
+    Subject(String),
+    To(String),
+    Since(DateTime<Utc>),
+    Before(DateTime<Utc>),
+    MaxSize(u32),
+    Header { name: String, value: Option<String> },
+    Custom(Predicate),
+    Any(Vec<Filter>),
+}
+
+impl Rule {
+    fn accepts(&self, email: &EmailInfo<'_>) -> bool {
+        match self {
+            Self::From(needle) => email.from().iter().any(|addr| contains(addr, needle)),
+            Self::Subject(needle) => email
+                .subject()
+                .is_some_and(|subject| contains(&subject, needle)),
+            Self::To(address) => email
+                .recipients()
+                .iter()
+                .any(|addr| addr.eq_ignore_ascii_case(address)),
+            // Emails without a known date are kept
+            Self::Since(since) => email.date().is_none_or(|date| date >= *since),
+            Self::Before(before) => email.date().is_none_or(|date| date < *before),
+            Self::MaxSize(max) => email.size().is_none_or(|size| size <= *max),
+            Self::Header { name, value } => email.header(name).is_some_and(|actual| {
+                value
+                    .as_deref()
+                    .is_none_or(|needle| contains(&actual, needle))
+            }),
+            Self::Custom(predicate) => predicate(email),
+            Self::Any(filters) => filters.iter().any(|filter| filter.accepts(email)),
+        }
+    }
+}
+
+impl std::fmt::Debug for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::From(needle) => f.debug_tuple("From").field(needle).finish(),
+            Self::Subject(needle) => f.debug_tuple("Subject").field(needle).finish(),
+            Self::To(address) => f.debug_tuple("To").field(address).finish(),
+            Self::Since(date) => f.debug_tuple("Since").field(date).finish(),
+            Self::Before(date) => f.debug_tuple("Before").field(date).finish(),
+            Self::MaxSize(max) => f.debug_tuple("MaxSize").field(max).finish(),
+            Self::Header { name, value } => f
+                .debug_struct("Header")
+                .field("name", name)
+                .field("value", value)
+                .finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+            Self::Any(filters) => f.debug_tuple("Any").field(filters).finish(),
+        }
+    }
+}
+
+/// Case-insensitive substring check.
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// A pipeline of predicates an email must pass before it is matched.
+///
+/// An empty filter accepts every email. Each builder method adds a predicate;
+/// all of them must hold. Use [`or`](Self::or) for alternatives.
+///
+/// Text comparisons are case-insensitive. Emails whose date or size is unknown
+/// pass the date and size predicates.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    rules: Vec<Rule>,
+}
+
+impl Filter {
+    /// Creates a filter that accepts every email.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires a sender address containing `sender`, e.g. `noreply@github.com`
+    /// or just the domain `github.com`.
+    #[must_use]
+    pub fn from(self, sender: impl Into<String>) -> Self {
+        self.rule(Rule::From(sender.into()))
+    }
+
+    /// Requires the subject to contain `text`.
+    #[must_use]
+    pub fn subject_contains(self, text: impl Into<String>) -> Self {
+        self.rule(Rule::Subject(text.into()))
+    }
+
+    /// Requires `address` among the recipients (`To`, `Cc`, `Delivered-To` or
+    /// `X-Original-To`).
+    #[must_use]
+    pub fn to(self, address: impl Into<String>) -> Self {
+        self.rule(Rule::To(address.into()))
+    }
+
+    /// Requires the email to have arrived at or after `date`.
+    #[must_use]
+    pub fn since(self, date: DateTime<Utc>) -> Self {
+        self.rule(Rule::Since(date))
+    }
+
+    /// Requires the email to have arrived before `date`.
+    #[must_use]
+    pub fn before(self, date: DateTime<Utc>) -> Self {
+        self.rule(Rule::Before(date))
+    }
+
+    /// Requires the email to be at most `bytes` large.
+    #[must_use]
+    pub fn max_size(self, bytes: u32) -> Self {
+        self.rule(Rule::MaxSize(bytes))
+    }
+
+    /// Requires a header called `name` whose value contains `value`.
+    #[must_use]
+    pub fn header(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.rule(Rule::Header {
+            name: name.into(),
+            value: Some(value.into()),
+        })
+    }
+
+    /// Requires a header called `name`, whatever its value.
+    #[must_use]
+    pub fn has_header(self, name: impl Into<String>) -> Self {
+        self.rule(Rule::Header {
+            name: name.into(),
+            value: None,
+        })
+    }
+
+    /// Requires `predicate` to return `true`.
+    #[must_use]
+    pub fn custom<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&EmailInfo<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.rule(Rule::Custom(Arc::new(predicate)))
+    }
+
+    /// Accepts emails passing either this filter or `other`.
+    ///
+    /// Predicates added afterwards apply to both alternatives.
+    #[must_use]
+    pub fn or(self, other: Filter) -> Self {
+        Self {
+            rules: vec![Rule::Any(vec![self, other])],
+        }
+    }
+
+    /// Returns `true` if `email` passes every predicate.
+    #[must_use]
+    pub fn accepts(&self, email: &EmailInfo<'_>) -> bool {
+        self.rules.iter().all(|rule| rule.accepts(email))
+    }
+
+    /// Returns `true` if the filter has no predicates.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns `true` if a fetched message passes the filter.
+    ///
+    /// Messages whose headers cannot be parsed are rejected.
+    pub(crate) fn accepts_message(&self, message: &async_imap::types::Fetch) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(raw) = crate::parser::raw_headers(message) else {
+            return false;
+        };
+        EmailInfo::parse(&raw, message.internal_date(), message.size)
+            .is_some_and(|email| self.accepts(&email))
+    }
+
+    fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"From: GitHub <noreply@GitHub.com>\r\n\
+        To: user+run42@example.com\r\n\
+        Subject: =?UTF-8?Q?Your_verification_code?=\r\n\
+        Date: Tue, 1 Jul 2025 10:00:00 +0000\r\n\
+        X-Mailer: Mailer 1.0\r\n\r\nbody";
+
+    fn email() -> EmailInfo<'static> {
+        EmailInfo::parse(RAW, None, Some(2048)).unwrap()
+    }
+
+    #[test]
+    fn test_empty_filter_accepts_all() {
+        assert!(Filter::new().accepts(&email()));
+    }
+
+    #[test]
+    fn test_header_predicates() {
+        let email = email();
+        assert!(Filter::new()
+            .from("github.com")
+            .subject_contains("VERIFICATION")
+            .to("USER+run42@example.com")
+            .header("x-mailer", "mailer")
+            .accepts(&email));
+        assert!(!Filter::new().from("gitlab.com").accepts(&email));
+        assert!(!Filter::new().has_header("List-Unsubscribe").accepts(&email));
+    }
+
+    #[test]
+    fn test_date_and_size_predicates() {
+        let email = email();
+        let date = DateTime::parse_from_rfc3339("2025-07-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(email.date(), Some(date));
+        assert!(Filter::new().since(date).accepts(&email));
+        assert!(!Filter::new().before(date).accepts(&email));
+        assert!(Filter::new().max_size(4096).accepts(&email));
+        assert!(!Filter::new().max_size(1024).accepts(&email));
+    }
+
+    #[test]
+    fn test_or_and_custom() {
+        let email = email();
+        let filter = Filter::new()
+            .from("gitlab.com")
+            .or(Filter::new().from("github.com"));
+        assert!(filter.accepts(&email));
+        assert!(!filter
+            .custom(|email| email.size().is_none())
+            .accepts(&email));
+    }
+}
@@ -0,0 +1,184 @@
+//! GSSAPI (Kerberos) authentication support.
+//!
+//! The crate speaks the SASL `GSSAPI` mechanism (RFC 4752) over IMAP, but does not
+//! link a Kerberos library itself: the security context comes from a
+//! [`GssapiProvider`] you supply, typically a thin wrapper around `libgssapi` or
+//! `cross-krb5` using the ticket cache of the logged-in user. No password is needed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::gssapi::{GssapiProvider, SecurityContext};
+//! use email_sync::{AuthMethod, ImapConfig};
+//! use std::sync::Arc;
+//!
+//! #[derive(Debug)]
+//! struct Kerberos;
+//!
+//! impl GssapiProvider for Kerberos {
+//!     fn start(
+//!         &self,
+//!         service: &str,
+//!     ) -> Result<Box<dyn SecurityContext>, Box<dyn std::error::Error + Send + Sync>> {
+//!         // Acquire default credentials and start a context for `service`
+//!         // ("imap@host") with your Kerberos library of choice
+//!         # unimplemented!()
+//!     }
+//! }
+//!
+//! let config = ImapConfig::builder()
+//!     .email("jdoe@corp.example.com")
+//!     .imap_host("imap.corp.example.com")
+//!     .auth_method(AuthMethod::Gssapi)
+//!     .gssapi_provider(Arc::new(Kerberos))
+//!     .build()
+//!     .expect("valid config");
+//! ```
+
+use tracing::warn;
+
+/// Error returned by [`GssapiProvider`] and [`SecurityContext`] implementations.
+pub type GssapiError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Creates Kerberos security contexts, one per connection.
+pub trait GssapiProvider: std::fmt::Debug + Send + Sync {
+    /// Starts a client security context for the service principal `service`
+    /// (`imap@host`, in GSSAPI host-based service form).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no credentials are available (e.g. no ticket).
+    fn start(&self, service: &str) -> Result<Box<dyn SecurityContext>, GssapiError>;
+}
+
+/// A client security context being established with the server.
+pub trait SecurityContext: Send {
+    /// Processes a token from the server (empty for the first call) and returns the
+    /// next token to send, which may be empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server token is rejected.
+    fn step(&mut self, token: &[u8]) -> Result<Vec<u8>, GssapiError>;
+
+    /// Returns `true` once the context is established.
+    fn is_complete(&self) -> bool;
+
+    /// Unwraps (verifies and decodes) a message from the server.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message fails verification.
+    fn unwrap(&mut self, message: &[u8]) -> Result<Vec<u8>, GssapiError>;
+
+    /// Wraps a message for the server (integrity protection is enough).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message cannot be wrapped.
+    fn wrap(&mut self, message: &[u8]) -> Result<Vec<u8>, GssapiError>;
+}
+
+/// Security layer bit for "no security layer" (RFC 4752, section 3.3).
+const NO_SECURITY_LAYER: u8 = 0x01;
+
+/// SASL `GSSAPI` authenticator for [`async_imap::Client::authenticate`].
+///
+/// `async_imap` authenticators cannot fail, so errors are logged and answered with
+/// an empty response, which makes the server abort the exchange.
+pub(crate) struct GssapiAuthenticator {
+    context: Box<dyn SecurityContext>,
+    /// Authorization identity requested after the handshake (empty for none).
+    authzid: String,
+}
+
+impl GssapiAuthenticator {
+    pub(crate) fn new(context: Box<dyn SecurityContext>, authzid: String) -> Self {
+        Self { context, authzid }
+    }
+
+    fn respond(&mut self, challenge: &[u8]) -> Result<Vec<u8>, GssapiError> {
+        if !self.context.is_complete() {
+            return self.context.step(challenge);
+        }
+
+        // Final round: the server offers security layers; take none
+        let offer = self.context.unwrap(challenge)?;
+        if offer
+            .first()
+            .is_none_or(|layers| layers & NO_SECURITY_LAYER == 0)
+        {
+            return Err("server requires a GSSAPI security layer".into());
+        }
+
+        let mut reply = vec![NO_SECURITY_LAYER, 0, 0, 0];
+        reply.extend_from_slice(self.authzid.as_bytes());
+        self.context.wrap(&reply)
+    }
+}
+
+impl async_imap::Authenticator for GssapiAuthenticator {
+    type Response = Vec<u8>;
+
+    fn process(&mut self, challenge: &[u8]) -> Self::Response {
+        self.respond(challenge).unwrap_or_else(|e| {
+            warn!(error = %e, "GSSAPI authentication step failed");
+            Vec::new()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_imap::Authenticator;
+
+    /// Context that completes after one server token and "wraps" by prefixing `w:`.
+    struct FakeContext {
+        steps: usize,
+    }
+
+    impl SecurityContext for FakeContext {
+        fn step(&mut self, token: &[u8]) -> Result<Vec<u8>, GssapiError> {
+            self.steps += 1;
+            Ok(if token.is_empty() {
+                b"initial".to_vec()
+            } else {
+                Vec::new()
+            })
+        }
+
+        fn is_complete(&self) -> bool {
+            self.steps == 2
+        }
+
+        fn unwrap(&mut self, message: &[u8]) -> Result<Vec<u8>, GssapiError> {
+            message
+                .strip_prefix(b"w:")
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| "not wrapped".into())
+        }
+
+        fn wrap(&mut self, message: &[u8]) -> Result<Vec<u8>, GssapiError> {
+            Ok([b"w:".as_slice(), message].concat())
+        }
+    }
+
+    #[test]
+    fn test_gssapi_exchange() {
+        let mut auth = GssapiAuthenticator::new(Box::new(FakeContext { steps: 0 }), String::new());
+
+        assert_eq!(auth.process(b""), b"initial");
+        assert_eq!(auth.process(b"server-token"), b"");
+        // Server offers "no security layer" with a 64 KiB max buffer
+        assert_eq!(auth.process(b"w:\x01\x01\x00\x00"), b"w:\x01\x00\x00\x00");
+    }
+
+    #[test]
+    fn test_gssapi_requires_no_security_layer_offer() {
+        let mut auth = GssapiAuthenticator::new(Box::new(FakeContext { steps: 2 }), String::new());
+
+        // Only integrity protection offered: not supported, abort
+        assert_eq!(auth.process(b"w:\x02\x01\x00\x00"), b"");
+    }
+}
@@ -0,0 +1,283 @@
+//! Cloneable handle for sharing one IMAP connection between tasks.
+//!
+//! [`ImapEmailClient`] methods take `&mut self`, so a client cannot be used from
+//! two tasks at once. [`ImapEmailClientHandle`] moves the client into a background
+//! task and forwards requests to it over a channel; handles are cheap to clone and
+//! can be passed to any number of tasks.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::{ImapConfig, ImapEmailClient};
+//! use email_sync::matcher::{OtpMatcher, UrlMatcher};
+//! use std::time::Duration;
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! # let config = ImapConfig::builder().email("a@b.c").password("x").build()?;
+//! let handle = ImapEmailClient::connect(config).await?.into_handle();
+//!
+//! let otp = {
+//!     let handle = handle.clone();
+//!     tokio::spawn(async move { handle.wait_for_match(OtpMatcher::six_digit()).await })
+//! };
+//! let link = handle
+//!     .find_recent_match(UrlMatcher::new("example.com"), Duration::from_secs(300))
+//!     .await;
+//!
+//! handle.logout().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::ImapEmailClient;
+use crate::error::{Error, Result};
+use crate::matcher::Matcher;
+use crate::runtime;
+use crate::shutdown::TaskGuard;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+/// Number of requests that can be queued before callers wait for the connection task.
+const REQUEST_QUEUE_CAPACITY: usize = 32;
+
+/// A request forwarded to the connection task.
+enum Request {
+    WaitForMatch {
+        matcher: Box<dyn Matcher>,
+        reply: oneshot::Sender<Result<String>>,
+    },
+    FindRecentMatch {
+        matcher: Box<dyn Matcher>,
+        max_age: Duration,
+        reply: oneshot::Sender<Result<String>>,
+    },
+    Logout {
+        reply: oneshot::Sender<Result<()>>,
+    },
+}
+
+/// Cloneable handle to an [`ImapEmailClient`] running in a background task.
+///
+/// Created by [`ImapEmailClient::into_handle`]. Requests from all handles are
+/// executed one at a time, in the order they arrive, on the single underlying
+/// connection — a long [`wait_for_match`](Self::wait_for_match) delays requests
+/// queued behind it.
+///
+/// The connection task logs out and stops when [`logout`](Self::logout) is called,
+/// when the last handle is dropped, or on [`Shutdown::shutdown`](crate::Shutdown::shutdown)
+/// when registered with [`ImapConfigBuilder::shutdown`](crate::ImapConfigBuilder::shutdown).
+/// With [`ImapConfigBuilder::keepalive`](crate::ImapConfigBuilder::keepalive) set,
+/// it sends a NOOP whenever the connection has been idle for the configured interval.
+#[derive(Clone)]
+pub struct ImapEmailClientHandle {
+    sender: mpsc::Sender<Request>,
+    email: String,
+}
+
+impl ImapEmailClientHandle {
+    /// Moves `client` into a background task and returns a handle to it.
+    pub(crate) fn spawn(client: ImapEmailClient) -> Self {
+        let (sender, receiver) = mpsc::channel(REQUEST_QUEUE_CAPACITY);
+        let email = client.email().to_string();
+
+        runtime::spawn(run(client, receiver));
+
+        Self { sender, email }
+    }
+
+    /// Waits for an email matching the provided pattern.
+    ///
+    /// See [`ImapEmailClient::wait_for_match`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ClientClosed`] if the connection task has stopped, or any
+    /// error returned by [`ImapEmailClient::wait_for_match`].
+    pub async fn wait_for_match(&self, matcher: impl Matcher + 'static) -> Result<String> {
+        self.request(|reply| Request::WaitForMatch {
+            matcher: Box::new(matcher),
+            reply,
+        })
+        .await
+    }
+
+    /// Finds a matching email among recent messages.
+    ///
+    /// See [`ImapEmailClient::find_recent_match`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ClientClosed`] if the connection task has stopped, or any
+    /// error returned by [`ImapEmailClient::find_recent_match`].
+    pub async fn find_recent_match(
+        &self,
+        matcher: impl Matcher + 'static,
+        max_age: Duration,
+    ) -> Result<String> {
+        self.request(|reply| Request::FindRecentMatch {
+            matcher: Box::new(matcher),
+            max_age,
+            reply,
+        })
+        .await
+    }
+
+    /// Logs out and stops the connection task.
+    ///
+    /// Requests queued before the logout are processed first; requests sent afterwards
+    /// by other handles fail with [`Error::ClientClosed`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ClientClosed`] if the connection task has already stopped,
+    /// or an error if the logout command fails.
+    pub async fn logout(&self) -> Result<()> {
+        self.request(|reply| Request::Logout { reply }).await
+    }
+
+    /// Returns the email address used for this connection.
+    #[must_use]
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    /// Returns `true` if the connection task has stopped.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Sends a request and waits for its reply.
+    async fn request<T>(
+        &self,
+        make: impl FnOnce(oneshot::Sender<Result<T>>) -> Request,
+    ) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+
+        self.sender
+            .send(make(reply))
+            .await
+            .map_err(|_| Error::ClientClosed)?;
+
+        response.await.map_err(|_| Error::ClientClosed)?
+    }
+}
+
+impl std::fmt::Debug for ImapEmailClientHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImapEmailClientHandle")
+            .field("email", &self.email)
+            .field("closed", &self.is_closed())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Connection task: executes requests one at a time until logout, shutdown or all
+/// handles are dropped.
+async fn run(mut client: ImapEmailClient, mut receiver: mpsc::Receiver<Request>) {
+    let mut task = TaskGuard::new(client.config().shutdown.as_ref());
+
+    loop {
+        let next = task.until_stopped(next_request(&mut client, &mut receiver));
+        let Some(Some(request)) = next.await else {
+            break;
+        };
+
+        match request {
+            Request::WaitForMatch { matcher, reply } => {
+                let result = task.until_stopped(Box::pin(client.wait_for_match(matcher.as_ref())));
+                let Some(result) = result.await else {
+                    break;
+                };
+                let _ = reply.send(result);
+            }
+            Request::FindRecentMatch {
+                matcher,
+                max_age,
+                reply,
+            } => {
+                let result = task.until_stopped(Box::pin(
+                    client.find_recent_match(matcher.as_ref(), max_age),
+                ));
+                let Some(result) = result.await else {
+                    break;
+                };
+                let _ = reply.send(result);
+            }
+            Request::Logout { reply } => {
+                receiver.close();
+                let _ = reply.send(client.logout().await);
+                return;
+            }
+        }
+    }
+
+    // Shutdown, or all handles dropped without an explicit logout
+    receiver.close();
+    let logout_timeout = client.config().timeouts.logout;
+    match runtime::timeout(logout_timeout, client.logout()).await {
+        Ok(Ok(())) => debug!("Client handle task logged out"),
+        Ok(Err(e)) => warn!(error = %e, "Client handle task logout failed"),
+        Err(_) => warn!(
+            timeout_secs = logout_timeout.as_secs(),
+            "Client handle task logout timed out"
+        ),
+    }
+}
+
+/// Waits for the next request, or `None` once all handles are dropped.
+///
+/// While no request arrives for the configured keepalive interval, a NOOP is sent
+/// instead.
+async fn next_request(
+    client: &mut ImapEmailClient,
+    receiver: &mut mpsc::Receiver<Request>,
+) -> Option<Request> {
+    let Some(interval) = client.config().keepalive else {
+        return receiver.recv().await;
+    };
+
+    loop {
+        if let Ok(request) = runtime::timeout(interval, receiver.recv()).await {
+            return request;
+        }
+        match client.keepalive().await {
+            Ok(()) => debug!("Sent keepalive NOOP"),
+            Err(e) => warn!(error = %e, "Keepalive NOOP failed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::OtpMatcher;
+
+    fn closed_handle() -> ImapEmailClientHandle {
+        let (sender, receiver) = mpsc::channel(1);
+        drop(receiver);
+        ImapEmailClientHandle {
+            sender,
+            email: "user@example.com".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_closed_handle_returns_client_closed() {
+        let handle = closed_handle();
+        assert!(handle.is_closed());
+
+        let result = handle.wait_for_match(OtpMatcher::six_digit()).await;
+        assert!(matches!(result, Err(Error::ClientClosed)));
+
+        let result = handle.logout().await;
+        assert!(matches!(result, Err(Error::ClientClosed)));
+    }
+
+    #[test]
+    fn test_handle_debug() {
+        let debug_str = format!("{:?}", closed_handle());
+        assert!(debug_str.contains("user@example.com"));
+    }
+}
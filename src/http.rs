@@ -0,0 +1,357 @@
+//! Internal HTTP/1.1 client for web-service backends.
+//!
+//! Just enough HTTP for SOAP and JSON APIs: one request at a time over a
+//! persistent TLS connection established like the IMAP connection (same proxy
+//! and TLS settings), with `Content-Length` and chunked response bodies.
+//! Connection-oriented authentication (NTLM) relies on the connection staying
+//! open between requests.
+
+use crate::config::TlsOptions;
+use crate::connection::{self, ConnectStats, TlsStream};
+use crate::error::{Error, Result};
+use crate::proxy::Socks5Proxy;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+/// Largest response body accepted, to bound memory use.
+const MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest status line or header line accepted.
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+/// An `https` URL split into the parts needed for a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Url {
+    /// Host name, without port.
+    pub(crate) host: String,
+    /// TCP port (443 unless given).
+    pub(crate) port: u16,
+    /// Path and query, starting with `/`.
+    pub(crate) path: String,
+}
+
+impl Url {
+    /// Parses an `https://host[:port][/path]` URL.
+    pub(crate) fn parse(url: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidConfig {
+            message: format!("invalid URL '{url}': {reason}"),
+        };
+
+        let rest = url
+            .strip_prefix("https://")
+            .ok_or_else(|| invalid("only https URLs are supported"))?;
+        let (authority, path) = rest.find('/').map_or((rest, "/"), |at| rest.split_at(at));
+        if authority.contains('@') {
+            return Err(invalid("credentials in the URL are not supported"));
+        }
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid("invalid port"))?),
+            None => (authority, 443),
+        };
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Returns the `Host` header value.
+    fn authority(&self) -> String {
+        if self.port == 443 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "https://{}{}", self.authority(), self.path)
+    }
+}
+
+/// A response with its body read in full.
+#[derive(Debug)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    /// Returns the values of header `name` (case-insensitive), in order.
+    pub(crate) fn headers<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the first value of header `name`.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns `true` unless the server announced it closes the connection.
+    pub(crate) fn keep_alive(&self) -> bool {
+        !self
+            .headers("Connection")
+            .any(|value| value.eq_ignore_ascii_case("close"))
+    }
+}
+
+/// A persistent connection to one server.
+pub(crate) struct HttpConnection {
+    stream: BufReader<TlsStream>,
+}
+
+impl HttpConnection {
+    /// Opens a TLS connection to the host of `url`.
+    pub(crate) async fn open(
+        url: &Url,
+        tls: &TlsOptions,
+        proxy: Option<&Socks5Proxy>,
+    ) -> Result<Self> {
+        let target = format!("{}:{}", url.host, url.port);
+        let stream = connection::establish_tls_connection(
+            &url.host,
+            tls,
+            &target,
+            proxy,
+            &mut ConnectStats::default(),
+        )
+        .await?;
+        Ok(Self {
+            stream: BufReader::new(stream),
+        })
+    }
+
+    /// Sends a `method` request to `url` and reads the response.
+    ///
+    /// `headers` come in addition to `Host`, `Content-Length` and `Connection`.
+    pub(crate) async fn request(
+        &mut self,
+        method: &str,
+        url: &Url,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> io::Result<Response> {
+        let request = request_head(method, url, headers, body.len());
+        let stream = self.stream.get_mut();
+        stream.write_all(request.as_bytes()).await?;
+        stream.write_all(body).await?;
+        stream.flush().await?;
+
+        read_response(&mut self.stream).await
+    }
+}
+
+impl std::fmt::Debug for HttpConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpConnection").finish_non_exhaustive()
+    }
+}
+
+/// Builds the request line and headers.
+fn request_head(method: &str, url: &Url, headers: &[(&str, &str)], body_len: usize) -> String {
+    let mut head = format!(
+        "{method} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {body_len}\r\nConnection: keep-alive\r\n",
+        url.path,
+        url.authority()
+    );
+    for (name, value) in headers {
+        head.push_str(name);
+        head.push_str(": ");
+        head.push_str(value);
+        head.push_str("\r\n");
+    }
+    head.push_str("\r\n");
+    head
+}
+
+/// Reads a response: status line, headers and body.
+async fn read_response<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Response> {
+    let status_line = read_line(reader).await?;
+    let status = status_line
+        .strip_prefix("HTTP/1.")
+        .and_then(|rest| rest.get(2..5))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid_data(format!("malformed status line '{status_line}'")))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader).await?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data(format!("malformed header '{line}'")))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let mut response = Response {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+
+    let chunked = response
+        .header("Transfer-Encoding")
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+    if chunked {
+        response.body = read_chunked(reader).await?;
+    } else if let Some(length) = response.header("Content-Length") {
+        let length: usize = length
+            .parse()
+            .map_err(|_| invalid_data(format!("malformed Content-Length '{length}'")))?;
+        response.body = read_exact(reader, length).await?;
+    } else if status != 204 && status != 304 && !(100..200).contains(&status) {
+        // No framing: the body runs until the server closes the connection
+        let mut body = Vec::new();
+        reader
+            .take(MAX_BODY_SIZE as u64)
+            .read_to_end(&mut body)
+            .await?;
+        response.body = body;
+        response.headers.push(("Connection".into(), "close".into()));
+    }
+
+    Ok(response)
+}
+
+/// Reads a chunked body, discarding any trailers.
+async fn read_chunked<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(reader).await?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| invalid_data(format!("malformed chunk size '{line}'")))?;
+        if size == 0 {
+            while !read_line(reader).await?.is_empty() {}
+            return Ok(body);
+        }
+        // The size comes from the server; compare without overflowing
+        if size > MAX_BODY_SIZE - body.len() {
+            return Err(invalid_data("response body too large".into()));
+        }
+        body.extend_from_slice(&read_exact(reader, size).await?);
+        read_line(reader).await?;
+    }
+}
+
+/// Reads exactly `length` bytes.
+async fn read_exact<R: AsyncBufRead + Unpin>(reader: &mut R, length: usize) -> io::Result<Vec<u8>> {
+    if length > MAX_BODY_SIZE {
+        return Err(invalid_data("response body too large".into()));
+    }
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+/// Reads a CRLF-terminated line, without the line ending.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(MAX_LINE_LENGTH as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(invalid_data("line too long".into()));
+    }
+    let line = String::from_utf8_lossy(&line);
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = Url::parse("https://mail.corp.example/EWS/Exchange.asmx").unwrap();
+        assert_eq!(url.host, "mail.corp.example");
+        assert_eq!(url.port, 443);
+        assert_eq!(url.path, "/EWS/Exchange.asmx");
+        assert_eq!(
+            url.to_string(),
+            "https://mail.corp.example/EWS/Exchange.asmx"
+        );
+
+        let url = Url::parse("https://mail.corp.example:8443").unwrap();
+        assert_eq!((url.port, url.path.as_str()), (8443, "/"));
+        assert_eq!(url.authority(), "mail.corp.example:8443");
+
+        assert!(Url::parse("http://mail.corp.example/").is_err());
+        assert!(Url::parse("https://user:pw@mail.corp.example/").is_err());
+        assert!(Url::parse("https://:443/").is_err());
+        assert!(Url::parse("https://mail.corp.example:x/").is_err());
+    }
+
+    #[test]
+    fn test_request_head() {
+        let url = Url::parse("https://mail.corp.example/EWS/Exchange.asmx").unwrap();
+        let head = request_head("POST", &url, &[("Content-Type", "text/xml")], 12);
+        assert_eq!(
+            head,
+            "POST /EWS/Exchange.asmx HTTP/1.1\r\nHost: mail.corp.example\r\nContent-Length: 12\r\n\
+             Connection: keep-alive\r\nContent-Type: text/xml\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_response() {
+        let raw: &[u8] = b"HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Negotiate\r\n\
+            WWW-Authenticate: NTLM\r\nContent-Length: 5\r\n\r\nhello";
+        let response = read_response(&mut &*raw).await.unwrap();
+        assert_eq!(response.status, 401);
+        assert_eq!(
+            response.headers("www-authenticate").collect::<Vec<_>>(),
+            ["Negotiate", "NTLM"]
+        );
+        assert_eq!(response.body, b"hello");
+        assert!(response.keep_alive());
+    }
+
+    #[tokio::test]
+    async fn test_read_chunked_response() {
+        let raw: &[u8] =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+            4\r\nWiki\r\n6;ext=1\r\npedia \r\n0\r\nTrailer: x\r\n\r\n";
+        let response = read_response(&mut &*raw).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"Wikipedia ");
+        assert!(!response.keep_alive());
+    }
+
+    #[tokio::test]
+    async fn test_read_malformed_response() {
+        let raw: &[u8] = b"SMTP 220 ready\r\n\r\n";
+        assert!(read_response(&mut &*raw).await.is_err());
+
+        let truncated: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort";
+        assert!(read_response(&mut &*truncated).await.is_err());
+
+        let huge_chunk: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\nWiki\r\nffffffffffffffff\r\npedia\r\n0\r\n\r\n";
+        assert!(read_response(&mut &*huge_chunk).await.is_err());
+    }
+}
@@ -6,17 +6,44 @@
 //! - Connecting to IMAP servers (with optional SOCKS5 proxy support)
 //! - Waiting for emails matching specific patterns (OTP codes, activation links, etc.)
 //! - Finding recent emails matching patterns
+//! - Doing the same on a local Maildir ([`maildir::MaildirBackend`]) or mbox
+//!   file ([`mbox::MboxBackend`]), e.g. for offline tests
+//! - Running the wait/find operations over any transport implementing
+//!   [`MailBackend`], chosen at runtime with [`MailClient`]
+//! - Sharing a set of test accounts between parallel jobs with [`AccountPool`]
+//! - Parsing archived `.eml` files with [`parse_email`]
+//! - Regression-testing matchers on `.eml` fixtures with
+//!   [`testing::assert_matches_eml`]
 //!
 //! ## Features
 //!
 //! - **`observability`**: Enables OpenTelemetry integration for distributed tracing.
 //!   Without this feature, tracing spans are still emitted but require no OTEL dependencies.
+//! - **`native-tls`**: Uses the platform TLS stack (Schannel, Secure Transport, OpenSSL)
+//!   instead of rustls, for deployments bound to the system TLS policy.
+//! - **`ews`**: Adds [`ews::EwsClient`], waiting for and searching emails over
+//!   Exchange Web Services where IMAP is disabled.
+//! - **`smtp`**: Adds [`smtp::SmtpSender`], sending emails with the same account
+//!   and connection settings, for round-trip tests, and
+//!   [`ImapEmailClient::round_trip`], measuring delivery latency of a probe email.
+//! - **`tempmail`**: Adds [`tempmail::TempMailProvider`], provisioning disposable
+//!   mailboxes on mail.tm-compatible APIs for signup-flow tests.
+//! - **`blocking`**: Adds [`ImapEmailClient::shutdown_blocking`], logging out from
+//!   synchronous teardown code. Pulls in Tokio's multi-threaded runtime.
+//!
+//! ## Runtime
+//!
+//! The crate requires Tokio: connections, TLS and the SOCKS5 proxy are built on
+//! Tokio I/O, its channels and locks are Tokio's, and there is no async-std or
+//! smol support.
 //!
 //! ## Quick Start
 //!
+//! The [`prelude`] brings the client, configuration, common matchers and [`Result`]
+//! into scope with a single import:
+//!
 //! ```no_run
-//! use email_sync::{ImapConfig, ImapEmailClient};
-//! use email_sync::matcher::OtpMatcher;
+//! use email_sync::prelude::*;
 //!
 //! # async fn example() -> email_sync::Result<()> {
 //! // Configure the client
@@ -123,6 +150,8 @@
 //!
 //! - `ImapEmailClient::connect` - Client connection
 //! - `ImapEmailClient::wait_for_match` - Waiting for email
+//! - `ImapEmailClient::poll` - One poll of a wait, with the poll number and the
+//!   time left (`remaining_ms`); linked to the previous poll with `observability`
 //! - `ImapEmailClient::find_recent_match` - Finding recent email
 //! - `ImapEmailClient::logout` - Logout
 //! - `session::authenticate` - IMAP authentication
@@ -142,27 +171,75 @@
 #![warn(clippy::all)]
 #![warn(clippy::pedantic)]
 #![allow(clippy::module_name_repetitions)]
+#![allow(clippy::duration_suboptimal_units)]
 
 // Public modules
+pub mod backend;
+pub mod capability;
 pub mod config;
+pub mod email;
 pub mod error;
+pub mod events;
+#[cfg(feature = "ews")]
+pub mod ews;
+pub mod filter;
+#[cfg(feature = "gssapi")]
+pub mod gssapi;
 pub mod known_servers;
+pub mod mailbox;
+pub mod maildir;
 pub mod matcher;
+pub mod mbox;
+pub mod pool;
+pub mod prelude;
 pub mod proxy;
+pub mod shutdown;
+#[cfg(feature = "smtp")]
+pub mod smtp;
+#[cfg(feature = "tempmail")]
+pub mod tempmail;
+pub mod testing;
+pub mod watcher;
 
 // Internal modules
 mod client;
 mod connection;
+mod handle;
+#[cfg(any(feature = "ews", feature = "tempmail"))]
+mod http;
+#[cfg(feature = "ntlm")]
+mod ntlm;
 mod parser;
+#[cfg(feature = "qr")]
+mod qr;
+mod runtime;
 mod session;
 
 // Re-exports for ergonomic API
-pub use client::{ImapEmailClient, ImapEmailClientGuard};
-pub use config::{ImapConfig, ImapConfigBuilder, PollingConfig, TimeoutConfig};
+pub use backend::{MailBackend, MailClient};
+pub use capability::Capabilities;
+pub use client::{HealthStatus, ImapEmailClient, ImapEmailClientGuard, ScopedTimeouts};
+pub use config::{
+    AuthMethod, ClientId, ExpungeMode, FetchItems, ImapConfig, ImapConfigBuilder, Order,
+    PollingConfig, Redaction, RetryConfig, ScanLimits, SearchOptions, Sha256, TimeoutConfig,
+    TlsOptions, TlsVersion, WaitOptions,
+};
+pub use connection::ConnectStats;
+pub use email::{
+    AttachmentInfo, EmailMessage, GmailAttributes, MatchedEmail, MessageSummary, WaitReport,
+};
 pub use email_address::EmailAddress;
 pub use error::{Error, ErrorCategory, Result};
+pub use filter::Filter;
+pub use handle::ImapEmailClientHandle;
 pub use known_servers::ServerRegistry;
-pub use proxy::{ProxyAuth, Socks5Proxy};
+pub use mailbox::{MailboxStatus, Namespace, Quota, QuotaResource, QuotaResourceKind};
+pub use parser::parse_email;
+pub use pool::{AccountLease, AccountLimits, AccountPool, PooledClient};
+pub use proxy::{ProxyAuth, ProxyPool, RotationStrategy, Socks5Proxy};
+pub use secrecy::{ExposeSecret, SecretString};
+pub use shutdown::Shutdown;
+pub use watcher::{EmailWatcher, MatchEvent};
 
 #[cfg(test)]
 mod tests {
@@ -175,4 +252,13 @@ mod tests {
         let _ = Socks5Proxy::new("localhost", 1080);
         let _ = matcher::OtpMatcher::six_digit();
     }
+
+    #[test]
+    fn test_prelude_accessible() {
+        use crate::prelude::*;
+
+        let _ = ImapConfig::builder();
+        let _ = OtpMatcher::six_digit();
+        let _: Result<()> = Err(Error::NoMatch);
+    }
 }
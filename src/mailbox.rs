@@ -0,0 +1,300 @@
+//! Types describing mailboxes on the server.
+//!
+//! Returned by mailbox-level client methods such as
+//! [`ImapEmailClient::mailbox_status`](crate::ImapEmailClient::mailbox_status).
+
+/// Counters of a mailbox, as reported by the IMAP `STATUS` command.
+///
+/// Fields are `None` when the server omits them from its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct MailboxStatus {
+    /// Number of messages in the mailbox (`MESSAGES`).
+    pub messages: u32,
+    /// Number of messages without the `\Seen` flag (`UNSEEN`).
+    pub unseen: Option<u32>,
+    /// UID the next message added to the mailbox will get (`UIDNEXT`).
+    pub uid_next: Option<u32>,
+    /// UID validity of the mailbox (`UIDVALIDITY`); UIDs are only comparable
+    /// while it stays the same.
+    pub uid_validity: Option<u32>,
+}
+
+/// Personal namespace of the account: how mailbox names are laid out.
+///
+/// Some servers (Courier, older Cyrus) keep every folder under `INBOX.`, others
+/// at the top level. Obtained with
+/// [`ImapEmailClient::namespace`](crate::ImapEmailClient::namespace).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Namespace {
+    /// Prefix of personal mailbox names, e.g. `"INBOX."` or `""`.
+    pub prefix: String,
+    /// Hierarchy delimiter, e.g. `"."` or `"/"`; `None` for flat servers.
+    pub delimiter: Option<String>,
+}
+
+impl Namespace {
+    /// Infers the namespace from the hierarchy delimiter and the existing mailbox names.
+    ///
+    /// The prefix is `INBOX<delimiter>` when every mailbox other than INBOX lives
+    /// below INBOX, and empty otherwise.
+    pub(crate) fn infer(delimiter: Option<String>, mailboxes: &[String]) -> Self {
+        let prefix = delimiter
+            .as_deref()
+            .map(|delimiter| format!("INBOX{delimiter}"))
+            .filter(|prefix| {
+                let mut others = mailboxes
+                    .iter()
+                    .filter(|name| !name.eq_ignore_ascii_case("INBOX"))
+                    .peekable();
+                others.peek().is_some() && others.all(|name| starts_with_ignore_case(name, prefix))
+            })
+            .unwrap_or_default();
+
+        Self { prefix, delimiter }
+    }
+
+    /// Parses the personal namespace from an untagged RFC 2342 `NAMESPACE` response,
+    /// e.g. `* NAMESPACE (("INBOX." ".")) NIL NIL`.
+    ///
+    /// Returns `None` when the server has no personal namespace (`NIL`) or the line
+    /// is malformed.
+    pub(crate) fn parse_response(line: &str) -> Option<Self> {
+        const KEYWORD: &str = "* NAMESPACE ";
+
+        if !starts_with_ignore_case(line, KEYWORD) {
+            return None;
+        }
+        let rest = line[KEYWORD.len()..].strip_prefix("((")?;
+        let (prefix, rest) = quoted_string(rest)?;
+        let rest = rest.trim_start();
+        let delimiter = if starts_with_ignore_case(rest, "NIL") {
+            None
+        } else {
+            Some(quoted_string(rest)?.0)
+        };
+
+        Some(Self { prefix, delimiter })
+    }
+
+    /// Turns a user-provided mailbox name into the full name on the server.
+    ///
+    /// `INBOX` (any case) and names that already carry the prefix are returned
+    /// unchanged; other names get the prefix prepended, so `"Archive"` becomes
+    /// `"INBOX.Archive"` on servers that keep folders under INBOX.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use email_sync::mailbox::Namespace;
+    ///
+    /// let namespace = Namespace::default();
+    /// assert_eq!(namespace.resolve("Archive"), "Archive");
+    /// ```
+    #[must_use]
+    pub fn resolve(&self, name: &str) -> String {
+        if name.eq_ignore_ascii_case("INBOX") {
+            return "INBOX".to_string();
+        }
+        if self.prefix.is_empty() || starts_with_ignore_case(name, &self.prefix) {
+            return name.to_string();
+        }
+        format!("{}{name}", self.prefix)
+    }
+}
+
+/// Reads an IMAP quoted string from the start of `input`, returning the unescaped
+/// value and the remaining input.
+fn quoted_string(input: &str) -> Option<(String, &str)> {
+    let quoted = input.strip_prefix('"')?;
+    let mut chars = quoted.char_indices();
+    let mut value = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &quoted[index + 1..])),
+            '\\' => value.push(chars.next()?.1),
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+/// Case-insensitive (ASCII) `str::starts_with`.
+fn starts_with_ignore_case(value: &str, prefix: &str) -> bool {
+    value
+        .get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+/// Storage quota of a quota root, as reported by `GETQUOTAROOT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Quota {
+    /// Name of the quota root (often `""` for the whole account).
+    pub root: String,
+    /// Usage and limit of each resource under this root.
+    pub resources: Vec<QuotaResource>,
+}
+
+impl Quota {
+    /// Returns the highest usage percentage across all resources.
+    ///
+    /// Returns `0.0` when there are no limited resources.
+    #[must_use]
+    pub fn max_usage_percent(&self) -> f64 {
+        self.resources
+            .iter()
+            .map(QuotaResource::usage_percent)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Usage and limit of one quota resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct QuotaResource {
+    /// What is being limited.
+    pub kind: QuotaResourceKind,
+    /// Current usage (KiB for [`QuotaResourceKind::Storage`]).
+    pub usage: u64,
+    /// Limit (KiB for [`QuotaResourceKind::Storage`]).
+    pub limit: u64,
+}
+
+impl QuotaResource {
+    /// Returns the usage as a percentage of the limit.
+    ///
+    /// A limit of `0` is treated as unlimited and reports `0.0`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn usage_percent(&self) -> f64 {
+        if self.limit == 0 {
+            return 0.0;
+        }
+        self.usage as f64 * 100.0 / self.limit as f64
+    }
+}
+
+/// Kind of resource a quota limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuotaResourceKind {
+    /// Total size of all messages (`STORAGE`), in KiB.
+    Storage,
+    /// Number of messages (`MESSAGE`).
+    Messages,
+    /// A server-specific resource.
+    Other(String),
+}
+
+impl From<async_imap::types::QuotaResource> for QuotaResource {
+    fn from(resource: async_imap::types::QuotaResource) -> Self {
+        use async_imap::types::QuotaResourceName;
+
+        Self {
+            kind: match resource.name {
+                QuotaResourceName::Storage => QuotaResourceKind::Storage,
+                QuotaResourceName::Message => QuotaResourceKind::Messages,
+                QuotaResourceName::Atom(name) => QuotaResourceKind::Other(name),
+            },
+            usage: resource.usage,
+            limit: resource.limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_namespace_infer() {
+        let nested = Namespace::infer(
+            Some(".".into()),
+            &names(&["INBOX", "INBOX.Sent", "inbox.Archive"]),
+        );
+        assert_eq!(nested.prefix, "INBOX.");
+
+        let flat = Namespace::infer(Some("/".into()), &names(&["INBOX", "Sent", "Archive"]));
+        assert_eq!(flat.prefix, "");
+
+        let inbox_only = Namespace::infer(Some(".".into()), &names(&["INBOX"]));
+        assert_eq!(inbox_only.prefix, "");
+
+        let no_delimiter = Namespace::infer(None, &names(&["INBOX", "INBOXSent"]));
+        assert_eq!(no_delimiter.prefix, "");
+    }
+
+    #[test]
+    fn test_namespace_parse_response() {
+        let nested = Namespace::parse_response(r#"* NAMESPACE (("INBOX." ".")) NIL NIL"#);
+        assert_eq!(
+            nested,
+            Some(Namespace {
+                prefix: "INBOX.".into(),
+                delimiter: Some(".".into()),
+            })
+        );
+
+        let flat = Namespace::parse_response(
+            r##"* namespace (("" "/")("Other/" "/")) (("~" "/")) (("#shared/" "/"))"##,
+        )
+        .unwrap();
+        assert_eq!(flat.prefix, "");
+        assert_eq!(flat.delimiter.as_deref(), Some("/"));
+
+        let escaped = Namespace::parse_response(r#"* NAMESPACE (("a\\b" NIL)) NIL NIL"#).unwrap();
+        assert_eq!(escaped.prefix, r"a\b");
+        assert_eq!(escaped.delimiter, None);
+
+        assert_eq!(Namespace::parse_response("* NAMESPACE NIL NIL NIL"), None);
+        assert_eq!(Namespace::parse_response(r#"* NAMESPACE (("INBOX."#), None);
+        assert_eq!(Namespace::parse_response("* OK still here"), None);
+    }
+
+    #[test]
+    fn test_namespace_resolve() {
+        let namespace = Namespace {
+            prefix: "INBOX.".into(),
+            delimiter: Some(".".into()),
+        };
+
+        assert_eq!(namespace.resolve("Archive"), "INBOX.Archive");
+        assert_eq!(namespace.resolve("INBOX.Archive"), "INBOX.Archive");
+        assert_eq!(namespace.resolve("inbox"), "INBOX");
+        assert_eq!(Namespace::default().resolve("Archive"), "Archive");
+    }
+
+    #[test]
+    fn test_quota_usage_percent() {
+        let quota = Quota {
+            root: String::new(),
+            resources: vec![
+                QuotaResource {
+                    kind: QuotaResourceKind::Storage,
+                    usage: 512,
+                    limit: 1024,
+                },
+                QuotaResource {
+                    kind: QuotaResourceKind::Messages,
+                    usage: 90,
+                    limit: 100,
+                },
+                QuotaResource {
+                    kind: QuotaResourceKind::Other("X".into()),
+                    usage: 5,
+                    limit: 0,
+                },
+            ],
+        };
+
+        assert!((quota.resources[0].usage_percent() - 50.0).abs() < f64::EPSILON);
+        assert!((quota.resources[2].usage_percent()).abs() < f64::EPSILON);
+        assert!((quota.max_usage_percent() - 90.0).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,419 @@
+//! Local Maildir backend.
+//!
+//! [`MaildirBackend`] offers the core wait/find operations of
+//! [`ImapEmailClient`](crate::ImapEmailClient) over a Maildir directory on
+//! disk, as written by fetchmail, offlineimap, getmail or a local MDA. Waiting
+//! polls the `new/` and `cur/` subdirectories for message files that were not
+//! there before; no server or network access is involved, which makes it a
+//! good fit for offline integration tests.
+//!
+//! A message keeps its Maildir unique name when a mail reader moves it from
+//! `new/` to `cur/` or changes its flags, so it is only checked once. The
+//! backend implements [`MailBackend`] with these names as message keys.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::maildir::MaildirBackend;
+//! use email_sync::matcher::OtpMatcher;
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let mut maildir = MaildirBackend::open("/home/ci/Maildir").await?;
+//! let code = maildir.wait_for_match(&OtpMatcher::six_digit()).await?;
+//! println!("Got code: {code}");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::backend::{self, MailBackend, RawMessage};
+use crate::config::PollingConfig;
+use crate::error::{Error, Result};
+use crate::matcher::Matcher;
+use crate::parser::Extractor;
+use crate::runtime;
+use futures::future::BoxFuture;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, instrument};
+
+/// Subdirectories holding delivered messages.
+const MESSAGE_DIRS: [&str; 2] = ["new", "cur"];
+
+/// Mailbox in a local Maildir directory.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct MaildirBackend {
+    root: PathBuf,
+    polling: PollingConfig,
+    normalize_text: bool,
+    /// Unique names of the messages already present or checked.
+    seen: HashSet<String>,
+    /// Paths of the messages returned by the last searches, until fetched.
+    paths: HashMap<String, PathBuf>,
+}
+
+/// A message file in `new/` or `cur/`.
+#[derive(Debug)]
+struct Entry {
+    /// Maildir unique name: the file name without the `:2,` info suffix.
+    unique: String,
+    path: PathBuf,
+    modified: SystemTime,
+}
+
+impl MaildirBackend {
+    /// Opens the Maildir at `root`.
+    ///
+    /// Messages already present are treated as old: the wait methods only
+    /// match messages delivered afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`] if `root` has no `new/` and `cur/`
+    /// subdirectories, or [`Error::LocalMailbox`] if they cannot be read.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a Tokio runtime.
+    pub async fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        if let Some(missing) = MESSAGE_DIRS.iter().find(|dir| !root.join(dir).is_dir()) {
+            return Err(Error::InvalidConfig {
+                message: format!("{} is not a Maildir (no {missing}/)", root.display()),
+            });
+        }
+
+        let mut maildir = Self {
+            root,
+            polling: PollingConfig::default(),
+            normalize_text: false,
+            seen: HashSet::new(),
+            paths: HashMap::new(),
+        };
+        maildir.reset_baseline().await?;
+        Ok(maildir)
+    }
+
+    /// Sets the polling interval and maximum wait of the wait methods.
+    #[must_use]
+    pub fn polling(mut self, polling: PollingConfig) -> Self {
+        self.polling = polling;
+        self
+    }
+
+    /// Applies NFKC normalization and strips invisible characters before
+    /// matching, like [`ImapConfigBuilder::normalize_text`](crate::ImapConfigBuilder::normalize_text).
+    #[must_use]
+    pub fn normalize_text(mut self, enabled: bool) -> Self {
+        self.normalize_text = enabled;
+        self
+    }
+
+    /// Waits for a newly delivered message matching the provided pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - The Maildir cannot be read ([`Error::LocalMailbox`])
+    #[instrument(
+        name = "MaildirBackend::wait_for_match",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
+        self.wait_for_any(&[matcher]).await.map(|(_, value)| value)
+    }
+
+    /// Waits for a newly delivered message matching any of the provided
+    /// patterns.
+    ///
+    /// Returns the index of the matcher that fired (into `matchers`) together
+    /// with the extracted value. Messages are checked in delivery order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - The Maildir cannot be read ([`Error::LocalMailbox`])
+    #[instrument(
+        name = "MaildirBackend::wait_for_any",
+        skip(self, matchers),
+        fields(matchers = matchers.len())
+    )]
+    pub async fn wait_for_any(&mut self, matchers: &[&dyn Matcher]) -> Result<(usize, String)> {
+        let polling = self.polling.clone();
+        let extract = Extractor::local(self.normalize_text);
+        backend::wait_for_any(self, matchers, &polling, extract).await
+    }
+
+    /// Finds a matching message among those delivered within `max_age`,
+    /// newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching message is found, or
+    /// [`Error::LocalMailbox`] if the Maildir cannot be read.
+    #[instrument(
+        name = "MaildirBackend::find_recent_match",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<String> {
+        let extract = Extractor::local(self.normalize_text);
+        backend::find_recent_match(self, matcher, max_age, extract).await
+    }
+
+    /// Treats every message delivered so far as old, so waits only match
+    /// messages delivered from now on.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LocalMailbox`] if the Maildir cannot be read.
+    pub async fn reset_baseline(&mut self) -> Result<()> {
+        let entries = self.list().await?;
+        self.seen = entries.into_iter().map(|entry| entry.unique).collect();
+        debug!(messages = self.seen.len(), "Maildir baseline set");
+        Ok(())
+    }
+
+    /// Returns the path of the Maildir.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Remembers the paths of `entries` for [`MailBackend::fetch`] and returns
+    /// their keys.
+    fn remember(&mut self, entries: Vec<Entry>) -> Vec<String> {
+        entries
+            .into_iter()
+            .map(|entry| {
+                self.paths.insert(entry.unique.clone(), entry.path);
+                entry.unique
+            })
+            .collect()
+    }
+
+    /// Lists the messages in `new/` and `cur/`, oldest first.
+    async fn list(&self) -> Result<Vec<Entry>> {
+        let root = self.root.clone();
+        runtime::spawn_blocking(move || list_messages(&root))
+            .await
+            .map_err(|source| Error::LocalMailbox {
+                path: self.root.display().to_string(),
+                source,
+            })
+    }
+}
+
+impl MailBackend for MaildirBackend {
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { backend::select_inbox("a Maildir", mailbox) })
+    }
+
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut entries = self.list().await?;
+            entries.retain(|entry| self.seen.insert(entry.unique.clone()));
+            Ok(self.remember(entries))
+        })
+    }
+
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut entries = self.list().await?;
+            if let Some(since) = SystemTime::now().checked_sub(max_age) {
+                entries.retain(|entry| entry.modified >= since);
+            }
+            entries.reverse();
+            self.paths.clear();
+            Ok(self.remember(entries))
+        })
+    }
+
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+        Box::pin(async move {
+            let mut messages = Vec::with_capacity(keys.len());
+            for key in keys {
+                let Some(path) = self.paths.remove(key) else {
+                    continue;
+                };
+                let reading = path.clone();
+                match runtime::spawn_blocking(move || std::fs::read(reading)).await {
+                    Ok(raw) => messages.push((key.clone(), raw)),
+                    // Moved to cur/ or deleted by a mail reader since listing;
+                    // found again by the next search if it was moved
+                    Err(e) => {
+                        debug!(path = %path.display(), error = %e, "Skipping unreadable message");
+                        self.seen.remove(key);
+                    }
+                }
+            }
+            Ok(messages)
+        })
+    }
+}
+
+/// Lists the message files of the Maildir at `root`, oldest first.
+fn list_messages(root: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for dir in MESSAGE_DIRS {
+        for file in std::fs::read_dir(root.join(dir))? {
+            let file = file?;
+            let name = file.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            // Dot files are not messages (Maildir spec)
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(metadata) = file.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            entries.push(Entry {
+                unique: unique_name(name).to_string(),
+                path: file.path(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+    entries.sort_by(|a, b| {
+        a.modified
+            .cmp(&b.modified)
+            .then_with(|| a.unique.cmp(&b.unique))
+    });
+    Ok(entries)
+}
+
+/// Returns the unique name of a message file: the name up to the info
+/// separator (`:`, or `!` where colons are not allowed in file names).
+fn unique_name(file_name: &str) -> &str {
+    file_name.split([':', '!']).next().unwrap_or(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::OtpMatcher;
+    use std::fs;
+
+    /// Creates an empty Maildir in the temporary directory.
+    fn temp_maildir(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("email-sync-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["tmp", "new", "cur"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        root
+    }
+
+    /// Delivers a message like an MDA: written to `tmp/`, then moved.
+    fn deliver(root: &Path, dir: &str, name: &str, body: &str) {
+        let tmp = root.join("tmp").join(name);
+        fs::write(&tmp, format!("Subject: Test\r\n\r\n{body}\r\n")).unwrap();
+        fs::rename(tmp, root.join(dir).join(name)).unwrap();
+    }
+
+    fn fast_polling() -> PollingConfig {
+        PollingConfig {
+            interval: Duration::from_millis(10),
+            max_wait: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_unique_name() {
+        assert_eq!(
+            unique_name("1700000000.M1P2.host:2,S"),
+            "1700000000.M1P2.host"
+        );
+        assert_eq!(
+            unique_name("1700000000.M1P2.host!2,RS"),
+            "1700000000.M1P2.host"
+        );
+        assert_eq!(unique_name("1700000000.M1P2.host"), "1700000000.M1P2.host");
+    }
+
+    #[tokio::test]
+    async fn test_open_rejects_non_maildir() {
+        let root = std::env::temp_dir().join(format!("email-sync-{}-plain", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let result = MaildirBackend::open(&root).await;
+        assert!(matches!(result, Err(Error::InvalidConfig { .. })));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_new_message() {
+        let root = temp_maildir("wait");
+        deliver(&root, "cur", "1.old:2,S", "Old code 111111");
+
+        let mut maildir = MaildirBackend::open(&root)
+            .await
+            .unwrap()
+            .polling(fast_polling());
+        let delivery = {
+            let root = root.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                deliver(&root, "new", "2.new", "Your code is 222222");
+            })
+        };
+
+        let code = maildir
+            .wait_for_match(&OtpMatcher::six_digit())
+            .await
+            .unwrap();
+        assert_eq!(code, "222222");
+        delivery.await.unwrap();
+
+        // Moving the message to cur/ does not make it new again
+        fs::rename(root.join("new/2.new"), root.join("cur/2.new:2,S")).unwrap();
+        maildir.polling = PollingConfig {
+            interval: Duration::from_millis(10),
+            max_wait: Duration::from_millis(50),
+        };
+        let result = maildir.wait_for_match(&OtpMatcher::six_digit()).await;
+        assert!(matches!(result, Err(Error::WaitTimeout { .. })));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_find_recent_match() {
+        let root = temp_maildir("find");
+        deliver(&root, "cur", "1.a:2,S", "Your code is 333333");
+        deliver(&root, "cur", "2.b:2,", "No code here");
+
+        let mut maildir = MaildirBackend::open(&root).await.unwrap();
+        let code = maildir
+            .find_recent_match(&OtpMatcher::six_digit(), Duration::from_hours(1))
+            .await
+            .unwrap();
+        assert_eq!(code, "333333");
+
+        let result = maildir
+            .find_recent_match(&OtpMatcher::n_digit(8), Duration::from_hours(1))
+            .await;
+        assert!(matches!(result, Err(Error::NoMatch)));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
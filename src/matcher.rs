@@ -20,6 +20,173 @@
 
 use regex::Regex;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, LazyLock};
+use unicode_normalization::{is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+/// Invisible characters removed by [`normalize_text`]: zero-width space,
+/// non-joiner and joiner, word joiner, invisible operators, byte order mark,
+/// soft hyphen and Mongolian vowel separator.
+const INVISIBLE_CHARS: [char; 11] = [
+    '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{2061}', '\u{2062}', '\u{2063}', '\u{2064}',
+    '\u{FEFF}', '\u{00AD}', '\u{180E}',
+];
+
+/// Matches a single Unicode decimal digit (general category `Nd`).
+static DECIMAL_DIGIT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\p{Nd}$").expect("valid regex"));
+
+/// A value found by a [`Matcher`], with where it was found and how sure the
+/// matcher is about it.
+///
+/// Returned by [`Matcher::find_match_details`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct MatchDetails<'a> {
+    /// The extracted value.
+    pub value: Cow<'a, str>,
+    /// Byte offsets of the value in the searched text, if known.
+    pub span: Option<Range<usize>>,
+    /// Confidence score between 0.0 and 1.0, for matchers that can rate their matches.
+    pub confidence: Option<f32>,
+    /// The value as it appeared in the text, when the matcher normalized it.
+    pub original: Option<Cow<'a, str>>,
+}
+
+impl<'a> MatchDetails<'a> {
+    /// Creates details for `value`, without span or confidence.
+    #[must_use]
+    pub fn new(value: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            value: value.into(),
+            span: None,
+            confidence: None,
+            original: None,
+        }
+    }
+
+    /// Sets the byte offsets of the value in the searched text.
+    #[must_use]
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Sets the confidence score, clamped to 0.0..=1.0.
+    #[must_use]
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = Some(confidence.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Converts into details that own the value.
+    #[must_use]
+    pub fn into_owned(self) -> MatchDetails<'static> {
+        MatchDetails {
+            value: Cow::Owned(self.value.into_owned()),
+            span: self.span,
+            confidence: self.confidence,
+            original: self
+                .original
+                .map(|original| Cow::Owned(original.into_owned())),
+        }
+    }
+}
+
+/// Replaces Unicode decimal digits (Arabic-Indic, Persian, Devanagari, fullwidth,
+/// ...) with their ASCII equivalents.
+///
+/// Text without non-ASCII digits is returned borrowed.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::matcher::normalize_digits;
+///
+/// assert_eq!(normalize_digits("١٢٣٤٥٦"), "123456"); // Arabic-Indic
+/// assert_eq!(normalize_digits("۱۲۳-456"), "123-456"); // Persian
+/// assert_eq!(normalize_digits("code 42"), "code 42");
+/// ```
+#[must_use]
+pub fn normalize_digits(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(|c| !c.is_ascii() && is_decimal_digit(c)) {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(
+        text.chars()
+            .map(|c| match decimal_digit_value(c) {
+                Some(value) if !c.is_ascii() => char::from(b'0' + value),
+                _ => c,
+            })
+            .collect(),
+    )
+}
+
+/// Applies NFKC normalization and removes zero-width and other invisible
+/// characters.
+///
+/// Senders insert invisible characters inside codes to defeat scrapers, and
+/// compatibility forms (fullwidth letters and digits, ligatures, non-breaking
+/// spaces) keep patterns from matching. Enable it for all matching with
+/// [`ImapConfigBuilder::normalize_text`](crate::ImapConfigBuilder::normalize_text).
+///
+/// Text that is already normalized is returned borrowed.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::matcher::normalize_text;
+///
+/// assert_eq!(normalize_text("12\u{200B}34\u{FEFF}56"), "123456");
+/// assert_eq!(normalize_text("ｃｏｄｅ：９８７６"), "code:9876");
+/// ```
+#[must_use]
+pub fn normalize_text(text: &str) -> Cow<'_, str> {
+    let has_invisible = text.contains(INVISIBLE_CHARS);
+    if !has_invisible && is_nfkc_quick(text.chars()) == IsNormalized::Yes {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(
+        text.chars()
+            .filter(|c| !INVISIBLE_CHARS.contains(c))
+            .nfkc()
+            .collect(),
+    )
+}
+
+fn is_decimal_digit(c: char) -> bool {
+    DECIMAL_DIGIT.is_match(c.encode_utf8(&mut [0; 4]))
+}
+
+/// Returns the value of a Unicode decimal digit.
+///
+/// Unicode encodes decimal digits in runs of ten, ascending from zero, so the
+/// value is the distance from the start of the run, modulo 10 (some runs hold
+/// several sets of digits back to back).
+fn decimal_digit_value(c: char) -> Option<u8> {
+    if !is_decimal_digit(c) {
+        return None;
+    }
+
+    let code = u32::from(c);
+    let run_start = (1..)
+        .map_while(|back| code.checked_sub(back).and_then(char::from_u32))
+        .take_while(|&previous| is_decimal_digit(previous))
+        .count();
+    u8::try_from(run_start % 10).ok()
+}
+
+/// Locates `value` in `text`: by address when borrowed from it, else by search.
+fn locate(text: &str, value: &str) -> Option<Range<usize>> {
+    let start = (value.as_ptr() as usize)
+        .checked_sub(text.as_ptr() as usize)
+        .filter(|start| start + value.len() <= text.len())
+        .or_else(|| text.find(value))?;
+    Some(start..start + value.len())
+}
 
 /// Trait for matching and extracting content from email bodies.
 ///
@@ -56,8 +223,54 @@ pub trait Matcher: Send + Sync {
 
     /// Returns a human-readable description of what this matcher looks for.
     ///
-    /// Used in logging and error messages.
+    /// Used in logging and error messages, and as the matcher identity in
+    /// [`MatchedEmail::matcher`](crate::MatchedEmail::matcher).
     fn description(&self) -> &str;
+
+    /// Like [`find_match`](Self::find_match), but also reports where the value
+    /// was found and, optionally, a confidence score.
+    ///
+    /// The default implementation locates the value returned by `find_match` in
+    /// the text and reports no confidence. Override it to give exact offsets or
+    /// to rate matches.
+    fn find_match_details<'a>(&self, text: &'a str) -> Option<MatchDetails<'a>> {
+        let value = self.find_match(text)?;
+        let span = locate(text, &value);
+        Some(MatchDetails {
+            value,
+            span,
+            confidence: None,
+            original: None,
+        })
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_match<'a>(&self, text: &'a str) -> Option<Cow<'a, str>> {
+        (**self).find_match(text)
+    }
+
+    fn find_match_details<'a>(&self, text: &'a str) -> Option<MatchDetails<'a>> {
+        (**self).find_match_details(text)
+    }
+
+    fn description(&self) -> &str {
+        (**self).description()
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for std::sync::Arc<M> {
+    fn find_match<'a>(&self, text: &'a str) -> Option<Cow<'a, str>> {
+        (**self).find_match(text)
+    }
+
+    fn find_match_details<'a>(&self, text: &'a str) -> Option<MatchDetails<'a>> {
+        (**self).find_match_details(text)
+    }
+
+    fn description(&self) -> &str {
+        (**self).description()
+    }
 }
 
 /// Regex-based matcher that extracts the first capture group.
@@ -140,10 +353,19 @@ impl Matcher for RegexMatcher {
     fn description(&self) -> &str {
         &self.description
     }
+
+    fn find_match_details<'a>(&self, text: &'a str) -> Option<MatchDetails<'a>> {
+        let group = self.regex.captures(text)?.get(1)?;
+        Some(MatchDetails::new(group.as_str()).with_span(group.range()))
+    }
 }
 
 /// Matcher for OTP (One-Time Password) codes.
 ///
+/// Codes written with non-ASCII decimal digits (e.g. Arabic-Indic digits in
+/// localized emails) are returned with ASCII digits, see [`normalize_digits`];
+/// [`MatchDetails::original`] keeps the code as written.
+///
 /// # Example
 ///
 /// ```
@@ -152,6 +374,7 @@ impl Matcher for RegexMatcher {
 /// let otp = OtpMatcher::six_digit();
 /// assert_eq!(otp.find_match("Your code is 123456."), Some("123456".into()));
 /// assert_eq!(otp.find_match("Code: 12345"), None); // Only 5 digits
+/// assert_eq!(otp.find_match("رمز التحقق: ١٢٣٤٥٦"), Some("123456".into()));
 /// ```
 #[derive(Debug, Clone)]
 pub struct OtpMatcher {
@@ -201,12 +424,23 @@ impl OtpMatcher {
 
 impl Matcher for OtpMatcher {
     fn find_match<'a>(&self, text: &'a str) -> Option<Cow<'a, str>> {
-        self.inner.find_match(text)
+        self.find_match_details(text).map(|details| details.value)
     }
 
     fn description(&self) -> &str {
         self.inner.description()
     }
+
+    fn find_match_details<'a>(&self, text: &'a str) -> Option<MatchDetails<'a>> {
+        let mut details = self.inner.find_match_details(text)?;
+        if let Cow::Borrowed(original) = details.value {
+            if let Cow::Owned(normalized) = normalize_digits(original) {
+                details.value = Cow::Owned(normalized);
+                details.original = Some(Cow::Borrowed(original));
+            }
+        }
+        Some(details)
+    }
 }
 
 /// Matcher for URLs matching a specific domain pattern.
@@ -338,6 +572,217 @@ where
     }
 }
 
+/// A matcher defined as data, e.g. in a configuration file.
+///
+/// With the `serde` feature, specs deserialize from TOML or JSON, with the kind
+/// of matcher given by a `type` field:
+///
+/// ```toml
+/// [[matchers]]
+/// type = "regex"
+/// pattern = 'Order #(\d+)'
+/// description = "Order number"
+///
+/// [[matchers]]
+/// type = "url"
+/// domain = "example.com"
+///
+/// [[matchers]]
+/// type = "otp"
+/// digits = 6
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use email_sync::matcher::MatcherSpec;
+///
+/// let spec = MatcherSpec::Otp { digits: 6 };
+/// let matcher = spec.build()?;
+/// assert_eq!(matcher.find_match("code 123456").as_deref(), Some("123456"));
+/// # Ok::<(), email_sync::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)
+)]
+#[non_exhaustive]
+pub enum MatcherSpec {
+    /// A [`RegexMatcher`] extracting the first capture group of `pattern`.
+    Regex {
+        /// The regular expression.
+        pattern: String,
+        /// Description shown in logs (defaults to the pattern).
+        #[cfg_attr(feature = "serde", serde(default))]
+        description: Option<String>,
+    },
+    /// A [`UrlMatcher`] for links to `domain`.
+    Url {
+        /// Domain the links point at.
+        domain: String,
+    },
+    /// An [`OtpMatcher`] for codes of `digits` digits.
+    Otp {
+        /// Number of digits in the code.
+        digits: usize,
+    },
+}
+
+impl MatcherSpec {
+    /// Builds the matcher described by this spec.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`](crate::Error::InvalidConfig) if the regex
+    /// pattern is invalid or `digits` is 0.
+    pub fn build(&self) -> crate::Result<Box<dyn Matcher>> {
+        let invalid = |message: String| crate::Error::InvalidConfig { message };
+
+        Ok(match self {
+            Self::Regex {
+                pattern,
+                description,
+            } => {
+                let matcher = match description {
+                    Some(description) => RegexMatcher::with_description(pattern, description),
+                    None => RegexMatcher::new(pattern),
+                };
+                Box::new(matcher.map_err(|e| invalid(format!("invalid matcher regex: {e}")))?)
+            }
+            Self::Url { domain } => Box::new(UrlMatcher::new(domain)),
+            Self::Otp { digits: 0 } => {
+                return Err(invalid("OTP matcher digits must be greater than 0".into()))
+            }
+            Self::Otp { digits } => Box::new(OtpMatcher::n_digit(*digits)),
+        })
+    }
+}
+
+/// Factory creating a matcher, registered in a [`MatcherRegistry`].
+type MatcherFactory = Arc<dyn Fn() -> Box<dyn Matcher> + Send + Sync>;
+
+/// A registry of matchers by name, to pick a matcher from configuration or
+/// command-line arguments at runtime.
+///
+/// Names are case-insensitive. [`MatcherRegistry::with_defaults`] provides:
+///
+/// | Name          | Matcher                                  |
+/// |---------------|------------------------------------------|
+/// | `otp4`        | [`OtpMatcher::n_digit(4)`](OtpMatcher::n_digit) |
+/// | `otp6`        | [`OtpMatcher::six_digit`]                |
+/// | `otp8`        | [`OtpMatcher::n_digit(8)`](OtpMatcher::n_digit) |
+/// | `github-link` | [`UrlMatcher::new("github.com")`](UrlMatcher::new) |
+///
+/// # Example
+///
+/// ```
+/// use email_sync::matcher::{MatcherRegistry, RegexMatcher};
+///
+/// let mut registry = MatcherRegistry::with_defaults();
+/// registry.register("order-id", || {
+///     Box::new(RegexMatcher::new(r"Order #(\d+)").expect("valid regex"))
+/// });
+///
+/// let matcher = registry.create("order-id").expect("registered");
+/// assert_eq!(matcher.find_match("Order #42 shipped").as_deref(), Some("42"));
+/// assert!(registry.create("otp6").is_some());
+/// assert!(registry.create("unknown").is_none());
+/// ```
+#[derive(Clone, Default)]
+pub struct MatcherRegistry {
+    factories: HashMap<String, MatcherFactory>,
+}
+
+impl MatcherRegistry {
+    /// Creates an empty registry.
+    ///
+    /// Use [`Self::with_defaults`] to include the built-in matchers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with the built-in matchers.
+    #[must_use]
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register("otp4", || Box::new(OtpMatcher::n_digit(4)));
+        registry.register("otp6", || Box::new(OtpMatcher::six_digit()));
+        registry.register("otp8", || Box::new(OtpMatcher::n_digit(8)));
+        registry.register("github-link", || Box::new(UrlMatcher::new("github.com")));
+        registry
+    }
+
+    /// Registers a matcher factory under `name`.
+    ///
+    /// This replaces any matcher registered under the same name, including a
+    /// built-in one.
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn() -> Box<dyn Matcher> + Send + Sync + 'static,
+    {
+        self.factories
+            .insert(name.into().to_lowercase(), Arc::new(factory));
+    }
+
+    /// Registers the matcher described by `spec` under `name`.
+    ///
+    /// The spec is validated and built once, and the matcher shared by every
+    /// [`create`](Self::create) call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the spec is invalid, see [`MatcherSpec::build`].
+    pub fn register_spec(
+        &mut self,
+        name: impl Into<String>,
+        spec: &MatcherSpec,
+    ) -> crate::Result<()> {
+        let matcher: Arc<dyn Matcher> = Arc::from(spec.build()?);
+        self.register(name, move || Box::new(Arc::clone(&matcher)));
+        Ok(())
+    }
+
+    /// Removes the matcher registered under `name`.
+    ///
+    /// Returns `true` if a matcher was registered.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.factories.remove(&name.to_lowercase()).is_some()
+    }
+
+    /// Creates the matcher registered under `name`.
+    #[must_use]
+    pub fn create(&self, name: &str) -> Option<Box<dyn Matcher>> {
+        self.factories
+            .get(&name.to_lowercase())
+            .map(|factory| factory())
+    }
+
+    /// Returns `true` if a matcher is registered under `name`.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(&name.to_lowercase())
+    }
+
+    /// Returns the registered names, sorted.
+    #[must_use]
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl std::fmt::Debug for MatcherRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MatcherRegistry")
+            .field("names", &self.names())
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -367,6 +812,46 @@ mod tests {
         assert_eq!(otp.find_match("Code: 1234567"), None); // 7 digits
     }
 
+    #[test]
+    fn test_otp_normalizes_unicode_digits() {
+        let otp = OtpMatcher::six_digit();
+
+        let text = "کد تأیید شما: ۴۸۲۹۱۵";
+        let details = otp.find_match_details(text).unwrap();
+        assert_eq!(details.value, "482915");
+        assert_eq!(details.original.as_deref(), Some("۴۸۲۹۱۵"));
+        assert_eq!(&text[details.span.unwrap()], "۴۸۲۹۱۵");
+
+        // ASCII codes are returned borrowed, without an original
+        let details = otp.find_match_details("code 123456").unwrap();
+        assert!(matches!(details.value, Cow::Borrowed("123456")));
+        assert!(details.original.is_none());
+    }
+
+    #[test]
+    fn test_normalize_digits() {
+        assert_eq!(normalize_digits("０１２３"), "0123"); // Fullwidth
+        assert_eq!(normalize_digits("०९"), "09"); // Devanagari
+        assert_eq!(normalize_digits("𝟎𝟗𝟘𝟡"), "0909"); // Mathematical runs of ten
+        assert_eq!(normalize_digits("½ ²"), "½ ²"); // Not decimal digits
+        assert!(matches!(normalize_digits("abc 123"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        let otp = OtpMatcher::six_digit();
+        let text = "Your code: 4\u{200B}8\u{200C}2\u{2060}9\u{00AD}1\u{200D}5";
+        assert_eq!(otp.find_match(text), None);
+        assert_eq!(
+            otp.find_match(&normalize_text(text)).as_deref(),
+            Some("482915")
+        );
+
+        // Compatibility forms
+        assert_eq!(normalize_text("ﬁle\u{00A0}①"), "file 1");
+        assert!(matches!(normalize_text("plain text"), Cow::Borrowed(_)));
+    }
+
     #[test]
     fn test_otp_n_digit() {
         let otp = OtpMatcher::n_digit(4);
@@ -416,6 +901,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_boxed_and_shared_matchers() {
+        let boxed: Box<dyn Matcher> = Box::new(OtpMatcher::six_digit());
+        assert_eq!(boxed.find_match("code 123456").as_deref(), Some("123456"));
+
+        let shared = std::sync::Arc::new(OtpMatcher::six_digit());
+        assert_eq!(shared.description(), "6-digit OTP code");
+    }
+
     #[test]
     fn test_regex_matcher_returns_borrowed() {
         // Verify that RegexMatcher returns a borrowed reference (no allocation)
@@ -423,4 +917,136 @@ mod tests {
         let result = matcher.find_match("Your code: 12345");
         assert!(matches!(result, Some(Cow::Borrowed(_))));
     }
+
+    #[test]
+    fn test_match_details_span() {
+        let text = "Your code is 123456, not 654321";
+        let details = OtpMatcher::six_digit().find_match_details(text).unwrap();
+        assert_eq!(details.value, "123456");
+        assert_eq!(details.span, Some(13..19));
+        assert_eq!(details.confidence, None);
+
+        // Owned values are located by search
+        let matcher = ClosureMatcher::new(
+            |text| {
+                text.contains("654321")
+                    .then(|| Cow::Owned("654321".to_string()))
+            },
+            "owned",
+        );
+        let details = matcher.find_match_details(text).unwrap();
+        assert_eq!(details.span, Some(25..31));
+    }
+
+    #[test]
+    fn test_matcher_registry_defaults() {
+        let registry = MatcherRegistry::with_defaults();
+        assert_eq!(registry.names(), ["github-link", "otp4", "otp6", "otp8"]);
+
+        let otp = registry.create("OTP6").unwrap();
+        assert_eq!(otp.find_match("code 123456").as_deref(), Some("123456"));
+
+        let link = registry.create("github-link").unwrap();
+        let html = r#"<a href="https://github.com/login/device">Verify</a>"#;
+        assert_eq!(
+            link.find_match(html).as_deref(),
+            Some("https://github.com/login/device")
+        );
+
+        assert!(MatcherRegistry::new().create("otp6").is_none());
+    }
+
+    #[test]
+    fn test_matcher_registry_register_overrides() {
+        let mut registry = MatcherRegistry::with_defaults();
+        registry.register("otp6", || {
+            Box::new(RegexMatcher::with_description(r"PIN (\d{6})", "PIN").unwrap())
+        });
+
+        assert_eq!(registry.create("otp6").unwrap().description(), "PIN");
+        assert!(registry.unregister("otp6"));
+        assert!(!registry.contains("otp6"));
+        assert!(!registry.unregister("otp6"));
+    }
+
+    #[test]
+    fn test_matcher_spec_build() {
+        let spec = MatcherSpec::Regex {
+            pattern: r"Order #(\d+)".into(),
+            description: Some("Order number".into()),
+        };
+        let matcher = spec.build().unwrap();
+        assert_eq!(matcher.description(), "Order number");
+        assert_eq!(matcher.find_match("Order #42").as_deref(), Some("42"));
+
+        let invalid = MatcherSpec::Regex {
+            pattern: "(".into(),
+            description: None,
+        };
+        assert!(invalid.build().is_err());
+        assert!(MatcherSpec::Otp { digits: 0 }.build().is_err());
+
+        let mut registry = MatcherRegistry::new();
+        registry
+            .register_spec(
+                "link",
+                &MatcherSpec::Url {
+                    domain: "example.com".into(),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            registry.create("link").unwrap().description(),
+            "URL from example.com"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_matcher_spec_deserialize() {
+        #[derive(serde::Deserialize)]
+        struct File {
+            matchers: Vec<MatcherSpec>,
+        }
+
+        let file: File = toml::from_str(
+            r#"
+            [[matchers]]
+            type = "regex"
+            pattern = 'code: (\w+)'
+
+            [[matchers]]
+            type = "url"
+            domain = "example.com"
+
+            [[matchers]]
+            type = "otp"
+            digits = 8
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            file.matchers,
+            [
+                MatcherSpec::Regex {
+                    pattern: r"code: (\w+)".into(),
+                    description: None,
+                },
+                MatcherSpec::Url {
+                    domain: "example.com".into(),
+                },
+                MatcherSpec::Otp { digits: 8 },
+            ]
+        );
+
+        let unknown = toml::from_str::<File>("[[matchers]]\ntype = \"qr\"");
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_match_details_confidence_clamped() {
+        let details = MatchDetails::new("x").with_confidence(1.5);
+        assert_eq!(details.confidence, Some(1.0));
+    }
 }
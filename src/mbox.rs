@@ -0,0 +1,504 @@
+//! Local mbox backend.
+//!
+//! [`MboxBackend`] offers the core wait/find operations of
+//! [`ImapEmailClient`](crate::ImapEmailClient) over an mbox file, such as a
+//! local spool (`/var/mail/ci`) or a mail drop written by a test SMTP sink.
+//! Waiting tails the file: only messages appended after opening are checked.
+//!
+//! Messages are separated by `From ` lines at the start of the file or after
+//! an empty line; `>From ` lines in bodies are unescaped (mboxrd). A message at
+//! the end of the file is only checked once the file stops growing, so a
+//! message still being written is not matched half-way. As a
+//! [`MailBackend`], messages are keyed by their byte offset in the file.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::mbox::MboxBackend;
+//! use email_sync::matcher::OtpMatcher;
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let mut mbox = MboxBackend::open("/var/mail/ci").await?;
+//! let code = mbox.wait_for_match(&OtpMatcher::six_digit()).await?;
+//! println!("Got code: {code}");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::backend::{self, MailBackend, RawMessage};
+use crate::config::PollingConfig;
+use crate::error::{Error, Result};
+use crate::matcher::Matcher;
+use crate::parser::Extractor;
+use crate::runtime;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, instrument};
+
+/// Mailbox in a local mbox file.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct MboxBackend {
+    path: PathBuf,
+    polling: PollingConfig,
+    normalize_text: bool,
+    /// Length of the file already read.
+    offset: u64,
+    /// Bytes read but not checked yet, starting at a message boundary.
+    pending: Vec<u8>,
+    /// Messages returned by the last searches, until fetched.
+    messages: HashMap<String, Vec<u8>>,
+}
+
+/// Bytes of an mbox file read by [`read_appended`].
+#[derive(Debug)]
+struct Appended {
+    /// Length of the file.
+    len: u64,
+    /// Bytes from the requested offset (or from the start when the file was
+    /// truncated) to the end.
+    bytes: Vec<u8>,
+    /// The file is shorter than the requested offset.
+    truncated: bool,
+}
+
+impl MboxBackend {
+    /// Opens the mbox file at `path`.
+    ///
+    /// Messages already in the file are treated as old: the wait methods only
+    /// match messages appended afterwards. A file that does not exist yet counts
+    /// as empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LocalMailbox`] if the file cannot be read.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a Tokio runtime.
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let mut mbox = Self {
+            path: path.into(),
+            polling: PollingConfig::default(),
+            normalize_text: false,
+            offset: 0,
+            pending: Vec::new(),
+            messages: HashMap::new(),
+        };
+        mbox.reset_baseline().await?;
+        Ok(mbox)
+    }
+
+    /// Sets the polling interval and maximum wait of the wait methods.
+    #[must_use]
+    pub fn polling(mut self, polling: PollingConfig) -> Self {
+        self.polling = polling;
+        self
+    }
+
+    /// Applies NFKC normalization and strips invisible characters before
+    /// matching, like [`ImapConfigBuilder::normalize_text`](crate::ImapConfigBuilder::normalize_text).
+    #[must_use]
+    pub fn normalize_text(mut self, enabled: bool) -> Self {
+        self.normalize_text = enabled;
+        self
+    }
+
+    /// Waits for an appended message matching the provided pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - The file cannot be read ([`Error::LocalMailbox`])
+    #[instrument(
+        name = "MboxBackend::wait_for_match",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
+        self.wait_for_any(&[matcher]).await.map(|(_, value)| value)
+    }
+
+    /// Waits for an appended message matching any of the provided patterns.
+    ///
+    /// Returns the index of the matcher that fired (into `matchers`) together
+    /// with the extracted value. Messages are checked in file order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - The file cannot be read ([`Error::LocalMailbox`])
+    #[instrument(
+        name = "MboxBackend::wait_for_any",
+        skip(self, matchers),
+        fields(matchers = matchers.len())
+    )]
+    pub async fn wait_for_any(&mut self, matchers: &[&dyn Matcher]) -> Result<(usize, String)> {
+        let polling = self.polling.clone();
+        let extract = Extractor::local(self.normalize_text);
+        backend::wait_for_any(self, matchers, &polling, extract).await
+    }
+
+    /// Finds a matching message among those dated within `max_age` (by their
+    /// `Date` header), newest first.
+    ///
+    /// Messages without a valid `Date` header are always checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching message is found, or
+    /// [`Error::LocalMailbox`] if the file cannot be read.
+    #[instrument(
+        name = "MboxBackend::find_recent_match",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<String> {
+        let extract = Extractor::local(self.normalize_text);
+        backend::find_recent_match(self, matcher, max_age, extract).await
+    }
+
+    /// Treats every message in the file so far as old, so waits only match
+    /// messages appended from now on.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LocalMailbox`] if the file cannot be read.
+    pub async fn reset_baseline(&mut self) -> Result<()> {
+        let path = self.path.clone();
+        let len = runtime::spawn_blocking(move || match std::fs::metadata(path) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        })
+        .await
+        .map_err(|source| self.error(source))?;
+
+        self.offset = len;
+        self.pending.clear();
+        self.messages.clear();
+        debug!(offset = len, "mbox baseline set");
+        Ok(())
+    }
+
+    /// Returns the path of the mbox file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps `message` (starting at byte `offset` of the file) for
+    /// [`MailBackend::fetch`] and returns its key.
+    fn remember(&mut self, offset: u64, message: &[u8]) -> String {
+        let key = offset.to_string();
+        self.messages.insert(key.clone(), unescape(message));
+        key
+    }
+
+    /// Reads the file from `offset`.
+    async fn read(&self, offset: u64) -> Result<Appended> {
+        let path = self.path.clone();
+        runtime::spawn_blocking(move || read_appended(&path, offset))
+            .await
+            .map_err(|source| self.error(source))
+    }
+
+    fn error(&self, source: io::Error) -> Error {
+        Error::LocalMailbox {
+            path: self.path.display().to_string(),
+            source,
+        }
+    }
+}
+
+impl MailBackend for MboxBackend {
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { backend::select_inbox("an mbox file", mailbox) })
+    }
+
+    /// Returns the messages appended since the last search. A message at the
+    /// end of the file is only returned once the file stops growing.
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let appended = self.read(self.offset).await?;
+            if appended.truncated {
+                debug!("mbox file was truncated, reading from the start");
+                self.pending.clear();
+            }
+            let grew = !appended.bytes.is_empty();
+            self.offset = appended.len;
+            self.pending.extend_from_slice(&appended.bytes);
+
+            let pending = std::mem::take(&mut self.pending);
+            let pending_start = self.offset - pending.len() as u64;
+            let mut messages = split_messages(&pending);
+            // The last message may still be being written while the file grows
+            let unfinished = if grew { messages.pop() } else { None };
+
+            let keys = messages
+                .into_iter()
+                .map(|range| self.remember(pending_start + range.start as u64, &pending[range]))
+                .collect();
+
+            // Bytes before the first message are dropped once anything follows them
+            self.pending = match unfinished {
+                Some(range) => pending[range].to_vec(),
+                None if grew => pending,
+                None => Vec::new(),
+            };
+            Ok(keys)
+        })
+    }
+
+    /// Returns the messages whose `Date` header lies within `max_age`, and
+    /// those without a valid `Date` header.
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let data = self.read(0).await?.bytes;
+            let since = SystemTime::now()
+                .checked_sub(max_age)
+                .and_then(|since| since.duration_since(UNIX_EPOCH).ok())
+                .and_then(|since| i64::try_from(since.as_secs()).ok());
+
+            self.messages.clear();
+            let mut keys = Vec::new();
+            for range in split_messages(&data).into_iter().rev() {
+                let message = &data[range.clone()];
+                if since
+                    .is_some_and(|since| date(&unescape(message)).is_some_and(|date| date < since))
+                {
+                    continue;
+                }
+                keys.push(self.remember(range.start as u64, message));
+            }
+            Ok(keys)
+        })
+    }
+
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+        Box::pin(async move {
+            Ok(keys
+                .iter()
+                .filter_map(|key| Some((key.clone(), self.messages.remove(key)?)))
+                .collect())
+        })
+    }
+}
+
+/// Reads `path` from `offset` to the end; a missing file reads as empty.
+fn read_appended(path: &Path, offset: u64) -> io::Result<Appended> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(Appended {
+                len: 0,
+                bytes: Vec::new(),
+                truncated: offset > 0,
+            });
+        }
+        Err(e) => return Err(e),
+    };
+
+    let len = file.metadata()?.len();
+    let truncated = len < offset;
+    let start = if truncated { 0 } else { offset };
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut bytes = Vec::new();
+    file.take(len - start).read_to_end(&mut bytes)?;
+    Ok(Appended {
+        len: start + bytes.len() as u64,
+        bytes,
+        truncated,
+    })
+}
+
+/// Returns the byte ranges of the messages in `data`, each starting with its
+/// `From ` line. Bytes before the first `From ` line are not part of any.
+fn split_messages(data: &[u8]) -> Vec<Range<usize>> {
+    let mut starts: Vec<usize> = Vec::new();
+    let mut line_start = 0;
+    let mut after_blank = true;
+
+    while line_start < data.len() {
+        let line_end = data[line_start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |at| line_start + at + 1);
+        let line = &data[line_start..line_end];
+
+        if after_blank && line.starts_with(b"From ") {
+            starts.push(line_start);
+        }
+        after_blank = line == b"\n" || line == b"\r\n";
+        line_start = line_end;
+    }
+
+    let ends = starts.iter().skip(1).copied().chain([data.len()]);
+    starts
+        .iter()
+        .zip(ends)
+        .map(|(&start, end)| start..end)
+        .collect()
+}
+
+/// Returns the message without its `From ` line, with `>From ` lines unescaped.
+fn unescape(message: &[u8]) -> Vec<u8> {
+    let body_start = message
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(message.len(), |at| at + 1);
+
+    let mut unescaped = Vec::with_capacity(message.len() - body_start);
+    for line in message[body_start..].split_inclusive(|&b| b == b'\n') {
+        let quotes = line.iter().take_while(|&&b| b == b'>').count();
+        if quotes > 0 && line[quotes..].starts_with(b"From ") {
+            unescaped.extend_from_slice(&line[1..]);
+        } else {
+            unescaped.extend_from_slice(line);
+        }
+    }
+    unescaped
+}
+
+/// Returns the `Date` header of `message` as a Unix timestamp.
+fn date(message: &[u8]) -> Option<i64> {
+    use mailparse::MailHeaderMap;
+
+    let (headers, _) = mailparse::parse_headers(message).ok()?;
+    let date = headers.get_first_value("Date")?;
+    mailparse::dateparse(&date).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::OtpMatcher;
+    use std::fs;
+    use std::io::Write;
+
+    fn temp_mbox(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("email-sync-{}-{name}.mbox", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn append(path: &Path, date: &str, body: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        write!(
+            file,
+            "From ci@example.com Mon Jan  1 00:00:00 2024\nDate: {date}\nSubject: Test\n\n{body}\n\n"
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_split_messages() {
+        let data =
+            b"junk\n\nFrom a Mon\nSubject: 1\n\nHi\nFrom here on\n>From escaped\n\nFrom b Tue\n\nbody\n";
+        let messages = split_messages(data);
+        assert_eq!(messages.len(), 2);
+
+        // "From here on" does not follow an empty line, so it is body text
+        let first = unescape(&data[messages[0].clone()]);
+        assert_eq!(first, b"Subject: 1\n\nHi\nFrom here on\nFrom escaped\n\n");
+        assert_eq!(unescape(&data[messages[1].clone()]), b"\nbody\n");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_appended_message() {
+        let path = temp_mbox("wait");
+        append(&path, "Mon, 1 Jan 2024 00:00:00 +0000", "Old code 111111");
+
+        let mut mbox = MboxBackend::open(&path)
+            .await
+            .unwrap()
+            .polling(PollingConfig {
+                interval: Duration::from_millis(10),
+                max_wait: Duration::from_secs(5),
+            });
+        let writer = {
+            let path = path.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                append(&path, "Mon, 1 Jan 2024 00:01:00 +0000", "No code");
+                append(
+                    &path,
+                    "Mon, 1 Jan 2024 00:02:00 +0000",
+                    "Your code is 222222",
+                );
+            })
+        };
+
+        let code = mbox.wait_for_match(&OtpMatcher::six_digit()).await.unwrap();
+        assert_eq!(code, "222222");
+        writer.await.unwrap();
+        assert!(mbox.pending.is_empty());
+
+        // A rotated (truncated) file is read from the start
+        fs::write(&path, "").unwrap();
+        append(
+            &path,
+            "Mon, 1 Jan 2024 00:03:00 +0000",
+            "Your code is 333333",
+        );
+        let code = mbox.wait_for_match(&OtpMatcher::six_digit()).await.unwrap();
+        assert_eq!(code, "333333");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_find_recent_match() {
+        let path = temp_mbox("find");
+        let now = chrono::Utc::now();
+        let old = now - chrono::Duration::days(2);
+        append(&path, &now.to_rfc2822(), "Your code is 444444");
+        append(&path, &old.to_rfc2822(), "Your code is 555555");
+
+        let mut mbox = MboxBackend::open(&path).await.unwrap();
+        let code = mbox
+            .find_recent_match(&OtpMatcher::six_digit(), Duration::from_hours(1))
+            .await
+            .unwrap();
+        assert_eq!(code, "444444");
+
+        let code = mbox
+            .find_recent_match(&OtpMatcher::six_digit(), Duration::from_hours(72))
+            .await
+            .unwrap();
+        assert_eq!(code, "555555");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_file_is_empty() {
+        let path = temp_mbox("missing");
+        let mut mbox = MboxBackend::open(&path).await.unwrap();
+        let result = mbox
+            .find_recent_match(&OtpMatcher::six_digit(), Duration::from_hours(1))
+            .await;
+        assert!(matches!(result, Err(Error::NoMatch)));
+    }
+}
@@ -0,0 +1,331 @@
+//! Internal NTLM SASL mechanism (`NTLMv2`, MS-NLMP).
+//!
+//! On-premises Exchange servers offer `AUTHENTICATE NTLM` for domain accounts. The
+//! exchange takes two rounds: the client sends a NEGOTIATE message, the server
+//! answers with a CHALLENGE, and the client proves knowledge of the password with
+//! an AUTHENTICATE message carrying the `NTLMv2` response. The same messages
+//! authenticate HTTP connections to Exchange Web Services (`ews` feature).
+
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Signature starting every NTLM message.
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+/// Flags sent in the NEGOTIATE message: Unicode, request target, NTLM, always
+/// sign, extended session security, target info, 128-bit and 56-bit encryption.
+const NEGOTIATE_FLAGS: u32 = 0xA088_8205;
+
+/// `MsvAvTimestamp` attribute of the target info (server time as a FILETIME).
+const AV_TIMESTAMP: u16 = 7;
+
+/// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01.
+const FILETIME_UNIX_OFFSET_SECS: u64 = 11_644_473_600;
+
+type HmacMd5 = Hmac<md5::Md5>;
+
+/// NTLM authenticator for [`async_imap::Client::authenticate`].
+pub(crate) struct NtlmAuthenticator<'a> {
+    user: &'a str,
+    domain: &'a str,
+    password: &'a str,
+    negotiated: bool,
+}
+
+impl<'a> NtlmAuthenticator<'a> {
+    pub(crate) fn new(user: &'a str, domain: &'a str, password: &'a str) -> Self {
+        Self {
+            user,
+            domain,
+            password,
+            negotiated: false,
+        }
+    }
+}
+
+impl async_imap::Authenticator for NtlmAuthenticator<'_> {
+    type Response = Vec<u8>;
+
+    fn process(&mut self, challenge: &[u8]) -> Self::Response {
+        if !self.negotiated {
+            self.negotiated = true;
+            return negotiate_message();
+        }
+
+        let Some(challenge) = ChallengeMessage::parse(challenge) else {
+            // An empty response makes the server fail the exchange
+            warn!("Malformed NTLM challenge from server");
+            return Vec::new();
+        };
+
+        let mut client_challenge = [0u8; 8];
+        if let Err(e) = getrandom::getrandom(&mut client_challenge) {
+            warn!(error = %e, "Failed to generate NTLM client challenge");
+            return Vec::new();
+        }
+
+        authenticate_message(
+            &challenge,
+            self.user,
+            self.domain,
+            self.password,
+            client_challenge,
+        )
+    }
+}
+
+/// Returns the user and domain to authenticate as: `user` of `user@example.com`
+/// in `domain` when one is given, otherwise the whole email address as user
+/// principal name.
+pub(crate) fn identity<'a>(email: &'a str, domain: Option<&'a str>) -> (&'a str, &'a str) {
+    match domain {
+        Some(domain) => (email.split('@').next().unwrap_or(email), domain),
+        None => (email, ""),
+    }
+}
+
+/// Server CHALLENGE message fields used for the response.
+struct ChallengeMessage {
+    flags: u32,
+    server_challenge: [u8; 8],
+    target_info: Vec<u8>,
+}
+
+impl ChallengeMessage {
+    fn parse(message: &[u8]) -> Option<Self> {
+        if message.len() < 32 || &message[..8] != SIGNATURE || read_u32(message, 8)? != 2 {
+            return None;
+        }
+
+        let flags = read_u32(message, 20)?;
+        let server_challenge = message.get(24..32)?.try_into().ok()?;
+        let target_info = if message.len() >= 48 {
+            let len = usize::from(read_u16(message, 40)?);
+            let offset = usize::try_from(read_u32(message, 44)?).ok()?;
+            message.get(offset..offset.checked_add(len)?)?.to_vec()
+        } else {
+            Vec::new()
+        };
+
+        Some(Self {
+            flags,
+            server_challenge,
+            target_info,
+        })
+    }
+
+    /// Returns the server time from the target info, if present.
+    fn timestamp(&self) -> Option<[u8; 8]> {
+        let mut pairs = self.target_info.as_slice();
+        while pairs.len() >= 4 {
+            let id = read_u16(pairs, 0)?;
+            let len = usize::from(read_u16(pairs, 2)?);
+            let value = pairs.get(4..4 + len)?;
+            if id == AV_TIMESTAMP {
+                return value.try_into().ok();
+            }
+            pairs = &pairs[4 + len..];
+        }
+        None
+    }
+}
+
+/// Builds the NEGOTIATE message (no domain or workstation supplied).
+fn negotiate_message() -> Vec<u8> {
+    let mut message = Vec::with_capacity(32);
+    message.extend_from_slice(SIGNATURE);
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+    // Empty domain and workstation fields
+    message.extend_from_slice(&[0; 16]);
+    message
+}
+
+/// Builds the AUTHENTICATE message answering `challenge`.
+fn authenticate_message(
+    challenge: &ChallengeMessage,
+    user: &str,
+    domain: &str,
+    password: &str,
+    client_challenge: [u8; 8],
+) -> Vec<u8> {
+    let key = ntowf_v2(user, domain, password);
+    let server_timestamp = challenge.timestamp();
+    let timestamp = server_timestamp.unwrap_or_else(current_filetime);
+
+    let nt_response = nt_response_v2(
+        &key,
+        challenge.server_challenge,
+        client_challenge,
+        timestamp,
+        &challenge.target_info,
+    );
+    // With a server timestamp, the LMv2 response must be zeroed (MS-NLMP 3.3.2)
+    let lm_response = if server_timestamp.is_some() {
+        vec![0; 24]
+    } else {
+        let mut response =
+            hmac_md5(&key, &[&challenge.server_challenge, &client_challenge]).to_vec();
+        response.extend_from_slice(&client_challenge);
+        response
+    };
+
+    let domain = utf16le(domain);
+    let user = utf16le(user);
+    let fields: [&[u8]; 6] = [&lm_response, &nt_response, &domain, &user, &[], &[]];
+
+    let mut header = Vec::with_capacity(64);
+    header.extend_from_slice(SIGNATURE);
+    header.extend_from_slice(&3u32.to_le_bytes());
+
+    let mut payload = Vec::new();
+    let mut offset: u32 = 64;
+    for field in fields {
+        let len = u16::try_from(field.len()).unwrap_or(u16::MAX);
+        header.extend_from_slice(&len.to_le_bytes());
+        header.extend_from_slice(&len.to_le_bytes());
+        header.extend_from_slice(&offset.to_le_bytes());
+        payload.extend_from_slice(field);
+        offset += u32::from(len);
+    }
+    header.extend_from_slice(&(challenge.flags & NEGOTIATE_FLAGS).to_le_bytes());
+
+    header.extend_from_slice(&payload);
+    header
+}
+
+/// Computes the `NTLMv2` response: `NTProofStr` followed by the client blob.
+fn nt_response_v2(
+    key: &[u8; 16],
+    server_challenge: [u8; 8],
+    client_challenge: [u8; 8],
+    timestamp: [u8; 8],
+    target_info: &[u8],
+) -> Vec<u8> {
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&timestamp);
+    blob.extend_from_slice(&client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(target_info);
+    blob.extend_from_slice(&[0; 4]);
+
+    let mut response = hmac_md5(key, &[&server_challenge, &blob]).to_vec();
+    response.extend_from_slice(&blob);
+    response
+}
+
+/// Derives the `NTLMv2` response key from the password.
+fn ntowf_v2(user: &str, domain: &str, password: &str) -> [u8; 16] {
+    let nt_hash = Md4::digest(utf16le(password));
+    let identity = utf16le(&format!("{}{domain}", user.to_uppercase()));
+    hmac_md5(&nt_hash, &[&identity])
+}
+
+fn hmac_md5(key: &[u8], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = HmacMd5::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Returns the current time as a little-endian FILETIME.
+fn current_filetime() -> [u8; 8] {
+    let unix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let ticks = (unix.as_secs() + FILETIME_UNIX_OFFSET_SECS) * 10_000_000
+        + u64::from(unix.subsec_nanos() / 100);
+    ticks.to_le_bytes()
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        use std::fmt::Write;
+
+        bytes.iter().fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+    }
+
+    // Test vectors from MS-NLMP section 4.2.4 (NTLMv2 authentication)
+    const SERVER_CHALLENGE: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+    const CLIENT_CHALLENGE: [u8; 8] = [0xaa; 8];
+    const TARGET_INFO: [u8; 36] = [
+        0x02, 0x00, 0x0c, 0x00, 0x44, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x61, 0x00, 0x69, 0x00, 0x6e,
+        0x00, 0x01, 0x00, 0x0c, 0x00, 0x53, 0x00, 0x65, 0x00, 0x72, 0x00, 0x76, 0x00, 0x65, 0x00,
+        0x72, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_ntlm_v2_response() {
+        let key = ntowf_v2("User", "Domain", "Password");
+        assert_eq!(hex(&key), "0c868a403bfd7a93a3001ef22ef02e3f");
+
+        let response = nt_response_v2(
+            &key,
+            SERVER_CHALLENGE,
+            CLIENT_CHALLENGE,
+            [0; 8],
+            &TARGET_INFO,
+        );
+        assert_eq!(hex(&response[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+    }
+
+    #[test]
+    fn test_challenge_parse() {
+        let mut message = Vec::new();
+        message.extend_from_slice(SIGNATURE);
+        message.extend_from_slice(&2u32.to_le_bytes());
+        message.extend_from_slice(&[0; 8]); // target name
+        message.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+        message.extend_from_slice(&SERVER_CHALLENGE);
+        message.extend_from_slice(&[0; 8]); // reserved
+        message.extend_from_slice(&36u16.to_le_bytes());
+        message.extend_from_slice(&36u16.to_le_bytes());
+        message.extend_from_slice(&48u32.to_le_bytes());
+        message.extend_from_slice(&TARGET_INFO);
+
+        let challenge = ChallengeMessage::parse(&message).unwrap();
+        assert_eq!(challenge.server_challenge, SERVER_CHALLENGE);
+        assert_eq!(challenge.target_info, TARGET_INFO);
+        assert!(challenge.timestamp().is_none());
+
+        assert!(ChallengeMessage::parse(b"NTLMSSP\0").is_none());
+    }
+
+    #[test]
+    fn test_authenticate_message_layout() {
+        let challenge = ChallengeMessage {
+            flags: NEGOTIATE_FLAGS,
+            server_challenge: SERVER_CHALLENGE,
+            target_info: TARGET_INFO.to_vec(),
+        };
+        let message = authenticate_message(&challenge, "User", "Domain", "Password", [0xaa; 8]);
+
+        assert_eq!(&message[..8], SIGNATURE);
+        assert_eq!(read_u32(&message, 8), Some(3));
+        // User name field points at "User" in UTF-16LE
+        let len = usize::from(read_u16(&message, 36).unwrap());
+        let offset = read_u32(&message, 40).unwrap() as usize;
+        assert_eq!(&message[offset..offset + len], utf16le("User").as_slice());
+    }
+}
@@ -1,106 +1,962 @@
 //! Internal module for parsing email content.
 
-use crate::matcher::Matcher;
-use mailparse::parse_mail;
+use crate::config::{FetchItems, ImapConfig, Redaction, DEFAULT_MAX_PARSE_SIZE};
+use crate::email::{AttachmentInfo, EmailMessage, MessageSummary};
+use crate::error::Error;
+use crate::events::{self, EventHook, Operation};
+use crate::matcher::{self, MatchDetails, Matcher};
+use async_imap::imap_proto::types::{Address, Envelope, MessageSection, SectionPath};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, FixedOffset};
+use mailparse::{
+    addrparse_header, parse_headers, parse_mail, MailAddr, MailHeader, MailHeaderMap,
+    MailParseError,
+};
 use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, warn};
 
+/// Parses fetched messages and runs matchers on their text.
+///
+/// The text of each message is decoded into a buffer that is reused for the
+/// next one, and matches borrow from it instead of being copied. Keep one
+/// extractor for all the messages of a scan.
+#[derive(Debug, Default)]
+pub(crate) struct Extractor {
+    /// Receives parsing and matching latencies.
+    hook: Option<Arc<dyn EventHook>>,
+    /// Normalize the text with [`matcher::normalize_text`] before matching.
+    normalize_text: bool,
+    /// How matched values are logged.
+    redaction: Redaction,
+    /// Cut emails off after this many bytes before parsing.
+    max_parse_size: Option<usize>,
+    /// FETCH items messages were fetched with instead of `BODY[]`.
+    fetch_items: Option<FetchItems>,
+    /// Text of the current message.
+    text: String,
+}
+
 /// Result of attempting to extract a match from a message.
 #[derive(Debug)]
 pub(crate) enum ExtractResult<'a> {
-    /// A match was found
-    Match(Cow<'a, str>),
+    /// A match was found by the matcher at `index`
+    Match {
+        index: usize,
+        details: MatchDetails<'a>,
+    },
     /// No match in this message
     NoMatch,
     /// Message couldn't be parsed (logged, but can continue to next message)
     ParseError,
 }
 
-/// Extracts matching content from an IMAP fetch result using the provided matcher.
+impl Extractor {
+    pub(crate) fn new(config: &ImapConfig) -> Self {
+        Self {
+            hook: config.event_hook.clone(),
+            normalize_text: config.normalize_text,
+            redaction: config.redact_matches,
+            max_parse_size: config.max_parse_size,
+            fetch_items: config.fetch_items.clone(),
+            text: String::new(),
+        }
+    }
+
+    /// Creates an extractor for local mailboxes, which have no [`ImapConfig`].
+    pub(crate) fn local(normalize_text: bool) -> Self {
+        Self {
+            normalize_text,
+            max_parse_size: Some(DEFAULT_MAX_PARSE_SIZE),
+            ..Self::default()
+        }
+    }
+
+    /// Extracts matching content from an IMAP fetch result using the provided matchers.
+    ///
+    /// The body is parsed once and the matchers are tried in order; the first one that
+    /// matches wins. The returned details borrow from the extractor until the next
+    /// message is processed.
+    ///
+    /// This function is designed to be resilient - it will log and skip malformed messages
+    /// rather than failing the entire operation. This allows processing to continue even
+    /// if some emails have parsing issues.
+    ///
+    /// Parsing and matching latencies are reported to the event hook.
+    pub(crate) fn extract_match(
+        &mut self,
+        message: &async_imap::types::Fetch,
+        matchers: &[&dyn Matcher],
+    ) -> ExtractResult<'_> {
+        if let Err(result) = self.load_text(message) {
+            return result;
+        }
+        self.find_match(message.uid, matchers)
+    }
+
+    /// Extracts matching content from a raw RFC 822 message, e.g. the MIME
+    /// content of an EWS item or a Maildir file.
+    ///
+    /// Behaves like [`extract_match`](Self::extract_match).
+    pub(crate) fn extract_raw_match(
+        &mut self,
+        raw: &[u8],
+        matchers: &[&dyn Matcher],
+    ) -> ExtractResult<'_> {
+        self.text.clear();
+
+        let started = Instant::now();
+        let loaded = parse_body_text(
+            None,
+            truncate(None, raw, self.max_parse_size),
+            &mut self.text,
+        );
+        if loaded.is_ok() {
+            self.normalize();
+        }
+        events::record(self.hook(), Operation::Parse, started);
+
+        if let Err(result) = loaded {
+            return result;
+        }
+        self.find_match(None, matchers)
+    }
+
+    /// Runs `matchers` on the loaded text of the email with `uid`.
+    fn find_match(&self, uid: Option<u32>, matchers: &[&dyn Matcher]) -> ExtractResult<'_> {
+        let started = Instant::now();
+        let found = matchers.iter().enumerate().find_map(|(index, matcher)| {
+            matcher
+                .find_match_details(&self.text)
+                .map(|details| (index, matcher, details))
+        });
+        events::record(self.hook(), Operation::Match, started);
+
+        if let Some((index, matcher, details)) = found {
+            debug!(
+                uid,
+                matcher = %matcher.description(),
+                value = %self.redaction.redact(&details.value),
+                span = ?details.span,
+                "Found match in email"
+            );
+            return ExtractResult::Match { index, details };
+        }
+
+        debug!(
+            uid,
+            matchers = matchers.len(),
+            "No match found in email body"
+        );
+        ExtractResult::NoMatch
+    }
+
+    /// Extracts content for every matcher that matches an IMAP fetch result.
+    ///
+    /// Like [`extract_match`](Self::extract_match), but the body is checked against
+    /// all matchers and each hit is returned as `(matcher index, value)`.
+    pub(crate) fn extract_all_matches(
+        &mut self,
+        message: &async_imap::types::Fetch,
+        matchers: &[&dyn Matcher],
+    ) -> Vec<(usize, Cow<'_, str>)> {
+        if self.load_text(message).is_err() {
+            return Vec::new();
+        }
+
+        let started = Instant::now();
+        let hits: Vec<(usize, Cow<'_, str>)> = matchers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, matcher)| {
+                matcher.find_match(&self.text).map(|value| (index, value))
+            })
+            .collect();
+        events::record(self.hook(), Operation::Match, started);
+
+        debug!(
+            uid = message.uid,
+            matchers = matchers.len(),
+            matched = hits.len(),
+            "Checked email against all matchers"
+        );
+
+        hits
+    }
+
+    /// Returns `true` if the subject of a fetched message matches any of `matchers`.
+    ///
+    /// Used to skip downloading the body of emails that carry their code in the
+    /// subject line.
+    pub(crate) fn subject_matches(
+        &mut self,
+        message: &async_imap::types::Fetch,
+        matchers: &[&dyn Matcher],
+    ) -> bool {
+        let Some(subject) = raw_headers(message).as_deref().and_then(header_subject) else {
+            return false;
+        };
+        self.text.clear();
+        self.text.push_str(&subject);
+        self.normalize();
+
+        let started = Instant::now();
+        let matched = matchers
+            .iter()
+            .any(|matcher| matcher.find_match(&self.text).is_some());
+        events::record(self.hook(), Operation::Match, started);
+
+        if matched {
+            debug!(uid = message.uid, "Subject matched, skipping body download");
+        }
+        matched
+    }
+
+    /// Parses a fetched message into the text buffer.
+    ///
+    /// Messages fetched with custom FETCH items are parsed from the sections
+    /// they have. When only the header section was fetched (see
+    /// [`subject_matches`](Self::subject_matches)), the subject stands in for the
+    /// body; when only the ENVELOPE was fetched, the subject and senders do. On
+    /// failure, returns the [`ExtractResult`] to report instead; problems are
+    /// logged here so callers can simply move on to the next message.
+    fn load_text(
+        &mut self,
+        message: &async_imap::types::Fetch,
+    ) -> std::result::Result<(), ExtractResult<'static>> {
+        let uid = message.uid;
+        self.text.clear();
+
+        let started = Instant::now();
+        let sections = self
+            .fetch_items
+            .as_ref()
+            .and_then(|items| fetched_sections(message, items));
+        let loaded = if let Some(body) = message.body().or(sections.as_deref()) {
+            parse_body_text(
+                uid,
+                truncate(uid, body, self.max_parse_size),
+                &mut self.text,
+            )
+        } else if let Some(subject) = message.header().and_then(header_subject) {
+            self.text.push_str(&subject);
+            Ok(())
+        } else if let Some(envelope) = message.envelope() {
+            envelope_text(envelope, &mut self.text);
+            Ok(())
+        } else {
+            debug!(uid, "Message has no body");
+            return Err(ExtractResult::NoMatch);
+        };
+        if loaded.is_ok() {
+            self.normalize();
+        }
+        events::record(self.hook(), Operation::Parse, started);
+        loaded
+    }
+
+    /// Normalizes the text buffer for matching if configured to.
+    fn normalize(&mut self) {
+        if !self.normalize_text {
+            return;
+        }
+        if let Cow::Owned(normalized) = matcher::normalize_text(&self.text) {
+            // Copy back rather than replace, to keep the buffer's capacity
+            self.text.clear();
+            self.text.push_str(&normalized);
+        }
+    }
+
+    fn hook(&self) -> Option<&dyn EventHook> {
+        self.hook.as_deref()
+    }
+}
+
+/// Headers checked by [`is_addressed_to`].
+pub(crate) const RECIPIENT_HEADERS: [&str; 4] = ["To", "Cc", "Delivered-To", "X-Original-To"];
+
+/// Returns `true` if a fetched message was sent to `address`.
 ///
-/// This function is designed to be resilient - it will log and skip malformed messages
-/// rather than failing the entire operation. This allows processing to continue even
-/// if some emails have parsing issues.
-pub(crate) fn extract_match_from_message(
-    message: &async_imap::types::Fetch,
-    pattern_matcher: &dyn Matcher,
-) -> ExtractResult<'static> {
-    let uid = message.uid;
+/// Checks the `To`, `Cc`, `Delivered-To` and `X-Original-To` headers. The comparison
+/// is case-insensitive, so it works for plus-addressed aliases such as
+/// `user+run42@example.com`.
+pub(crate) fn is_addressed_to(message: &async_imap::types::Fetch, address: &str) -> bool {
+    let Some(body) = raw_headers(message) else {
+        return false;
+    };
+    let addressed = headers_mention_recipient(&body, address);
 
-    let Some(body) = message.body() else {
-        debug!(uid, "Message has no body");
-        return ExtractResult::NoMatch;
+    if !addressed {
+        debug!(
+            uid = message.uid,
+            "Email not addressed to recipient alias, skipping"
+        );
+    }
+    addressed
+}
+
+/// Checks the recipient headers of a raw message for `address`.
+fn headers_mention_recipient(raw: &[u8], address: &str) -> bool {
+    let Ok((headers, _)) = parse_headers(raw) else {
+        return false;
     };
 
-    let parsed = match parse_mail(body) {
-        Ok(p) => p,
-        Err(e) => {
+    header_addresses(&headers, &RECIPIENT_HEADERS)
+        .iter()
+        .any(|addr| addr.eq_ignore_ascii_case(address))
+}
+
+/// Returns the addresses listed in the headers called `names` (case-insensitive),
+/// with groups flattened.
+pub(crate) fn header_addresses(headers: &[MailHeader<'_>], names: &[&str]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|header| {
+            names
+                .iter()
+                .any(|name| header.get_key_ref().eq_ignore_ascii_case(name))
+        })
+        .filter_map(|header| addrparse_header(header).ok())
+        .flat_map(|list| list.iter().cloned().collect::<Vec<_>>())
+        .flat_map(|addr| match addr {
+            MailAddr::Single(info) => vec![info.addr],
+            MailAddr::Group(group) => group.addrs.into_iter().map(|info| info.addr).collect(),
+        })
+        .collect()
+}
+
+/// Parses a fetched message into an [`EmailMessage`].
+pub(crate) fn parse_message(message: &async_imap::types::Fetch) -> crate::Result<EmailMessage> {
+    let raw = message.body().ok_or(Error::ParseEmail {
+        source: MailParseError::Generic("message has no body"),
+    })?;
+    let mut parsed = parse_raw_message(message.uid.unwrap_or_default(), raw)?;
+    parsed.internal_date = message.internal_date();
+    parsed.size = message.size;
+    Ok(parsed)
+}
+
+/// Parses a raw RFC 5322 message, e.g. an archived `.eml` file, into an
+/// [`EmailMessage`].
+///
+/// Uses the same parser as [`ImapEmailClient::fetch_message`]; the `uid` of the
+/// result is 0 and the server-side `internal_date` and `size` are `None`. To run a
+/// matcher over the message the way the client would, see
+/// [`testing::extract_eml`](crate::testing::extract_eml).
+///
+/// # Errors
+///
+/// Returns [`Error::ParseEmail`] if the message cannot be parsed, or
+/// [`Error::ExtractBody`] if a body part cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// let raw = b"From: Example <noreply@example.com>\r\n\
+///     Subject: Welcome\r\n\
+///     \r\n\
+///     Your code is 482913\r\n";
+///
+/// let message = email_sync::parse_email(raw)?;
+/// assert_eq!(message.from, ["noreply@example.com"]);
+/// assert_eq!(message.subject.as_deref(), Some("Welcome"));
+/// # Ok::<(), email_sync::Error>(())
+/// ```
+///
+/// [`ImapEmailClient::fetch_message`]: crate::ImapEmailClient::fetch_message
+pub fn parse_email(raw: &[u8]) -> crate::Result<EmailMessage> {
+    parse_raw_message(0, raw)
+}
+
+/// Parses a raw message with UID `uid` into an [`EmailMessage`].
+fn parse_raw_message(uid: u32, raw: &[u8]) -> crate::Result<EmailMessage> {
+    let parsed = parse_mail(raw).map_err(|source| Error::ParseEmail { source })?;
+    let headers = &parsed.headers;
+
+    let mut message = EmailMessage {
+        uid,
+        message_id: headers
+            .get_first_value("Message-ID")
+            .map(|id| bare_message_id(&id).to_string()),
+        from: header_addresses(headers, &["From"]),
+        to: header_addresses(headers, &["To"]),
+        cc: header_addresses(headers, &["Cc"]),
+        subject: headers.get_first_value("Subject"),
+        date: headers
+            .get_first_value("Date")
+            .and_then(|date| parse_date(&date)),
+        text_body: None,
+        html_body: None,
+        headers: headers
+            .iter()
+            .map(|header| (header.get_key(), header.get_value()))
+            .collect(),
+        attachments: Vec::new(),
+        internal_date: None,
+        size: None,
+    };
+    collect_parts(&parsed, &mut message).map_err(|source| Error::ExtractBody { source })?;
+
+    Ok(message)
+}
+
+/// Fills in the bodies and attachments of `message` from `part` and its subparts.
+///
+/// The first `text/plain` and `text/html` parts become the bodies; any other leaf
+/// part, or one with a filename or an `attachment` disposition, is an attachment.
+fn collect_parts(
+    part: &mailparse::ParsedMail<'_>,
+    message: &mut EmailMessage,
+) -> Result<(), MailParseError> {
+    if !part.subparts.is_empty() {
+        for subpart in &part.subparts {
+            collect_parts(subpart, message)?;
+        }
+        return Ok(());
+    }
+
+    let disposition = part.get_content_disposition();
+    let filename = disposition
+        .params
+        .get("filename")
+        .or_else(|| part.ctype.params.get("name"))
+        .cloned();
+    let mimetype = part.ctype.mimetype.to_ascii_lowercase();
+    let is_attachment =
+        filename.is_some() || disposition.disposition == mailparse::DispositionType::Attachment;
+
+    match mimetype.as_str() {
+        "text/plain" if !is_attachment && message.text_body.is_none() => {
+            message.text_body = Some(part.get_body()?);
+        }
+        "text/html" if !is_attachment && message.html_body.is_none() => {
+            message.html_body = Some(part.get_body()?);
+        }
+        _ => message.attachments.push(AttachmentInfo {
+            filename,
+            content_type: mimetype,
+            size: part.get_body_raw()?.len(),
+        }),
+    }
+    Ok(())
+}
+
+/// Parses a `Date` header, keeping its UTC offset when it is well-formed.
+fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc2822(value.trim()).ok().or_else(|| {
+        let timestamp = mailparse::dateparse(value).ok()?;
+        DateTime::from_timestamp(timestamp, 0).map(|date| date.fixed_offset())
+    })
+}
+
+/// Returns `true` if a fetched message is a reply within the thread of `message_id`.
+///
+/// Checks whether the `In-Reply-To` or `References` header contains the Message-ID
+/// (compared without angle brackets).
+pub(crate) fn is_reply_to(message: &async_imap::types::Fetch, message_id: &str) -> bool {
+    raw_headers(message).is_some_and(|body| headers_reference(&body, message_id))
+}
+
+/// Checks the threading headers of a raw message for `message_id`.
+fn headers_reference(raw: &[u8], message_id: &str) -> bool {
+    let Ok((headers, _)) = parse_headers(raw) else {
+        return false;
+    };
+    let wanted = bare_message_id(message_id);
+
+    headers
+        .iter()
+        .filter(|header| {
+            let key = header.get_key_ref();
+            key.eq_ignore_ascii_case("In-Reply-To") || key.eq_ignore_ascii_case("References")
+        })
+        .any(|header| {
+            header
+                .get_value()
+                .split_whitespace()
+                .any(|id| bare_message_id(id) == wanted)
+        })
+}
+
+/// Returns raw bytes starting with the headers of a fetched message: the full
+/// message, the header section when only that was fetched, or a header section
+/// rebuilt from the ENVELOPE in envelope-only mode.
+pub(crate) fn raw_headers(message: &async_imap::types::Fetch) -> Option<Cow<'_, [u8]>> {
+    message
+        .body()
+        .or_else(|| message.header())
+        .map(Cow::Borrowed)
+        .or_else(|| {
+            message
+                .envelope()
+                .map(|envelope| envelope_headers(envelope).into())
+        })
+}
+
+/// Writes the fields of an IMAP ENVELOPE as an RFC 5322 header section.
+///
+/// Values are copied undecoded, so encoded words are decoded by the header
+/// parser as for a fetched header section.
+fn envelope_headers(envelope: &Envelope<'_>) -> Vec<u8> {
+    fn field(raw: &mut Vec<u8>, name: &str, value: Option<&[u8]>) {
+        let Some(value) = value else {
+            return;
+        };
+        raw.extend_from_slice(name.as_bytes());
+        raw.extend_from_slice(b": ");
+        // Literals may contain line breaks, which would end the field early
+        raw.extend(
+            value
+                .iter()
+                .map(|&b| if b == b'\r' || b == b'\n' { b' ' } else { b }),
+        );
+        raw.extend_from_slice(b"\r\n");
+    }
+
+    let mut raw = Vec::new();
+    field(&mut raw, "Date", envelope.date.as_deref());
+    field(&mut raw, "Subject", envelope.subject.as_deref());
+    for (name, addresses) in [
+        ("From", &envelope.from),
+        ("To", &envelope.to),
+        ("Cc", &envelope.cc),
+    ] {
+        let list = addresses.as_deref().map(address_list);
+        field(&mut raw, name, list.as_deref());
+    }
+    field(&mut raw, "In-Reply-To", envelope.in_reply_to.as_deref());
+    field(&mut raw, "Message-ID", envelope.message_id.as_deref());
+    raw.extend_from_slice(b"\r\n");
+    raw
+}
+
+/// Formats ENVELOPE addresses as an address list (`"Name" <mailbox@host>, ...`).
+///
+/// Group markers, which have no host, are left out.
+fn address_list(addresses: &[Address<'_>]) -> Vec<u8> {
+    let mut list = Vec::new();
+    for address in addresses {
+        let (Some(mailbox), Some(host)) = (&address.mailbox, &address.host) else {
+            continue;
+        };
+        if !list.is_empty() {
+            list.extend_from_slice(b", ");
+        }
+        if let Some(name) = &address.name {
+            list.push(b'"');
+            for &b in name.iter() {
+                if b == b'"' || b == b'\\' {
+                    list.push(b'\\');
+                }
+                list.push(b);
+            }
+            list.extend_from_slice(b"\" ");
+        }
+        list.push(b'<');
+        list.extend_from_slice(mailbox);
+        list.push(b'@');
+        list.extend_from_slice(host);
+        list.push(b'>');
+    }
+    list
+}
+
+/// Appends the subject and senders of an ENVELOPE to `text`, one per line.
+fn envelope_text(envelope: &Envelope<'_>, text: &mut String) {
+    let raw = envelope_headers(envelope);
+    let Ok((headers, _)) = parse_headers(&raw) else {
+        return;
+    };
+    for name in ["Subject", "From"] {
+        if let Some(value) = headers.get_first_value(name) {
+            text.push_str(&value);
+            text.push('\n');
+        }
+    }
+}
+
+/// Summarizes a fetched message from its headers.
+pub(crate) fn summarize(message: &async_imap::types::Fetch) -> MessageSummary {
+    summarize_headers(
+        message.uid.unwrap_or_default(),
+        &raw_headers(message).unwrap_or_default(),
+        message.internal_date(),
+    )
+}
+
+/// Summarizes the message with UID `uid` from its raw headers.
+///
+/// `internal_date` stands in for a missing or unparsable `Date` header.
+fn summarize_headers(
+    uid: u32,
+    raw: &[u8],
+    internal_date: Option<DateTime<FixedOffset>>,
+) -> MessageSummary {
+    let headers = parse_headers(raw)
+        .map(|(headers, _)| headers)
+        .unwrap_or_default();
+    MessageSummary {
+        uid,
+        from: header_addresses(&headers, &["From"]),
+        subject: headers.get_first_value("Subject"),
+        date: headers
+            .get_first_value("Date")
+            .and_then(|date| parse_date(&date))
+            .or(internal_date),
+    }
+}
+
+/// Returns the decoded `Subject` header of a raw message.
+fn header_subject(raw: &[u8]) -> Option<String> {
+    let (headers, _) = parse_headers(raw).ok()?;
+    headers.iter().find_map(|header| {
+        header
+            .get_key_ref()
+            .eq_ignore_ascii_case("Subject")
+            .then(|| header.get_value())
+    })
+}
+
+/// Returns the Message-ID of a fetched message, without angle brackets.
+pub(crate) fn message_id(message: &async_imap::types::Fetch) -> Option<String> {
+    let raw = raw_headers(message)?;
+    let (headers, _) = parse_headers(&raw).ok()?;
+    headers
+        .get_first_value("Message-ID")
+        .map(|id| bare_message_id(&id).to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// Strips surrounding whitespace and angle brackets from a Message-ID.
+pub(crate) fn bare_message_id(message_id: &str) -> &str {
+    message_id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+}
+
+/// Cuts `raw` off after `limit` bytes, logging a warning when it does.
+fn truncate(uid: Option<u32>, raw: &[u8], limit: Option<usize>) -> &[u8] {
+    match limit {
+        Some(limit) if raw.len() > limit => {
             warn!(
                 uid,
-                error = %e,
-                "Failed to parse email, skipping message"
+                size = raw.len(),
+                limit,
+                "Email exceeds the parse size limit, truncating"
             );
-            return ExtractResult::ParseError;
+            &raw[..limit]
         }
-    };
+        _ => raw,
+    }
+}
 
-    // Try to get the body, handling multipart messages
-    let text = match extract_body_text(&parsed) {
-        Ok(t) => t,
+/// Boundary of the multipart container [`fetched_sections`] puts body parts in.
+const PARTS_BOUNDARY: &str = "email-sync-fetched-parts";
+
+/// Rebuilds a parsable message from the sections fetched with custom `items`,
+/// or returns `None` if the message has no text or body part (e.g. only its
+/// header was fetched).
+fn fetched_sections(message: &async_imap::types::Fetch, items: &FetchItems) -> Option<Vec<u8>> {
+    let parts: Vec<_> = items
+        .parts()
+        .iter()
+        .filter_map(|path| {
+            let data = message.section(&SectionPath::Part(path.clone(), None))?;
+            let mime =
+                message.section(&SectionPath::Part(path.clone(), Some(MessageSection::Mime)));
+            Some((mime, data))
+        })
+        .collect();
+    assemble_sections(message.header(), message.text(), &parts)
+}
+
+/// Assembles fetched sections into a message.
+///
+/// The header section, if any, is followed by the text. Body parts, given with
+/// their MIME header if that was fetched too, are put in a `multipart/mixed`
+/// container instead, so they are decoded as in the whole message.
+fn assemble_sections(
+    header: Option<&[u8]>,
+    text: Option<&[u8]>,
+    parts: &[(Option<&[u8]>, &[u8])],
+) -> Option<Vec<u8>> {
+    let mut raw = Vec::new();
+    if parts.is_empty() {
+        let text = text?;
+        match header {
+            Some(header) => {
+                raw.extend_from_slice(header);
+                if !header.ends_with(b"\r\n\r\n") {
+                    raw.extend_from_slice(b"\r\n");
+                }
+            }
+            None => raw.extend_from_slice(b"\r\n"),
+        }
+        raw.extend_from_slice(text);
+        return Some(raw);
+    }
+
+    raw.extend_from_slice(
+        format!("Content-Type: multipart/mixed; boundary=\"{PARTS_BOUNDARY}\"\r\n\r\n").as_bytes(),
+    );
+    for (mime, data) in parts {
+        raw.extend_from_slice(format!("--{PARTS_BOUNDARY}\r\n").as_bytes());
+        // Without its MIME header, a part is taken as plain text
+        raw.extend_from_slice(mime.unwrap_or(b"\r\n"));
+        raw.extend_from_slice(data);
+        raw.extend_from_slice(b"\r\n");
+    }
+    raw.extend_from_slice(format!("--{PARTS_BOUNDARY}--\r\n").as_bytes());
+    Some(raw)
+}
+
+/// Parses a raw message and appends its body text to `text`.
+fn parse_body_text(
+    uid: Option<u32>,
+    body: &[u8],
+    text: &mut String,
+) -> std::result::Result<(), ExtractResult<'static>> {
+    let parsed = match parse_mail(body) {
+        Ok(p) => p,
         Err(e) => {
             warn!(
                 uid,
                 error = %e,
-                "Failed to extract body from email, skipping message"
+                "Failed to parse email, skipping message"
             );
-            return ExtractResult::ParseError;
+            return Err(ExtractResult::ParseError);
         }
     };
 
-    if let Some(result) = pattern_matcher.find_match(&text) {
-        debug!(
-            uid,
-            matcher = %pattern_matcher.description(),
-            matched_len = result.len(),
-            "Found match in email"
-        );
-        // Convert the Cow result to an owned Cow since we can't keep
-        // borrowing from `text` (a local variable)
-        ExtractResult::Match(Cow::Owned(result.into_owned()))
-    } else {
-        debug!(
+    // Try to get the body, handling multipart messages
+    extract_body_text(&parsed, text).map_err(|e| {
+        warn!(
             uid,
-            matcher = %pattern_matcher.description(),
-            "No match found in email body"
+            error = %e,
+            "Failed to extract body from email, skipping message"
         );
-        ExtractResult::NoMatch
+        ExtractResult::ParseError
+    })?;
+
+    // Expose QR code payloads of image attachments to the matchers
+    #[cfg(feature = "qr")]
+    for payload in crate::qr::decode_qr_payloads(&parsed) {
+        debug!(uid, "Decoded QR code in image attachment");
+        text.push('\n');
+        text.push_str(&payload);
     }
+
+    Ok(())
 }
 
-/// Extracts text content from a parsed email, handling multipart messages.
+/// Appends the text content of a parsed email to `text`, handling multipart messages.
 fn extract_body_text(
     parsed: &mailparse::ParsedMail<'_>,
-) -> Result<String, mailparse::MailParseError> {
+    text: &mut String,
+) -> Result<(), mailparse::MailParseError> {
     // If the message has subparts, try to find text content
     if !parsed.subparts.is_empty() {
         // Look for text/plain first, then text/html
         for part in &parsed.subparts {
             let content_type = part.ctype.mimetype.to_lowercase();
-            if content_type == "text/plain" || content_type == "text/html" {
-                if let Ok(body) = part.get_body() {
-                    return Ok(body);
-                }
+            if (content_type == "text/plain" || content_type == "text/html")
+                && part_text(part, text).is_ok()
+            {
+                return Ok(());
             }
         }
 
         // If no text parts found, try to get body from first subpart
         if let Some(first_part) = parsed.subparts.first() {
-            return extract_body_text(first_part);
+            return extract_body_text(first_part, text);
         }
     }
 
     // Single part message or fallback
-    parsed.get_body()
+    part_text(parsed, text)
+}
+
+/// Appends the decoded body of a part to `text`, with HTML entities decoded in HTML
+/// parts. Nothing is appended if the body cannot be decoded.
+///
+/// Links in HTML carry `&amp;` between query parameters; decoding makes matched
+/// URLs usable as-is. Plain text parts get any embedded encoded blob decoded and
+/// appended, see [`decode_embedded_blobs`].
+fn part_text(
+    part: &mailparse::ParsedMail<'_>,
+    text: &mut String,
+) -> Result<(), mailparse::MailParseError> {
+    let body = part.get_body()?;
+    if part.ctype.mimetype.eq_ignore_ascii_case("text/html") {
+        decode_html_entities_into(&body, text);
+    } else {
+        text.push_str(&body);
+        if let Some(decoded) = decode_embedded_blobs(&body) {
+            text.push('\n');
+            text.push_str(&decoded);
+        }
+    }
+    Ok(())
+}
+
+/// Minimum length of an embedded base64 blob, so ordinary tokens are left alone.
+const MIN_BLOB_LEN: usize = 64;
+
+/// Minimum number of `=XX` escapes and soft line breaks for text to be taken as
+/// quoted-printable.
+const MIN_QP_ESCAPES: usize = 3;
+
+/// Decodes base64 or quoted-printable content wrapped inside a plain text body.
+///
+/// Some providers put the real (often HTML) message inside a base64 blob of a
+/// text/plain part, or send quoted-printable text without declaring the
+/// transfer encoding. Returns the decoded text of such blobs, to be searched
+/// in addition to the body. The detection is heuristic: base64 runs must decode
+/// to mostly printable UTF-8 text.
+fn decode_embedded_blobs(text: &str) -> Option<String> {
+    let mut decoded: Vec<String> = base64_runs(text)
+        .filter_map(|run| decode_base64_text(&run))
+        .collect();
+
+    if looks_quoted_printable(text) {
+        if let Ok(bytes) = quoted_printable::decode(text, quoted_printable::ParseMode::Robust) {
+            decoded.push(String::from_utf8_lossy(&bytes).into_owned());
+        }
+    }
+
+    if decoded.is_empty() {
+        return None;
+    }
+    let decoded = decoded.join("\n");
+    Some(match decode_html_entities(&decoded) {
+        Cow::Borrowed(_) => decoded,
+        Cow::Owned(html) => html,
+    })
+}
+
+/// Returns runs of consecutive lines made only of base64 characters, joined,
+/// that are long enough to be an encoded blob.
+fn base64_runs(text: &str) -> impl Iterator<Item = String> + '_ {
+    let is_base64_line = |line: &&str| {
+        !line.is_empty()
+            && line
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+    };
+
+    let mut lines = text.lines().map(str::trim).peekable();
+    std::iter::from_fn(move || loop {
+        // Skip to the next base64 line, then take the whole run
+        while lines.next_if(|line| !is_base64_line(line)).is_some() {}
+        lines.peek()?;
+
+        let mut run = String::new();
+        while let Some(line) = lines.next_if(is_base64_line) {
+            run.push_str(line);
+        }
+        if run.len() >= MIN_BLOB_LEN {
+            return Some(run);
+        }
+    })
+}
+
+/// Decodes a base64 run, keeping it only if the result is mostly printable text.
+fn decode_base64_text(run: &str) -> Option<String> {
+    let bytes = STANDARD_NO_PAD.decode(run.trim_end_matches('=')).ok()?;
+    let text = String::from_utf8(bytes).ok()?;
+
+    let total = text.chars().count();
+    let printable = text
+        .chars()
+        .filter(|c| !c.is_control() || c.is_ascii_whitespace())
+        .count();
+    (printable * 10 >= total * 9).then_some(text)
+}
+
+/// Returns `true` if `text` has enough `=XX` escapes or soft line breaks to be
+/// quoted-printable.
+fn looks_quoted_printable(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let escapes = bytes
+        .windows(3)
+        .filter(|window| {
+            window[0] == b'='
+                && ((window[1].is_ascii_digit() || (b'A'..=b'F').contains(&window[1]))
+                    && (window[2].is_ascii_digit() || (b'A'..=b'F').contains(&window[2]))
+                    || window[1] == b'\n'
+                    || window[1..] == *b"\r\n")
+        })
+        .count();
+    escapes >= MIN_QP_ESCAPES
+}
+
+/// Named entities decoded by [`decode_html_entities`]; others are kept as-is.
+const HTML_ENTITIES: [(&str, char); 14] = [
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{A0}'),
+    ("equals", '='),
+    ("quest", '?'),
+    ("num", '#'),
+    ("percnt", '%'),
+    ("sol", '/'),
+    ("colon", ':'),
+    ("period", '.'),
+    ("lowbar", '_'),
+];
+
+/// Decodes numeric character references and common named entities.
+pub(crate) fn decode_html_entities(html: &str) -> Cow<'_, str> {
+    if !html.contains('&') {
+        return Cow::Borrowed(html);
+    }
+
+    let mut decoded = String::with_capacity(html.len());
+    decode_html_entities_into(html, &mut decoded);
+    Cow::Owned(decoded)
+}
+
+/// Appends `html` to `decoded` with its entities decoded.
+fn decode_html_entities_into(html: &str, decoded: &mut String) {
+    let mut rest = html;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // Entities are short; a `;` further away means this `&` is literal text
+        let entity = rest[1..]
+            .char_indices()
+            .take(32)
+            .find(|&(_, c)| c == ';')
+            .and_then(|(end, _)| Some((decode_entity(&rest[1..=end])?, end + 2)));
+
+        let (c, len) = entity.unwrap_or(('&', 1));
+        decoded.push(c);
+        rest = &rest[len..];
+    }
+    decoded.push_str(rest);
+}
+
+/// Decodes the name of one entity (without `&` and `;`).
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+
+    HTML_ENTITIES
+        .iter()
+        .find(|(entity, _)| *entity == name)
+        .map(|&(_, c)| c)
 }
 
 #[cfg(test)]
@@ -108,30 +964,277 @@ mod tests {
     use super::*;
     use crate::matcher::OtpMatcher;
 
+    #[test]
+    fn test_parse_raw_message() {
+        let raw = b"From: \"Example\" <noreply@example.com>\r\n\
+            To: a@example.com, b@example.com\r\n\
+            Subject: Your code\r\n\
+            Message-ID: <abc@host>\r\n\
+            Date: Tue, 1 Jul 2025 12:00:00 +0200\r\n\r\n\
+            Your code is 123456";
+        let message = parse_raw_message(7, raw).unwrap();
+
+        assert_eq!(message.uid, 7);
+        assert_eq!(message.message_id.as_deref(), Some("abc@host"));
+        assert_eq!(message.from, ["noreply@example.com"]);
+        assert_eq!(message.to, ["a@example.com", "b@example.com"]);
+        assert!(message.cc.is_empty());
+        assert_eq!(message.subject.as_deref(), Some("Your code"));
+        assert_eq!(
+            message.date.map(|date| date.to_rfc3339()).as_deref(),
+            Some("2025-07-01T12:00:00+02:00")
+        );
+        assert_eq!(message.text_body.as_deref(), Some("Your code is 123456"));
+        assert_eq!(message.html_body, None);
+        assert_eq!(message.header("message-id"), Some("<abc@host>"));
+        assert_eq!((message.internal_date, message.size), (None, None));
+    }
+
+    fn address<'a>(name: Option<&'a str>, mailbox: &'a str, host: &'a str) -> Address<'a> {
+        Address {
+            name: name.map(|name| Cow::Borrowed(name.as_bytes())),
+            adl: None,
+            mailbox: Some(Cow::Borrowed(mailbox.as_bytes())),
+            host: Some(Cow::Borrowed(host.as_bytes())),
+        }
+    }
+
+    #[test]
+    fn test_assemble_sections() {
+        let header = b"Subject: Code\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n";
+        let raw = assemble_sections(Some(header), Some(b"t=3Dabc\r\n"), &[]).unwrap();
+        let mut text = String::new();
+        parse_body_text(None, &raw, &mut text).unwrap();
+        assert_eq!(text.trim(), "t=abc");
+
+        let mime = b"Content-Type: text/plain\r\nContent-Transfer-Encoding: base64\r\n\r\n";
+        let raw = assemble_sections(None, None, &[(Some(mime), b"Q29kZSA0ODI5MTM=")]).unwrap();
+        text.clear();
+        parse_body_text(None, &raw, &mut text).unwrap();
+        assert_eq!(text.trim(), "Code 482913");
+
+        let raw = assemble_sections(None, None, &[(None, b"Plain part")]).unwrap();
+        text.clear();
+        parse_body_text(None, &raw, &mut text).unwrap();
+        assert_eq!(text.trim(), "Plain part");
+
+        // A header alone has nothing to match on
+        assert!(assemble_sections(Some(header), None, &[]).is_none());
+    }
+
+    #[test]
+    fn test_envelope_headers() {
+        let envelope = Envelope {
+            date: Some(Cow::Borrowed(b"Tue, 1 Jul 2025 12:00:00 +0200")),
+            subject: Some(Cow::Borrowed(b"=?utf-8?Q?Code_482913?=")),
+            from: Some(vec![address(Some("Git \"Hub\""), "noreply", "github.com")]),
+            sender: None,
+            reply_to: None,
+            to: Some(vec![
+                address(None, "user+run42", "example.com"),
+                address(None, "other", "example.com"),
+            ]),
+            cc: None,
+            bcc: None,
+            in_reply_to: None,
+            message_id: Some(Cow::Borrowed(b"<abc@host>\r\nX-Injected: 1")),
+        };
+
+        let raw = envelope_headers(&envelope);
+        let summary = summarize_headers(1, &raw, None);
+        assert_eq!(summary.from, ["noreply@github.com"]);
+        assert_eq!(summary.subject.as_deref(), Some("Code 482913"));
+        assert!(summary.date.is_some());
+        assert!(headers_mention_recipient(&raw, "USER+run42@example.com"));
+        let (headers, _) = parse_headers(&raw).unwrap();
+        assert_eq!(headers.get_first_value("X-Injected"), None);
+
+        let mut text = String::new();
+        envelope_text(&envelope, &mut text);
+        assert!(text.starts_with("Code 482913\n"));
+        assert!(text.contains("noreply@github.com"));
+    }
+
+    #[test]
+    fn test_summarize_headers() {
+        let raw = b"From: Example <noreply@example.com>\r\n\
+            Subject: =?utf-8?Q?Caf=C3=A9?=\r\n\
+            Date: Tue, 1 Jul 2025 12:00:00 +0200\r\n\r\n";
+        let summary = summarize_headers(3, raw, None);
+        assert_eq!(summary.uid, 3);
+        assert_eq!(summary.from, ["noreply@example.com"]);
+        assert_eq!(summary.subject.as_deref(), Some("Café"));
+        assert_eq!(
+            summary.date.map(|date| date.to_rfc3339()).as_deref(),
+            Some("2025-07-01T12:00:00+02:00")
+        );
+
+        let arrived = DateTime::parse_from_rfc3339("2025-07-02T08:00:00Z").ok();
+        let summary = summarize_headers(4, b"X-Mailer: test\r\n\r\n", arrived);
+        assert!(summary.from.is_empty());
+        assert_eq!(summary.subject, None);
+        assert_eq!(summary.date, arrived);
+    }
+
+    #[test]
+    fn test_parse_email_garbage() {
+        let inputs: [&[u8]; 5] = [
+            b"",
+            b"\xff\xfe\x00",
+            b"Content-Type: multipart/mixed; boundary=x\r\n\r\n--x\r\n",
+            b"Subject: =?utf-8?B?not base64?=\r\n\r\nbody",
+            b"Content-Transfer-Encoding: base64\r\n\r\n!!!",
+        ];
+        for raw in inputs {
+            if let Ok(message) = parse_email(raw) {
+                assert_eq!(message.uid, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_raw_message_parts() {
+        let raw = b"Content-Type: multipart/mixed; boundary=outer\r\n\r\n\
+            --outer\r\n\
+            Content-Type: multipart/alternative; boundary=inner\r\n\r\n\
+            --inner\r\nContent-Type: text/plain\r\n\r\nPlain\r\n\
+            --inner\r\nContent-Type: text/html\r\n\r\n<p>Html</p>\r\n\
+            --inner--\r\n\
+            --outer\r\n\
+            Content-Type: text/plain\r\n\
+            Content-Disposition: attachment; filename=\"notes.txt\"\r\n\r\n\
+            notes\r\n\
+            --outer--\r\n";
+        let message = parse_raw_message(1, raw).unwrap();
+
+        assert_eq!(message.text_body.as_deref(), Some("Plain"));
+        assert_eq!(message.html_body.as_deref(), Some("<p>Html</p>"));
+        assert_eq!(message.attachments.len(), 1);
+        let attachment = &message.attachments[0];
+        assert_eq!(attachment.filename.as_deref(), Some("notes.txt"));
+        assert_eq!(attachment.content_type, "text/plain");
+        assert_eq!(attachment.size, 5);
+    }
+
+    fn body_text(parsed: &mailparse::ParsedMail<'_>) -> String {
+        let mut text = String::new();
+        extract_body_text(parsed, &mut text).unwrap();
+        text
+    }
+
     #[test]
     fn test_extract_body_text_simple() {
         let raw = b"From: test@example.com\r\nTo: user@example.com\r\n\r\nYour code is 123456.";
         let parsed = parse_mail(raw).unwrap();
-        let text = extract_body_text(&parsed).unwrap();
+        let text = body_text(&parsed);
         assert!(text.contains("123456"));
     }
 
+    #[test]
+    fn test_html_entities_decoded() {
+        let raw = b"Content-Type: text/html\r\n\r\n<a href=\"https://example.com/verify?id=1&amp;token=abc&#x26;x=&#49;\">Verify</a> R&D &unknown; &amp";
+        let parsed = parse_mail(raw).unwrap();
+        let text = body_text(&parsed);
+        assert_eq!(
+            text,
+            "<a href=\"https://example.com/verify?id=1&token=abc&x=1\">Verify</a> R&D &unknown; &amp"
+        );
+
+        // Plain text parts are left alone
+        let raw = b"Content-Type: text/plain\r\n\r\nTom &amp; Jerry";
+        let parsed = parse_mail(raw).unwrap();
+        assert_eq!(body_text(&parsed), "Tom &amp; Jerry");
+    }
+
+    #[test]
+    fn test_embedded_base64_html_decoded() {
+        let html = r#"<p>Confirm: <a href="https://example.com/confirm?a=1&amp;b=2">link</a></p>"#;
+        let blob = base64::engine::general_purpose::STANDARD.encode(html);
+        let wrapped = blob
+            .as_bytes()
+            .chunks(76)
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        let raw = format!("Content-Type: text/plain\r\n\r\nView this email:\r\n{wrapped}");
+
+        let parsed = parse_mail(raw.as_bytes()).unwrap();
+        let text = body_text(&parsed);
+        assert!(text.contains("https://example.com/confirm?a=1&b=2"));
+    }
+
+    #[test]
+    fn test_embedded_quoted_printable_decoded() {
+        let raw = b"Content-Type: text/plain\r\n\r\nYour code is 48=\r\n2915. Visit https://example.com/?t=3Dabc&u=3D1";
+        let parsed = parse_mail(raw).unwrap();
+        let text = body_text(&parsed);
+        assert!(text.contains("482915"));
+        assert!(text.contains("?t=abc&u=1"));
+    }
+
+    #[test]
+    fn test_tokens_are_not_blobs() {
+        // A long hex token decodes to binary, not text
+        let token = "a3f1".repeat(20);
+        assert_eq!(decode_embedded_blobs(&format!("Token:\n{token}\n")), None);
+        assert_eq!(decode_embedded_blobs("x=1 y=2 a==b"), None);
+    }
+
     #[test]
     fn test_matcher_integration() {
         let raw = b"From: test@example.com\r\nTo: user@example.com\r\n\r\nYour verification code is 654321.";
         let parsed = parse_mail(raw).unwrap();
-        let text = extract_body_text(&parsed).unwrap();
+        let text = body_text(&parsed);
 
         let matcher = OtpMatcher::six_digit();
         let result = matcher.find_match(&text);
         assert_eq!(result.as_deref(), Some("654321"));
     }
 
+    #[test]
+    fn test_headers_mention_recipient() {
+        let raw = b"From: test@example.com\r\nTo: Runner <User+Run42@example.com>, other@example.com\r\n\r\nBody";
+        assert!(headers_mention_recipient(raw, "user+run42@example.com"));
+        assert!(!headers_mention_recipient(raw, "user+run7@example.com"));
+
+        let raw = b"Delivered-To: user+run7@example.com\r\nTo: list@example.com\r\n\r\nBody";
+        assert!(headers_mention_recipient(raw, "user+run7@example.com"));
+        assert!(!headers_mention_recipient(raw, "test@example.com"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate(None, b"abcdef", Some(4)), b"abcd");
+        assert_eq!(truncate(None, b"abcdef", Some(10)), b"abcdef");
+        assert_eq!(truncate(None, b"abcdef", None), b"abcdef");
+    }
+
+    #[test]
+    fn test_header_subject() {
+        let raw = b"From: a@example.com\r\nSubject: =?UTF-8?Q?123456_is_your_code?=\r\n\r\n";
+        assert_eq!(header_subject(raw).as_deref(), Some("123456 is your code"));
+        assert_eq!(header_subject(b"From: a@example.com\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_headers_reference() {
+        let raw = b"Message-ID: <c@example.com>\r\nIn-Reply-To: <b@example.com>\r\nReferences: <a@example.com>\r\n <b@example.com>\r\n\r\nBody";
+        assert!(headers_reference(raw, "<a@example.com>"));
+        assert!(headers_reference(raw, "b@example.com"));
+        assert!(!headers_reference(raw, "<c@example.com>"));
+    }
+
     #[test]
     fn test_extract_result_variants() {
         // Test that ExtractResult has the expected variants
-        let match_result: ExtractResult<'_> = ExtractResult::Match(Cow::Borrowed("test"));
-        assert!(matches!(match_result, ExtractResult::Match(_)));
+        let match_result: ExtractResult<'_> = ExtractResult::Match {
+            index: 0,
+            details: MatchDetails::new("test"),
+        };
+        assert!(matches!(
+            match_result,
+            ExtractResult::Match { index: 0, .. }
+        ));
 
         let no_match: ExtractResult<'_> = ExtractResult::NoMatch;
         assert!(matches!(no_match, ExtractResult::NoMatch));
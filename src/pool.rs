@@ -0,0 +1,541 @@
+//! Pool of accounts handed out as exclusive leases.
+//!
+//! Test farms run many jobs in parallel against a fixed set of inboxes. Two jobs
+//! waiting on the same inbox steal each other's emails, so [`AccountPool`] gives
+//! each job exclusive use of one account until its [`AccountLease`] is dropped.
+//! An account can rest for a cooldown after each lease, e.g. to let late
+//! emails of the previous job arrive, or to back off after a rate limit.
+//!
+//! Connections opened through [`AccountLease::connect`] also obey per-account
+//! [`AccountLimits`]: at most [`max_sessions`](AccountLimits::max_sessions)
+//! open at once (Gmail refuses more than 15 simultaneous IMAP connections per
+//! account), and connects spaced by at least
+//! [`min_connect_interval`](AccountLimits::min_connect_interval). A connect
+//! beyond a limit waits instead of failing.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::matcher::OtpMatcher;
+//! use email_sync::{AccountLimits, AccountPool, ImapConfig};
+//! use std::time::Duration;
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let accounts = (1..=4)
+//!     .map(|n| {
+//!         ImapConfig::builder()
+//!             .email(format!("ci-{n}@example.com"))
+//!             .password("secret")
+//!             .build()
+//!     })
+//!     .collect::<email_sync::Result<Vec<_>>>()?;
+//! let pool = AccountPool::new(accounts)
+//!     .cooldown(Duration::from_secs(30))
+//!     .limits(AccountLimits {
+//!         max_sessions: 5,
+//!         min_connect_interval: Duration::from_secs(1),
+//!     });
+//!
+//! // In each job:
+//! let lease = pool.checkout().await?;
+//! let mut client = lease.connect().await?;
+//! // ... trigger an email to lease.config().email() ...
+//! let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+//! client.logout().await?;
+//! drop(lease); // the account is available again after the cooldown
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::ImapEmailClient;
+use crate::config::ImapConfig;
+use crate::error::{Error, Result};
+use crate::runtime;
+use std::ops::{Deref, DerefMut};
+use std::pin::pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// A set of accounts, each leased to one user at a time.
+///
+/// The pool is cheap to clone; clones share the leases, so one pool can be
+/// passed to every job.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct AccountPool {
+    inner: Arc<PoolInner>,
+}
+
+/// Limits on the connections to each account of an [`AccountPool`].
+///
+/// # Example
+///
+/// ```
+/// use email_sync::AccountLimits;
+/// use std::time::Duration;
+///
+/// let limits = AccountLimits {
+///     min_connect_interval: Duration::from_millis(500),
+///     ..AccountLimits::default()
+/// };
+/// assert_eq!(limits.max_sessions, 10);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountLimits {
+    /// Maximum number of connections open at once (at least 1).
+    pub max_sessions: usize,
+    /// Minimum time between two connects.
+    pub min_connect_interval: Duration,
+}
+
+impl Default for AccountLimits {
+    /// 10 sessions, below Gmail's limit of 15 with room for other clients, and
+    /// no spacing between connects.
+    fn default() -> Self {
+        Self {
+            max_sessions: 10,
+            min_connect_interval: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    accounts: Vec<ImapConfig>,
+    /// How long an account rests after a lease ends.
+    cooldown: Duration,
+    limits: AccountLimits,
+    state: Mutex<Vec<AccountState>>,
+    /// Open connections of each account, bounded by `limits.max_sessions`.
+    sessions: Vec<Arc<Semaphore>>,
+    /// Woken whenever a lease ends.
+    released: Notify,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct AccountState {
+    leased: bool,
+    /// End of the cooldown after the last lease.
+    available_at: Option<Instant>,
+    /// Time of the last connect, or of the next one already scheduled.
+    last_connect: Option<Instant>,
+}
+
+/// Exclusive use of one account of an [`AccountPool`].
+///
+/// The account returns to the pool when the lease is dropped, and can be
+/// checked out again once its cooldown has passed.
+#[derive(Debug)]
+pub struct AccountLease {
+    pool: AccountPool,
+    index: usize,
+    /// Cooldown replacing the pool's one for this lease.
+    cooldown: Option<Duration>,
+}
+
+/// A client connected through [`AccountLease::connect`].
+///
+/// Dereferences to [`ImapEmailClient`]. Counts towards
+/// [`AccountLimits::max_sessions`] until dropped.
+#[derive(Debug)]
+pub struct PooledClient {
+    client: ImapEmailClient,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl AccountPool {
+    /// Creates a pool of `accounts` without cooldown.
+    #[must_use]
+    pub fn new(accounts: Vec<ImapConfig>) -> Self {
+        Self::build(accounts, Duration::ZERO, AccountLimits::default())
+    }
+
+    /// Rests each account for `cooldown` after its lease ends.
+    ///
+    /// Call before sharing the pool: clones made earlier keep the previous
+    /// settings and their own leases.
+    #[must_use]
+    pub fn cooldown(self, cooldown: Duration) -> Self {
+        Self::build(self.inner.accounts.clone(), cooldown, self.inner.limits)
+    }
+
+    /// Sets the limits on the connections to each account.
+    ///
+    /// Like [`cooldown`](Self::cooldown), call before sharing the pool.
+    #[must_use]
+    pub fn limits(self, limits: AccountLimits) -> Self {
+        Self::build(self.inner.accounts.clone(), self.inner.cooldown, limits)
+    }
+
+    fn build(accounts: Vec<ImapConfig>, cooldown: Duration, limits: AccountLimits) -> Self {
+        let max_sessions = limits.max_sessions.clamp(1, Semaphore::MAX_PERMITS);
+        Self {
+            inner: Arc::new(PoolInner {
+                state: Mutex::new(vec![AccountState::default(); accounts.len()]),
+                sessions: (0..accounts.len())
+                    .map(|_| Arc::new(Semaphore::new(max_sessions)))
+                    .collect(),
+                accounts,
+                cooldown,
+                limits,
+                released: Notify::new(),
+            }),
+        }
+    }
+
+    /// Returns the accounts of the pool.
+    #[must_use]
+    pub fn accounts(&self) -> &[ImapConfig] {
+        &self.inner.accounts
+    }
+
+    /// Returns the number of accounts in the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inner.accounts.len()
+    }
+
+    /// Returns `true` if the pool has no accounts.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.inner.accounts.is_empty()
+    }
+
+    /// Returns the number of accounts that can be checked out right now:
+    /// not leased and not cooling down.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        let now = Instant::now();
+        self.lock()
+            .iter()
+            .filter(|account| account.is_available(now))
+            .count()
+    }
+
+    /// Leases an account, waiting until one is free and past its cooldown.
+    ///
+    /// Among available accounts, the one idle the longest is picked, spreading
+    /// the load over the pool. Waits indefinitely; wrap the call in a timeout to
+    /// bound it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`] if the pool has no accounts.
+    pub async fn checkout(&self) -> Result<AccountLease> {
+        if self.is_empty() {
+            return Err(Error::InvalidConfig {
+                message: "account pool is empty".into(),
+            });
+        }
+
+        loop {
+            let mut released = pin!(self.inner.released.notified());
+            // Registered before checking, so a release in between is not missed
+            released.as_mut().enable();
+
+            let next_cooldown_end = {
+                let now = Instant::now();
+                let mut state = self.lock();
+                if let Some(index) = pick(&state, now) {
+                    state[index].leased = true;
+                    return Ok(self.lease(index));
+                }
+                state
+                    .iter()
+                    .filter(|account| !account.leased)
+                    .filter_map(|account| account.available_at)
+                    .min()
+            };
+
+            match next_cooldown_end {
+                Some(end) => {
+                    let wait = end.saturating_duration_since(Instant::now());
+                    let _ = runtime::timeout(wait, released).await;
+                }
+                None => released.await,
+            }
+        }
+    }
+
+    /// Leases an account if one is available right now.
+    #[must_use]
+    pub fn try_checkout(&self) -> Option<AccountLease> {
+        let mut state = self.lock();
+        let index = pick(&state, Instant::now())?;
+        state[index].leased = true;
+        drop(state);
+        Some(self.lease(index))
+    }
+
+    fn lease(&self, index: usize) -> AccountLease {
+        debug!(account = %self.inner.accounts[index].email(), "Account checked out");
+        AccountLease {
+            pool: self.clone(),
+            index,
+            cooldown: None,
+        }
+    }
+
+    /// Ends the lease of the account at `index`.
+    fn release(&self, index: usize, cooldown: Duration) {
+        let mut state = self.lock();
+        state[index].leased = false;
+        state[index].available_at = Some(cooldown_end(Instant::now(), cooldown));
+        drop(state);
+        debug!(
+            account = %self.inner.accounts[index].email(),
+            cooldown_secs = cooldown.as_secs(),
+            "Account released"
+        );
+        self.inner.released.notify_waiters();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<AccountState>> {
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl AccountState {
+    fn is_available(&self, now: Instant) -> bool {
+        !self.leased && self.available_at.is_none_or(|at| at <= now)
+    }
+}
+
+/// Returns when a cooldown of `cooldown` starting at `now` ends.
+///
+/// Cooldowns beyond what `Instant` can represent end in about 30 years instead,
+/// which keeps the account out of rotation rather than releasing it at once.
+fn cooldown_end(now: Instant, cooldown: Duration) -> Instant {
+    const FAR_FUTURE: Duration = Duration::from_secs(86_400 * 365 * 30);
+
+    now.checked_add(cooldown)
+        .unwrap_or_else(|| now + FAR_FUTURE)
+}
+
+/// Returns the index of the available account idle the longest.
+fn pick(state: &[AccountState], now: Instant) -> Option<usize> {
+    state
+        .iter()
+        .enumerate()
+        .filter(|(_, account)| account.is_available(now))
+        .min_by_key(|(_, account)| account.available_at)
+        .map(|(index, _)| index)
+}
+
+impl AccountLease {
+    /// Returns the configuration of the leased account.
+    #[must_use]
+    pub fn config(&self) -> &ImapConfig {
+        &self.pool.inner.accounts[self.index]
+    }
+
+    /// Returns the position of the leased account in [`AccountPool::accounts`].
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Connects to the leased account, within the pool's [`AccountLimits`].
+    ///
+    /// Waits for a session to close when
+    /// [`max_sessions`](AccountLimits::max_sessions) are open, then until
+    /// [`min_connect_interval`](AccountLimits::min_connect_interval) has passed
+    /// since the previous connect to the account.
+    ///
+    /// # Errors
+    ///
+    /// See [`ImapEmailClient::connect`].
+    pub async fn connect(&self) -> Result<PooledClient> {
+        let inner = &self.pool.inner;
+        let permit = Arc::clone(&inner.sessions[self.index])
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::ClientClosed)?;
+
+        let now = Instant::now();
+        let slot = {
+            let mut state = self.pool.lock();
+            let last_connect = &mut state[self.index].last_connect;
+            let slot = last_connect
+                .and_then(|last| last.checked_add(inner.limits.min_connect_interval))
+                .map_or(now, |earliest| earliest.max(now));
+            *last_connect = Some(slot);
+            slot
+        };
+        let wait = slot.saturating_duration_since(now);
+        if !wait.is_zero() {
+            debug!(
+                wait_ms = wait.as_millis(),
+                "Spacing connects to the account"
+            );
+            runtime::sleep(wait).await;
+        }
+
+        let client = ImapEmailClient::connect(self.config().clone()).await?;
+        Ok(PooledClient {
+            client,
+            _permit: permit,
+        })
+    }
+
+    /// Returns the number of connections to the account currently open through
+    /// [`connect`](Self::connect).
+    #[must_use]
+    pub fn open_sessions(&self) -> usize {
+        let inner = &self.pool.inner;
+        let max_sessions = inner.limits.max_sessions.clamp(1, Semaphore::MAX_PERMITS);
+        max_sessions - inner.sessions[self.index].available_permits()
+    }
+
+    /// Rests the account for `cooldown` after this lease instead of the pool's
+    /// cooldown, e.g. longer after the server rate-limited it.
+    pub fn cool_down(&mut self, cooldown: Duration) {
+        self.cooldown = Some(cooldown);
+    }
+}
+
+impl PooledClient {
+    /// Returns the client, which then no longer counts towards
+    /// [`AccountLimits::max_sessions`].
+    #[must_use]
+    pub fn into_inner(self) -> ImapEmailClient {
+        self.client
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = ImapEmailClient;
+
+    fn deref(&self) -> &ImapEmailClient {
+        &self.client
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut ImapEmailClient {
+        &mut self.client
+    }
+}
+
+impl Drop for AccountLease {
+    fn drop(&mut self) {
+        let cooldown = self.cooldown.unwrap_or(self.pool.inner.cooldown);
+        self.pool.release(self.index, cooldown);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(size: usize) -> AccountPool {
+        let accounts = (0..size)
+            .map(|n| {
+                ImapConfig::builder()
+                    .email(format!("ci-{n}@example.com"))
+                    .password("secret")
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+        AccountPool::new(accounts)
+    }
+
+    #[tokio::test]
+    async fn test_leases_are_exclusive() {
+        let pool = pool(2);
+        let first = pool.checkout().await.unwrap();
+        let second = pool.checkout().await.unwrap();
+        assert_ne!(first.index(), second.index());
+        assert_eq!(pool.available(), 0);
+        assert!(pool.try_checkout().is_none());
+
+        drop(first);
+        assert_eq!(pool.available(), 1);
+        assert!(pool.try_checkout().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_checkout_waits_for_release() {
+        let pool = pool(1);
+        let lease = pool.checkout().await.unwrap();
+
+        let waiter = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.checkout().await.map(|lease| lease.index()) }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        drop(lease);
+        assert_eq!(waiter.await.unwrap().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cooldown() {
+        let pool = pool(2).cooldown(Duration::from_millis(50));
+        let mut first = pool.checkout().await.unwrap();
+        let second = pool.checkout().await.unwrap();
+        first.cool_down(Duration::from_mins(1));
+        drop(first);
+        drop(second);
+        assert_eq!(pool.available(), 0);
+
+        // The account with the shorter cooldown comes back first
+        let started = Instant::now();
+        let lease = pool.checkout().await.unwrap();
+        assert_eq!(lease.index(), 1);
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_unrepresentable_cooldown_keeps_account_out() {
+        let pool = pool(1);
+        let mut lease = pool.checkout().await.unwrap();
+        lease.cool_down(Duration::MAX);
+        drop(lease);
+
+        assert_eq!(pool.available(), 0);
+        assert!(pool.try_checkout().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connects_are_spaced() {
+        // Nothing listens on the discard port, so each connect fails right away
+        let config = ImapConfig::builder()
+            .email("ci@example.com")
+            .password("secret")
+            .imap_host("127.0.0.1")
+            .imap_port(9)
+            .connect_attempts(1)
+            .build()
+            .unwrap();
+        let pool = AccountPool::new(vec![config]).limits(AccountLimits {
+            max_sessions: 1,
+            min_connect_interval: Duration::from_millis(50),
+        });
+        let lease = pool.checkout().await.unwrap();
+
+        let started = Instant::now();
+        assert!(lease.connect().await.is_err());
+        assert!(lease.connect().await.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(40));
+        assert_eq!(lease.open_sessions(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_empty_pool() {
+        let pool = AccountPool::new(Vec::new());
+        assert!(pool.is_empty());
+        assert!(matches!(
+            pool.checkout().await,
+            Err(Error::InvalidConfig { .. })
+        ));
+    }
+}
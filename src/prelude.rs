@@ -0,0 +1,31 @@
+//! Convenience re-exports for the most commonly used types.
+//!
+//! ```no_run
+//! use email_sync::prelude::*;
+//!
+//! # async fn example() -> Result<()> {
+//! let config = ImapConfig::builder()
+//!     .email("user@gmail.com")
+//!     .password("app-password")
+//!     .build()?;
+//!
+//! let mut client = ImapEmailClient::connect(config).await?;
+//! let code = client.wait_for_match(&OtpMatcher::six_digit()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::client::{HealthStatus, ImapEmailClient, ImapEmailClientGuard};
+pub use crate::config::{
+    AuthMethod, ClientId, ExpungeMode, ImapConfig, ImapConfigBuilder, PollingConfig, RetryConfig,
+    TimeoutConfig, WaitOptions,
+};
+pub use crate::connection::ConnectStats;
+pub use crate::email::{EmailMessage, MatchedEmail};
+pub use crate::error::{Error, ErrorCategory, Result};
+pub use crate::filter::Filter;
+pub use crate::handle::ImapEmailClientHandle;
+pub use crate::matcher::{
+    ClosureMatcher, MatchDetails, Matcher, OtpMatcher, RegexMatcher, UrlMatcher,
+};
+pub use crate::proxy::{ProxyPool, RotationStrategy, Socks5Proxy};
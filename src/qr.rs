@@ -0,0 +1,129 @@
+//! Internal QR code decoding of image attachments.
+//!
+//! Some 2FA enrollment emails deliver the secret only as a QR image (typically an
+//! `otpauth://` URI). The payloads found in image parts are appended to the text
+//! searched by matchers.
+
+use image::{ImageReader, Limits};
+use std::io::Cursor;
+use tracing::debug;
+
+/// Largest image side decoded, to bound the work spent on one attachment.
+const MAX_IMAGE_SIDE: u32 = 4096;
+
+/// Returns the payloads of all QR codes found in the image parts of `parsed`.
+pub(crate) fn decode_qr_payloads(parsed: &mailparse::ParsedMail<'_>) -> Vec<String> {
+    let mut payloads = Vec::new();
+    collect_payloads(parsed, &mut payloads);
+    payloads
+}
+
+fn collect_payloads(part: &mailparse::ParsedMail<'_>, payloads: &mut Vec<String>) {
+    if part
+        .ctype
+        .mimetype
+        .to_ascii_lowercase()
+        .starts_with("image/")
+    {
+        match part.get_body_raw() {
+            Ok(bytes) => payloads.extend(decode_image(&bytes)),
+            Err(e) => debug!(error = %e, "Failed to decode image attachment"),
+        }
+    }
+
+    for subpart in &part.subparts {
+        collect_payloads(subpart, payloads);
+    }
+}
+
+/// Decodes the QR codes in an encoded image (PNG, JPEG or GIF).
+fn decode_image(bytes: &[u8]) -> Vec<String> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIDE);
+    limits.max_image_height = Some(MAX_IMAGE_SIDE);
+
+    let mut reader = match ImageReader::new(Cursor::new(bytes)).with_guessed_format() {
+        Ok(reader) => reader,
+        Err(e) => {
+            debug!(error = %e, "Unrecognized image attachment");
+            return Vec::new();
+        }
+    };
+    reader.limits(limits);
+
+    let image = match reader.decode() {
+        Ok(image) => image.to_luma8(),
+        Err(e) => {
+            debug!(error = %e, "Failed to decode image attachment");
+            return Vec::new();
+        }
+    };
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let pixels = image.as_raw();
+    let mut prepared =
+        rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| pixels[y * width + x]);
+    prepared
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| match grid.decode() {
+            Ok((_, payload)) => Some(payload),
+            Err(e) => {
+                debug!(error = %e, "Failed to decode QR code");
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use image::{GrayImage, ImageFormat, Luma};
+
+    /// Renders `data` as a PNG QR code, 4 pixels per module with a quiet zone.
+    fn qr_png(data: &str) -> Vec<u8> {
+        const SCALE: u32 = 4;
+        const QUIET: u32 = 4;
+
+        let code = qrcode::QrCode::new(data).unwrap();
+        let width = u32::try_from(code.width()).unwrap();
+        let colors = code.to_colors();
+        let side = (width + 2 * QUIET) * SCALE;
+
+        let image = GrayImage::from_fn(side, side, |x, y| {
+            let (mx, my) = (x / SCALE, y / SCALE);
+            let dark = (QUIET..QUIET + width).contains(&mx)
+                && (QUIET..QUIET + width).contains(&my)
+                && colors[((my - QUIET) * width + (mx - QUIET)) as usize] == qrcode::Color::Dark;
+            Luma([if dark { 0 } else { 255 }])
+        });
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_decode_qr_attachment() {
+        let uri = "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example";
+        let png = base64::engine::general_purpose::STANDARD.encode(qr_png(uri));
+        let raw = format!(
+            "Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+             --b\r\nContent-Type: text/plain\r\n\r\nScan the code below.\r\n\
+             --b\r\nContent-Type: image/png\r\nContent-Transfer-Encoding: base64\r\n\r\n{png}\r\n\
+             --b--\r\n"
+        );
+
+        let parsed = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        assert_eq!(decode_qr_payloads(&parsed), [uri]);
+    }
+
+    #[test]
+    fn test_invalid_image_ignored() {
+        assert!(decode_image(b"not an image").is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Timer, TCP and task-spawning helpers over Tokio.
+//!
+//! Internal groundwork, not a runtime abstraction: timers, TCP connects and
+//! background spawns go through this module, but channels and locks
+//! (`tokio::sync`) and stream I/O (`tokio::io`) are used directly across the
+//! crate. Only Tokio is supported.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// TCP stream type of the active runtime.
+pub(crate) type TcpStream = tokio::net::TcpStream;
+
+/// Error returned by [`timeout`] when the deadline elapses first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Elapsed;
+
+/// Resolves `addr` ("host:port") to socket addresses.
+pub(crate) async fn lookup_host(addr: &str) -> std::io::Result<Vec<SocketAddr>> {
+    Ok(tokio::net::lookup_host(addr).await?.collect())
+}
+
+/// Opens a TCP connection to the first reachable address of `addrs`.
+pub(crate) async fn connect_tcp(addrs: &[SocketAddr]) -> std::io::Result<TcpStream> {
+    TcpStream::connect(addrs).await
+}
+
+/// Runs `future` to completion, failing with [`Elapsed`] if it takes longer than `duration`.
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> std::result::Result<F::Output, Elapsed> {
+    tokio::time::timeout(duration, future)
+        .await
+        .map_err(|_| Elapsed)
+}
+
+/// Sleeps for `duration`.
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Spawns `future` as a background task on the current runtime.
+///
+/// # Panics
+///
+/// Panics when called outside a runtime context.
+pub(crate) fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(future);
+}
+
+/// Runs the blocking function `f` (e.g. file system access) on a thread where
+/// blocking is allowed, and returns its result.
+///
+/// # Panics
+///
+/// Panics when called outside a runtime context, or if `f` panics.
+pub(crate) async fn spawn_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(output) => output,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Spawns `future` on the current runtime, if one is running.
+///
+/// Hands the future back when called outside a runtime context, e.g. from a
+/// `Drop` impl running in synchronous code.
+pub(crate) fn try_spawn<F>(future: F) -> std::result::Result<(), F>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(future);
+            Ok(())
+        }
+        Err(_) => Err(future),
+    }
+}
+
+/// Blocks the current thread until `future` completes.
+///
+/// Inside a multi-threaded runtime the worker thread is handed off with
+/// `block_in_place`; outside any runtime a temporary single-threaded runtime
+/// drives the future. Returns `None` inside a single-threaded runtime, where
+/// blocking would deadlock the executor.
+#[cfg(feature = "blocking")]
+pub(crate) fn block_on<F: Future>(future: F) -> Option<F::Output> {
+    use tokio::runtime::{Builder, Handle, RuntimeFlavor};
+
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            Some(tokio::task::block_in_place(|| handle.block_on(future)))
+        }
+        Ok(_) => None,
+        Err(_) => {
+            let runtime = Builder::new_current_thread().enable_all().build().ok()?;
+            Some(runtime.block_on(future))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_timeout_elapsed() {
+        let result = timeout(Duration::from_millis(10), sleep(Duration::from_secs(5))).await;
+        assert_eq!(result, Err(Elapsed));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_completes() {
+        let result = timeout(Duration::from_secs(5), async { 42 }).await;
+        assert_eq!(result, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_blocking() {
+        assert_eq!(spawn_blocking(|| 6 * 7).await, 42);
+    }
+
+    #[test]
+    fn test_try_spawn_outside_runtime() {
+        assert!(try_spawn(async {}).is_err());
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_block_on_outside_runtime() {
+        assert_eq!(block_on(async { 7 }), Some(7));
+    }
+
+    #[cfg(feature = "blocking")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_on_multi_thread_runtime() {
+        assert_eq!(block_on(async { 7 }), Some(7));
+    }
+
+    #[cfg(feature = "blocking")]
+    #[tokio::test]
+    async fn test_block_on_current_thread_runtime() {
+        assert_eq!(block_on(async { 7 }), None);
+    }
+}
@@ -2,21 +2,87 @@
 //!
 //! This module wraps async-imap operations with proper error handling.
 
+use crate::capability::Capabilities;
+use crate::config::{AuthMethod, ClientId, FetchItems};
 use crate::connection::TlsStream;
+use crate::email::GmailAttributes;
 use crate::error::{Error, Result};
+use crate::mailbox::{MailboxStatus, Namespace, Quota};
+use async_imap::imap_proto;
+use async_imap::imap_proto::{
+    AttributeValue, MailboxDatum, RequestId, Response, ResponseCode, Status, UidSetMember,
+};
+use async_imap::types::{Capability, UnsolicitedResponse};
 use async_imap::Session;
-use chrono::NaiveDate;
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use futures::stream::BoxStream;
 use futures::StreamExt;
-use tracing::{debug, instrument};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, instrument, warn};
 
 /// Type alias for IMAP session over TLS.
 pub(crate) type ImapSession = Session<TlsStream>;
 
+/// Type alias for an IMAP client over TLS that has not authenticated yet.
+type ImapClient = async_imap::Client<TlsStream>;
+
 /// Authentication configuration for IMAP.
 pub(crate) struct AuthConfig<'a> {
     pub email: &'a str,
     pub password: &'a str,
+    pub method: AuthMethod,
+    #[cfg(feature = "ntlm")]
+    pub ntlm_domain: Option<&'a str>,
+    /// Security context provider and host name for [`AuthMethod::Gssapi`].
+    #[cfg(feature = "gssapi")]
+    pub gssapi: Option<(&'a dyn crate::gssapi::GssapiProvider, &'a str)>,
+}
+
+/// SASL `PLAIN` authenticator (RFC 4616): `\0user\0password`.
+struct PlainAuthenticator<'a> {
+    email: &'a str,
+    password: &'a str,
+}
+
+impl async_imap::Authenticator for PlainAuthenticator<'_> {
+    type Response = Vec<u8>;
+
+    fn process(&mut self, _challenge: &[u8]) -> Self::Response {
+        format!("\0{}\0{}", self.email, self.password).into_bytes()
+    }
+}
+
+/// SASL `CRAM-MD5` authenticator (RFC 2195): the user name and the hex-encoded
+/// HMAC-MD5 of the server challenge, keyed with the password.
+struct CramMd5Authenticator<'a> {
+    email: &'a str,
+    password: &'a str,
+}
+
+impl async_imap::Authenticator for CramMd5Authenticator<'_> {
+    type Response = String;
+
+    fn process(&mut self, challenge: &[u8]) -> Self::Response {
+        cram_md5_response(self.email, self.password, challenge)
+    }
+}
+
+/// Computes the `CRAM-MD5` response to `challenge`.
+pub(crate) fn cram_md5_response(user: &str, password: &str, challenge: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    use std::fmt::Write;
+
+    let mut mac = Hmac::<md5::Md5>::new_from_slice(password.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(challenge);
+
+    let mut response = format!("{user} ");
+    for byte in mac.finalize().into_bytes() {
+        let _ = write!(response, "{byte:02x}");
+    }
+    response
 }
 
 /// Authenticates to IMAP server and returns a session.
@@ -29,25 +95,154 @@ pub(crate) async fn authenticate(
     tls_stream: TlsStream,
     config: &AuthConfig<'_>,
 ) -> Result<ImapSession> {
-    let client = async_imap::Client::new(tls_stream);
+    let mut client = async_imap::Client::new(tls_stream);
+
+    let method = match config.method {
+        AuthMethod::Auto => {
+            let capabilities = pre_auth_capabilities(&mut client).await;
+            choose_auth_method(capabilities.as_ref())
+        }
+        method => method,
+    };
+
+    debug!(?method, "Authenticating to IMAP server");
+
+    let result = match method {
+        AuthMethod::Plain => {
+            let authenticator = PlainAuthenticator {
+                email: config.email,
+                password: config.password,
+            };
+            client.authenticate("PLAIN", authenticator).await
+        }
+        AuthMethod::CramMd5 => {
+            let authenticator = CramMd5Authenticator {
+                email: config.email,
+                password: config.password,
+            };
+            client.authenticate("CRAM-MD5", authenticator).await
+        }
+        #[cfg(feature = "ntlm")]
+        AuthMethod::Ntlm => {
+            let (user, domain) = crate::ntlm::identity(config.email, config.ntlm_domain);
+            let authenticator = crate::ntlm::NtlmAuthenticator::new(user, domain, config.password);
+            client.authenticate("NTLM", authenticator).await
+        }
+        #[cfg(feature = "gssapi")]
+        AuthMethod::Gssapi => {
+            let Some((provider, host)) = config.gssapi else {
+                return Err(Error::InvalidConfig {
+                    message: "GSSAPI authentication requires a gssapi_provider".into(),
+                });
+            };
+            let context =
+                provider
+                    .start(&format!("imap@{host}"))
+                    .map_err(|e| Error::InvalidConfig {
+                        message: format!("failed to start GSSAPI security context: {e}"),
+                    })?;
+            // Authorize as the mailbox owner
+            let authenticator =
+                crate::gssapi::GssapiAuthenticator::new(context, config.email.to_string());
+            client.authenticate("GSSAPI", authenticator).await
+        }
+        _ => client.login(config.email, config.password).await,
+    };
+
+    result.map_err(|e| Error::ImapLogin {
+        email: config.email.to_string(),
+        source: e.0,
+    })
+}
+
+/// Picks the method used by [`AuthMethod::Auto`].
+///
+/// Prefers `CRAM-MD5` whenever it is advertised, since it keeps the password off
+/// the wire. Otherwise uses `LOGIN`, or SASL `PLAIN` when the server disables
+/// `LOGIN`. Without capabilities, `LOGIN` is used.
+fn choose_auth_method(capabilities: Option<&Capabilities>) -> AuthMethod {
+    capabilities.map_or(AuthMethod::Login, |caps| {
+        AuthMethod::choose(|m| caps.supports_auth(m), !caps.has("LOGINDISABLED"))
+    })
+}
 
-    debug!("Authenticating to IMAP server");
+/// Queries the capabilities advertised before login.
+///
+/// Failures are logged and reported as `None`, leaving the choice to the default.
+async fn pre_auth_capabilities(client: &mut ImapClient) -> Option<Capabilities> {
+    let (sender, receiver) = async_channel::unbounded();
 
-    client
-        .login(config.email, config.password)
+    if let Err(e) = client
+        .run_command_and_check_ok("CAPABILITY", Some(sender))
         .await
-        .map_err(|e| Error::ImapLogin {
-            email: config.email.to_string(),
-            source: e.0,
+    {
+        warn!(error = %e, "Failed to query capabilities before login");
+        return None;
+    }
+
+    let mut names = Vec::new();
+    while let Ok(response) = receiver.try_recv() {
+        if let UnsolicitedResponse::Other(data) = response {
+            match data.parsed() {
+                Response::Capabilities(capabilities) => {
+                    names.extend(capabilities.iter().map(capability_name));
+                }
+                Response::Data {
+                    code: Some(ResponseCode::Capabilities(capabilities)),
+                    ..
+                } => names.extend(capabilities.iter().map(capability_name)),
+                _ => {}
+            }
+        }
+    }
+
+    let capabilities = Capabilities::new(names);
+    debug!(
+        capability_count = capabilities.len(),
+        "Fetched capabilities before login"
+    );
+    Some(capabilities)
+}
+
+/// Formats a capability from a raw response like [`capabilities`] does.
+fn capability_name(capability: &imap_proto::Capability<'_>) -> String {
+    match capability {
+        imap_proto::Capability::Imap4rev1 => "IMAP4REV1".to_string(),
+        imap_proto::Capability::Auth(mechanism) => format!("AUTH={mechanism}"),
+        imap_proto::Capability::Atom(atom) => atom.to_string(),
+    }
+}
+
+/// Identifies the client to the server with the `ID` command (RFC 2971).
+///
+/// Returns the server's identification, if it sent one.
+#[instrument(name = "session::identify", skip_all)]
+pub(crate) async fn identify(
+    session: &mut ImapSession,
+    client_id: &ClientId,
+) -> Result<Option<HashMap<String, String>>> {
+    debug!("Sending ID");
+
+    session
+        .id(client_id.fields().map(|(key, value)| (key, Some(value))))
+        .await
+        .map_err(|source| Error::ImapCommand {
+            command: "ID".into(),
+            source,
         })
 }
 
 /// Selects a mailbox (typically "INBOX").
+///
+/// Returns the UIDVALIDITY of the mailbox, if the server reported it.
 #[instrument(name = "session::select", skip(session), fields(mailbox = %mailbox))]
-pub(crate) async fn select_mailbox(session: &mut ImapSession, mailbox: &str) -> Result<()> {
+pub(crate) async fn select_mailbox(
+    session: &mut ImapSession,
+    mailbox: &str,
+) -> Result<Option<u32>> {
     debug!("Selecting mailbox");
 
-    session
+    let selected = session
         .select(mailbox)
         .await
         .map_err(|source| Error::SelectMailbox {
@@ -55,7 +250,39 @@ pub(crate) async fn select_mailbox(session: &mut ImapSession, mailbox: &str) ->
             source,
         })?;
 
-    Ok(())
+    debug!(uid_validity = selected.uid_validity, "Selected mailbox");
+    Ok(selected.uid_validity)
+}
+
+/// Mailbox selected to leave the selected state on servers without UNSELECT.
+const UNSELECT_FALLBACK_MAILBOX: &str = "email-sync.unselect.nonexistent";
+
+/// Leaves the selected mailbox without expunging it.
+///
+/// Uses `UNSELECT` (RFC 3691) when `supported`. Otherwise SELECTs a mailbox that
+/// does not exist: the server answers NO and drops the selection, whereas `CLOSE`
+/// would expunge every `\Deleted` email.
+#[instrument(name = "session::unselect", skip(session))]
+pub(crate) async fn unselect(session: &mut ImapSession, supported: bool) -> Result<()> {
+    if supported {
+        debug!("Sending UNSELECT");
+        return session
+            .run_command_and_check_ok("UNSELECT")
+            .await
+            .map_err(|source| Error::ImapCommand {
+                command: "UNSELECT".into(),
+                source,
+            });
+    }
+
+    debug!("Server lacks UNSELECT, selecting a nonexistent mailbox");
+    match session.select(UNSELECT_FALLBACK_MAILBOX).await {
+        Ok(_) | Err(async_imap::error::Error::No(_)) => Ok(()),
+        Err(source) => Err(Error::SelectMailbox {
+            mailbox: UNSELECT_FALLBACK_MAILBOX.to_string(),
+            source,
+        }),
+    }
 }
 
 /// Gets the latest UID from the current mailbox.
@@ -79,15 +306,66 @@ pub(crate) async fn get_latest_uid(session: &mut ImapSession) -> Result<u32> {
     Ok(max_uid)
 }
 
-/// Searches for email UIDs since a given date.
+/// Returns all UIDs in the current mailbox, ascending.
+#[instrument(name = "session::search_all_uids", skip(session))]
+pub(crate) async fn search_all_uids(session: &mut ImapSession) -> Result<Vec<u32>> {
+    // NOOP to ensure we have latest state
+    session
+        .noop()
+        .await
+        .map_err(|source| Error::ImapNoop { source })?;
+
+    let mut uids: Vec<u32> = session
+        .uid_search("ALL")
+        .await
+        .map_err(|source| Error::ImapSearch { source })?
+        .into_iter()
+        .collect();
+    uids.sort_unstable();
+    debug!(uid_count = uids.len(), "Found UIDs");
+    Ok(uids)
+}
+
+/// Returns the UIDs greater than `uid` in the current mailbox, ascending.
+#[instrument(name = "session::search_uids_after", skip(session))]
+pub(crate) async fn search_uids_after(session: &mut ImapSession, uid: u32) -> Result<Vec<u32>> {
+    // NOOP to ensure we have latest state
+    session
+        .noop()
+        .await
+        .map_err(|source| Error::ImapNoop { source })?;
+
+    // "n:*" also matches the last message when every UID is below n
+    let uids = session
+        .uid_search(format!("UID {}:*", uid.saturating_add(1)))
+        .await
+        .map_err(|source| Error::ImapSearch { source })?;
+
+    let mut uids: Vec<u32> = uids.into_iter().filter(|found| *found > uid).collect();
+    uids.sort_unstable();
+    debug!(uid_count = uids.len(), "Found new UIDs");
+    Ok(uids)
+}
+
+/// Counts the messages without the `\Seen` flag in the current mailbox.
+#[instrument(name = "session::count_unseen", skip(session))]
+pub(crate) async fn count_unseen(session: &mut ImapSession) -> Result<u32> {
+    let uids = session
+        .uid_search("UNSEEN")
+        .await
+        .map_err(|source| Error::ImapSearch { source })?;
+    Ok(u32::try_from(uids.len()).unwrap_or(u32::MAX))
+}
+
+/// Searches for email UIDs since a given date, or all of them without one.
 #[instrument(
     name = "session::search_since",
     skip(session),
-    fields(since_date = %since_date)
+    fields(since_date = ?since_date)
 )]
 pub(crate) async fn search_emails_since(
     session: &mut ImapSession,
-    since_date: NaiveDate,
+    since_date: Option<NaiveDate>,
 ) -> Result<Vec<u32>> {
     // NOOP to ensure we have latest state
     session
@@ -96,8 +374,10 @@ pub(crate) async fn search_emails_since(
         .map_err(|source| Error::ImapNoop { source })?;
 
     // IMAP SINCE format: "DD-Mon-YYYY" (e.g., "07-Dec-2025")
-    let since_str = since_date.format("%d-%b-%Y").to_string();
-    let query = format!("SINCE {since_str}");
+    let query = since_date.map_or_else(
+        || "ALL".to_string(),
+        |date| format!("SINCE {}", date.format("%d-%b-%Y")),
+    );
 
     let uids = session
         .uid_search(&query)
@@ -108,13 +388,208 @@ pub(crate) async fn search_emails_since(
 
     debug!(
         uid_count = uids_vec.len(),
-        since = %since_str,
+        query = %query,
         "Found emails"
     );
 
     Ok(uids_vec)
 }
 
+/// Searches for replies within the thread of `message_id`.
+///
+/// Matches messages whose `In-Reply-To` or `References` header contains the ID.
+#[instrument(name = "session::search_thread", skip(session, capabilities))]
+pub(crate) async fn search_thread(
+    session: &mut ImapSession,
+    capabilities: &Capabilities,
+    message_id: &str,
+) -> Result<Vec<u32>> {
+    // NOOP to ensure we have latest state
+    session
+        .noop()
+        .await
+        .map_err(|source| Error::ImapNoop { source })?;
+
+    let id = search_string(capabilities, message_id)?;
+    let query = search_criteria(
+        capabilities,
+        &format!("OR HEADER In-Reply-To {id} HEADER References {id}"),
+    );
+
+    let uids = session
+        .uid_search(&query)
+        .await
+        .map_err(|source| Error::ImapSearch { source })?;
+
+    let uids_vec: Vec<u32> = uids.into_iter().collect();
+
+    debug!(uid_count = uids_vec.len(), "Found emails in thread");
+
+    Ok(uids_vec)
+}
+
+/// Searches for emails whose `Message-ID` header contains `message_id`.
+#[instrument(name = "session::search_message_id", skip(session, capabilities))]
+pub(crate) async fn search_message_id(
+    session: &mut ImapSession,
+    capabilities: &Capabilities,
+    message_id: &str,
+) -> Result<Vec<u32>> {
+    // NOOP to ensure we have latest state
+    session
+        .noop()
+        .await
+        .map_err(|source| Error::ImapNoop { source })?;
+
+    let query = search_criteria(
+        capabilities,
+        &format!(
+            "HEADER Message-ID {}",
+            search_string(capabilities, message_id)?
+        ),
+    );
+
+    let uids = session
+        .uid_search(&query)
+        .await
+        .map_err(|source| Error::ImapSearch { source })?;
+
+    let uids_vec: Vec<u32> = uids.into_iter().collect();
+
+    debug!(uid_count = uids_vec.len(), "Found emails with Message-ID");
+
+    Ok(uids_vec)
+}
+
+/// Searches for emails carrying a Gmail label (`X-GM-LABELS`).
+#[instrument(name = "session::search_gmail_label", skip(session, capabilities))]
+pub(crate) async fn search_gmail_label(
+    session: &mut ImapSession,
+    capabilities: &Capabilities,
+    label: &str,
+) -> Result<Vec<u32>> {
+    // NOOP to ensure we have latest state
+    session
+        .noop()
+        .await
+        .map_err(|source| Error::ImapNoop { source })?;
+
+    let query = search_criteria(
+        capabilities,
+        &format!("X-GM-LABELS {}", search_string(capabilities, label)?),
+    );
+
+    let uids = session
+        .uid_search(&query)
+        .await
+        .map_err(|source| Error::ImapSearch { source })?;
+
+    let uids_vec: Vec<u32> = uids.into_iter().collect();
+
+    debug!(uid_count = uids_vec.len(), "Found emails with label");
+
+    Ok(uids_vec)
+}
+
+/// Fetches the Gmail extension attributes (`X-GM-MSGID`, `X-GM-THRID`,
+/// `X-GM-LABELS`) of the email with `uid`.
+///
+/// Returns `None` if the server does not return them.
+#[instrument(name = "session::fetch_gmail_attributes", skip(session))]
+pub(crate) async fn fetch_gmail_attributes(
+    session: &mut ImapSession,
+    uid: u32,
+) -> Result<Option<GmailAttributes>> {
+    let command = format!("UID FETCH {uid} (X-GM-MSGID X-GM-THRID X-GM-LABELS)");
+    let (mut message_id, mut thread_id, mut labels) = (None, None, Vec::new());
+
+    run_tagged_command(session, "FETCH", &command, |response| {
+        let Response::Fetch(_, attributes) = response else {
+            return;
+        };
+        if !attributes.contains(&AttributeValue::Uid(uid)) {
+            return;
+        }
+        for attribute in attributes {
+            match attribute {
+                AttributeValue::GmailMsgId(id) => message_id = Some(*id),
+                AttributeValue::GmailThrId(id) => thread_id = Some(*id),
+                AttributeValue::GmailLabels(values) => {
+                    labels = values.iter().map(ToString::to_string).collect();
+                }
+                _ => {}
+            }
+        }
+    })
+    .await?;
+
+    Ok(message_id
+        .zip(thread_id)
+        .map(|(message_id, thread_id)| GmailAttributes {
+            message_id,
+            thread_id,
+            labels,
+        }))
+}
+
+/// Formats `value` as an IMAP quoted string.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidConfig`] if `value` contains CR, LF or NUL, which
+/// quoted strings cannot carry and which would otherwise end the command early
+/// and let the rest of `value` run as a command of its own.
+pub(crate) fn quote(value: &str) -> Result<String> {
+    check_string_argument(value)?;
+    Ok(format!(
+        "\"{}\"",
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+/// Rejects values with CR, LF or NUL as arguments of IMAP commands.
+fn check_string_argument(value: &str) -> Result<()> {
+    if value.contains(['\r', '\n', '\0']) {
+        return Err(Error::InvalidConfig {
+            message: format!("IMAP argument {value:?} must not contain CR, LF or NUL"),
+        });
+    }
+    Ok(())
+}
+
+/// Formats `value` as a string argument of a search.
+///
+/// Non-ASCII values are sent as a non-synchronizing literal (RFC 7888) when the
+/// server accepts one, since not every server takes 8-bit text in quoted
+/// strings. Synchronizing literals would need a round trip mid-command, so
+/// without `LITERAL+` the value is quoted regardless. With `UTF8=ACCEPT`
+/// enabled, quoted UTF-8 is valid as is.
+///
+/// Values with CR, LF or NUL are rejected either way, see [`quote`].
+fn search_string(capabilities: &Capabilities, value: &str) -> Result<String> {
+    if value.is_ascii()
+        || capabilities.utf8_enabled()
+        || !capabilities.accepts_non_sync_literal(value.len())
+    {
+        return quote(value);
+    }
+    check_string_argument(value)?;
+    Ok(format!("{{{}+}}\r\n{value}", value.len()))
+}
+
+/// Prepares search `criteria` for the session.
+///
+/// Criteria with non-ASCII text are declared as UTF-8 with `CHARSET`, unless
+/// `UTF8=ACCEPT` is enabled, where UTF-8 is the default and `CHARSET` is not
+/// needed.
+fn search_criteria(capabilities: &Capabilities, criteria: &str) -> String {
+    if criteria.is_ascii() || capabilities.utf8_enabled() {
+        criteria.to_string()
+    } else {
+        format!("CHARSET UTF-8 {criteria}")
+    }
+}
+
 /// Fetches messages by UID range.
 ///
 /// Returns a boxed stream of fetch results.
@@ -123,10 +598,68 @@ pub(crate) async fn fetch_messages_by_uid_range<'a>(
     uid_range: &str,
 ) -> Result<BoxStream<'a, std::result::Result<async_imap::types::Fetch, async_imap::error::Error>>>
 {
-    debug!(uid_range = %uid_range, "Fetching messages");
+    fetch_by_uid_range(session, uid_range, "(INTERNALDATE RFC822.SIZE BODY[])").await
+}
+
+/// Fetches only the header section of messages by UID range.
+///
+/// Returns a boxed stream of fetch results.
+pub(crate) async fn fetch_headers_by_uid_range<'a>(
+    session: &'a mut ImapSession,
+    uid_range: &str,
+) -> Result<BoxStream<'a, std::result::Result<async_imap::types::Fetch, async_imap::error::Error>>>
+{
+    fetch_by_uid_range(
+        session,
+        uid_range,
+        "(INTERNALDATE RFC822.SIZE BODY[HEADER])",
+    )
+    .await
+}
+
+/// Fetches only the ENVELOPE of messages by UID range.
+///
+/// Returns a boxed stream of fetch results.
+pub(crate) async fn fetch_envelopes_by_uid_range<'a>(
+    session: &'a mut ImapSession,
+    uid_range: &str,
+) -> Result<BoxStream<'a, std::result::Result<async_imap::types::Fetch, async_imap::error::Error>>>
+{
+    fetch_by_uid_range(session, uid_range, "(INTERNALDATE RFC822.SIZE ENVELOPE)").await
+}
+
+/// Fetches the configured `items` of messages by UID range.
+///
+/// Returns a boxed stream of fetch results.
+pub(crate) async fn fetch_items_by_uid_range<'a>(
+    session: &'a mut ImapSession,
+    uid_range: &str,
+    items: &FetchItems,
+) -> Result<BoxStream<'a, std::result::Result<async_imap::types::Fetch, async_imap::error::Error>>>
+{
+    let items = items.as_str().trim();
+    let items = items
+        .strip_prefix('(')
+        .and_then(|items| items.strip_suffix(')'))
+        .unwrap_or(items);
+    fetch_by_uid_range(
+        session,
+        uid_range,
+        &format!("(INTERNALDATE RFC822.SIZE {items})"),
+    )
+    .await
+}
+
+async fn fetch_by_uid_range<'a>(
+    session: &'a mut ImapSession,
+    uid_range: &str,
+    items: &str,
+) -> Result<BoxStream<'a, std::result::Result<async_imap::types::Fetch, async_imap::error::Error>>>
+{
+    debug!(uid_range = %uid_range, items, "Fetching messages");
 
     let stream = session
-        .uid_fetch(uid_range, "BODY[]")
+        .uid_fetch(uid_range, items)
         .await
         .map_err(|source| Error::ImapFetch {
             uid_range: uid_range.to_string(),
@@ -136,6 +669,697 @@ pub(crate) async fn fetch_messages_by_uid_range<'a>(
     Ok(stream.boxed())
 }
 
+/// Fetches the INTERNALDATE (server arrival time) of each message in `uids`.
+///
+/// Messages the server returns without an INTERNALDATE are omitted.
+#[instrument(name = "session::fetch_internal_dates", skip_all, fields(uid_count = uids.len()))]
+pub(crate) async fn fetch_internal_dates(
+    session: &mut ImapSession,
+    uids: &[u32],
+) -> Result<Vec<(u32, DateTime<FixedOffset>)>> {
+    let uid_set = uid_set(uids);
+    let map_err = |source| Error::ImapFetch {
+        uid_range: uid_set.clone(),
+        source,
+    };
+
+    let messages: Vec<_> = session
+        .uid_fetch(&uid_set, "INTERNALDATE")
+        .await
+        .map_err(map_err)?
+        .collect()
+        .await;
+
+    let mut dates = Vec::with_capacity(messages.len());
+    for message in messages {
+        let message = message.map_err(map_err)?;
+        if let (Some(uid), Some(date)) = (message.uid, message.internal_date()) {
+            dates.push((uid, date));
+        }
+    }
+
+    debug!(date_count = dates.len(), "Fetched internal dates");
+
+    Ok(dates)
+}
+
+/// Fetches the capabilities advertised by the server.
+#[instrument(name = "session::capabilities", skip(session))]
+pub(crate) async fn capabilities(session: &mut ImapSession) -> Result<Capabilities> {
+    let capabilities = session
+        .capabilities()
+        .await
+        .map_err(|source| Error::ImapCommand {
+            command: "CAPABILITY".into(),
+            source,
+        })?;
+
+    let capabilities = Capabilities::new(capabilities.iter().map(|capability| match capability {
+        Capability::Imap4rev1 => "IMAP4REV1".to_string(),
+        Capability::Auth(mechanism) => format!("AUTH={mechanism}"),
+        Capability::Atom(atom) => atom.clone(),
+    }));
+
+    debug!(
+        capability_count = capabilities.len(),
+        "Fetched capabilities"
+    );
+
+    Ok(capabilities)
+}
+
+/// Fetches the capabilities and enables the useful extensions among them.
+///
+/// Called once after login. A server refusing `ENABLE` is not fatal: the
+/// session then runs without the extensions.
+#[instrument(name = "session::negotiate", skip(session))]
+pub(crate) async fn negotiate(session: &mut ImapSession) -> Result<Capabilities> {
+    let mut capabilities = capabilities(session).await?;
+
+    let wanted = capabilities.negotiable();
+    if !wanted.is_empty() {
+        match enable(session, &wanted).await {
+            Ok(enabled) => capabilities.set_enabled(enabled),
+            Err(e) => warn!(error = %e, "Server refused to enable extensions"),
+        }
+    }
+
+    Ok(capabilities)
+}
+
+/// Enables `extensions` with `ENABLE` (RFC 5161).
+///
+/// Returns the extensions the server reported as enabled.
+#[instrument(name = "session::enable", skip(session))]
+async fn enable(session: &mut ImapSession, extensions: &[&str]) -> Result<Vec<String>> {
+    let command = format!("ENABLE {}", extensions.join(" "));
+    let mut enabled = Vec::new();
+
+    run_tagged_command(session, "ENABLE", &command, |response| {
+        if let Response::Capabilities(capabilities) = response {
+            enabled.extend(capabilities.iter().map(capability_name));
+        }
+    })
+    .await?;
+
+    debug!(?enabled, "Enabled extensions");
+
+    Ok(enabled)
+}
+
+/// Orders `uids` newest first using the SORT extension (RFC 5256).
+///
+/// Issues `UID SORT (REVERSE DATE)`, which orders by the `Date` header. The server
+/// must advertise the `SORT` capability.
+#[instrument(name = "session::sort_newest_first", skip_all, fields(uid_count = uids.len()))]
+pub(crate) async fn sort_newest_first(session: &mut ImapSession, uids: &[u32]) -> Result<Vec<u32>> {
+    let command = format!("UID SORT (REVERSE DATE) UTF-8 UID {}", uid_set(uids));
+    let mut sorted = Vec::new();
+
+    run_tagged_command(session, "SORT", &command, |response| {
+        if let Response::MailboxData(MailboxDatum::Sort(uids)) = response {
+            sorted.extend_from_slice(uids);
+        }
+    })
+    .await?;
+
+    debug!(sorted_count = sorted.len(), "Sorted emails by date");
+
+    Ok(sorted)
+}
+
+/// Sends a raw tagged command and feeds each untagged response to `on_response`
+/// until the command completes.
+///
+/// For commands async-imap has no dedicated method for. `name` is used in errors.
+pub(crate) async fn run_tagged_command(
+    session: &mut ImapSession,
+    name: &str,
+    command: &str,
+    mut on_response: impl FnMut(&Response<'_>),
+) -> Result<()> {
+    let map_err = |source| Error::ImapCommand {
+        command: name.to_string(),
+        source,
+    };
+
+    let tag = session.run_command(command).await.map_err(map_err)?;
+
+    read_until_done(session, &tag, name, None, &mut on_response, |_| ()).await
+}
+
+/// Reads responses until the command tagged `tag` completes, feeding untagged
+/// responses to `on_response`.
+///
+/// On a tagged `OK`, returns `on_ok` applied to its response code (e.g. `APPENDUID`).
+/// `mailbox` is the target a `NO [TRYCREATE]` refers to, if the command has one.
+async fn read_until_done<T>(
+    session: &mut ImapSession,
+    tag: &RequestId,
+    name: &str,
+    mailbox: Option<&str>,
+    on_response: &mut impl FnMut(&Response<'_>),
+    on_ok: impl FnOnce(Option<&ResponseCode<'_>>) -> T,
+) -> Result<T> {
+    let map_err = |source| Error::ImapCommand {
+        command: name.to_string(),
+        source,
+    };
+
+    loop {
+        let response = session
+            .read_response()
+            .await
+            .ok_or(async_imap::error::Error::ConnectionLost)
+            .and_then(|response| response.map_err(Into::into))
+            .map_err(map_err)?;
+
+        if let Response::Done {
+            tag: done_tag,
+            status,
+            code,
+            information,
+        } = response.parsed()
+        {
+            if done_tag == tag {
+                return match status {
+                    Status::Ok => Ok(on_ok(code.as_ref())),
+                    _ => Err(command_failed(
+                        name,
+                        mailbox,
+                        status,
+                        code.as_ref(),
+                        information.as_deref(),
+                    )),
+                };
+            }
+        }
+
+        on_response(response.parsed());
+    }
+}
+
+/// Returns the error for a command that completed with `status` other than `OK`.
+///
+/// A `NO [TRYCREATE]` (RFC 3501 §6.3.11) on a command targeting `mailbox` is
+/// reported as [`Error::MailboxNotFound`].
+fn command_failed(
+    name: &str,
+    mailbox: Option<&str>,
+    status: &Status,
+    code: Option<&ResponseCode<'_>>,
+    information: Option<&str>,
+) -> Error {
+    let information = information.unwrap_or_default().to_string();
+    let source = match (status, code, mailbox) {
+        (Status::No, Some(ResponseCode::TryCreate), Some(mailbox)) => {
+            return Error::MailboxNotFound {
+                mailbox: mailbox.to_string(),
+            };
+        }
+        (Status::No, ..) => async_imap::error::Error::No(information),
+        _ => async_imap::error::Error::Bad(information),
+    };
+    Error::ImapCommand {
+        command: name.to_string(),
+        source,
+    }
+}
+
+/// Appends `content` (a raw RFC 822 message) to `mailbox`.
+///
+/// `flags` is a parenthesized flag list (e.g. `(\Seen)`) and `date` a quoted
+/// IMAP date-time. Returns the UID of the new message when the server reports
+/// it with `APPENDUID` (UIDPLUS).
+///
+/// The message is sent as a non-synchronizing literal when the server accepts
+/// one (`LITERAL+`/`LITERAL-`, RFC 7888), saving the round trip for the
+/// continuation request.
+#[instrument(
+    name = "session::append",
+    skip(session, capabilities, content),
+    fields(size = content.len())
+)]
+pub(crate) async fn append(
+    session: &mut ImapSession,
+    capabilities: &Capabilities,
+    mailbox: &str,
+    flags: Option<&str>,
+    date: Option<&str>,
+    content: &[u8],
+) -> Result<Option<u32>> {
+    let map_err = |source| Error::ImapCommand {
+        command: "APPEND".into(),
+        source,
+    };
+
+    let mut parts = vec![format!("APPEND {}", quote(mailbox)?)];
+    parts.extend([flags, date].into_iter().flatten().map(str::to_string));
+    let non_sync = capabilities.accepts_non_sync_literal(content.len());
+    if non_sync {
+        parts.push(format!("{{{}+}}", content.len()));
+    } else {
+        parts.push(format!("{{{}}}", content.len()));
+    }
+    let command = parts.join(" ");
+
+    let tag = session.run_command(&command).await.map_err(map_err)?;
+
+    // Otherwise the server asks for the message literal with a continuation
+    // request, possibly after untagged responses, or rejects the command
+    if !non_sync {
+        loop {
+            let response = session
+                .read_response()
+                .await
+                .ok_or(async_imap::error::Error::ConnectionLost)
+                .and_then(|response| response.map_err(Into::into))
+                .map_err(map_err)?;
+            match response.parsed() {
+                Response::Continue { .. } => break,
+                Response::Done {
+                    tag: done_tag,
+                    status,
+                    code,
+                    information,
+                } if *done_tag == tag => {
+                    return Err(command_failed(
+                        "APPEND",
+                        Some(mailbox),
+                        status,
+                        code.as_ref(),
+                        information.as_deref(),
+                    ));
+                }
+                other => debug!(response = ?other, "Skipping response before APPEND continuation"),
+            }
+        }
+    }
+
+    let stream = session.as_mut();
+    stream
+        .write_all(content)
+        .await
+        .map_err(|e| map_err(e.into()))?;
+    stream
+        .write_all(b"\r\n")
+        .await
+        .map_err(|e| map_err(e.into()))?;
+    stream.flush().await.map_err(|e| map_err(e.into()))?;
+
+    let uid = read_until_done(
+        session,
+        &tag,
+        "APPEND",
+        Some(mailbox),
+        &mut |_| {},
+        |code| match code {
+            Some(ResponseCode::AppendUid(_, uids)) => uids.first().map(|member| match member {
+                UidSetMember::Uid(uid) => *uid,
+                UidSetMember::UidRange(range) => *range.start(),
+            }),
+            _ => None,
+        },
+    )
+    .await?;
+
+    debug!(uid, "Appended message");
+
+    Ok(uid)
+}
+
+/// Formats UIDs as a compact IMAP sequence set, e.g. `1:3,7,9:10`.
+pub(crate) fn uid_set(uids: &[u32]) -> String {
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for uid in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == uid => *end = uid,
+            _ => ranges.push((uid, uid)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}:{end}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Lists all mailboxes visible to the authenticated user.
+#[instrument(name = "session::list_mailboxes", skip(session))]
+pub(crate) async fn list_mailboxes(session: &mut ImapSession) -> Result<Vec<String>> {
+    let map_err = |source| Error::ImapCommand {
+        command: "LIST".into(),
+        source,
+    };
+
+    let names: Vec<_> = session
+        .list(Some(""), Some("*"))
+        .await
+        .map_err(map_err)?
+        .collect()
+        .await;
+
+    let mailboxes = names
+        .into_iter()
+        .map(|name| name.map(|n| n.name().to_string()).map_err(map_err))
+        .collect::<Result<Vec<_>>>()?;
+
+    debug!(mailbox_count = mailboxes.len(), "Listed mailboxes");
+
+    Ok(mailboxes)
+}
+
+/// Queries the counters of `mailbox` with STATUS, without selecting it.
+#[instrument(name = "session::mailbox_status", skip(session))]
+pub(crate) async fn mailbox_status(
+    session: &mut ImapSession,
+    mailbox: &str,
+) -> Result<MailboxStatus> {
+    let status = session
+        .status(mailbox, "(MESSAGES UNSEEN UIDNEXT UIDVALIDITY)")
+        .await
+        .map_err(|source| Error::ImapCommand {
+            command: "STATUS".into(),
+            source,
+        })?;
+
+    debug!(
+        messages = status.exists,
+        unseen = status.unseen,
+        "Fetched mailbox status"
+    );
+
+    Ok(MailboxStatus {
+        messages: status.exists,
+        unseen: status.unseen,
+        uid_next: status.uid_next,
+        uid_validity: status.uid_validity,
+    })
+}
+
+/// Queries the quotas that apply to `mailbox` with GETQUOTAROOT.
+#[instrument(name = "session::quota_root", skip(session))]
+pub(crate) async fn quota_root(session: &mut ImapSession, mailbox: &str) -> Result<Vec<Quota>> {
+    let (_, quotas) =
+        session
+            .get_quota_root(mailbox)
+            .await
+            .map_err(|source| Error::ImapCommand {
+                command: "GETQUOTAROOT".into(),
+                source,
+            })?;
+
+    debug!(quota_count = quotas.len(), "Fetched quotas");
+
+    Ok(quotas
+        .into_iter()
+        .map(|quota| Quota {
+            root: quota.root_name,
+            resources: quota.resources.into_iter().map(Into::into).collect(),
+        })
+        .collect())
+}
+
+/// Returns the hierarchy delimiter of the personal namespace (`LIST "" ""`).
+#[instrument(name = "session::hierarchy_delimiter", skip(session))]
+pub(crate) async fn hierarchy_delimiter(session: &mut ImapSession) -> Result<Option<String>> {
+    let map_err = |source| Error::ImapCommand {
+        command: "LIST".into(),
+        source,
+    };
+
+    let names: Vec<_> = session
+        .list(Some(""), Some(""))
+        .await
+        .map_err(map_err)?
+        .collect()
+        .await;
+
+    let mut delimiter = None;
+    for name in names {
+        if let Some(d) = name.map_err(map_err)?.delimiter() {
+            delimiter = Some(d.to_string());
+        }
+    }
+
+    debug!(delimiter = ?delimiter, "Fetched hierarchy delimiter");
+
+    Ok(delimiter)
+}
+
+/// Queries the personal namespace with the RFC 2342 `NAMESPACE` command.
+///
+/// `imap_proto` has no grammar for the `NAMESPACE` response, and a response it cannot
+/// parse leaves the async-imap session unusable, so the command is exchanged on the
+/// raw stream instead. Returns `None` when the server has no personal namespace.
+#[instrument(name = "session::namespace", skip(session))]
+pub(crate) async fn namespace(session: &mut ImapSession) -> Result<Option<Namespace>> {
+    /// Numbers the command tags, so a late completion of an abandoned exchange
+    /// is not taken for the current one.
+    static NEXT_TAG: AtomicU32 = AtomicU32::new(1);
+
+    // Distinct from the `A<n>` tags async-imap generates
+    let tag = format!("NS{}", NEXT_TAG.fetch_add(1, Ordering::Relaxed));
+    let map_err = |e: std::io::Error| Error::ImapCommand {
+        command: "NAMESPACE".into(),
+        source: e.into(),
+    };
+
+    let stream = session.as_mut();
+    stream
+        .write_all(format!("{tag} NAMESPACE\r\n").as_bytes())
+        .await
+        .map_err(map_err)?;
+    stream.flush().await.map_err(map_err)?;
+
+    let namespace = read_namespace_response(stream, &tag).await?;
+    debug!(namespace = ?namespace, "Fetched namespace");
+
+    Ok(namespace)
+}
+
+/// Reads the response to the `NAMESPACE` command tagged `tag`.
+///
+/// The stream is read a line at a time up to the tagged completion, so
+/// responses that follow it are left to async-imap. Other untagged responses
+/// and completions of other tags are skipped.
+async fn read_namespace_response<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    tag: &str,
+) -> Result<Option<Namespace>> {
+    /// Upper bound on a response line, which is short in practice.
+    const MAX_LINE_SIZE: usize = 64 * 1024;
+
+    let map_err = |source| Error::ImapCommand {
+        command: "NAMESPACE".into(),
+        source,
+    };
+
+    let mut namespace = None;
+    loop {
+        let mut line = Vec::new();
+        let mut byte = [0];
+        while !line.ends_with(b"\r\n") {
+            if stream
+                .read(&mut byte)
+                .await
+                .map_err(|e| map_err(e.into()))?
+                == 0
+            {
+                return Err(map_err(async_imap::error::Error::ConnectionLost));
+            }
+            line.push(byte[0]);
+            if line.len() > MAX_LINE_SIZE {
+                return Err(map_err(async_imap::error::Error::Bad(
+                    "NAMESPACE response too large".into(),
+                )));
+            }
+        }
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end();
+
+        let Some(done) = line
+            .strip_prefix(tag)
+            .and_then(|rest| rest.strip_prefix(' '))
+        else {
+            match Namespace::parse_response(line) {
+                Some(parsed) if namespace.is_none() => namespace = Some(parsed),
+                _ => debug!(response = line, "Skipping response during NAMESPACE"),
+            }
+            continue;
+        };
+
+        let (status, information) = done.split_once(' ').unwrap_or((done, ""));
+        if status.eq_ignore_ascii_case("NO") {
+            return Err(map_err(async_imap::error::Error::No(information.into())));
+        }
+        if !status.eq_ignore_ascii_case("OK") {
+            return Err(map_err(async_imap::error::Error::Bad(information.into())));
+        }
+        return Ok(namespace);
+    }
+}
+
+/// Creates `mailbox`.
+#[instrument(name = "session::create_mailbox", skip(session))]
+pub(crate) async fn create_mailbox(session: &mut ImapSession, mailbox: &str) -> Result<()> {
+    debug!("Creating mailbox");
+
+    session
+        .create(mailbox)
+        .await
+        .map_err(|source| Error::ImapCommand {
+            command: "CREATE".into(),
+            source,
+        })
+}
+
+/// Deletes `mailbox`.
+#[instrument(name = "session::delete_mailbox", skip(session))]
+pub(crate) async fn delete_mailbox(session: &mut ImapSession, mailbox: &str) -> Result<()> {
+    debug!("Deleting mailbox");
+
+    session
+        .delete(mailbox)
+        .await
+        .map_err(|source| Error::ImapCommand {
+            command: "DELETE".into(),
+            source,
+        })
+}
+
+/// Renames mailbox `from` to `to`.
+#[instrument(name = "session::rename_mailbox", skip(session))]
+pub(crate) async fn rename_mailbox(session: &mut ImapSession, from: &str, to: &str) -> Result<()> {
+    debug!("Renaming mailbox");
+
+    session
+        .rename(from, to)
+        .await
+        .map_err(|source| Error::ImapCommand {
+            command: "RENAME".into(),
+            source,
+        })
+}
+
+/// Subscribes to (or, with `subscribed == false`, unsubscribes from) `mailbox`.
+#[instrument(name = "session::set_subscribed", skip(session))]
+pub(crate) async fn set_subscribed(
+    session: &mut ImapSession,
+    mailbox: &str,
+    subscribed: bool,
+) -> Result<()> {
+    let (command, result) = if subscribed {
+        ("SUBSCRIBE", session.subscribe(mailbox).await)
+    } else {
+        ("UNSUBSCRIBE", session.unsubscribe(mailbox).await)
+    };
+
+    result.map_err(|source| Error::ImapCommand {
+        command: command.into(),
+        source,
+    })
+}
+
+/// Adds `flags` (e.g. `\Deleted`) to the emails in `uids`.
+#[instrument(name = "session::add_flags", skip(session, uids), fields(uid_count = uids.len()))]
+pub(crate) async fn add_flags(session: &mut ImapSession, uids: &[u32], flags: &str) -> Result<()> {
+    let map_err = |source| Error::ImapCommand {
+        command: "STORE".into(),
+        source,
+    };
+
+    let responses: Vec<_> = session
+        .uid_store(uid_set(uids), format!("+FLAGS.SILENT ({flags})"))
+        .await
+        .map_err(map_err)?
+        .collect()
+        .await;
+    for response in responses {
+        response.map_err(map_err)?;
+    }
+
+    Ok(())
+}
+
+/// Permanently removes `\Deleted` emails from the selected mailbox.
+///
+/// With `uids`, only those emails are removed (`UID EXPUNGE`, requires UIDPLUS);
+/// without, every `\Deleted` email is (`EXPUNGE`).
+///
+/// With `QRESYNC` enabled the server reports removals as `VANISHED` instead of
+/// `EXPUNGE` responses, which async-imap does not collect.
+#[instrument(name = "session::expunge", skip(session, capabilities, uids))]
+pub(crate) async fn expunge(
+    session: &mut ImapSession,
+    capabilities: &Capabilities,
+    uids: Option<&[u32]>,
+) -> Result<usize> {
+    let map_err = |command: &str, source| Error::ImapCommand {
+        command: command.into(),
+        source,
+    };
+
+    let removed = if capabilities.is_enabled("QRESYNC") {
+        let command = match uids {
+            Some(uids) => format!("UID EXPUNGE {}", uid_set(uids)),
+            None => "EXPUNGE".to_string(),
+        };
+        let mut removed = 0;
+        run_tagged_command(session, "EXPUNGE", &command, |response| match response {
+            Response::Vanished { uids, .. } => {
+                removed += uids
+                    .iter()
+                    .map(|range| range.clone().count())
+                    .sum::<usize>();
+            }
+            Response::Expunge(_) => removed += 1,
+            _ => {}
+        })
+        .await?;
+        removed
+    } else if let Some(uids) = uids {
+        let responses: Vec<_> = session
+            .uid_expunge(uid_set(uids))
+            .await
+            .map_err(|e| map_err("UID EXPUNGE", e))?
+            .collect()
+            .await;
+        responses
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| map_err("UID EXPUNGE", e))?
+            .len()
+    } else {
+        let responses: Vec<_> = session
+            .expunge()
+            .await
+            .map_err(|e| map_err("EXPUNGE", e))?
+            .collect()
+            .await;
+        responses
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| map_err("EXPUNGE", e))?
+            .len()
+    };
+
+    debug!(removed, "Expunged emails");
+
+    Ok(removed)
+}
+
 /// Logs out from IMAP session.
 #[instrument(name = "session::logout", skip(session))]
 pub(crate) async fn logout(session: &mut ImapSession) -> Result<()> {
@@ -148,3 +1372,173 @@ pub(crate) async fn logout(session: &mut ImapSession) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_auth_method() {
+        let login_disabled =
+            Capabilities::new(["IMAP4rev1", "LOGINDISABLED", "AUTH=PLAIN"].map(String::from));
+        assert_eq!(choose_auth_method(Some(&login_disabled)), AuthMethod::Plain);
+
+        let login_allowed = Capabilities::new(["IMAP4rev1", "AUTH=PLAIN"].map(String::from));
+        assert_eq!(choose_auth_method(Some(&login_allowed)), AuthMethod::Login);
+        assert_eq!(choose_auth_method(None), AuthMethod::Login);
+
+        let legacy =
+            Capabilities::new(["IMAP4rev1", "LOGINDISABLED", "AUTH=CRAM-MD5"].map(String::from));
+        assert_eq!(choose_auth_method(Some(&legacy)), AuthMethod::CramMd5);
+    }
+
+    #[test]
+    fn test_choose_auth_method_prefers_cram_md5() {
+        let login_allowed =
+            Capabilities::new(["IMAP4rev1", "AUTH=PLAIN", "AUTH=CRAM-MD5"].map(String::from));
+        assert_eq!(
+            choose_auth_method(Some(&login_allowed)),
+            AuthMethod::CramMd5
+        );
+
+        let login_disabled = Capabilities::new(
+            ["IMAP4rev1", "LOGINDISABLED", "AUTH=PLAIN", "AUTH=CRAM-MD5"].map(String::from),
+        );
+        assert_eq!(
+            choose_auth_method(Some(&login_disabled)),
+            AuthMethod::CramMd5
+        );
+    }
+
+    #[test]
+    fn test_cram_md5_response() {
+        // Example from RFC 2195, section 2
+        let challenge = b"<1896.697170952@postoffice.reston.mci.net>";
+        assert_eq!(
+            cram_md5_response("tim", "tanstaaftanstaaf", challenge),
+            "tim b913a602c7eda7a495b4e6e7334d3890"
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("<a@example.com>").unwrap(), "\"<a@example.com>\"");
+        assert_eq!(quote(r#"a"b\c"#).unwrap(), r#""a\"b\\c""#);
+    }
+
+    #[test]
+    fn test_string_arguments_reject_line_breaks() {
+        let literal_plus = Capabilities::new(["LITERAL+".to_string()]);
+        for value in [
+            "x\r\nA1 DELETE INBOX",
+            "x\nA1 DELETE INBOX",
+            "x\0",
+            "Ü\r\nA1 LOGOUT",
+        ] {
+            assert!(matches!(quote(value), Err(Error::InvalidConfig { .. })));
+            assert!(matches!(
+                search_string(&literal_plus, value),
+                Err(Error::InvalidConfig { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_search_criteria_charset() {
+        let mut capabilities = Capabilities::new(["ENABLE", "UTF8=ACCEPT"].map(String::from));
+        assert_eq!(
+            search_criteria(&capabilities, "X-GM-LABELS \"Work\""),
+            "X-GM-LABELS \"Work\""
+        );
+        assert_eq!(
+            search_criteria(&capabilities, "X-GM-LABELS \"Arbeit/Übersicht\""),
+            "CHARSET UTF-8 X-GM-LABELS \"Arbeit/Übersicht\""
+        );
+
+        capabilities.set_enabled(["UTF8=ACCEPT".to_string()]);
+        assert_eq!(
+            search_criteria(&capabilities, "X-GM-LABELS \"Arbeit/Übersicht\""),
+            "X-GM-LABELS \"Arbeit/Übersicht\""
+        );
+    }
+
+    #[test]
+    fn test_search_string_literal() {
+        let quoted = Capabilities::new(["IMAP4rev1".to_string()]);
+        assert_eq!(
+            search_string(&quoted, "Übersicht").unwrap(),
+            "\"Übersicht\""
+        );
+
+        let literal_plus = Capabilities::new(["LITERAL+".to_string()]);
+        assert_eq!(search_string(&literal_plus, "Work").unwrap(), "\"Work\"");
+        assert_eq!(
+            search_string(&literal_plus, "Übersicht").unwrap(),
+            "{10+}\r\nÜbersicht"
+        );
+    }
+
+    #[test]
+    fn test_uid_set() {
+        assert_eq!(uid_set(&[]), "");
+        assert_eq!(uid_set(&[5]), "5");
+        assert_eq!(uid_set(&[9, 1, 2, 3, 7, 10, 2]), "1:3,7,9:10");
+    }
+
+    #[tokio::test]
+    async fn test_read_namespace_response() {
+        let mut stream: &[u8] = b"* 3 EXISTS\r\n\
+            NS1 OK abandoned\r\n\
+            * NAMESPACE ((\"INBOX.\" \".\")) NIL NIL\r\n\
+            NS2 OK NAMESPACE completed\r\n\
+            * 4 EXISTS\r\n";
+        let namespace = read_namespace_response(&mut stream, "NS2")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(namespace.prefix, "INBOX.");
+        // What follows the completion is left on the stream
+        assert_eq!(stream, b"* 4 EXISTS\r\n");
+
+        let mut stream: &[u8] = b"NS3 NO not allowed\r\n";
+        assert!(matches!(
+            read_namespace_response(&mut stream, "NS3").await,
+            Err(Error::ImapCommand { .. })
+        ));
+
+        let mut stream: &[u8] = b"* NAMESPACE NIL NIL NIL\r\n";
+        assert!(read_namespace_response(&mut stream, "NS4").await.is_err());
+    }
+
+    #[test]
+    fn test_command_failed() {
+        let try_create = command_failed(
+            "APPEND",
+            Some("Archive"),
+            &Status::No,
+            Some(&ResponseCode::TryCreate),
+            Some("mailbox does not exist"),
+        );
+        assert!(matches!(try_create, Error::MailboxNotFound { mailbox } if mailbox == "Archive"));
+        assert!(!Error::MailboxNotFound {
+            mailbox: "Archive".into()
+        }
+        .is_retryable());
+
+        let rejected = command_failed("APPEND", Some("INBOX"), &Status::No, None, Some("quota"));
+        assert!(matches!(
+            rejected,
+            Error::ImapCommand { command, source: async_imap::error::Error::No(information) }
+                if command == "APPEND" && information == "quota"
+        ));
+
+        let bad = command_failed("SORT", None, &Status::Bad, None, None);
+        assert!(matches!(
+            bad,
+            Error::ImapCommand {
+                source: async_imap::error::Error::Bad(_),
+                ..
+            }
+        ));
+    }
+}
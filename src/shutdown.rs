@@ -0,0 +1,200 @@
+//! Coordinated shutdown of background tasks.
+//!
+//! Some parts of the crate run in background tasks: the connection task of an
+//! [`ImapEmailClientHandle`] (with its keepalive NOOPs), an [`EmailWatcher`] and
+//! the logout of a dropped [`ImapEmailClientGuard`]. Register a [`Shutdown`] with
+//! [`ImapConfigBuilder::shutdown`] and every task spawned for clients of that
+//! configuration stops when [`Shutdown::shutdown`] is called: requests in
+//! progress are abandoned (their callers get [`Error::ClientClosed`]), the
+//! sessions are logged out, and `shutdown` resolves once all of them are done.
+//!
+//! Clients owned directly by the application are not affected; log them out as
+//! usual.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::{ImapConfig, ImapEmailClient, Shutdown};
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let shutdown = Shutdown::new();
+//! let config = ImapConfig::builder()
+//!     .email("user@example.com")
+//!     .password("secret")
+//!     .keepalive(std::time::Duration::from_secs(300))
+//!     .shutdown(shutdown.clone())
+//!     .build()?;
+//!
+//! let handle = ImapEmailClient::connect(config).await?.into_handle();
+//! // ... serve requests with `handle` ...
+//!
+//! // On SIGTERM: stop the connection task and wait for its logout
+//! shutdown.shutdown().await;
+//! assert!(handle.is_closed());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ImapEmailClientHandle`]: crate::ImapEmailClientHandle
+//! [`ImapEmailClientGuard`]: crate::ImapEmailClientGuard
+//! [`EmailWatcher`]: crate::watcher::EmailWatcher
+//! [`ImapConfigBuilder::shutdown`]: crate::ImapConfigBuilder::shutdown
+//! [`Error::ClientClosed`]: crate::Error::ClientClosed
+
+use futures::future::{self, Either};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// State shared by a [`Shutdown`] and its tasks.
+struct Inner {
+    /// Set once shutdown is requested.
+    stop: watch::Sender<bool>,
+    /// Number of registered tasks still running.
+    tasks: watch::Sender<usize>,
+}
+
+/// Cloneable handle stopping the background tasks of the clients it is
+/// registered with.
+///
+/// See the [module documentation](self).
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+impl Shutdown {
+    /// Creates a handle with no tasks registered yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                stop: watch::Sender::new(false),
+                tasks: watch::Sender::new(0),
+            }),
+        }
+    }
+
+    /// Stops all registered background tasks and waits until they have logged
+    /// out their sessions.
+    ///
+    /// Tasks started afterwards stop right away. Calling this again (or from
+    /// several clones) waits for the same tasks.
+    pub async fn shutdown(&self) {
+        self.inner.stop.send_replace(true);
+        let mut tasks = self.inner.tasks.subscribe();
+        // The sender lives in `inner`, so waiting cannot fail
+        let _ = tasks.wait_for(|running| *running == 0).await;
+    }
+
+    /// Returns `true` once [`shutdown`](Self::shutdown) has been called.
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        *self.inner.stop.borrow()
+    }
+
+    /// Returns the number of registered background tasks still running.
+    #[must_use]
+    pub fn running_tasks(&self) -> usize {
+        *self.inner.tasks.borrow()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("is_shutdown", &self.is_shutdown())
+            .field("running_tasks", &self.running_tasks())
+            .finish()
+    }
+}
+
+/// Registration of a background task with an optional [`Shutdown`].
+///
+/// The task counts as running until the guard is dropped, so it should hold the
+/// guard until its cleanup (logout) is done.
+pub(crate) struct TaskGuard {
+    registration: Option<(watch::Receiver<bool>, Arc<Inner>)>,
+}
+
+impl TaskGuard {
+    /// Registers a task with `shutdown`; without one the task is never stopped.
+    pub(crate) fn new(shutdown: Option<&Shutdown>) -> Self {
+        let registration = shutdown.map(|shutdown| {
+            shutdown.inner.tasks.send_modify(|running| *running += 1);
+            (shutdown.inner.stop.subscribe(), Arc::clone(&shutdown.inner))
+        });
+        Self { registration }
+    }
+
+    /// Runs `future` to completion, or returns `None` as soon as shutdown is
+    /// requested (dropping the future).
+    pub(crate) async fn until_stopped<F: Future>(&mut self, future: F) -> Option<F::Output> {
+        let Some((stop, _)) = &mut self.registration else {
+            return Some(future.await);
+        };
+
+        let stopped = pin!(stop.wait_for(|stop| *stop));
+        match future::select(pin!(future), stopped).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if let Some((_, inner)) = &self.registration {
+            inner.tasks.send_modify(|running| *running -= 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_tasks() {
+        let shutdown = Shutdown::new();
+        let mut guard = TaskGuard::new(Some(&shutdown));
+        assert_eq!(shutdown.running_tasks(), 1);
+
+        let (done, finished) = tokio::sync::oneshot::channel();
+        runtime::spawn(async move {
+            let stopped = guard.until_stopped(std::future::pending::<()>()).await;
+            assert!(stopped.is_none());
+            // Cleanup still counts as running
+            runtime::sleep(Duration::from_millis(20)).await;
+            drop(guard);
+            let _ = done.send(());
+        });
+
+        shutdown.shutdown().await;
+        assert!(shutdown.is_shutdown());
+        assert_eq!(shutdown.running_tasks(), 0);
+        finished.await.unwrap();
+
+        // Tasks registered afterwards stop right away
+        let mut late = TaskGuard::new(Some(&shutdown));
+        assert!(late
+            .until_stopped(std::future::pending::<()>())
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_task_runs_to_completion() {
+        let mut guard = TaskGuard::new(None);
+        assert_eq!(guard.until_stopped(async { 7 }).await, Some(7));
+    }
+}
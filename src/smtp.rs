@@ -0,0 +1,724 @@
+//! SMTP sender for round-trip tests.
+//!
+//! [`SmtpSender`] submits a message to the account's SMTP server, so an
+//! end-to-end test can send an email to the monitored mailbox and then wait for
+//! it with [`ImapEmailClient::wait_for_match`]. It takes the same [`ImapConfig`]
+//! as the client: credentials, TLS options, SOCKS5 proxy or proxy pool and
+//! timeouts are shared. Each send opens its own connection.
+//!
+//! Servers that do not advertise `AUTH` (local test servers such as `MailHog`
+//! or `smtp4dev`) are used without authentication.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::smtp::SmtpSender;
+//! use email_sync::matcher::RegexMatcher;
+//! use email_sync::{ImapConfig, ImapEmailClient};
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let config = ImapConfig::builder()
+//!     .email("user@gmail.com")
+//!     .password("app-password")
+//!     .build()?;
+//!
+//! let mut client = ImapEmailClient::connect(config.clone()).await?;
+//! SmtpSender::new(config)
+//!     .send("user@gmail.com", "Round trip", "Token: RT-1234")
+//!     .await?;
+//!
+//! let matcher = RegexMatcher::new(r"RT-(\d+)").expect("valid regex");
+//! let token = client.wait_for_match(&matcher).await?;
+//! assert_eq!(token, "1234");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ImapEmailClient::wait_for_match`]: crate::ImapEmailClient::wait_for_match
+
+use crate::config::{self, AuthMethod, ImapConfig};
+use crate::connection::{self, ConnectStats};
+use crate::email::MatchedEmail;
+use crate::error::{Error, Result};
+use crate::proxy::ProxyChoice;
+use crate::runtime;
+use crate::session;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tracing::{debug, info, instrument};
+
+/// Largest reply line accepted.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum SmtpSecurity {
+    /// TLS from the start (submission over TLS, port 465).
+    #[default]
+    Tls,
+    /// Plain connection upgraded with `STARTTLS` (submission, port 587).
+    StartTls,
+}
+
+impl SmtpSecurity {
+    /// Returns the standard submission port for this security.
+    fn default_port(self) -> u16 {
+        match self {
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::StartTls => 587,
+        }
+    }
+}
+
+/// Sends emails over SMTP with the account and connection settings of an
+/// [`ImapConfig`].
+///
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct SmtpSender {
+    config: ImapConfig,
+    host: String,
+    port: u16,
+    security: SmtpSecurity,
+}
+
+/// A probe email for [`ImapEmailClient::round_trip`].
+///
+/// # Example
+///
+/// ```
+/// use email_sync::smtp::{SendRequest, SmtpSender};
+/// use email_sync::ImapConfig;
+///
+/// # fn example() -> email_sync::Result<()> {
+/// let config = ImapConfig::builder()
+///     .email("monitor@example.com")
+///     .password("secret")
+///     .build()?;
+///
+/// let request = SendRequest::new(SmtpSender::new(config), "monitor@example.com")
+///     .subject("Deliverability probe")
+///     .body("Probe sent by the monitoring job");
+/// assert_eq!(request.to(), "monitor@example.com");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`ImapEmailClient::round_trip`]: crate::ImapEmailClient::round_trip
+#[derive(Debug, Clone)]
+pub struct SendRequest {
+    sender: SmtpSender,
+    to: String,
+    subject: String,
+    body: String,
+}
+
+impl SendRequest {
+    /// Creates a request sending a probe through `sender` to `to`.
+    #[must_use]
+    pub fn new(sender: SmtpSender, to: impl Into<String>) -> Self {
+        Self {
+            sender,
+            to: to.into(),
+            subject: "email-sync probe".into(),
+            body: "This message was sent to test email delivery.".into(),
+        }
+    }
+
+    /// Sets the subject (default: `email-sync probe`).
+    #[must_use]
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = subject.into();
+        self
+    }
+
+    /// Sets the plain-text body.
+    #[must_use]
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Returns the recipient address.
+    #[must_use]
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// Sends the probe, returning its Message-ID.
+    ///
+    /// # Errors
+    ///
+    /// See [`SmtpSender::send`].
+    pub async fn send(&self) -> Result<String> {
+        self.sender.send(&self.to, &self.subject, &self.body).await
+    }
+}
+
+/// Result of [`ImapEmailClient::round_trip`].
+///
+/// [`ImapEmailClient::round_trip`]: crate::ImapEmailClient::round_trip
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RoundTrip {
+    /// Message-ID of the probe (with angle brackets).
+    pub message_id: String,
+    /// Time the SMTP server took to accept the probe.
+    pub send_time: Duration,
+    /// Time from the server accepting the probe to the client finding it.
+    ///
+    /// Includes up to one poll interval of the client's [`PollingConfig`].
+    ///
+    /// [`PollingConfig`]: crate::PollingConfig
+    pub latency: Duration,
+    /// The probe as found in the mailbox.
+    pub email: MatchedEmail,
+}
+
+/// A reply of the server: code and text lines.
+#[derive(Debug)]
+struct Reply {
+    code: u16,
+    lines: Vec<String>,
+}
+
+impl Reply {
+    fn text(&self) -> String {
+        self.lines.join(" ")
+    }
+}
+
+/// One SMTP session over `S`.
+struct Session<S> {
+    stream: BufReader<S>,
+    host: String,
+    timeout: Duration,
+}
+
+impl SmtpSender {
+    /// Creates a sender for the account of `config`.
+    ///
+    /// The SMTP host is derived from the IMAP host (`imap.example.com` becomes
+    /// `smtp.example.com`); the connection uses TLS on port 465.
+    #[must_use]
+    pub fn new(config: ImapConfig) -> Self {
+        let host = smtp_host(&config.effective_imap_host());
+        Self {
+            config,
+            host,
+            port: SmtpSecurity::Tls.default_port(),
+            security: SmtpSecurity::Tls,
+        }
+    }
+
+    /// Sets the SMTP host.
+    #[must_use]
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Sets the SMTP port. Call after [`security`](Self::security), which
+    /// resets the port to the standard one.
+    #[must_use]
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets how the connection is secured, and the port to the standard one
+    /// (465 for [`SmtpSecurity::Tls`], 587 for [`SmtpSecurity::StartTls`]).
+    #[must_use]
+    pub fn security(mut self, security: SmtpSecurity) -> Self {
+        self.security = security;
+        self.port = security.default_port();
+        self
+    }
+
+    /// Sends a plain-text email from the account's address to `to`.
+    ///
+    /// Returns the Message-ID of the email (with angle brackets), e.g. to find it
+    /// with [`ImapEmailClient::find_by_message_id`](crate::ImapEmailClient::find_by_message_id).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `to` is not a valid address ([`Error::InvalidEmailFormat`])
+    /// - `subject` contains a line break ([`Error::InvalidConfig`])
+    /// - The connection fails or times out
+    /// - The server rejects a command ([`Error::SmtpReply`])
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> Result<String> {
+        config::validate_email(to)?;
+        if subject.contains(['\r', '\n']) {
+            return Err(Error::InvalidConfig {
+                message: "email subject must not contain line breaks".into(),
+            });
+        }
+
+        let from = self.config.email();
+        let message_id = message_id(from);
+        let message = compose(from, to, subject, body, &message_id);
+        self.send_raw(from, &[to], message.as_bytes()).await?;
+        Ok(message_id)
+    }
+
+    /// Sends the RFC 5322 `message` as is, with `from` as envelope sender and
+    /// `recipients` as envelope recipients.
+    ///
+    /// Lines may end with LF or CRLF; leading dots are escaped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `recipients` is empty ([`Error::InvalidConfig`])
+    /// - `from` or a recipient is not a valid address
+    ///   ([`Error::InvalidEmailFormat`]); `from` may be empty for a null sender
+    /// - The connection fails or times out
+    /// - Authentication fails or the server rejects a command ([`Error::SmtpReply`])
+    #[instrument(
+        name = "SmtpSender::send_raw",
+        skip(self, message),
+        fields(host = %self.host, port = self.port, size = message.len())
+    )]
+    pub async fn send_raw(&self, from: &str, recipients: &[&str], message: &[u8]) -> Result<()> {
+        if recipients.is_empty() {
+            return Err(Error::InvalidConfig {
+                message: "at least one recipient is required".into(),
+            });
+        }
+        // Addresses go into MAIL FROM and RCPT TO as is; a line break in one
+        // would inject commands
+        if !from.is_empty() {
+            config::validate_email(from)?;
+        }
+        for recipient in recipients {
+            config::validate_email(recipient)?;
+        }
+
+        let target = format!("{}:{}", self.host, self.port);
+        let proxy = ProxyChoice::pick(self.config.proxy.as_ref(), self.config.proxy_pool.as_ref());
+        let connect_timeout = self.config.timeouts.connect;
+        let timed_out = || Error::ConnectTimeout {
+            target: target.clone(),
+            timeout: connect_timeout,
+        };
+        let mut stats = ConnectStats::default();
+
+        match self.security {
+            SmtpSecurity::Tls => {
+                let connecting = connection::establish_tls_connection(
+                    &self.host,
+                    &self.config.tls,
+                    &target,
+                    proxy.proxy(),
+                    &mut stats,
+                );
+                let connected = runtime::timeout(connect_timeout, connecting)
+                    .await
+                    .map_err(|_| timed_out())
+                    .and_then(|result| result);
+                proxy.report(connected.is_ok());
+                let stream = connected?;
+
+                let mut session = self.session(stream);
+                session.command("greeting", None, 220).await?;
+                self.transact(session, from, recipients, message).await
+            }
+            SmtpSecurity::StartTls => {
+                let connecting = connection::connect_tcp(&target, proxy.proxy(), &mut stats);
+                let connected = runtime::timeout(connect_timeout, connecting)
+                    .await
+                    .map_err(|_| timed_out())
+                    .and_then(|result| result);
+                proxy.report(connected.is_ok());
+                let stream = connected?;
+
+                let mut session = self.session(stream);
+                session.command("greeting", None, 220).await?;
+                let ehlo = session
+                    .command("EHLO", Some(b"EHLO localhost"), 250)
+                    .await?;
+                if !extensions(&ehlo).any(|ext| ext.eq_ignore_ascii_case("STARTTLS")) {
+                    return Err(Error::MissingCapability {
+                        capability: "STARTTLS".into(),
+                    });
+                }
+                session.command("STARTTLS", Some(b"STARTTLS"), 220).await?;
+
+                let upgrading = connection::start_tls(
+                    &self.host,
+                    &self.config.tls,
+                    &target,
+                    session.stream.into_inner(),
+                    &mut stats,
+                );
+                let stream = runtime::timeout(connect_timeout, upgrading)
+                    .await
+                    .map_err(|_| timed_out())??;
+                self.transact(self.session(stream), from, recipients, message)
+                    .await
+            }
+        }
+    }
+
+    /// Returns the SMTP host.
+    #[must_use]
+    pub fn smtp_host(&self) -> &str {
+        &self.host
+    }
+
+    fn session<S: AsyncRead>(&self, stream: S) -> Session<S> {
+        Session {
+            stream: BufReader::new(stream),
+            host: self.host.clone(),
+            timeout: self.config.timeouts.command,
+        }
+    }
+
+    /// Runs the mail transaction on a secured session, from `EHLO` to `QUIT`.
+    async fn transact<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut session: Session<S>,
+        from: &str,
+        recipients: &[&str],
+        message: &[u8],
+    ) -> Result<()> {
+        let ehlo = session
+            .command("EHLO", Some(b"EHLO localhost"), 250)
+            .await?;
+        let mechanisms: Vec<String> = extensions(&ehlo)
+            .find_map(|ext| {
+                let (name, mechanisms) = ext.split_once(' ')?;
+                name.eq_ignore_ascii_case("AUTH").then(|| {
+                    mechanisms
+                        .split_whitespace()
+                        .map(str::to_ascii_uppercase)
+                        .collect()
+                })
+            })
+            .unwrap_or_default();
+        if mechanisms.is_empty() {
+            debug!("Server does not offer AUTH, sending unauthenticated");
+        } else {
+            self.authenticate(&mut session, &mechanisms).await?;
+        }
+
+        let mail_from = format!("MAIL FROM:<{from}>");
+        session
+            .command("MAIL FROM", Some(mail_from.as_bytes()), 250)
+            .await?;
+        for recipient in recipients {
+            let rcpt_to = format!("RCPT TO:<{recipient}>");
+            session
+                .command("RCPT TO", Some(rcpt_to.as_bytes()), 250)
+                .await?;
+        }
+        session.command("DATA", Some(b"DATA"), 354).await?;
+        let mut data = dot_stuff(message);
+        data.push(b'.');
+        session.command("DATA", Some(&data), 250).await?;
+
+        // The message is accepted; a failed QUIT does not matter
+        if let Err(e) = session.command("QUIT", Some(b"QUIT"), 221).await {
+            debug!(error = %e, "SMTP QUIT failed");
+        }
+        info!(recipients = recipients.len(), "Email sent");
+        Ok(())
+    }
+
+    /// Authenticates with the configured method, choosing among `mechanisms`
+    /// for [`AuthMethod::Auto`].
+    async fn authenticate<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        session: &mut Session<S>,
+        mechanisms: &[String],
+    ) -> Result<()> {
+        let (user, password) = (self.config.email(), self.config.password());
+        let offers = |mechanism: &str| mechanisms.iter().any(|offered| offered == mechanism);
+
+        let method = match self.config.auth {
+            AuthMethod::Auto => AuthMethod::choose(offers, offers("LOGIN")),
+            method => method,
+        };
+        let mechanism = match method {
+            AuthMethod::Plain => "PLAIN",
+            AuthMethod::Login => "LOGIN",
+            AuthMethod::CramMd5 => "CRAM-MD5",
+            #[allow(unreachable_patterns)]
+            method => {
+                return Err(Error::InvalidConfig {
+                    message: format!("{method:?} authentication is not supported for SMTP"),
+                });
+            }
+        };
+        if !offers(mechanism) {
+            return Err(Error::MissingCapability {
+                capability: format!("AUTH {mechanism}"),
+            });
+        }
+        debug!(mechanism, "Authenticating");
+
+        match mechanism {
+            "PLAIN" => {
+                let credentials = STANDARD.encode(format!("\0{user}\0{password}"));
+                let line = format!("AUTH PLAIN {credentials}");
+                session.command("AUTH", Some(line.as_bytes()), 235).await?;
+            }
+            "LOGIN" => {
+                session.command("AUTH", Some(b"AUTH LOGIN"), 334).await?;
+                let user = STANDARD.encode(user);
+                session.command("AUTH", Some(user.as_bytes()), 334).await?;
+                let password = STANDARD.encode(password);
+                session
+                    .command("AUTH", Some(password.as_bytes()), 235)
+                    .await?;
+            }
+            _ => {
+                let reply = session.command("AUTH", Some(b"AUTH CRAM-MD5"), 334).await?;
+                let challenge =
+                    STANDARD
+                        .decode(reply.text().trim())
+                        .map_err(|_| Error::SmtpReply {
+                            command: "AUTH".into(),
+                            code: reply.code,
+                            message: "malformed CRAM-MD5 challenge".into(),
+                        })?;
+                let response =
+                    STANDARD.encode(session::cram_md5_response(user, password, &challenge));
+                session
+                    .command("AUTH", Some(response.as_bytes()), 235)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Session<S> {
+    /// Sends `line` (nothing for the greeting) and reads the reply, failing
+    /// unless its code is `expected`.
+    async fn command(&mut self, name: &str, line: Option<&[u8]>, expected: u16) -> Result<Reply> {
+        let exchange = async {
+            if let Some(line) = line {
+                let stream = self.stream.get_mut();
+                stream.write_all(line).await?;
+                stream.write_all(b"\r\n").await?;
+                stream.flush().await?;
+            }
+            read_reply(&mut self.stream).await
+        };
+
+        let reply = runtime::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| Error::CommandTimeout {
+                command: format!("SMTP {name}"),
+                timeout: self.timeout,
+            })?
+            .map_err(|source| Error::SmtpRequest {
+                host: self.host.clone(),
+                source,
+            })?;
+
+        if reply.code != expected {
+            return Err(Error::SmtpReply {
+                command: name.to_string(),
+                code: reply.code,
+                message: reply.text(),
+            });
+        }
+        Ok(reply)
+    }
+}
+
+/// Reads a (possibly multi-line) reply.
+async fn read_reply<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Reply> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        let read = (&mut *reader)
+            .take(MAX_LINE_LENGTH as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        let code = line
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("malformed reply '{line}'"),
+                )
+            })?;
+        lines.push(line.get(4..).unwrap_or_default().to_string());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(Reply { code, lines });
+        }
+    }
+}
+
+/// Returns the extensions advertised in an `EHLO` reply (the lines after the
+/// greeting).
+fn extensions(ehlo: &Reply) -> impl Iterator<Item = &str> {
+    ehlo.lines.iter().skip(1).map(String::as_str)
+}
+
+/// Converts line endings to CRLF, escapes lines starting with a dot and ends
+/// the data with a line break.
+fn dot_stuff(message: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(message.len() + 64);
+    let message = message.strip_suffix(b"\n").unwrap_or(message);
+    for line in message.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.starts_with(b".") {
+            data.push(b'.');
+        }
+        data.extend_from_slice(line);
+        data.extend_from_slice(b"\r\n");
+    }
+    data
+}
+
+/// Returns the SMTP host of a provider from its IMAP host.
+fn smtp_host(imap_host: &str) -> String {
+    match imap_host {
+        "outlook.office365.com" => "smtp.office365.com".into(),
+        host => match host.strip_prefix("imap.") {
+            Some(domain) => format!("smtp.{domain}"),
+            None => format!("smtp.{host}"),
+        },
+    }
+}
+
+/// Returns a new, unique Message-ID in the domain of `from`.
+fn message_id(from: &str) -> String {
+    let domain = from
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain);
+    let timestamp = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let random = RandomState::new().build_hasher().finish();
+    format!("<{timestamp:x}.{random:016x}@{domain}>")
+}
+
+/// Builds a plain-text email.
+fn compose(from: &str, to: &str, subject: &str, body: &str, message_id: &str) -> String {
+    let subject = if subject.is_ascii() {
+        subject.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(subject))
+    };
+    let body = body.replace("\r\n", "\n").replace('\n', "\r\n");
+
+    format!(
+        "From: <{from}>\r\nTo: <{to}>\r\nSubject: {subject}\r\nDate: {date}\r\n\
+         Message-ID: {message_id}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         Content-Transfer-Encoding: quoted-printable\r\n\r\n{body}\r\n",
+        date = chrono::Utc::now().to_rfc2822(),
+        body = quoted_printable::encode_to_str(body),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mailparse::MailHeaderMap;
+
+    #[test]
+    fn test_send_request() {
+        let config = ImapConfig::builder()
+            .email("me@example.com")
+            .password("secret")
+            .build()
+            .unwrap();
+        let request = SendRequest::new(SmtpSender::new(config), "you@example.com");
+        assert_eq!(request.subject, "email-sync probe");
+
+        let request = request.subject("Probe 7").body("Hello");
+        assert_eq!(request.to(), "you@example.com");
+        assert_eq!(request.subject, "Probe 7");
+        assert_eq!(request.body, "Hello");
+    }
+
+    #[test]
+    fn test_smtp_host() {
+        assert_eq!(smtp_host("imap.gmail.com"), "smtp.gmail.com");
+        assert_eq!(smtp_host("outlook.office365.com"), "smtp.office365.com");
+        assert_eq!(smtp_host("mail.example.com"), "smtp.mail.example.com");
+    }
+
+    #[test]
+    fn test_dot_stuff() {
+        assert_eq!(
+            dot_stuff(b"Subject: x\n\n.hidden\r\nend\n"),
+            b"Subject: x\r\n\r\n..hidden\r\nend\r\n"
+        );
+        assert_eq!(dot_stuff(b"no newline"), b"no newline\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_reply() {
+        let raw: &[u8] = b"250-smtp.example.com\r\n250-STARTTLS\r\n250 AUTH PLAIN LOGIN\r\n";
+        let reply = read_reply(&mut &*raw).await.unwrap();
+        assert_eq!(reply.code, 250);
+        assert_eq!(
+            extensions(&reply).collect::<Vec<_>>(),
+            ["STARTTLS", "AUTH PLAIN LOGIN"]
+        );
+
+        let malformed: &[u8] = b"OK\r\n";
+        assert!(read_reply(&mut &*malformed).await.is_err());
+    }
+
+    #[test]
+    fn test_compose() {
+        let id = message_id("me@example.com");
+        assert!(id.starts_with('<') && id.ends_with("@example.com>"));
+
+        let message = compose(
+            "me@example.com",
+            "you@example.com",
+            "Café",
+            "Code: 123\n",
+            &id,
+        );
+        let parsed = mailparse::parse_mail(message.as_bytes()).unwrap();
+        assert_eq!(parsed.headers.get_first_value("Subject").unwrap(), "Café");
+        assert_eq!(parsed.headers.get_first_value("Message-ID").unwrap(), id);
+        assert_eq!(parsed.get_body().unwrap().trim_end(), "Code: 123");
+    }
+
+    #[tokio::test]
+    async fn test_send_validates_input() {
+        let config = ImapConfig::builder()
+            .email("me@example.com")
+            .password("secret")
+            .build()
+            .unwrap();
+        let sender = SmtpSender::new(config);
+        assert_eq!(sender.smtp_host(), "smtp.example.com");
+
+        let result = sender.send("not an address", "Hi", "").await;
+        assert!(matches!(result, Err(Error::InvalidEmailFormat { .. })));
+        let result = sender.send("you@example.com", "Hi\r\nBcc: x", "").await;
+        assert!(matches!(result, Err(Error::InvalidConfig { .. })));
+
+        let injected = "you@example.com>\r\nRCPT TO:<other@example.com";
+        let result = sender.send_raw(injected, &["you@example.com"], b"").await;
+        assert!(matches!(result, Err(Error::InvalidEmailFormat { .. })));
+        let result = sender.send_raw("", &[injected], b"").await;
+        assert!(matches!(result, Err(Error::InvalidEmailFormat { .. })));
+    }
+}
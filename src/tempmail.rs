@@ -0,0 +1,695 @@
+//! Disposable mailboxes from temp-mail APIs.
+//!
+//! Signup-flow tests need a fresh address for every run, without maintaining
+//! real accounts. [`TempMailProvider`] provisions a throwaway account on a
+//! [mail.tm](https://mail.tm)-compatible API (mail.tm itself, mail.gw, or a
+//! self-hosted instance), and [`TempMailbox`] offers the core wait/find
+//! operations of [`ImapEmailClient`](crate::ImapEmailClient) on its Inbox:
+//! messages are listed over the API, their raw source is downloaded and matched
+//! exactly like emails fetched over IMAP. As a [`MailBackend`], messages are
+//! keyed by their API ID.
+//!
+//! Requests go through the same TLS and SOCKS5 proxy settings as IMAP
+//! connections. The account is not deleted automatically: call
+//! [`TempMailbox::delete`] when done.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::matcher::UrlMatcher;
+//! use email_sync::tempmail::TempMailProvider;
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let mut mailbox = TempMailProvider::mail_tm().create_mailbox().await?;
+//! println!("Sign up with {}", mailbox.address());
+//!
+//! let link = mailbox
+//!     .wait_for_match(&UrlMatcher::new("example.com"))
+//!     .await?;
+//! println!("Confirmation link: {link}");
+//!
+//! mailbox.delete().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::backend::{self, MailBackend, RawMessage};
+use crate::config::{PollingConfig, TimeoutConfig, TlsOptions};
+use crate::error::{Error, Result};
+use crate::http::{HttpConnection, Url};
+use crate::matcher::Matcher;
+use crate::parser::Extractor;
+use crate::proxy::Socks5Proxy;
+use crate::runtime;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use secrecy::{ExposeSecret, SecretString};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tracing::{debug, info, instrument};
+
+/// Base URL of the public mail.tm API.
+pub const MAIL_TM: &str = "https://api.mail.tm";
+
+/// A temp-mail API and the settings of the mailboxes created on it.
+///
+/// # Example
+///
+/// ```
+/// use email_sync::tempmail::TempMailProvider;
+/// use email_sync::PollingConfig;
+/// use std::time::Duration;
+///
+/// # fn example() -> email_sync::Result<()> {
+/// let provider = TempMailProvider::new("https://api.mail.gw")?
+///     .domain("mail.gw")
+///     .polling(PollingConfig {
+///         interval: Duration::from_secs(5),
+///         max_wait: Duration::from_mins(10),
+///     });
+/// assert_eq!(provider.api_url(), "https://api.mail.gw/");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TempMailProvider {
+    url: Url,
+    domain: Option<String>,
+    tls: TlsOptions,
+    proxy: Option<Socks5Proxy>,
+    timeouts: TimeoutConfig,
+    polling: PollingConfig,
+    normalize_text: bool,
+}
+
+/// A disposable account on a temp-mail API.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct TempMailbox {
+    provider: TempMailProvider,
+    connection: Option<HttpConnection>,
+    address: String,
+    password: SecretString,
+    account_id: String,
+    token: Option<SecretString>,
+    /// Messages already checked by a wait.
+    seen: HashSet<String>,
+}
+
+/// A list answer: a plain JSON array, or a JSON-LD (Hydra) collection.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Collection<T> {
+    Plain(Vec<T>),
+    Hydra {
+        #[serde(rename = "hydra:member")]
+        member: Vec<T>,
+    },
+}
+
+impl<T> Collection<T> {
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            Self::Plain(items) | Self::Hydra { member: items } => items,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Domain {
+    domain: String,
+    is_active: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Account {
+    id: String,
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Token {
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageSummary {
+    id: String,
+    created_at: Option<String>,
+}
+
+impl MessageSummary {
+    fn created_at(&self) -> Option<DateTime<Utc>> {
+        let created_at = self.created_at.as_deref()?;
+        DateTime::parse_from_rfc3339(created_at)
+            .ok()
+            .map(|date| date.with_timezone(&Utc))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    data: String,
+}
+
+impl TempMailProvider {
+    /// Uses the mail.tm-compatible API at `api_url` (e.g. `https://api.mail.gw`).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`] if `api_url` is not an `https` URL.
+    pub fn new(api_url: &str) -> Result<Self> {
+        Url::parse(api_url).map(Self::with_url)
+    }
+
+    /// Uses the public [mail.tm](https://mail.tm) API.
+    #[must_use]
+    pub fn mail_tm() -> Self {
+        Self::with_url(Url {
+            host: "api.mail.tm".into(),
+            port: 443,
+            path: "/".into(),
+        })
+    }
+
+    fn with_url(url: Url) -> Self {
+        Self {
+            url,
+            domain: None,
+            tls: TlsOptions::default(),
+            proxy: None,
+            timeouts: TimeoutConfig::default(),
+            polling: PollingConfig::default(),
+            normalize_text: false,
+        }
+    }
+
+    /// Creates addresses on `domain` instead of the first domain the API offers.
+    #[must_use]
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Sets the TLS options of the API connections.
+    #[must_use]
+    pub fn tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Connects to the API through a SOCKS5 proxy.
+    #[must_use]
+    pub fn proxy(mut self, proxy: Socks5Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Sets the timeouts: [`connect`](TimeoutConfig::connect) for opening a
+    /// connection, [`command`](TimeoutConfig::command) for API calls and
+    /// [`message_fetch`](TimeoutConfig::message_fetch) for downloading messages.
+    #[must_use]
+    pub fn timeouts(mut self, timeouts: TimeoutConfig) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sets the polling interval and maximum wait of the wait methods.
+    #[must_use]
+    pub fn polling(mut self, polling: PollingConfig) -> Self {
+        self.polling = polling;
+        self
+    }
+
+    /// Applies NFKC normalization and strips invisible characters before
+    /// matching, like [`ImapConfigBuilder::normalize_text`](crate::ImapConfigBuilder::normalize_text).
+    #[must_use]
+    pub fn normalize_text(mut self, enabled: bool) -> Self {
+        self.normalize_text = enabled;
+        self
+    }
+
+    /// Returns the API base URL.
+    #[must_use]
+    pub fn api_url(&self) -> String {
+        self.url.to_string()
+    }
+
+    /// Creates an account with a random address and password, and signs in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The API offers no active domain ([`Error::MalformedResponse`])
+    /// - A request fails, e.g. [`Error::HttpStatus`] with status `429` when
+    ///   accounts are created too quickly
+    #[instrument(
+        name = "TempMailProvider::create_mailbox",
+        skip(self),
+        fields(api = %self.url)
+    )]
+    pub async fn create_mailbox(&self) -> Result<TempMailbox> {
+        let mut mailbox = TempMailbox {
+            provider: self.clone(),
+            connection: None,
+            address: String::new(),
+            password: SecretString::from(random_token()),
+            account_id: String::new(),
+            token: None,
+            seen: HashSet::new(),
+        };
+
+        let domain = match &self.domain {
+            Some(domain) => domain.clone(),
+            None => mailbox.default_domain().await?,
+        };
+        let address = format!("es{}@{domain}", random_token());
+        let body = serde_json::json!({
+            "address": address,
+            "password": mailbox.password.expose_secret(),
+        });
+        let account: Account = mailbox.call("POST", "/accounts", Some(&body)).await?;
+        mailbox.address = account.address;
+        mailbox.account_id = account.id;
+        mailbox.sign_in().await?;
+
+        info!(address = %mailbox.address, "Disposable mailbox created");
+        Ok(mailbox)
+    }
+}
+
+impl TempMailbox {
+    /// Returns the email address of the mailbox.
+    #[must_use]
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns the account password, e.g. to open the mailbox in a browser.
+    #[must_use]
+    pub fn password(&self) -> &str {
+        self.password.expose_secret()
+    }
+
+    /// Waits for a new message matching the provided pattern.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - API requests fail with a permanent error
+    #[instrument(
+        name = "TempMailbox::wait_for_match",
+        skip(self, matcher),
+        fields(matcher = %matcher.description())
+    )]
+    pub async fn wait_for_match(&mut self, matcher: &dyn Matcher) -> Result<String> {
+        self.wait_for_any(&[matcher]).await.map(|(_, value)| value)
+    }
+
+    /// Waits for a new message matching any of the provided patterns.
+    ///
+    /// Returns the index of the matcher that fired (into `matchers`) together
+    /// with the extracted value. When several matchers match the same message,
+    /// the one listed first wins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - `matchers` is empty ([`Error::InvalidConfig`])
+    /// - Timeout is reached without finding a match ([`Error::WaitTimeout`])
+    /// - API requests fail with a permanent error
+    #[instrument(
+        name = "TempMailbox::wait_for_any",
+        skip(self, matchers),
+        fields(matchers = matchers.len())
+    )]
+    pub async fn wait_for_any(&mut self, matchers: &[&dyn Matcher]) -> Result<(usize, String)> {
+        let polling = self.provider.polling.clone();
+        let extract = Extractor::local(self.provider.normalize_text);
+        backend::wait_for_any(self, matchers, &polling, extract).await
+    }
+
+    /// Finds a matching message among those received within `max_age`, newest
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoMatch`] if no matching message is found.
+    #[instrument(
+        name = "TempMailbox::find_recent_match",
+        skip(self, matcher),
+        fields(
+            matcher = %matcher.description(),
+            max_age_secs = max_age.as_secs()
+        )
+    )]
+    pub async fn find_recent_match(
+        &mut self,
+        matcher: &dyn Matcher,
+        max_age: Duration,
+    ) -> Result<String> {
+        let extract = Extractor::local(self.provider.normalize_text);
+        backend::find_recent_match(self, matcher, max_age, extract).await
+    }
+
+    /// Treats every message received so far as old, so waits only match
+    /// messages arriving from now on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if listing the messages fails.
+    pub async fn reset_baseline(&mut self) -> Result<()> {
+        let messages = self.list_messages().await?;
+        self.seen = messages.into_iter().map(|message| message.id).collect();
+        Ok(())
+    }
+
+    /// Deletes the account and its messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    #[instrument(name = "TempMailbox::delete", skip(self), fields(address = %self.address))]
+    pub async fn delete(mut self) -> Result<()> {
+        let path = format!("/accounts/{}", self.account_id);
+        self.request("DELETE", &path, None, self.provider.timeouts.command)
+            .await?;
+        info!("Disposable mailbox deleted");
+        Ok(())
+    }
+
+    /// Returns the first active domain offered by the API.
+    async fn default_domain(&mut self) -> Result<String> {
+        let domains: Collection<Domain> = self.call("GET", "/domains", None).await?;
+        domains
+            .into_vec()
+            .into_iter()
+            .find(|domain| domain.is_active != Some(false))
+            .map(|domain| domain.domain)
+            .ok_or_else(|| Error::MalformedResponse {
+                url: self.url("/domains").to_string(),
+                message: "no active domain".into(),
+            })
+    }
+
+    /// Requests a new bearer token for the account.
+    async fn sign_in(&mut self) -> Result<()> {
+        self.token = None;
+        let body = serde_json::json!({
+            "address": self.address,
+            "password": self.password.expose_secret(),
+        });
+        let token: Token = self.call("POST", "/token", Some(&body)).await?;
+        self.token = Some(SecretString::from(token.token));
+        Ok(())
+    }
+
+    /// Returns the newest messages of the Inbox, newest first.
+    async fn list_messages(&mut self) -> Result<Vec<MessageSummary>> {
+        let messages: Collection<MessageSummary> =
+            self.call("GET", "/messages?page=1", None).await?;
+        let mut messages = messages.into_vec();
+        // Stable: messages without a date keep the API order
+        messages.sort_by_key(|message| std::cmp::Reverse(message.created_at()));
+        Ok(messages)
+    }
+
+    /// Sends an API request and decodes the JSON response.
+    async fn call<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T> {
+        let timeout = self.provider.timeouts.command;
+        let body = self.request(method, path, body, timeout).await?;
+        serde_json::from_slice(&body).map_err(|e| Error::MalformedResponse {
+            url: self.url(path).to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Sends an API request and returns the response body.
+    ///
+    /// A connection found closed by the server is reopened once.
+    async fn request(
+        &mut self,
+        method: &str,
+        path: &str,
+        body: Option<&serde_json::Value>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        let url = self.url(path);
+        let body = body.map(ToString::to_string).unwrap_or_default();
+        let authorization = self
+            .token
+            .as_ref()
+            .map(|token| format!("Bearer {}", token.expose_secret()));
+        let mut headers = vec![("Accept", "application/json")];
+        if !body.is_empty() {
+            headers.push(("Content-Type", "application/json"));
+        }
+        if let Some(authorization) = &authorization {
+            headers.push(("Authorization", authorization.as_str()));
+        }
+
+        let response = loop {
+            let reused = self.connection.is_some();
+            if !reused {
+                self.open().await?;
+            }
+            let Some(connection) = self.connection.as_mut() else {
+                continue;
+            };
+
+            let exchange = connection.request(method, &url, &headers, body.as_bytes());
+            match runtime::timeout(timeout, exchange).await {
+                Ok(Ok(response)) => break response,
+                // Servers close idle keep-alive connections
+                Ok(Err(e)) if reused => {
+                    debug!(error = %e, "Temp-mail connection closed, reconnecting");
+                    self.connection = None;
+                }
+                Ok(Err(source)) => {
+                    self.connection = None;
+                    return Err(Error::HttpRequest {
+                        url: url.to_string(),
+                        source,
+                    });
+                }
+                Err(runtime::Elapsed) => {
+                    self.connection = None;
+                    return Err(Error::HttpTimeout {
+                        url: url.to_string(),
+                        timeout,
+                    });
+                }
+            }
+        };
+
+        if !response.keep_alive() {
+            self.connection = None;
+        }
+        if (200..300).contains(&response.status) {
+            Ok(response.body)
+        } else {
+            Err(Error::HttpStatus {
+                url: url.to_string(),
+                status: response.status,
+            })
+        }
+    }
+
+    /// Opens a new connection to the API.
+    async fn open(&mut self) -> Result<()> {
+        let provider = &self.provider;
+        let timeout = provider.timeouts.connect;
+        let connecting =
+            HttpConnection::open(&provider.url, &provider.tls, provider.proxy.as_ref());
+        let connection =
+            runtime::timeout(timeout, connecting)
+                .await
+                .map_err(|_| Error::ConnectTimeout {
+                    target: format!("{}:{}", provider.url.host, provider.url.port),
+                    timeout,
+                })??;
+
+        debug!("Temp-mail connection established");
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    /// Returns the URL of the API endpoint `path`.
+    fn url(&self, path: &str) -> Url {
+        let base = &self.provider.url;
+        Url {
+            path: format!("{}{path}", base.path.trim_end_matches('/')),
+            ..base.clone()
+        }
+    }
+}
+
+impl MailBackend for TempMailbox {
+    /// Drops the connection and signs in again, in case the token expired.
+    fn connect(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.connection = None;
+            self.sign_in().await
+        })
+    }
+
+    fn select<'a>(&'a mut self, mailbox: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { backend::select_inbox("the temp-mail backend", mailbox) })
+    }
+
+    fn search_new(&mut self) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let messages = self.list_messages().await?;
+            Ok(new_ids(messages, &mut self.seen))
+        })
+    }
+
+    fn search_recent(&mut self, max_age: Duration) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let since = chrono::Duration::from_std(max_age)
+                .ok()
+                .and_then(|max_age| Utc::now().checked_sub_signed(max_age));
+            let messages = self.list_messages().await?;
+            Ok(recent_ids(messages, since))
+        })
+    }
+
+    /// Downloads the raw source of the messages `keys`, one request each.
+    fn fetch<'a>(&'a mut self, keys: &'a [String]) -> BoxFuture<'a, Result<Vec<RawMessage>>> {
+        Box::pin(async move {
+            let timeout = self.provider.timeouts.message_fetch;
+            let mut messages = Vec::with_capacity(keys.len());
+            for key in keys {
+                let path = format!("/sources/{key}");
+                let body = self.request("GET", &path, None, timeout).await?;
+                let source: Source =
+                    serde_json::from_slice(&body).map_err(|e| Error::MalformedResponse {
+                        url: self.url(&path).to_string(),
+                        message: e.to_string(),
+                    })?;
+                messages.push((key.clone(), source.data.into_bytes()));
+            }
+            Ok(messages)
+        })
+    }
+}
+
+/// Returns the IDs of `messages` (newest first) not in `seen`, oldest first,
+/// and adds them to `seen`.
+fn new_ids(messages: Vec<MessageSummary>, seen: &mut HashSet<String>) -> Vec<String> {
+    let mut ids: Vec<String> = messages
+        .into_iter()
+        .map(|message| message.id)
+        .filter(|id| seen.insert(id.clone()))
+        .collect();
+    ids.reverse();
+    ids
+}
+
+/// Returns the IDs of `messages` received since `since`, in the same order.
+///
+/// Messages without a date are kept.
+fn recent_ids(messages: Vec<MessageSummary>, since: Option<DateTime<Utc>>) -> Vec<String> {
+    messages
+        .into_iter()
+        .filter(|message| {
+            since.is_none_or(|since| message.created_at().is_none_or(|date| date >= since))
+        })
+        .map(|message| message.id)
+        .collect()
+}
+
+/// Returns 16 random lowercase hex digits.
+fn random_token() -> String {
+    format!("{:016x}", RandomState::new().build_hasher().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summaries(json: &str) -> Vec<MessageSummary> {
+        serde_json::from_str::<Collection<MessageSummary>>(json)
+            .unwrap()
+            .into_vec()
+    }
+
+    #[test]
+    fn test_collection_shapes() {
+        let plain = summaries(r#"[{"id": "a", "createdAt": null}]"#);
+        assert_eq!(plain[0].id, "a");
+
+        let hydra = summaries(
+            r#"{"hydra:member": [{"id": "b", "createdAt": "2024-05-01T10:00:00+00:00"}],
+                "hydra:totalItems": 1}"#,
+        );
+        assert_eq!(hydra[0].id, "b");
+        assert_eq!(
+            hydra[0].created_at().unwrap().to_rfc3339(),
+            "2024-05-01T10:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_new_ids() {
+        let messages = || {
+            summaries(
+                r#"[{"id": "c", "createdAt": "2024-05-01T10:02:00Z"},
+                    {"id": "b", "createdAt": "2024-05-01T10:01:00Z"},
+                    {"id": "a", "createdAt": "2024-05-01T10:00:00Z"}]"#,
+            )
+        };
+        let mut seen = HashSet::from(["a".to_string()]);
+        assert_eq!(new_ids(messages(), &mut seen), ["b", "c"]);
+        assert!(new_ids(messages(), &mut seen).is_empty());
+    }
+
+    #[test]
+    fn test_recent_ids() {
+        let messages = || {
+            summaries(
+                r#"[{"id": "new", "createdAt": "2024-05-01T10:00:00Z"},
+                    {"id": "undated"},
+                    {"id": "old", "createdAt": "2024-04-01T10:00:00Z"}]"#,
+            )
+        };
+        let since = DateTime::parse_from_rfc3339("2024-04-15T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(recent_ids(messages(), Some(since)), ["new", "undated"]);
+        assert_eq!(recent_ids(messages(), None).len(), 3);
+    }
+
+    #[test]
+    fn test_provider() {
+        assert_eq!(
+            TempMailProvider::mail_tm().api_url(),
+            TempMailProvider::new(MAIL_TM).unwrap().api_url()
+        );
+        assert!(matches!(
+            TempMailProvider::new("http://api.mail.tm"),
+            Err(Error::InvalidConfig { .. })
+        ));
+    }
+
+    #[test]
+    fn test_random_token() {
+        let token = random_token();
+        assert_eq!(token.len(), 16);
+        assert!(token.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_ne!(token, random_token());
+    }
+}
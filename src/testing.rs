@@ -0,0 +1,179 @@
+//! Helpers for testing matchers against saved emails.
+//!
+//! [`assert_matches_eml`] runs a matcher over an `.eml` fixture through the
+//! same parsing pipeline the client uses for fetched emails (MIME decoding,
+//! HTML to text, embedded base64 blobs, QR codes with the `qr` feature), so a
+//! custom matcher can be regression-tested on real emails without a server.
+//!
+//! # Example
+//!
+//! ```
+//! use email_sync::matcher::OtpMatcher;
+//! use email_sync::testing::assert_matches_eml;
+//!
+//! let eml = b"Subject: Sign-in\r\n\
+//!     Content-Type: text/html\r\n\
+//!     \r\n\
+//!     <p>Your code: <b>482913</b></p>\r\n";
+//!
+//! assert_matches_eml(&OtpMatcher::six_digit(), eml, Some("482913"));
+//! assert_matches_eml(&OtpMatcher::n_digit(8), eml, None);
+//! ```
+//!
+//! Fixtures on disk are passed by path:
+//!
+//! ```no_run
+//! # use email_sync::matcher::OtpMatcher;
+//! # use email_sync::testing::assert_matches_eml;
+//! assert_matches_eml(&OtpMatcher::six_digit(), "tests/fixtures/signup.eml", Some("482913"));
+//! ```
+
+use crate::matcher::Matcher;
+use crate::parser::{ExtractResult, Extractor};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// An email fixture: the path of an `.eml` file, or the raw message.
+///
+/// Strings are taken as paths; pass the message itself as bytes.
+#[derive(Debug, Clone, Copy)]
+pub enum Eml<'a> {
+    /// Path of an `.eml` file.
+    Path(&'a Path),
+    /// Raw RFC 5322 message.
+    Bytes(&'a [u8]),
+}
+
+impl<'a> From<&'a Path> for Eml<'a> {
+    fn from(path: &'a Path) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for Eml<'a> {
+    fn from(path: &'a PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl<'a> From<&'a str> for Eml<'a> {
+    fn from(path: &'a str) -> Self {
+        Self::Path(Path::new(path))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Eml<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for Eml<'a> {
+    fn from(bytes: &'a [u8; N]) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for Eml<'a> {
+    fn from(bytes: &'a Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl<'a> Eml<'a> {
+    /// Returns the raw message, reading the file if needed.
+    #[track_caller]
+    fn load(self) -> Cow<'a, [u8]> {
+        match self {
+            Self::Bytes(bytes) => Cow::Borrowed(bytes),
+            Self::Path(path) => match std::fs::read(path) {
+                Ok(bytes) => Cow::Owned(bytes),
+                Err(e) => panic!("cannot read email fixture {}: {e}", path.display()),
+            },
+        }
+    }
+}
+
+/// Returns the value `matcher` extracts from `eml`, or `None` if it does not
+/// match, as the client would on receiving the email.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or the email cannot be parsed.
+#[track_caller]
+#[must_use]
+pub fn extract_eml<'a>(matcher: &dyn Matcher, eml: impl Into<Eml<'a>>) -> Option<String> {
+    let raw = eml.into().load();
+    match Extractor::local(false).extract_raw_match(&raw, &[matcher]) {
+        ExtractResult::Match { details, .. } => Some(details.value.into_owned()),
+        ExtractResult::NoMatch => None,
+        ExtractResult::ParseError => panic!("email fixture cannot be parsed"),
+    }
+}
+
+/// Asserts that `matcher` extracts `expected` from `eml`, or does not match it
+/// when `expected` is `None`.
+///
+/// See the [module documentation](self).
+///
+/// # Panics
+///
+/// Panics with the matcher description and the actual result if the
+/// extraction differs, or if the fixture cannot be read or parsed.
+#[track_caller]
+pub fn assert_matches_eml<'a>(
+    matcher: &dyn Matcher,
+    eml: impl Into<Eml<'a>>,
+    expected: Option<&str>,
+) {
+    let actual = extract_eml(matcher, eml);
+    assert!(
+        actual.as_deref() == expected,
+        "matcher {} extracted {actual:?}, expected {expected:?}",
+        matcher.description()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{OtpMatcher, UrlMatcher};
+
+    const SIGNUP: &[u8] = b"Subject: Welcome\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        <a href=3D\"https://example.com/confirm?t=3Dabc\">Confirm</a>\r\n";
+
+    #[test]
+    fn test_assert_matches_bytes() {
+        assert_matches_eml(
+            &UrlMatcher::new("example.com"),
+            SIGNUP,
+            Some("https://example.com/confirm?t=abc"),
+        );
+        assert_matches_eml(&OtpMatcher::six_digit(), SIGNUP, None);
+    }
+
+    #[test]
+    fn test_assert_matches_path() {
+        let path =
+            std::env::temp_dir().join(format!("email-sync-{}-fixture.eml", std::process::id()));
+        std::fs::write(&path, SIGNUP).unwrap();
+        assert_eq!(
+            extract_eml(&UrlMatcher::new("example.com"), &path).as_deref(),
+            Some("https://example.com/confirm?t=abc")
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected Some(\"000000\")")]
+    fn test_assert_reports_mismatch() {
+        assert_matches_eml(
+            &OtpMatcher::six_digit(),
+            b"\r\nCode 123456\r\n",
+            Some("000000"),
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Background watcher delivering matches over a channel.
+//!
+//! [`EmailWatcher::spawn`] runs a task that owns the whole client lifecycle: it
+//! connects, polls the INBOX at the configured interval, reconnects after
+//! transient errors and sends a [`MatchEvent`] for every new email matching the
+//! matcher, oldest first by server arrival time (INTERNALDATE). Unlike
+//! [`ImapEmailClient::wait_for_match`], the watcher does not stop at the first
+//! match or after `max_wait`; it runs until the [`EmailWatcher`] is dropped, a
+//! permanent error occurs, or a registered [`Shutdown`](crate::Shutdown) stops it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use email_sync::watcher::{EmailWatcher, MatchEvent};
+//! use email_sync::matcher::OtpMatcher;
+//! use email_sync::ImapConfig;
+//!
+//! # async fn example() -> email_sync::Result<()> {
+//! let config = ImapConfig::builder()
+//!     .email("user@example.com")
+//!     .password("secret")
+//!     .build()?;
+//!
+//! let mut watcher = EmailWatcher::spawn(config, OtpMatcher::six_digit());
+//! while let Some(event) = watcher.recv().await {
+//!     match event {
+//!         MatchEvent::Matched(email) => println!("code {}", email.value),
+//!         MatchEvent::Reconnected => println!("connection restored"),
+//!         MatchEvent::Failed(e) => eprintln!("watcher stopped: {e}"),
+//!         _ => {}
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Dashboards that only care about the current value can turn the watcher into
+//! a [`watch`] channel holding the latest match with
+//! [`EmailWatcher::latest`].
+//!
+//! [`ImapEmailClient::wait_for_match`]: crate::ImapEmailClient::wait_for_match
+
+use crate::client::ImapEmailClient;
+use crate::config::ImapConfig;
+use crate::email::MatchedEmail;
+use crate::error::Error;
+use crate::matcher::Matcher;
+use crate::runtime;
+use crate::shutdown::TaskGuard;
+use futures::future::{self, Either};
+use std::pin::pin;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, instrument, warn};
+
+/// Number of events buffered before the watcher waits for the receiver.
+const EVENT_QUEUE_CAPACITY: usize = 32;
+
+/// Delay before the first reconnection attempt; doubled after each failure.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between two reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_mins(1);
+
+/// Something that happened in an [`EmailWatcher`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MatchEvent {
+    /// A new email matched.
+    Matched(MatchedEmail),
+    /// The connection was lost and has been re-established; emails that arrived
+    /// in between are still checked.
+    Reconnected,
+    /// The watcher hit an error it cannot recover from (e.g. rejected
+    /// credentials) and stopped. This is the last event.
+    Failed(Error),
+}
+
+/// Receiving end of a background task watching a mailbox for matching emails.
+///
+/// Created by [`EmailWatcher::spawn`]. Dropping the watcher (or its receiver)
+/// stops the task, which logs out.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct EmailWatcher {
+    receiver: mpsc::Receiver<MatchEvent>,
+}
+
+impl EmailWatcher {
+    /// Spawns a task watching the INBOX for new emails matching `matcher`.
+    ///
+    /// Only emails arriving after the first connection are considered. Polling
+    /// uses [`PollingConfig::interval`](crate::PollingConfig::interval);
+    /// `max_wait` does not apply.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a Tokio runtime.
+    #[must_use]
+    pub fn spawn(config: ImapConfig, matcher: impl Matcher + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+        runtime::spawn(run(config, Box::new(matcher), sender));
+        Self { receiver }
+    }
+
+    /// Receives the next event, or `None` once the watcher has stopped.
+    pub async fn recv(&mut self) -> Option<MatchEvent> {
+        self.receiver.recv().await
+    }
+
+    /// Returns the channel the events are delivered on.
+    #[must_use]
+    pub fn into_receiver(self) -> mpsc::Receiver<MatchEvent> {
+        self.receiver
+    }
+
+    /// Returns a [`watch`] channel holding the most recent match (`None` until
+    /// the first one).
+    ///
+    /// Other events are logged and dropped. The watcher stops once every clone
+    /// of the returned receiver is dropped; the channel closes when the watcher
+    /// stops.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use email_sync::watcher::EmailWatcher;
+    /// use email_sync::matcher::OtpMatcher;
+    /// # use email_sync::ImapConfig;
+    ///
+    /// # async fn example(config: ImapConfig) {
+    /// let mut latest = EmailWatcher::spawn(config, OtpMatcher::six_digit()).latest();
+    /// while latest.changed().await.is_ok() {
+    ///     if let Some(email) = &*latest.borrow() {
+    ///         println!("current code: {}", email.value);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn latest(self) -> watch::Receiver<Option<MatchedEmail>> {
+        let (sender, latest) = watch::channel(None);
+        runtime::spawn(forward_latest(self.receiver, sender));
+        latest
+    }
+}
+
+/// Keeps `sender` updated with the latest match received on `receiver`, until
+/// the watcher stops or nobody watches any more.
+async fn forward_latest(
+    mut receiver: mpsc::Receiver<MatchEvent>,
+    sender: watch::Sender<Option<MatchedEmail>>,
+) {
+    loop {
+        let recv = pin!(receiver.recv());
+        let closed = pin!(sender.closed());
+        match future::select(recv, closed).await {
+            Either::Left((Some(MatchEvent::Matched(email)), _)) => {
+                sender.send_replace(Some(email));
+            }
+            Either::Left((Some(MatchEvent::Failed(e)), _)) => {
+                warn!(error = %e, "Watcher failed");
+            }
+            Either::Left((Some(event), _)) => debug!(?event, "Watcher event"),
+            Either::Left((None, _)) | Either::Right(_) => return,
+        }
+    }
+}
+
+/// Watcher task: connects, then polls and forwards matches until stopped.
+#[instrument(name = "EmailWatcher::run", skip_all, fields(matcher = %matcher.description()))]
+async fn run(config: ImapConfig, matcher: Box<dyn Matcher>, sender: mpsc::Sender<MatchEvent>) {
+    let mut task = TaskGuard::new(config.shutdown.as_ref());
+    let interval = config.polling.interval;
+
+    let Some(Some(mut client)) = task.until_stopped(Box::pin(connect(config, &sender))).await
+    else {
+        return;
+    };
+    debug!("Watcher connected");
+
+    while !sender.is_closed() {
+        let polled = task.until_stopped(Box::pin(client.poll_matches(matcher.as_ref())));
+        let Some(polled) = polled.await else {
+            break;
+        };
+
+        match polled {
+            Ok(emails) => {
+                for email in emails {
+                    if sender.send(MatchEvent::Matched(email)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            Err(e) if e.is_retryable() => {
+                warn!(error = %e, "Watcher poll failed, reconnecting");
+                match task.until_stopped(Box::pin(reconnect(&mut client))).await {
+                    Some(Ok(())) => {
+                        if sender.send(MatchEvent::Reconnected).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Err(e)) => {
+                        let _ = sender.send(MatchEvent::Failed(e)).await;
+                        break;
+                    }
+                    None => break,
+                }
+            }
+            Err(e) => {
+                let _ = sender.send(MatchEvent::Failed(e)).await;
+                break;
+            }
+        }
+
+        if task.until_stopped(runtime::sleep(interval)).await.is_none() {
+            break;
+        }
+    }
+
+    let logout_timeout = client.config().timeouts.logout;
+    match runtime::timeout(logout_timeout, client.logout()).await {
+        Ok(Ok(())) => debug!("Watcher logged out"),
+        Ok(Err(e)) => warn!(error = %e, "Watcher logout failed"),
+        Err(_) => warn!(
+            timeout_secs = logout_timeout.as_secs(),
+            "Watcher logout timed out"
+        ),
+    }
+}
+
+/// Connects, retrying transient errors for as long as the receiver is alive.
+///
+/// Returns `None` after sending [`MatchEvent::Failed`] for a permanent error, or
+/// when the receiver is gone.
+async fn connect(config: ImapConfig, sender: &mpsc::Sender<MatchEvent>) -> Option<ImapEmailClient> {
+    let mut failures = 0;
+    loop {
+        match ImapEmailClient::connect(config.clone()).await {
+            Ok(client) => return Some(client),
+            Err(e) if e.is_retryable() && !sender.is_closed() => {
+                let delay = reconnect_delay(failures);
+                warn!(error = %e, ?delay, "Watcher connection failed, retrying");
+                runtime::sleep(delay).await;
+                failures += 1;
+            }
+            Err(e) => {
+                let _ = sender.send(MatchEvent::Failed(e)).await;
+                return None;
+            }
+        }
+    }
+}
+
+/// Reconnects `client`, retrying transient errors with backoff.
+///
+/// Fails with the first permanent error.
+async fn reconnect(client: &mut ImapEmailClient) -> crate::Result<()> {
+    let mut failures = 0;
+    loop {
+        match client.reconnect().await {
+            Err(e) if e.is_retryable() => {
+                let delay = reconnect_delay(failures);
+                warn!(error = %e, ?delay, "Watcher reconnection failed, retrying");
+                runtime::sleep(delay).await;
+                failures += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns the delay after `failures` consecutive failed connection attempts.
+fn reconnect_delay(failures: u32) -> Duration {
+    let factor = 1u32.checked_shl(failures).unwrap_or(u32::MAX);
+    RECONNECT_DELAY
+        .saturating_mul(factor)
+        .min(MAX_RECONNECT_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_latest_match() {
+        let (events, receiver) = mpsc::channel(4);
+        let mut latest = EmailWatcher { receiver }.latest();
+        assert!(latest.borrow().is_none());
+
+        for code in ["111111", "222222"] {
+            let email = MatchedEmail::new(
+                ("INBOX", 1),
+                0,
+                "6-digit OTP",
+                crate::matcher::MatchDetails::new(code),
+            );
+            events.send(MatchEvent::Matched(email)).await.unwrap();
+        }
+        events.send(MatchEvent::Reconnected).await.unwrap();
+        drop(events);
+
+        // The channel closes once the watcher stops, keeping the last value
+        while latest.changed().await.is_ok() {}
+        let value = latest.borrow().as_ref().map(|email| email.value.clone());
+        assert_eq!(value.as_deref(), Some("222222"));
+    }
+
+    #[tokio::test]
+    async fn test_latest_stops_watcher_when_dropped() {
+        let (events, receiver) = mpsc::channel(4);
+        drop(EmailWatcher { receiver }.latest());
+        events.closed().await;
+    }
+
+    #[test]
+    fn test_reconnect_delay() {
+        assert_eq!(reconnect_delay(0), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(40), MAX_RECONNECT_DELAY);
+    }
+}
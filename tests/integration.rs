@@ -12,12 +12,18 @@
 //! export EMAIL_SYNC_TEST_PROXY_HOST="proxy.example.com"
 //! export EMAIL_SYNC_TEST_PROXY_PORT="1080"
 //!
+//! # Optional: Exchange Web Services endpoint (with `--features ews`)
+//! export EMAIL_SYNC_TEST_EWS_URL="https://mail.example.com/EWS/Exchange.asmx"
+//!
 //! # Run with
 //! cargo test -- --ignored
 //! ```
 
 use email_sync::matcher::{ClosureMatcher, OtpMatcher, RegexMatcher, UrlMatcher};
-use email_sync::{ImapConfig, ImapEmailClient, Socks5Proxy};
+use email_sync::{
+    ImapConfig, ImapEmailClient, MailBackend, MailClient, Order, SearchOptions, Socks5Proxy,
+    TimeoutConfig,
+};
 use std::borrow::Cow;
 use std::env;
 use std::time::Duration;
@@ -125,6 +131,44 @@ async fn test_connect_displays_debug_info() {
     assert!(debug_str.contains("email"));
 }
 
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_baseline_control() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let initial = client.baseline_uid();
+
+    client.start_from_uid(1);
+    assert_eq!(client.baseline_uid(), 0);
+
+    client
+        .reset_baseline()
+        .await
+        .expect("Failed to reset baseline");
+    assert!(client.baseline_uid() >= initial);
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+#[ignore = "requires real IMAP server"]
+fn test_shutdown_blocking_outside_runtime() {
+    let config = get_test_config().expect("Test config from environment variables");
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to build runtime");
+
+    let client = runtime
+        .block_on(ImapEmailClient::connect(config))
+        .expect("Failed to connect");
+
+    // Synchronous teardown: no `.await` available here
+    client.shutdown_blocking().expect("Failed to logout");
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Find Recent Match Tests
 // ─────────────────────────────────────────────────────────────────────────────
@@ -209,10 +253,215 @@ async fn test_find_recent_with_url_matcher() {
     client.logout().await.expect("Failed to logout");
 }
 
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_find_recent_does_not_return_consumed_email() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    // Match any email with a non-empty body, so the newest one is consumed first
+    let matcher = ClosureMatcher::new(
+        |text| text.lines().next().map(|line| Cow::Owned(line.to_string())),
+        "first line",
+    );
+    let max_age = Duration::from_secs(24 * 60);
+
+    if let Ok(first) = client.find_recent_match(&matcher, max_age).await {
+        if let Ok(second) = client.find_recent_match(&matcher, max_age).await {
+            println!("Consumed '{first}', then '{second}'");
+        }
+
+        // After clearing, the newest email is returned again
+        client.clear_consumed();
+        let again = client
+            .find_recent_match(&matcher, max_age)
+            .await
+            .expect("Email should match again after clear_consumed");
+        assert_eq!(again, first);
+    }
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_find_in_unknown_thread() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let result = client
+        .find_in_thread(
+            "<no-such-thread@email-sync.invalid>",
+            &OtpMatcher::six_digit(),
+        )
+        .await;
+    assert!(matches!(result, Err(email_sync::Error::NoMatch)));
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_inbox_status() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let status = client
+        .mailbox_status("INBOX")
+        .await
+        .expect("Failed to get INBOX status");
+    assert!(status.uid_next.unwrap_or(u32::MAX) > client.baseline_uid());
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_health_check() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    assert!(client.health_check().await.is_connected());
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_connect_stats() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let stats = client.connect_stats();
+    assert!(stats.endpoint.is_some());
+    assert!(stats.total_ms() >= stats.tls_ms);
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_reconnect_keeps_baseline() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+    let baseline = client.baseline_uid();
+
+    client.reconnect().await.expect("Failed to reconnect");
+    assert_eq!(client.baseline_uid(), baseline);
+    assert!(client.health_check().await.is_connected());
+
+    client.logout().await.expect("Failed to logout");
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Wait For Match Tests
 // ─────────────────────────────────────────────────────────────────────────────
 
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_mailbox_management() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    assert!(client
+        .ensure_mailbox("email-sync-test")
+        .await
+        .expect("Failed to create mailbox"));
+    assert!(!client
+        .ensure_mailbox("email-sync-test")
+        .await
+        .expect("Failed to check mailbox"));
+
+    client
+        .rename_mailbox("email-sync-test", "email-sync-test-renamed")
+        .await
+        .expect("Failed to rename mailbox");
+    client
+        .delete_mailbox("email-sync-test-renamed")
+        .await
+        .expect("Failed to delete mailbox");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_append_then_wait_for_match() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let message =
+        b"From: email-sync@example.com\r\nSubject: Planted\r\n\r\nPlanted code: PLANT-4711";
+    client
+        .append("INBOX", message, &["\\Seen"], None)
+        .await
+        .expect("Failed to append message");
+
+    let matcher = RegexMatcher::new(r"PLANT-(\d+)").expect("Valid regex");
+    let code = client
+        .wait_for_match(&matcher)
+        .await
+        .expect("Planted message should match");
+    assert_eq!(code, "4711");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_mail_client_over_imap_backend() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+    let message =
+        b"From: email-sync@example.com\r\nSubject: Planted\r\n\r\nPlanted code: PLANT-4712";
+    client
+        .append("INBOX", message, &["\\Seen"], None)
+        .await
+        .expect("Failed to append message");
+
+    let backend: Box<dyn MailBackend> = Box::new(client);
+    let mut client = MailClient::new(backend);
+    let matcher = RegexMatcher::new(r"PLANT-(\d+)").expect("Valid regex");
+    let code = client
+        .wait_for_match(&matcher)
+        .await
+        .expect("Planted message should match");
+    assert_eq!(code, "4712");
+
+    client
+        .backend_mut()
+        .logout()
+        .await
+        .expect("Failed to logout");
+}
+
 #[tokio::test]
 #[ignore = "requires real IMAP server"]
 async fn test_wait_for_match_timeout() {
@@ -236,6 +485,66 @@ async fn test_wait_for_match_timeout() {
     client.logout().await.expect("Failed to logout");
 }
 
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_connect_and_wait_budget() {
+    // The configured max_wait is far longer than the budget
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let budget = Duration::from_secs(10);
+    let started = std::time::Instant::now();
+    let matcher = RegexMatcher::new(r"WILL_NEVER_MATCH_XYZ123").unwrap();
+    let result = ImapEmailClient::connect_and_wait(config, &matcher, budget).await;
+
+    assert!(matches!(
+        result,
+        Err(email_sync::Error::WaitTimeout { .. } | email_sync::Error::ConnectTimeout { .. })
+    ));
+    // Allow for the poll interval granularity
+    assert!(started.elapsed() < budget + Duration::from_secs(1));
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_wait_for_any_timeout() {
+    let config =
+        get_test_config_with_short_timeout().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let first = RegexMatcher::new(r"WILL_NEVER_MATCH_ABC").unwrap();
+    let second = RegexMatcher::new(r"WILL_NEVER_MATCH_DEF").unwrap();
+    let result = client.wait_for_any(&[&first, &second]).await;
+
+    assert!(result.is_err());
+    assert!(!result.unwrap_err().is_retryable());
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_wait_for_all_timeout() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let first = RegexMatcher::new(r"WILL_NEVER_MATCH_ABC").unwrap();
+    let second = RegexMatcher::new(r"WILL_NEVER_MATCH_DEF").unwrap();
+    let result = client
+        .wait_for_all(&[&first, &second], Duration::from_secs(3))
+        .await;
+
+    assert!(result.is_err());
+    assert!(!result.unwrap_err().is_retryable());
+
+    client.logout().await.expect("Failed to logout");
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Matcher Tests (Unit-style, but with real client context)
 // ─────────────────────────────────────────────────────────────────────────────
@@ -318,3 +627,494 @@ async fn test_missing_required_fields() {
     let result = ImapConfig::builder().email("test@example.com").build();
     assert!(result.is_err());
 }
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_select_mailbox_switches_watched_mailbox() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    client
+        .ensure_mailbox("email-sync-select")
+        .await
+        .expect("Failed to create mailbox");
+    client
+        .select_mailbox("email-sync-select")
+        .await
+        .expect("Failed to switch mailbox");
+    assert_eq!(client.selected_mailbox(), "email-sync-select");
+
+    client
+        .select_mailbox("INBOX")
+        .await
+        .expect("Failed to switch back to INBOX");
+    client
+        .delete_mailbox("email-sync-select")
+        .await
+        .expect("Failed to delete mailbox");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_watch_folders_reports_mailbox() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config.clone())
+        .await
+        .expect("Failed to connect");
+    client
+        .ensure_mailbox("email-sync-watch")
+        .await
+        .expect("Failed to create mailbox");
+
+    // Plant the email from a second connection once the watch has started
+    let planter = tokio::spawn(async move {
+        let mut other = ImapEmailClient::connect(config)
+            .await
+            .expect("Failed to connect");
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        let message =
+            b"From: email-sync@example.com\r\nSubject: Planted\r\n\r\nPlanted code: WATCH-815";
+        other
+            .append("email-sync-watch", message, &[], None)
+            .await
+            .expect("Failed to append message");
+        other.logout().await.expect("Failed to logout");
+    });
+
+    let matcher = RegexMatcher::new(r"WATCH-(\d+)").expect("Valid regex");
+    let email = client
+        .watch_folders(&["INBOX", "email-sync-watch"], &matcher)
+        .await
+        .expect("Planted message should match");
+    planter.await.expect("Planter task failed");
+
+    assert_eq!(email.value, "815");
+    assert_eq!(email.mailbox, "email-sync-watch");
+    assert_eq!(client.selected_mailbox(), "INBOX");
+
+    client
+        .delete_mailbox("email-sync-watch")
+        .await
+        .expect("Failed to delete mailbox");
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_unread_count() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+    let before = client.unread_count().await.expect("Failed to count");
+
+    let message = b"From: email-sync@example.com\r\nSubject: Unread\r\n\r\nUnread mail";
+    client
+        .append("INBOX", message, &[], None)
+        .await
+        .expect("Failed to append message");
+    assert!(client.unread_count().await.expect("Failed to count") > before);
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_has_new_mail() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+    assert!(!client.has_new_mail().await.expect("Failed to check"));
+
+    let message = b"From: email-sync@example.com\r\nSubject: New\r\n\r\nNew mail";
+    client
+        .append("INBOX", message, &["\\Seen"], None)
+        .await
+        .expect("Failed to append message");
+    assert!(client.has_new_mail().await.expect("Failed to check"));
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_keepalive_bare_client() {
+    let (email, password) = get_test_credentials().expect("Test credentials from environment");
+    let config = ImapConfig::builder()
+        .email(email)
+        .password(password)
+        .keepalive(Duration::from_secs(1))
+        .build()
+        .expect("Failed to build config");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    // Idle past the interval: the next command sends a NOOP first
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    client.has_new_mail().await.expect("Failed after keepalive");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_count_matching() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let matcher = RegexMatcher::new(r"count-token-(\d{8})").expect("valid regex");
+    let message = b"From: email-sync@example.com\r\nSubject: Count\r\n\r\ncount-token-48151623";
+    for _ in 0..2 {
+        client
+            .append("INBOX", message, &["\\Seen"], None)
+            .await
+            .expect("Failed to append message");
+    }
+
+    let count = client
+        .count_matching(&matcher, Duration::from_secs(300))
+        .await
+        .expect("Failed to count");
+    assert!(count >= 2, "expected both appended emails, got {count}");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_find_all_recent_matches() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let matcher = RegexMatcher::new(r"all-token-(\d{8})").expect("valid regex");
+    for token in ["all-token-11111111", "all-token-22222222"] {
+        let message = format!("From: email-sync@example.com\r\nSubject: All\r\n\r\n{token}");
+        client
+            .append("INBOX", message.as_bytes(), &["\\Seen"], None)
+            .await
+            .expect("Failed to append message");
+    }
+
+    let options = SearchOptions::new().order(Order::OldestFirst);
+    let emails = client
+        .find_all_recent_matches(&matcher, Duration::from_secs(300), &options)
+        .await
+        .expect("Failed to search");
+    assert!(
+        emails.len() >= 2,
+        "expected both appended emails, got {}",
+        emails.len()
+    );
+    assert!(emails
+        .windows(2)
+        .all(|pair| pair[0].internal_date <= pair[1].internal_date));
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_envelope_only() {
+    let (email, password) = get_test_credentials().expect("Test credentials from environment");
+    let config = ImapConfig::builder()
+        .email(email)
+        .password(password)
+        .envelope_only()
+        .build()
+        .expect("Valid config");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let message = b"From: email-sync@example.com\r\nSubject: Envelope code 615243\r\n\r\n\
+        Body code 999999";
+    client
+        .append("INBOX", message, &["\\Seen"], None)
+        .await
+        .expect("Failed to append message");
+
+    let code = client
+        .find_recent_match(&OtpMatcher::six_digit(), Duration::from_secs(300))
+        .await
+        .expect("Subject code should be found");
+    assert_eq!(code, "615243");
+
+    let result = client.fetch_message(1).await;
+    assert!(matches!(
+        result,
+        Err(email_sync::Error::InvalidConfig { .. })
+    ));
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_peek_recent() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let message = b"From: email-sync@example.com\r\nSubject: Peek\r\n\r\nPeek at me";
+    client
+        .append("INBOX", message, &["\\Seen"], None)
+        .await
+        .expect("Failed to append message");
+
+    let recent = client.peek_recent(3).await.expect("Failed to peek");
+    assert!(!recent.is_empty() && recent.len() <= 3);
+    assert!(recent.windows(2).all(|pair| pair[0].uid > pair[1].uid));
+    assert_eq!(recent[0].subject.as_deref(), Some("Peek"));
+    assert_eq!(recent[0].from, ["email-sync@example.com"]);
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_with_timeouts() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+    let configured = client.config().timeouts.message_fetch;
+
+    {
+        let timeouts = TimeoutConfig {
+            message_fetch: Duration::from_mins(2),
+            ..client.config().timeouts.clone()
+        };
+        let mut scoped = client.with_timeouts(timeouts);
+        assert_eq!(
+            scoped.config().timeouts.message_fetch,
+            Duration::from_mins(2)
+        );
+        let _ = scoped
+            .find_recent_match(&OtpMatcher::six_digit(), Duration::from_mins(5))
+            .await;
+    }
+    assert_eq!(client.config().timeouts.message_fetch, configured);
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_find_by_message_id() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let message = b"From: email-sync@example.com\r\nSubject: Lookup\r\n\
+        Message-ID: <lookup-4711@email-sync.test>\r\n\r\nLook me up";
+    client
+        .append("INBOX", message, &["\\Seen"], None)
+        .await
+        .expect("Failed to append message");
+
+    let found = client
+        .find_by_message_id("<lookup-4711@email-sync.test>")
+        .await
+        .expect("Appended message should be found");
+    assert_eq!(found.subject.as_deref(), Some("Lookup"));
+    assert_eq!(found.text_body.as_deref(), Some("Look me up"));
+    assert!(found.internal_date.is_some());
+    assert_eq!(found.size, u32::try_from(message.len()).ok());
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_wait_for_reply() {
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+
+    let unrelated = b"From: email-sync@example.com\r\nSubject: Other\r\n\r\nCode: REPLY-1";
+    let reply = b"From: email-sync@example.com\r\nSubject: Re: Request\r\n\
+        In-Reply-To: <request-42@email-sync.test>\r\n\r\nCode: REPLY-2";
+    for message in [&unrelated[..], &reply[..]] {
+        client
+            .append("INBOX", message, &["\\Seen"], None)
+            .await
+            .expect("Failed to append message");
+    }
+
+    let matcher = RegexMatcher::new(r"REPLY-(\d+)").expect("Valid regex");
+    let code = client
+        .wait_for_reply("<request-42@email-sync.test>", &matcher)
+        .await
+        .expect("Reply should match");
+    assert_eq!(code, "2");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_email_watcher_delivers_every_match() {
+    use email_sync::{EmailWatcher, MatchEvent, Shutdown};
+
+    let shutdown = Shutdown::new();
+    let mut config = get_test_config().expect("Test config from environment variables");
+    config.shutdown = Some(shutdown.clone());
+
+    let matcher = RegexMatcher::new(r"WATCH-(\d+)").expect("Valid regex");
+    let mut watcher = EmailWatcher::spawn(config.clone(), matcher);
+
+    // Give the watcher time to set its baseline before the emails arrive
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    let mut client = ImapEmailClient::connect(config)
+        .await
+        .expect("Failed to connect");
+    for code in ["1", "2"] {
+        let message =
+            format!("From: email-sync@example.com\r\nSubject: Watch\r\n\r\nCode: WATCH-{code}");
+        client
+            .append("INBOX", message.as_bytes(), &["\\Seen"], None)
+            .await
+            .expect("Failed to append message");
+    }
+    client.logout().await.expect("Failed to logout");
+
+    let mut codes = Vec::new();
+    while codes.len() < 2 {
+        match tokio::time::timeout(Duration::from_secs(60), watcher.recv()).await {
+            Ok(Some(MatchEvent::Matched(email))) => codes.push(email.value),
+            Ok(Some(event)) => println!("Watcher event: {event:?}"),
+            Ok(None) => panic!("Watcher stopped"),
+            Err(_) => panic!("No match within 60s"),
+        }
+    }
+    assert_eq!(codes, ["1", "2"]);
+
+    shutdown.shutdown().await;
+    assert!(watcher.recv().await.is_none());
+}
+
+#[cfg(feature = "ews")]
+#[tokio::test]
+#[ignore = "requires real Exchange server"]
+async fn test_ews_find_recent() {
+    use email_sync::ews::EwsClient;
+
+    // EWS endpoint of the test account, e.g. https://mail.example.com/EWS/Exchange.asmx
+    let endpoint = env::var("EMAIL_SYNC_TEST_EWS_URL").expect("EMAIL_SYNC_TEST_EWS_URL not set");
+    let config = get_test_config().expect("Test config from environment variables");
+
+    let mut client = EwsClient::connect(config, &endpoint)
+        .await
+        .expect("Failed to connect to EWS");
+
+    let matcher = RegexMatcher::new(r"(?s)(.+)").expect("Valid regex");
+    match client
+        .find_recent_match(&matcher, Duration::from_secs(7 * 24 * 3600))
+        .await
+    {
+        Ok(text) => println!("Newest email starts with: {:.40}", text),
+        Err(email_sync::Error::NoMatch) => println!("No email in the last week"),
+        Err(e) => panic!("Unexpected error: {e}"),
+    }
+}
+
+#[cfg(feature = "smtp")]
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_smtp_round_trip() {
+    use email_sync::smtp::SmtpSender;
+
+    let config = get_test_config().expect("Test config from environment variables");
+    let mut client = ImapEmailClient::connect(config.clone())
+        .await
+        .expect("Failed to connect");
+
+    let message_id = SmtpSender::new(config.clone())
+        .send(config.email(), "Round trip", "Round-trip code: RT-4713")
+        .await
+        .expect("Failed to send email");
+    assert!(message_id.ends_with('>'));
+
+    let matcher = RegexMatcher::new(r"RT-(\d+)").expect("Valid regex");
+    let code = client
+        .wait_for_match(&matcher)
+        .await
+        .expect("Sent email should arrive");
+    assert_eq!(code, "4713");
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[cfg(feature = "smtp")]
+#[tokio::test]
+#[ignore = "requires real IMAP server"]
+async fn test_round_trip_latency() {
+    use email_sync::smtp::{SendRequest, SmtpSender};
+
+    let config = get_test_config().expect("Test config from environment variables");
+    let mut client = ImapEmailClient::connect(config.clone())
+        .await
+        .expect("Failed to connect");
+
+    let request = SendRequest::new(SmtpSender::new(config.clone()), config.email())
+        .subject("Deliverability probe")
+        .body("Probe code: RT-2718");
+    let matcher = RegexMatcher::new(r"RT-(\d+)").expect("Valid regex");
+    let trip = client
+        .round_trip(&request, &matcher, Duration::from_secs(120))
+        .await
+        .expect("Probe should arrive");
+    assert_eq!(trip.email.value, "2718");
+    println!(
+        "Probe {} sent in {:?}, delivered in {:?}",
+        trip.message_id, trip.send_time, trip.latency
+    );
+
+    client.logout().await.expect("Failed to logout");
+}
+
+#[cfg(feature = "tempmail")]
+#[tokio::test]
+#[ignore = "requires network access to mail.tm"]
+async fn test_tempmail_mailbox() {
+    use email_sync::tempmail::TempMailProvider;
+
+    let mut mailbox = TempMailProvider::mail_tm()
+        .create_mailbox()
+        .await
+        .expect("Failed to create mailbox");
+    assert!(mailbox.address().contains('@'));
+
+    let matcher = RegexMatcher::new(r"never-sent-(\d+)").expect("Valid regex");
+    let result = mailbox
+        .find_recent_match(&matcher, Duration::from_secs(3600))
+        .await;
+    assert!(matches!(result, Err(email_sync::Error::NoMatch)));
+
+    mailbox.delete().await.expect("Failed to delete mailbox");
+}